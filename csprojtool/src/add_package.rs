@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use log::warn;
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli, cpm, list,
+    package_version::{cmp_version_str, package_usages},
+    path_extensions::relative_path,
+    plan::Effects,
+    xml_extensions::add_item,
+};
+
+const ARG_PACKAGE: &'static str = "package";
+const ARG_PRIVATE_ASSETS: &'static str = "private-assets";
+const ARG_VERSION: &'static str = "version";
+const CMD_ADD_PACKAGE: &'static str = "add-package";
+
+#[derive(Debug)]
+pub struct AddPackageCommand {
+    package: String,
+    version: Option<String>,
+    private_assets: Option<String>,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    follow_project_references: bool,
+    plan_json: Option<PathBuf>,
+}
+
+impl AddPackageCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_ADD_PACKAGE)
+            .about("Add a package reference to projects")
+            .arg(
+                Arg::with_name(ARG_PACKAGE)
+                    .value_name("PACKAGE")
+                    .help("The id of the package")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(
+                Arg::with_name(ARG_VERSION)
+                    .long("version")
+                    .value_name("VERSION")
+                    .help(
+                        "The version to reference, defaults to the highest version already in use",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_PRIVATE_ASSETS)
+                    .long("private-assets")
+                    .value_name("ASSETS")
+                    .help("Sets PrivateAssets on the reference, use all for analyzers")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_ADD_PACKAGE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            package: matches.value_of(ARG_PACKAGE).unwrap().to_owned(),
+            version: matches.value_of(ARG_VERSION).map(str::to_owned),
+            private_assets: matches.value_of(ARG_PRIVATE_ASSETS).map(str::to_owned),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            follow_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: self.follow_project_references,
+        });
+
+        let key = self.package.to_lowercase();
+
+        // Without an explicit version, use the highest version already referenced so that the
+        // projects stay consistent.
+        let version_in_use = package_usages(&projects).get(&key).and_then(|usage| {
+            usage
+                .versions
+                .keys()
+                .max_by(|a, b| cmp_version_str(a, b))
+                .cloned()
+        });
+        let version = self.version.clone().or(version_in_use);
+
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_ADD_PACKAGE, self.plan_json.as_deref());
+        let mut central_versions = BTreeMap::new();
+
+        for project in projects.iter() {
+            let rel_path = relative_path(&current_dir, &project.path);
+
+            if project
+                .package_references
+                .iter()
+                .any(|reference| reference.name.to_lowercase() == key)
+            {
+                status!("{} already references {}", rel_path.display(), self.package);
+                continue;
+            }
+
+            let reference_version = match cpm::find_packages_props(project.path.parent().unwrap()) {
+                Some(props_path) => {
+                    let versions =
+                        central_versions
+                            .entry(props_path.clone())
+                            .or_insert_with(|| {
+                                cpm::read_package_versions(&props_path).unwrap_or_else(|e| {
+                                    panic!("Failed to read {}: {}", props_path.display(), e)
+                                })
+                            });
+
+                    match (versions.get(&key), &self.version) {
+                        (Some(central), Some(requested)) if central != requested => {
+                            warn!(
+                                "{} is centrally managed at {} in {}, not using {}",
+                                self.package,
+                                central,
+                                relative_path(&current_dir, &props_path).display(),
+                                requested
+                            );
+                        }
+                        (Some(_), _) => {}
+                        (None, _) => {
+                            let version = version.clone().unwrap_or_else(|| {
+                                panic!(
+                                    "{} does not define a version for {}, pass --version",
+                                    props_path.display(),
+                                    self.package
+                                )
+                            });
+                            effects
+                                .transform_xml_file(&props_path, |mut root| {
+                                    add_item(
+                                        &mut root,
+                                        package_element("PackageVersion", &self.package, &version),
+                                    );
+                                    Some(root)
+                                })
+                                .unwrap();
+                            status!(
+                                "Added {} {} to {}",
+                                self.package,
+                                version,
+                                relative_path(&current_dir, &props_path).display()
+                            );
+                            versions.insert(key.clone(), version);
+                        }
+                    }
+
+                    None
+                }
+                None => Some(version.clone().unwrap_or_else(|| {
+                    panic!("No project references {} yet, pass --version", self.package)
+                })),
+            };
+
+            let mut reference = match &reference_version {
+                Some(version) => package_element("PackageReference", &self.package, version),
+                None => {
+                    let mut reference = Element::new("PackageReference");
+                    reference
+                        .attributes
+                        .insert("Include".to_owned(), self.package.clone());
+                    reference
+                }
+            };
+            if let Some(private_assets) = &self.private_assets {
+                let mut element = Element::new("PrivateAssets");
+                element.children.push(XMLNode::Text(private_assets.clone()));
+                reference.children.push(XMLNode::Element(element));
+            }
+
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    add_item(&mut root, reference);
+                    Some(root)
+                })
+                .unwrap();
+            status!("Added {} to {}", self.package, rel_path.display());
+        }
+
+        effects.finish();
+    }
+}
+
+pub fn package_element(name: &str, package: &str, version: &str) -> Element {
+    let mut element = Element::new(name);
+    element
+        .attributes
+        .insert("Include".to_owned(), package.to_owned());
+    element
+        .attributes
+        .insert("Version".to_owned(), version.to_owned());
+    element
+}
@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::{
+    cli,
+    csproj::Project,
+    error::Result,
+    git,
+    list::{self, TestFilter},
+    output,
+    path_extensions::{relative_path, PathExt},
+};
+
+const ARG_FORMAT: &'static str = "format";
+const ARG_SINCE: &'static str = "since";
+const CMD_AFFECTED: &'static str = "affected";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct AffectedCommand {
+    search_path: PathBuf,
+    since: String,
+    tests: TestFilter,
+    format: Format,
+}
+
+impl AffectedCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_AFFECTED)
+            .about("List the projects whose build or tests are affected by the changes since a revision")
+            .arg(cli::arg_search())
+            .arg(cli::arg_only_tests())
+            .arg(cli::arg_no_tests())
+            .arg(
+                Arg::with_name(ARG_SINCE)
+                    .long("since")
+                    .value_name("REV")
+                    .help("Sets the revision to compare with, changes since its merge base with HEAD and uncommitted changes count")
+                    .takes_value(true)
+                    .default_value("origin/main"),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_AFFECTED)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            since: matches.value_of(ARG_SINCE).unwrap().to_owned(),
+            tests: cli::get_test_filter(matches),
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        let search_path = std::fs::canonicalize(self.search_path.simplified_absolute().unwrap())
+            .expect("Failed to find the search path!");
+        let root = git::root(if search_path.is_file() {
+            search_path.parent().unwrap()
+        } else {
+            &search_path
+        })?;
+
+        // Dependents can live anywhere in the repository, so all of its projects are listed. Only
+        // the affected projects in the search path that pass the test filter are printed.
+        let projects = list::list(list::Options {
+            search_path: &root,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        let files = git::changed_files(&root, &self.since)?;
+
+        let affected = affected_by(&projects, &files, true);
+        debug!(
+            "{} changed files affect {} projects",
+            files.len(),
+            affected.len()
+        );
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut affected = projects
+            .iter()
+            .filter(|project| {
+                affected.contains(&project.path)
+                    && project.path.starts_with(&search_path)
+                    && self.tests.matches(project)
+            })
+            .map(|project| relative_path(&current_dir, &project.path))
+            .collect::<Vec<_>>();
+        affected.sort();
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                for path in affected.iter() {
+                    println!("{}", path.display());
+                }
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &affected).unwrap();
+                println!();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the projects that the changed files affect like `affected`, through their imports and
+/// shared projects and, if `follow_project_references`, through the projects they reference.
+pub fn affected_by(
+    projects: &[Project],
+    files: &[PathBuf],
+    follow_project_references: bool,
+) -> BTreeSet<PathBuf> {
+    affected(
+        projects.iter().map(|project| project.path.as_path()),
+        projects.iter().flat_map(|project| {
+            let project_references = if follow_project_references {
+                project.project_references.as_slice()
+            } else {
+                &[]
+            };
+            project_references
+                .iter()
+                .chain(project.imports.iter())
+                .chain(project.shared_projects.iter())
+                .map(move |reference| (project.path.as_path(), reference.as_path()))
+        }),
+        files,
+    )
+}
+
+/// Returns the projects that own a changed file and the projects that reference them directly
+/// or indirectly. A file is owned by the projects in the nearest directory above it. A changed
+/// file that a project references, through a project reference or an import, affects that
+/// project too, so does a changed project file that was deleted and is no longer listed.
+fn affected<'a>(
+    projects: impl Iterator<Item = &'a Path>,
+    references: impl Iterator<Item = (&'a Path, &'a Path)>,
+    files: &[PathBuf],
+) -> BTreeSet<PathBuf> {
+    let mut owners = BTreeMap::<&Path, Vec<&Path>>::new();
+    for project in projects {
+        owners
+            .entry(project.parent().unwrap())
+            .or_default()
+            .push(project);
+    }
+
+    let mut referenced_by = BTreeMap::<&Path, Vec<&Path>>::new();
+    for (path, reference) in references {
+        referenced_by.entry(reference).or_default().push(path);
+    }
+
+    let mut found = BTreeSet::<&Path>::new();
+    let mut queue = VecDeque::new();
+    for file in files.iter() {
+        if let Some(dir_owners) = file.ancestors().skip(1).find_map(|dir| owners.get(dir)) {
+            queue.extend(dir_owners);
+        }
+        queue.extend(referenced_by.get(file.as_path()).into_iter().flatten());
+    }
+
+    while let Some(path) = queue.pop_front() {
+        if found.insert(path) {
+            queue.extend(referenced_by.get(path).into_iter().flatten());
+        }
+    }
+
+    found.into_iter().map(Path::to_owned).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affected_includes_the_owners_of_changed_files_and_their_dependents() {
+        let projects = [
+            PathBuf::from("/repo/src/A/A.csproj"),
+            PathBuf::from("/repo/src/A/Nested/Nested.csproj"),
+            PathBuf::from("/repo/src/B/B.csproj"),
+            PathBuf::from("/repo/src/C/C.csproj"),
+            PathBuf::from("/repo/tests/D/D.csproj"),
+        ];
+        let references = [
+            (
+                PathBuf::from("/repo/src/B/B.csproj"),
+                PathBuf::from("/repo/src/A/Nested/Nested.csproj"),
+            ),
+            (
+                PathBuf::from("/repo/src/C/C.csproj"),
+                PathBuf::from("/repo/src/B/B.csproj"),
+            ),
+            (
+                PathBuf::from("/repo/tests/D/D.csproj"),
+                PathBuf::from("/repo/tests/Directory.Build.props"),
+            ),
+        ];
+
+        let affected = affected(
+            projects.iter().map(PathBuf::as_path),
+            references
+                .iter()
+                .map(|(path, reference)| (path.as_path(), reference.as_path())),
+            &[
+                PathBuf::from("/repo/src/A/Nested/Class.cs"),
+                PathBuf::from("/repo/tests/Directory.Build.props"),
+                PathBuf::from("/repo/README.md"),
+            ],
+        );
+
+        assert_eq!(
+            affected.into_iter().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/repo/src/A/Nested/Nested.csproj"),
+                PathBuf::from("/repo/src/B/B.csproj"),
+                PathBuf::from("/repo/src/C/C.csproj"),
+                PathBuf::from("/repo/tests/D/D.csproj"),
+            ]
+        );
+    }
+
+    #[test]
+    fn affected_by_only_follows_project_references_when_asked() {
+        let projects = [
+            Project {
+                imports: vec![PathBuf::from("/repo/Directory.Build.props")],
+                ..Project::new("/repo/src/A/A.csproj")
+            },
+            Project {
+                project_references: vec![PathBuf::from("/repo/src/A/A.csproj")],
+                ..Project::new("/repo/src/B/B.csproj")
+            },
+        ];
+        let files = [PathBuf::from("/repo/Directory.Build.props")];
+
+        assert_eq!(
+            affected_by(&projects, &files, false)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/repo/src/A/A.csproj")]
+        );
+        assert_eq!(affected_by(&projects, &files, true).len(), 2);
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    cli,
+    csproj::Project,
+    encoding, list,
+    migrate_packages::read_packages_config,
+    output,
+    package_version::{cmp_version_str, PackageVersion},
+    path_extensions::relative_path,
+};
+
+const ARG_FORMAT: &'static str = "format";
+const CMD_ANALYZE_REDIRECTS: &'static str = "analyze-redirects";
+
+/// The configuration files that hold the binding redirects of desktop and web applications.
+const CONFIG_FILE_NAMES: &[&str] = &["app.config", "web.config"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct AnalyzeRedirectsCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    format: Format,
+}
+
+/// A `bindingRedirect` in a `dependentAssembly` of a configuration file.
+#[derive(Debug, PartialEq)]
+struct Redirect {
+    name: String,
+    old_version: String,
+    new_version: String,
+}
+
+/// The versions in which a project and the projects it references use an assembly, either as the
+/// id of a package or as the Version of a Reference.
+#[derive(Debug, Default)]
+struct Referenced {
+    /// The name as first seen, the map is keyed by the lowercase name.
+    name: String,
+    package_versions: BTreeSet<String>,
+    assembly_versions: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Finding {
+    /// The redirect points at another version than the one that is referenced.
+    Stale {
+        name: String,
+        new_version: String,
+        referenced: String,
+    },
+    /// The redirect is for an assembly that nothing references anymore.
+    Unreferenced { name: String, new_version: String },
+    /// Several versions are referenced but nothing redirects them to one.
+    Missing { name: String, versions: Vec<String> },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Finding::Stale {
+                name,
+                new_version,
+                referenced,
+            } => write!(
+                f,
+                "{} is redirected to {} but {} is referenced",
+                name, new_version, referenced
+            ),
+            Finding::Unreferenced { name, new_version } => write!(
+                f,
+                "{} is redirected to {} but is not referenced",
+                name, new_version
+            ),
+            Finding::Missing { name, versions } => write!(
+                f,
+                "{} is referenced in versions {} but is not redirected",
+                name,
+                versions.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ProjectFindings {
+    path: PathBuf,
+    config: Option<PathBuf>,
+    findings: Vec<Finding>,
+}
+
+impl AnalyzeRedirectsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_ANALYZE_REDIRECTS)
+            .about("Check the binding redirects in app.config and web.config against the package versions that the projects reference")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_ANALYZE_REDIRECTS)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) {
+        // The referenced projects are needed for the versions that end up in the output folder.
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: true,
+        });
+        let by_path = projects
+            .iter()
+            .map(|project| (&project.path, project))
+            .collect::<HashMap<_, _>>();
+        let mut checked = projects.clone();
+        list::retain_glob_matches(&mut checked, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let results = checked
+            .iter()
+            .filter_map(|project| {
+                let config_path = find_config(project.path.parent().unwrap());
+                // Libraries without a configuration file get the redirects of the application.
+                if config_path.is_none() && !project.is_exe {
+                    return None;
+                }
+                let redirects = match &config_path {
+                    Some(config_path) => match encoding::read_to_string(config_path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|contents| parse_redirects(&contents).map_err(|e| e.to_string()))
+                    {
+                        Ok(redirects) => redirects,
+                        Err(e) => {
+                            warn!("Failed to read {}: {}", config_path.display(), e);
+                            return None;
+                        }
+                    },
+                    None => Vec::new(),
+                };
+                let auto_generated = project
+                    .last_unconditional_property("AutoGenerateBindingRedirects")
+                    .map_or(false, |value| value.eq_ignore_ascii_case("true"));
+
+                let findings = analyze(&redirects, &referenced(project, &by_path), auto_generated);
+                if findings.is_empty() {
+                    return None;
+                }
+                Some(ProjectFindings {
+                    path: relative_path(&current_dir, &project.path),
+                    config: config_path.map(|path| relative_path(&current_dir, &path)),
+                    findings,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                for result in results.iter() {
+                    match &result.config {
+                        Some(config) => {
+                            println!("{} ({})", result.path.display(), config.display())
+                        }
+                        None => println!("{}", result.path.display()),
+                    }
+                    for finding in result.findings.iter() {
+                        println!("  {}", finding);
+                    }
+                }
+                if results.is_empty() {
+                    println!("The binding redirects of {} projects match", checked.len());
+                }
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &results).unwrap();
+                println!();
+            }
+        }
+    }
+}
+
+fn find_config(project_dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(project_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    CONFIG_FILE_NAMES
+                        .iter()
+                        .any(|config| name.eq_ignore_ascii_case(config))
+                })
+                && path.is_file()
+        })
+}
+
+/// Collects the referenced versions of the project and the projects it references, directly or
+/// through other projects, keyed by the lowercase name.
+fn referenced(
+    project: &Project,
+    by_path: &HashMap<&PathBuf, &Project>,
+) -> BTreeMap<String, Referenced> {
+    let mut referenced = BTreeMap::<String, Referenced>::new();
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![project];
+    while let Some(project) = stack.pop() {
+        if !visited.insert(&project.path) {
+            continue;
+        }
+        let project_dir = project.path.parent().unwrap();
+
+        for package in project.package_references.iter() {
+            if let Some(version) = &package.version {
+                referenced_as(&mut referenced, &package.name)
+                    .package_versions
+                    .insert(version.clone());
+            }
+        }
+        let packages_config_path = project_dir.join("packages.config");
+        if packages_config_path.is_file() {
+            for package in read_packages_config(&packages_config_path).unwrap_or_default() {
+                referenced_as(&mut referenced, &package.id)
+                    .package_versions
+                    .insert(package.version);
+            }
+        }
+        for (name, version) in assembly_references(&project.path) {
+            referenced_as(&mut referenced, &name)
+                .assembly_versions
+                .insert(version);
+        }
+
+        stack.extend(
+            project
+                .project_references
+                .iter()
+                .filter_map(|reference| by_path.get(reference).copied()),
+        );
+    }
+    referenced
+}
+
+fn referenced_as<'a>(
+    referenced: &'a mut BTreeMap<String, Referenced>,
+    name: &str,
+) -> &'a mut Referenced {
+    referenced
+        .entry(name.to_lowercase())
+        .or_insert_with(|| Referenced {
+            name: name.to_owned(),
+            ..Referenced::default()
+        })
+}
+
+/// Returns the names and versions of the References that name a Version in their Include.
+fn assembly_references(project_path: &Path) -> Vec<(String, String)> {
+    let contents = encoding::read_to_string(project_path).unwrap_or_default();
+    let document = match roxmltree::Document::parse(&contents) {
+        Ok(document) => document,
+        Err(_) => return Vec::new(),
+    };
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("Reference"))
+        .filter_map(|node| {
+            let mut parts = node.attribute("Include")?.split(',').map(str::trim);
+            let name = parts.next()?;
+            let version = parts.find_map(|part| part.strip_prefix("Version="))?;
+            Some((name.to_owned(), version.to_owned()))
+        })
+        .collect()
+}
+
+fn parse_redirects(contents: &str) -> Result<Vec<Redirect>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(contents)?;
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "dependentAssembly")
+        .filter_map(|node| {
+            let child = |name: &str| {
+                node.children()
+                    .find(|child| child.tag_name().name() == name)
+            };
+            let identity = child("assemblyIdentity")?;
+            let redirect = child("bindingRedirect")?;
+            Some(Redirect {
+                name: identity.attribute("name")?.to_owned(),
+                old_version: redirect.attribute("oldVersion")?.to_owned(),
+                new_version: redirect.attribute("newVersion")?.to_owned(),
+            })
+        })
+        .collect())
+}
+
+/// Compares the redirects with the referenced versions. Assembly versions are compared in full,
+/// package versions by their major version only because packages usually keep the assembly
+/// version at `<major>.0.0.0` across their releases. Missing redirects are not reported when the
+/// build generates them.
+fn analyze(
+    redirects: &[Redirect],
+    referenced: &BTreeMap<String, Referenced>,
+    auto_generated: bool,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for redirect in redirects.iter() {
+        let versions = match referenced.get(&redirect.name.to_lowercase()) {
+            Some(versions) => versions,
+            None => {
+                findings.push(Finding::Unreferenced {
+                    name: redirect.name.clone(),
+                    new_version: redirect.new_version.clone(),
+                });
+                continue;
+            }
+        };
+        let new_version = PackageVersion::parse(&redirect.new_version);
+
+        let stale_assembly = versions
+            .assembly_versions
+            .iter()
+            .filter(|version| PackageVersion::parse(version) != new_version)
+            .max_by(|a, b| cmp_version_str(a, b));
+        let highest_package = versions
+            .package_versions
+            .iter()
+            .max_by(|a, b| cmp_version_str(a, b));
+        let stale_package = highest_package.filter(|version| {
+            PackageVersion::parse(version).map(|version| version.major())
+                != new_version.as_ref().map(PackageVersion::major)
+        });
+        if let Some(referenced) = stale_assembly.or(stale_package) {
+            findings.push(Finding::Stale {
+                name: redirect.name.clone(),
+                new_version: redirect.new_version.clone(),
+                referenced: referenced.clone(),
+            });
+        }
+    }
+
+    if auto_generated {
+        return findings;
+    }
+    for (key, versions) in referenced.iter() {
+        if versions.package_versions.len() < 2 && versions.assembly_versions.len() < 2 {
+            continue;
+        }
+        if redirects
+            .iter()
+            .any(|redirect| redirect.name.to_lowercase() == *key)
+        {
+            continue;
+        }
+        let mut all_versions = versions
+            .package_versions
+            .union(&versions.assembly_versions)
+            .cloned()
+            .collect::<Vec<_>>();
+        all_versions.sort_by(|a, b| cmp_version_str(a, b));
+        findings.push(Finding::Missing {
+            name: versions.name.clone(),
+            versions: all_versions,
+        });
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_redirects_reads_dependent_assemblies() {
+        let contents = r#"<?xml version="1.0" encoding="utf-8"?>
+<configuration>
+  <runtime>
+    <assemblyBinding xmlns="urn:schemas-microsoft-com:asm.v1">
+      <dependentAssembly>
+        <assemblyIdentity name="Newtonsoft.Json" publicKeyToken="30ad4fe6b2a6aeed" culture="neutral" />
+        <bindingRedirect oldVersion="0.0.0.0-12.0.0.0" newVersion="12.0.0.0" />
+      </dependentAssembly>
+      <dependentAssembly>
+        <assemblyIdentity name="Unredirected" />
+      </dependentAssembly>
+    </assemblyBinding>
+  </runtime>
+</configuration>"#;
+
+        assert_eq!(
+            parse_redirects(contents).unwrap(),
+            vec![Redirect {
+                name: String::from("Newtonsoft.Json"),
+                old_version: String::from("0.0.0.0-12.0.0.0"),
+                new_version: String::from("12.0.0.0"),
+            }]
+        );
+    }
+
+    #[test]
+    fn analyze_reports_stale_unreferenced_and_missing_redirects() {
+        let redirect = |name: &str, new_version: &str| Redirect {
+            name: String::from(name),
+            old_version: format!("0.0.0.0-{}", new_version),
+            new_version: String::from(new_version),
+        };
+        let redirects = [
+            redirect("Newtonsoft.Json", "11.0.0.0"),
+            redirect("System.Memory", "4.0.1.1"),
+            redirect("Legacy", "1.0.0.0"),
+            redirect("Gone", "2.0.0.0"),
+        ];
+        let packages = |versions: &[&str]| Referenced {
+            name: String::from("Serilog"),
+            package_versions: versions.iter().map(|version| version.to_string()).collect(),
+            assembly_versions: BTreeSet::new(),
+        };
+        let referenced = vec![
+            (String::from("newtonsoft.json"), packages(&["12.0.3"])),
+            (String::from("system.memory"), packages(&["4.5.4"])),
+            (
+                String::from("legacy"),
+                Referenced {
+                    name: String::from("Legacy"),
+                    package_versions: BTreeSet::new(),
+                    assembly_versions: std::iter::once(String::from("1.2.0.0")).collect(),
+                },
+            ),
+            (String::from("serilog"), packages(&["2.10.0", "2.9.0"])),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            analyze(&redirects, &referenced, false),
+            vec![
+                Finding::Stale {
+                    name: String::from("Newtonsoft.Json"),
+                    new_version: String::from("11.0.0.0"),
+                    referenced: String::from("12.0.3"),
+                },
+                Finding::Stale {
+                    name: String::from("Legacy"),
+                    new_version: String::from("1.0.0.0"),
+                    referenced: String::from("1.2.0.0"),
+                },
+                Finding::Unreferenced {
+                    name: String::from("Gone"),
+                    new_version: String::from("2.0.0.0"),
+                },
+                Finding::Missing {
+                    name: String::from("Serilog"),
+                    versions: vec![String::from("2.9.0"), String::from("2.10.0")],
+                },
+            ]
+        );
+        assert_eq!(analyze(&redirects, &referenced, true).len(), 3);
+    }
+}
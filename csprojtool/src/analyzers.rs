@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{
+    cli, config::Config, cpm, csproj::Project, list, output, path_extensions::relative_path,
+};
+
+const ARG_FORMAT: &'static str = "format";
+const ARG_REQUIRE: &'static str = "require";
+const CMD_ANALYZERS: &'static str = "analyzers";
+
+/// Analyzer packages whose names do not say so.
+const KNOWN_ANALYZERS: &[&str] = &[
+    "Meziantou.Analyzer",
+    "Microsoft.CodeAnalysis.NetAnalyzers",
+    "Microsoft.CodeAnalysis.FxCopAnalyzers",
+    "Microsoft.VisualStudio.Threading.Analyzers",
+    "SonarAnalyzer.CSharp",
+    "SonarAnalyzer.VisualBasic",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct AnalyzersCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    required: Vec<String>,
+    format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct ProjectAnalyzers {
+    path: PathBuf,
+    /// The analyzer packages by name with their version, absent when no version is set.
+    analyzers: BTreeMap<String, Option<String>>,
+    /// The required analyzer packages that the project does not reference.
+    missing: Vec<String>,
+}
+
+impl AnalyzersCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_ANALYZERS)
+            .about("List the analyzer packages that the projects reference and the required ones they miss")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(
+                Arg::with_name(ARG_REQUIRE)
+                    .long("require")
+                    .value_name("PACKAGE")
+                    .help("Requires every project to reference the analyzer package, in addition to [analyzers] required in the configuration")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_ANALYZERS)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        let mut required = Config::current().analyzers.required.clone();
+        required.extend(
+            matches
+                .values_of(ARG_REQUIRE)
+                .into_iter()
+                .flatten()
+                .map(str::to_owned),
+        );
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            required,
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let config = Config::current();
+        let mut builder = globset::GlobSetBuilder::new();
+        for glob in config.analyzers.packages.iter() {
+            builder.add(
+                globset::GlobBuilder::new(glob)
+                    .case_insensitive(true)
+                    .build()
+                    .unwrap_or_else(|e| {
+                        panic!("Invalid glob {} in the configuration: {}", glob, e)
+                    }),
+            );
+        }
+        let package_matcher = builder.build().unwrap();
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut central_versions = BTreeMap::new();
+        let reports = projects
+            .iter()
+            .map(|project| {
+                let central =
+                    cpm::find_packages_props(project.path.parent().unwrap()).map(|props_path| {
+                        central_versions
+                            .entry(props_path.clone())
+                            .or_insert_with(|| {
+                                cpm::read_package_versions(&props_path).unwrap_or_default()
+                            })
+                            .clone()
+                    });
+                let analyzers = analyzers(project, &self.required, |name| {
+                    package_matcher.is_match(name)
+                })
+                .into_iter()
+                .map(|(name, version)| {
+                    let version = version.or_else(|| {
+                        central
+                            .as_ref()
+                            .and_then(|central| central.get(&name.to_lowercase()).cloned())
+                    });
+                    (name, version)
+                })
+                .collect::<BTreeMap<_, _>>();
+                let missing = missing(&analyzers, &self.required);
+                ProjectAnalyzers {
+                    path: relative_path(&current_dir, &project.path),
+                    analyzers,
+                    missing,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                for report in reports.iter() {
+                    println!("{}", report.path.display());
+                    for (name, version) in report.analyzers.iter() {
+                        println!("  {} {}", name, version.as_deref().unwrap_or("-"));
+                    }
+                    for name in report.missing.iter() {
+                        println!("  missing {}", name);
+                    }
+                }
+                if !self.required.is_empty() {
+                    println!(
+                        "{} of {} projects reference the required analyzers",
+                        reports
+                            .iter()
+                            .filter(|report| report.missing.is_empty())
+                            .count(),
+                        reports.len()
+                    );
+                }
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &reports).unwrap();
+                println!();
+            }
+        }
+
+        if reports.iter().any(|report| !report.missing.is_empty()) {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+    }
+}
+
+/// Returns the analyzer packages that the project references with their versions. Packages are
+/// analyzers if they are known, required or matched, or if their name ends with Analyzers or
+/// Analyzer like StyleCop.Analyzers and Roslynator.Analyzers.
+fn analyzers<F>(project: &Project, required: &[String], matches: F) -> Vec<(String, Option<String>)>
+where
+    F: Fn(&str) -> bool,
+{
+    project
+        .package_references
+        .iter()
+        .filter(|package| {
+            let name = package.name.as_str();
+            let lowercase = name.to_lowercase();
+            lowercase.ends_with("analyzers")
+                || lowercase.ends_with("analyzer")
+                || KNOWN_ANALYZERS
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(name))
+                || required
+                    .iter()
+                    .any(|required| required.eq_ignore_ascii_case(name))
+                || matches(name)
+        })
+        .map(|package| (package.name.clone(), package.version.clone()))
+        .collect()
+}
+
+fn missing(analyzers: &BTreeMap<String, Option<String>>, required: &[String]) -> Vec<String> {
+    required
+        .iter()
+        .filter(|name| {
+            !analyzers
+                .keys()
+                .any(|analyzer| analyzer.eq_ignore_ascii_case(name))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csproj::PackageReference;
+
+    #[test]
+    fn analyzers_are_recognized_by_name_and_missing_ones_reported() {
+        let package = |name: &str, version: Option<&str>| PackageReference {
+            name: String::from(name),
+            version: version.map(String::from),
+        };
+        let project = Project {
+            target_frameworks: vec![String::from("net6.0")],
+            package_references: vec![
+                package("StyleCop.Analyzers", Some("1.1.118")),
+                package("SonarAnalyzer.CSharp", None),
+                package("Contoso.Rules", Some("2.0.0")),
+                package("Newtonsoft.Json", Some("13.0.1")),
+            ],
+            ..Project::new("/repo/A/A.csproj")
+        };
+        let required = [
+            String::from("stylecop.analyzers"),
+            String::from("Roslynator.Analyzers"),
+        ];
+
+        let analyzers = analyzers(&project, &required, |name| name.starts_with("Contoso."))
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            analyzers.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec![
+                "Contoso.Rules",
+                "SonarAnalyzer.CSharp",
+                "StyleCop.Analyzers"
+            ]
+        );
+        assert_eq!(
+            missing(&analyzers, &required),
+            vec![String::from("Roslynator.Analyzers")]
+        );
+    }
+}
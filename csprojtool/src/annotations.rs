@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use crate::{
+    path_extensions::relative_path,
+    sarif::{Issue, Level},
+};
+
+/// Prints the issues as GitHub Actions workflow commands, which show them inline on the changed
+/// files of a pull request. Paths are relative to the repository root, which is the working
+/// directory of a checkout in a workflow.
+pub fn print(issues: &[Issue], root: &Path) {
+    for issue in issues.iter() {
+        for annotation in annotations(issue, root) {
+            println!("{}", annotation);
+        }
+    }
+}
+
+/// Returns a workflow command for every location of the issue, or one without a file if it has
+/// none.
+fn annotations(issue: &Issue, root: &Path) -> Vec<String> {
+    let command = match issue.level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+    };
+    let title = format!("title={}", escape_property(issue.rule));
+    let message = escape_data(&issue.message);
+
+    if issue.locations.is_empty() {
+        return vec![format!("::{} {}::{}", command, title, message)];
+    }
+
+    issue
+        .locations
+        .iter()
+        .map(|location| {
+            let file = relative_path(root, &location.path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let mut properties = vec![format!("file={}", escape_property(&file))];
+            if let Some(line) = location.line {
+                properties.push(format!("line={}", line));
+            }
+            properties.push(title.clone());
+            format!("::{} {}::{}", command, properties.join(","), message)
+        })
+        .collect()
+}
+
+/// Escapes the message of a workflow command, see
+/// https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::sarif::Location;
+
+    #[test]
+    fn annotations_are_printed_per_location() {
+        let issue = Issue {
+            rule: "missing-project-references",
+            level: Level::Error,
+            message: String::from("../B/B.csproj does not exist, 100% sure"),
+            locations: vec![
+                Location {
+                    path: PathBuf::from("/repo/src/A/A.csproj"),
+                    line: Some(12),
+                },
+                Location {
+                    path: PathBuf::from("/repo/All.sln"),
+                    line: None,
+                },
+            ],
+        };
+        assert_eq!(
+            annotations(&issue, Path::new("/repo")),
+            vec![
+                "::error file=src/A/A.csproj,line=12,title=missing-project-references::../B/B.csproj does not exist, 100%25 sure",
+                "::error file=All.sln,title=missing-project-references::../B/B.csproj does not exist, 100%25 sure",
+            ]
+        );
+    }
+
+    #[test]
+    fn properties_escape_separators() {
+        assert_eq!(escape_property("C:\\a,b"), "C%3A\\a%2Cb");
+        assert_eq!(escape_data("a\nb: c,d"), "a%0Ab: c,d");
+    }
+}
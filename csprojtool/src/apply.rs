@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::plan::Plan;
+
+const ARG_PLAN_PATH: &'static str = "plan-path";
+const CMD_APPLY: &'static str = "apply";
+
+#[derive(Debug)]
+pub struct ApplyCommand {
+    plan_path: PathBuf,
+}
+
+impl ApplyCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+        use clap::SubCommand;
+
+        SubCommand::with_name(CMD_APPLY)
+            .about("Apply a plan written with --plan-json")
+            .arg(
+                Arg::with_name(ARG_PLAN_PATH)
+                    .value_name("PLAN_PATH")
+                    .help("The plan to apply")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_APPLY)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            plan_path: matches.value_of_os(ARG_PLAN_PATH).unwrap().into(),
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        let plan = Plan::read(&self.plan_path)?;
+        plan.apply()?;
+        println!(
+            "Applied {} operations of {}",
+            plan.operations.len(),
+            plan.command
+        );
+        Ok(())
+    }
+}
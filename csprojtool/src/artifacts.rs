@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use xmltree::Element;
+
+use crate::output::status;
+use crate::{
+    cli,
+    evaluation::Evaluator,
+    list,
+    path_extensions::{relative_path, PathExt},
+    plan::Effects,
+    xml_extensions::set_property,
+};
+
+const CMD_ARTIFACTS: &'static str = "artifacts";
+const CMD_ENABLE: &'static str = "enable";
+
+pub const DIRECTORY_BUILD_PROPS: &'static str = "Directory.Build.props";
+pub const DIRECTORY_BUILD_TARGETS: &'static str = "Directory.Build.targets";
+
+/// Properties that move the build output elsewhere and thereby defeat the artifacts layout.
+const OUTPUT_PATH_PROPERTIES: [&'static str; 4] = [
+    "OutputPath",
+    "BaseOutputPath",
+    "IntermediateOutputPath",
+    "BaseIntermediateOutputPath",
+];
+
+/// The artifacts output layout (`UseArtifactsOutput`) places the output of every project under
+/// `<ArtifactsPath>\bin\<ProjectName>` instead of next to the project.
+#[derive(Debug, Clone)]
+pub struct ArtifactsLayout {
+    pub artifacts_path: PathBuf,
+}
+
+impl ArtifactsLayout {
+    pub fn output_dir(&self, project_path: &Path) -> PathBuf {
+        self.artifacts_path
+            .join("bin")
+            .join(project_path.file_stem().unwrap())
+    }
+}
+
+/// Returns the artifacts layout of the project if the nearest Directory.Build.props enables it.
+/// The property has no effect in the project file itself because it is read by the SDK props.
+pub fn artifacts_layout(project_path: &Path) -> Option<ArtifactsLayout> {
+    let props_path = find_directory_build_props(project_path.parent()?)?;
+    let evaluator = Evaluator::for_file(&props_path)?;
+
+    if !evaluator
+        .property("UseArtifactsOutput")
+        .map_or(false, |value| value.eq_ignore_ascii_case("true"))
+    {
+        return None;
+    }
+
+    let artifacts_path = evaluator
+        .property("ArtifactsPath")
+        .and_then(|value| evaluator.resolve_path(value))
+        .unwrap_or_else(|| props_path.parent().unwrap().join("artifacts"));
+
+    Some(ArtifactsLayout { artifacts_path })
+}
+
+pub fn find_directory_build_props(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(DIRECTORY_BUILD_PROPS))
+        .find(|path| path.is_file())
+}
+
+pub fn find_directory_build_targets(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(DIRECTORY_BUILD_TARGETS))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug)]
+pub enum ArtifactsCommand {
+    Enable(EnableCommand),
+}
+
+#[derive(Debug)]
+pub struct EnableCommand {
+    search_path: PathBuf,
+    plan_json: Option<PathBuf>,
+}
+
+impl ArtifactsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::SubCommand;
+
+        SubCommand::with_name(CMD_ARTIFACTS)
+            .about("Manage the artifacts output layout")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(CMD_ENABLE)
+                    .about("Enable UseArtifactsOutput for all projects in a directory")
+                    .arg(cli::arg_search())
+                    .arg(cli::arg_plan_json()),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_ARTIFACTS)?;
+
+        matches.subcommand_matches(CMD_ENABLE).map(|matches| {
+            Self::Enable(EnableCommand {
+                search_path: cli::get_search_path(matches),
+                plan_json: cli::get_plan_json(matches),
+            })
+        })
+    }
+
+    pub fn execute(&self) {
+        match self {
+            Self::Enable(command) => command.execute(),
+        }
+    }
+}
+
+impl EnableCommand {
+    pub fn execute(&self) {
+        let search_dir = self.search_path.simplified_absolute().unwrap();
+        if !search_dir.is_dir() {
+            panic!(
+                "The search path {} must be a directory",
+                search_dir.display()
+            );
+        }
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_ARTIFACTS, self.plan_json.as_deref());
+
+        let props_path = search_dir.join(DIRECTORY_BUILD_PROPS);
+        if effects.is_file(&props_path) {
+            effects
+                .transform_xml_file(&props_path, |mut root| {
+                    if set_property(&mut root, "UseArtifactsOutput", "true") {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+        } else {
+            let mut root = Element::new("Project");
+            set_property(&mut root, "UseArtifactsOutput", "true");
+            effects.write_xml_file(&props_path, &root).unwrap();
+        }
+        status!(
+            "Enabled UseArtifactsOutput in {}",
+            relative_path(&current_dir, &props_path).display()
+        );
+
+        if ensure_gitignore_entry(&mut effects, &search_dir, "/artifacts/") {
+            status!(
+                "Added /artifacts/ to {}",
+                relative_path(&current_dir, &search_dir.join(".gitignore")).display()
+            );
+        }
+
+        let projects = list::list(list::Options {
+            search_path: &search_dir,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+
+        for project in projects.iter() {
+            for property in project
+                .properties
+                .iter()
+                .filter(|property| OUTPUT_PATH_PROPERTIES.contains(&property.name.as_str()))
+            {
+                status!(
+                    "{} sets {}, which overrides the artifacts layout",
+                    relative_path(&current_dir, &project.path).display(),
+                    property.name
+                );
+            }
+        }
+
+        effects.finish();
+    }
+}
+
+/// Appends the entry to the .gitignore in the directory unless it is already present.
+fn ensure_gitignore_entry(effects: &mut Effects, dir: &Path, entry: &str) -> bool {
+    let path = dir.join(".gitignore");
+    let mut contents = match effects.read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => Err(e).unwrap(),
+    };
+
+    let trimmed_entry = entry.trim_matches('/');
+    if contents
+        .lines()
+        .any(|line| line.trim().trim_matches('/') == trimmed_entry)
+    {
+        return false;
+    }
+
+    let line_separator = if contents.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push_str(line_separator);
+    }
+    contents.push_str(entry);
+    contents.push_str(line_separator);
+
+    effects.write_file(&path, contents, Vec::new()).unwrap();
+    true
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::check::Finding;
+use crate::csproj::Project;
+use crate::path_extensions::{relative_path, PathExt};
+use crate::tools;
+
+pub const NAME: &'static str = "binlog";
+
+/// The project graph and properties of a real build, as recorded in an MSBuild binary log.
+#[derive(Debug, Default)]
+pub struct Build {
+    pub projects: BTreeMap<PathBuf, BuiltProject>,
+}
+
+#[derive(Debug, Default)]
+pub struct BuiltProject {
+    /// The projects this project built, which are its project references in practice.
+    pub references: BTreeSet<PathBuf>,
+    /// The global and evaluated properties of the first time the project was built.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl Build {
+    /// Replays the binary log into a diagnostic text log with MSBuild and reads that, the binary
+    /// format changes with every MSBuild release.
+    pub fn read(binlog_path: &Path) -> Self {
+        let log_file = tempfile::NamedTempFile::new().unwrap();
+        let mut msbuild = tools::msbuild();
+        msbuild
+            .arg(binlog_path)
+            .args(["-noconlog", "-nologo"])
+            .arg(format!(
+                "-flp:verbosity=diagnostic;logfile={}",
+                log_file.path().display()
+            ));
+        debug!("{:?}", &msbuild);
+
+        let output = msbuild
+            .output()
+            .unwrap_or_else(|e| panic!("Failed to run {:?}: {}", msbuild, e));
+        if !output.status.success() {
+            panic!(
+                "Failed to replay {}: {}",
+                binlog_path.display(),
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+        }
+
+        let log = std::fs::read(log_file.path()).unwrap();
+        Self::parse_log(&String::from_utf8_lossy(&log))
+    }
+
+    /// Reads the `Project "A" (1) is building "B" (2)` lines and the `Initial Properties:`
+    /// blocks of a diagnostic text log. Lines of parallel builds are prefixed with the number of
+    /// the project they belong to, like `2>`.
+    pub fn parse_log(log: &str) -> Self {
+        lazy_static::lazy_static! {
+            static ref PREFIX_RE: regex::Regex = regex::Regex::new(r"^\s*(\d+)>").unwrap();
+            static ref BUILDING_RE: regex::Regex = regex::Regex::new(
+                r#"Project "([^"]+)"(?: \((\d+)(?::\d+)?\))? is building "([^"]+)" \((\d+)(?::\d+)?\)"#
+            )
+            .unwrap();
+            static ref STARTED_RE: regex::Regex =
+                regex::Regex::new(r#"^Project "([^"]+)" on node \d+"#).unwrap();
+            static ref PROPERTY_RE: regex::Regex =
+                regex::Regex::new(r"^([A-Za-z_][A-Za-z0-9_.\-]*) = (.*)$").unwrap();
+        }
+
+        let mut build = Self::default();
+        // The project numbers of the log, only unique within one build.
+        let mut numbers = HashMap::<String, PathBuf>::new();
+        let mut current = None::<PathBuf>;
+        let mut reading_properties = false;
+
+        for line in log.lines() {
+            let (number, text) = match PREFIX_RE.captures(line) {
+                Some(captures) => (
+                    Some(captures[1].to_owned()),
+                    line[captures.get(0).unwrap().end()..].trim(),
+                ),
+                None => (None, line.trim()),
+            };
+            if let Some(project) = number.as_ref().and_then(|number| numbers.get(number)) {
+                current = Some(project.clone());
+            }
+
+            if let Some(captures) = BUILDING_RE.captures(text) {
+                reading_properties = false;
+                let from = log_path(&captures[1]);
+                let to = log_path(&captures[3]);
+                if let Some(number) = captures.get(2) {
+                    numbers.insert(number.as_str().to_owned(), from.clone());
+                }
+                numbers.insert(captures[4].to_owned(), to.clone());
+
+                // Solutions build their projects through a generated metaproj.
+                if is_project(&from) && is_project(&to) && from != to {
+                    build
+                        .projects
+                        .entry(from)
+                        .or_default()
+                        .references
+                        .insert(to.clone());
+                }
+                if is_project(&to) {
+                    build.projects.entry(to.clone()).or_default();
+                }
+                current = Some(to);
+            } else if let Some(captures) = STARTED_RE.captures(text) {
+                reading_properties = false;
+                let path = log_path(&captures[1]);
+                if let Some(number) = number {
+                    numbers.insert(number, path.clone());
+                }
+                if is_project(&path) {
+                    build.projects.entry(path.clone()).or_default();
+                }
+                current = Some(path);
+            } else if text == "Initial Properties:" {
+                reading_properties = true;
+            } else if reading_properties {
+                match (PROPERTY_RE.captures(text), current.as_ref()) {
+                    (Some(captures), Some(path)) if is_project(path) => {
+                        build
+                            .projects
+                            .entry(path.clone())
+                            .or_default()
+                            .properties
+                            .entry(captures[1].to_owned())
+                            .or_insert_with(|| captures[2].to_owned());
+                    }
+                    (Some(_), _) => {}
+                    (None, _) => reading_properties = false,
+                }
+            }
+        }
+
+        build
+    }
+}
+
+/// Paths in the log are those of the machine that ran the build, which is usually this one.
+fn log_path(value: &str) -> PathBuf {
+    let path = PathBuf::from(value).simplify();
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+fn is_project(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            extension.ends_with("proj") && extension != "metaproj"
+        })
+}
+
+/// Parses the value the same way declared target frameworks are, sorted and without duplicates.
+fn split_target_frameworks(value: &str) -> Vec<String> {
+    let mut target_frameworks = value
+        .split(';')
+        .map(str::trim)
+        .filter(|target_framework| !target_framework.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    target_frameworks.sort();
+    target_frameworks.dedup();
+    target_frameworks
+}
+
+/// Compares the declared project references and target frameworks with what the build did,
+/// conditions and custom targets can make them differ. Projects that were not built are skipped.
+pub fn compare(projects: &[Project], build: &Build) -> Vec<Finding> {
+    let current_dir = std::env::current_dir().unwrap();
+    let display = |path: &Path| relative_path(&current_dir, path).display().to_string();
+    let mut findings = Vec::new();
+
+    for project in projects.iter() {
+        let built = match build.projects.get(&project.path) {
+            Some(built) => built,
+            None => continue,
+        };
+
+        let declared = project
+            .project_references
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        for reference in declared.difference(&built.references) {
+            findings.push(Finding {
+                rule: NAME,
+                message: format!(
+                    "{} declares a reference to {} that the build did not use",
+                    display(&project.path),
+                    display(reference)
+                ),
+                paths: vec![project.path.clone(), reference.clone()],
+            });
+        }
+        for reference in built.references.difference(&declared) {
+            findings.push(Finding {
+                rule: NAME,
+                message: format!(
+                    "{} built {} without declaring a reference to it",
+                    display(&project.path),
+                    display(reference)
+                ),
+                paths: vec![project.path.clone(), reference.clone()],
+            });
+        }
+
+        let built_frameworks = built
+            .properties
+            .get("TargetFrameworks")
+            .or_else(|| built.properties.get("TargetFramework"))
+            .map(|value| split_target_frameworks(value))
+            .unwrap_or_default();
+        if !built_frameworks.is_empty() && built_frameworks != project.target_frameworks {
+            findings.push(Finding {
+                rule: NAME,
+                message: format!(
+                    "{} declares target frameworks {} but was built for {}",
+                    display(&project.path),
+                    project.target_frameworks.join(";"),
+                    built_frameworks.join(";")
+                ),
+                paths: vec![project.path.clone()],
+            });
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_log_reads_graph_and_initial_properties() {
+        let log = r#"
+     1>Project "/src/All.sln" on node 1 (default targets).
+     1>Project "/src/All.sln" (1) is building "/src/All.sln.metaproj" (2) on node 1 (default targets).
+     2>Project "/src/All.sln.metaproj" (2) is building "/src/A/A.csproj" (3) on node 1 (default targets).
+     3>Initial Properties:
+       AssemblyName = A
+       TargetFramework = net6.0
+
+     3>Project "/src/A/A.csproj" (3) is building "/src/B/B.csproj" (4:2) on node 1 (default targets).
+     4>Initial Properties:
+       TargetFramework = netstandard2.0
+"#;
+        let build = Build::parse_log(log);
+
+        assert_eq!(
+            build.projects.keys().collect::<Vec<_>>(),
+            vec![Path::new("/src/A/A.csproj"), Path::new("/src/B/B.csproj")]
+        );
+        let a = &build.projects[Path::new("/src/A/A.csproj")];
+        assert_eq!(
+            a.references.iter().collect::<Vec<_>>(),
+            vec![Path::new("/src/B/B.csproj")]
+        );
+        assert_eq!(a.properties["TargetFramework"], "net6.0");
+        assert_eq!(a.properties["AssemblyName"], "A");
+        let b = &build.projects[Path::new("/src/B/B.csproj")];
+        assert_eq!(b.properties["TargetFramework"], "netstandard2.0");
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    csproj::{self, Error, Project},
+    encoding, evaluation,
+    xml_extensions::write_file_atomic,
+};
+
+/// The directory in the .git directory of a repository that holds the cache.
+const CACHE_DIR_NAME: &'static str = "csprojtool-cache";
+const CACHE_FILE_NAME: &'static str = "projects.json";
+
+/// Increment when the way projects are parsed changes without a new release, together with the
+/// crate version it discards caches written by other versions.
+const CACHE_VERSION: u32 = 4;
+
+/// Whether parsed projects are cached, cleared by the global `--no-cache` flag.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Applies the options given on the command line, must be called before any project is listed.
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: String,
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+/// A parsed project with what the file and its imports looked like when it was parsed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    stamp: Option<Stamp>,
+    hash: u64,
+    project: Project,
+    /// The stamps of the imports of the project, in the same order.
+    import_stamps: Vec<Option<Stamp>>,
+}
+
+impl Entry {
+    /// Whether the imported files are unchanged and no Directory.Build.props or
+    /// Directory.Build.targets closer to the project was added.
+    fn imports_unchanged(&self) -> bool {
+        let (props, targets) = csproj::implicit_imports(&self.project.path);
+        self.import_stamps == import_stamps(&self.project.imports)
+            && props
+                .iter()
+                .chain(targets.iter())
+                .all(|path| self.project.imports.contains(path))
+    }
+}
+
+/// The modification time and size of a file, a file with the same stamp is assumed unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    modified_secs: u64,
+    modified_nanos: u32,
+    len: u64,
+}
+
+fn import_stamps(imports: &[PathBuf]) -> Vec<Option<Stamp>> {
+    imports
+        .iter()
+        .map(|path| std::fs::metadata(path).ok().as_ref().and_then(Stamp::of))
+        .collect()
+}
+
+impl Stamp {
+    fn of(metadata: &std::fs::Metadata) -> Option<Self> {
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        Some(Self {
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// The projects of a repository as parsed by earlier runs, stored in its .git directory.
+///
+/// A project is parsed again when its file has another modification time or size and different
+/// contents, or when one of its imports changed. Only the projects that were read in this run are
+/// saved, which drops the projects that were deleted.
+#[derive(Debug)]
+pub struct Cache {
+    /// The cache file, None when caching is disabled.
+    path: Option<PathBuf>,
+    loaded: BTreeMap<PathBuf, Entry>,
+    used: Mutex<BTreeMap<PathBuf, Entry>>,
+    changed: AtomicBool,
+}
+
+impl Cache {
+    /// Opens the cache of the repository at the root directory. Caching is disabled outside of
+    /// git repositories and with `--no-cache`.
+    pub fn open(root_dir: &Path) -> Self {
+        let git_dir = root_dir.join(".git");
+        let path = if ENABLED.load(Ordering::Relaxed) && git_dir.is_dir() {
+            Some(git_dir.join(CACHE_DIR_NAME).join(CACHE_FILE_NAME))
+        } else {
+            None
+        };
+        let loaded = path.as_deref().map(load).unwrap_or_default();
+        Self {
+            path,
+            loaded,
+            used: Mutex::new(BTreeMap::new()),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the cached project if the file did not change, parses it otherwise.
+    pub fn read_and_parse_project(&self, project_path: PathBuf) -> Result<Project, Error> {
+        if self.path.is_none() {
+            return csproj::read_and_parse_project(project_path);
+        }
+
+        let stamp = Stamp::of(&std::fs::metadata(&project_path)?);
+        let cached = self.loaded.get(&project_path);
+        let cached = cached.filter(|entry| entry.imports_unchanged());
+        if let Some(entry) = cached.filter(|entry| stamp.is_some() && entry.stamp == stamp) {
+            let project = entry.project.clone();
+            self.use_entry(project_path, entry.clone());
+            return Ok(project);
+        }
+
+        // Checkouts and touches change the modification time without changing the contents.
+        let contents = encoding::read_to_string(&project_path)?;
+        let hash = content_hash(&contents);
+        let project = match cached.filter(|entry| entry.hash == hash) {
+            Some(entry) => entry.project.clone(),
+            None => csproj::parse_project(project_path.clone(), &contents)?,
+        };
+        self.changed.store(true, Ordering::Relaxed);
+        self.use_entry(
+            project_path,
+            Entry {
+                stamp,
+                hash,
+                import_stamps: import_stamps(&project.imports),
+                project: project.clone(),
+            },
+        );
+        Ok(project)
+    }
+
+    fn use_entry(&self, project_path: PathBuf, entry: Entry) {
+        self.used.lock().unwrap().insert(project_path, entry);
+    }
+
+    /// Writes the projects that were read back to the cache if anything changed.
+    pub fn save(&self) {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+        let entries = std::mem::take(&mut *self.used.lock().unwrap());
+        if !self.changed.load(Ordering::Relaxed) && entries.len() == self.loaded.len() {
+            return;
+        }
+
+        debug!("Saving {} projects to {}", entries.len(), path.display());
+        let contents = serde_json::to_string(&CacheFile {
+            version: version(),
+            entries,
+        })
+        .unwrap();
+        if let Err(e) = write_file_atomic(path, contents.as_bytes()) {
+            warn!("Failed to write the cache {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn load(path: &Path) -> BTreeMap<PathBuf, Entry> {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(_) => return BTreeMap::new(),
+    };
+    match serde_json::from_slice::<CacheFile>(&contents) {
+        Ok(file) if file.version == version() => file.entries,
+        Ok(_) => {
+            debug!("Discarding {} written by another version", path.display());
+            BTreeMap::new()
+        }
+        Err(e) => {
+            warn!("Discarding the unreadable cache {}: {}", path.display(), e);
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Projects parsed with other `--msbuild-property` values differ, so those are part of the
+/// version as well.
+fn version() -> String {
+    let mut version = format!("{}+{}", env!("CARGO_PKG_VERSION"), CACHE_VERSION);
+    for (name, value) in evaluation::global_properties().unwrap_or_default() {
+        version.push_str(&format!(";{}={}", name, value));
+    }
+    version
+}
+
+fn content_hash(contents: &str) -> u64 {
+    let mut hasher = siphasher::sip::SipHasher::new();
+    hasher.write(contents.as_bytes());
+    hasher.finish()
+}
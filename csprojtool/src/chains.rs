@@ -0,0 +1,236 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{cli, list, output, path_extensions::relative_path};
+
+const ARG_FORMAT: &'static str = "format";
+const ARG_TOP: &'static str = "top";
+const CMD_CHAINS: &'static str = "chains";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct ChainsCommand {
+    search_path: PathBuf,
+    follow_project_references: bool,
+    top: usize,
+    format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Chain {
+    length: usize,
+    /// The projects from the referencing project down to the most deeply referenced one.
+    projects: Vec<PathBuf>,
+}
+
+impl ChainsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_CHAINS)
+            .about("List the longest chains of project references, the critical path of incremental builds")
+            .arg(cli::arg_search())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(
+                Arg::with_name(ARG_TOP)
+                    .long("top")
+                    .value_name("N")
+                    .help("The number of chains to list")
+                    .takes_value(true)
+                    .default_value("10")
+                    .validator(|value| {
+                        value
+                            .parse::<usize>()
+                            .map(|_| ())
+                            .map_err(|e| e.to_string())
+                    }),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_CHAINS)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            follow_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            top: matches.value_of(ARG_TOP).unwrap().parse().unwrap(),
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: self.follow_project_references,
+        });
+
+        let current_dir = std::env::current_dir().unwrap();
+        let chains = longest_chains(projects.iter().map(|project| {
+            (
+                project.path.as_path(),
+                project.project_references.as_slice(),
+            )
+        }))
+        .into_iter()
+        .take(self.top)
+        .map(|chain| Chain {
+            length: chain.len(),
+            projects: chain
+                .into_iter()
+                .map(|path| relative_path(&current_dir, path))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                for (index, chain) in chains.iter().enumerate() {
+                    if index > 0 {
+                        println!();
+                    }
+                    println!("{} projects", chain.length);
+                    for path in chain.projects.iter() {
+                        println!("  {}", path.display());
+                    }
+                }
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &chains).unwrap();
+                println!();
+            }
+        }
+    }
+}
+
+/// Returns the longest chain of project references from every project that no other project
+/// references, longest first. References to projects that are not listed end a chain and
+/// references that close a cycle are not followed, so projects that are only referenced from
+/// within a cycle start no chain.
+pub fn longest_chains<'a>(
+    projects: impl Iterator<Item = (&'a Path, &'a [PathBuf])>,
+) -> Vec<Vec<&'a Path>> {
+    /// Returns the length of the longest chain from the project, remembering the next project
+    /// of the chain.
+    fn depth<'a>(
+        path: &'a Path,
+        references: &HashMap<&'a Path, &'a [PathBuf]>,
+        longest: &mut HashMap<&'a Path, (usize, Option<&'a Path>)>,
+        visiting: &mut BTreeSet<&'a Path>,
+    ) -> usize {
+        if let Some((depth, _)) = longest.get(path) {
+            return *depth;
+        }
+        if !visiting.insert(path) {
+            return 0;
+        }
+        let mut deepest = (0, None);
+        for reference in references[path].iter().map(PathBuf::as_path) {
+            if !references.contains_key(reference) {
+                continue;
+            }
+            let depth = depth(reference, references, longest, visiting);
+            if depth > deepest.0 {
+                deepest = (depth, Some(reference));
+            }
+        }
+        visiting.remove(path);
+        longest.insert(path, (deepest.0 + 1, deepest.1));
+        deepest.0 + 1
+    }
+
+    let references = projects.collect::<HashMap<_, _>>();
+    let referenced = references
+        .values()
+        .flat_map(|references| references.iter().map(PathBuf::as_path))
+        .collect::<BTreeSet<_>>();
+    let mut roots = references
+        .keys()
+        .copied()
+        .filter(|path| !referenced.contains(path))
+        .collect::<Vec<_>>();
+    roots.sort();
+
+    let mut longest = HashMap::new();
+    let mut visiting = BTreeSet::new();
+    let mut chains = roots
+        .into_iter()
+        .map(|root| {
+            depth(root, &references, &mut longest, &mut visiting);
+            let mut chain = vec![root];
+            while let Some((_, Some(next))) = longest.get(chain.last().unwrap()) {
+                chain.push(*next);
+            }
+            chain
+        })
+        .collect::<Vec<_>>();
+    // The sort is stable, chains of the same length stay ordered by their first project.
+    chains.sort_by_key(|chain| std::cmp::Reverse(chain.len()));
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_chains_start_at_unreferenced_projects() {
+        let references = [
+            (PathBuf::from("/A.csproj"), vec![]),
+            (PathBuf::from("/B.csproj"), vec![PathBuf::from("/A.csproj")]),
+            (
+                PathBuf::from("/C.csproj"),
+                vec![PathBuf::from("/A.csproj"), PathBuf::from("/B.csproj")],
+            ),
+            (
+                PathBuf::from("/D.csproj"),
+                vec![PathBuf::from("/A.csproj"), PathBuf::from("/Missing.csproj")],
+            ),
+        ];
+
+        let chains = longest_chains(
+            references
+                .iter()
+                .map(|(path, references)| (path.as_path(), references.as_slice())),
+        );
+        assert_eq!(
+            chains
+                .iter()
+                .map(|chain| chain.iter().map(|path| path.to_str().unwrap()).collect())
+                .collect::<Vec<Vec<_>>>(),
+            vec![
+                vec!["/C.csproj", "/B.csproj", "/A.csproj"],
+                vec!["/D.csproj", "/A.csproj"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+mod adjacent_groups;
+mod output_paths;
+pub mod package_versions;
+mod project_directories;
+mod project_references;
+mod public_api;
+pub mod rooted_project_references;
+
+use std::path::PathBuf;
+
+use log::debug;
+use serde::Serialize;
+
+use crate::{
+    affected::affected_by,
+    binlog, cli,
+    csproj::Project,
+    error::{Error, Result},
+    git, list, output,
+    output_format::{print_issues, render_summary, Links, OutputFormat},
+    path_extensions::{relative_path, PathExt},
+    plan::Effects,
+    sarif,
+};
+
+const CMD_CHECK: &'static str = "check";
+const ARG_BINLOG: &'static str = "binlog";
+const ARG_CHANGED_ONLY: &'static str = "changed-only";
+const ARG_FIX: &'static str = "fix";
+const ARG_RULE: &'static str = "rule";
+const ARG_SINCE: &'static str = "since";
+
+/// A problem found by a rule, involving one or more files.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub message: String,
+    pub paths: Vec<PathBuf>,
+}
+
+/// Fixes the findings of a rule and returns those it could not fix.
+type Fix = fn(&[Project], &mut Effects) -> Vec<Finding>;
+
+/// The projects whose changes can affect the findings of a rule for a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// Only the project itself.
+    Project,
+    /// The project and the projects it references.
+    Dependencies,
+    /// Any project, the rule compares projects with each other.
+    Repository,
+}
+
+struct Rule {
+    name: &'static str,
+    scope: Scope,
+    check: fn(&[Project]) -> Vec<Finding>,
+    fix: Option<Fix>,
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        name: adjacent_groups::NAME,
+        scope: Scope::Project,
+        check: adjacent_groups::check,
+        fix: Some(adjacent_groups::fix),
+    },
+    Rule {
+        name: output_paths::NAME,
+        scope: Scope::Repository,
+        check: output_paths::check,
+        fix: None,
+    },
+    Rule {
+        name: package_versions::NAME,
+        scope: Scope::Repository,
+        check: package_versions::check,
+        fix: None,
+    },
+    Rule {
+        name: project_directories::NAME,
+        scope: Scope::Repository,
+        check: project_directories::check,
+        fix: Some(project_directories::fix),
+    },
+    Rule {
+        name: project_references::NAME,
+        scope: Scope::Dependencies,
+        check: project_references::check,
+        fix: Some(project_references::fix),
+    },
+    Rule {
+        name: rooted_project_references::NAME,
+        scope: Scope::Project,
+        check: rooted_project_references::check,
+        fix: Some(rooted_project_references::fix),
+    },
+    Rule {
+        name: public_api::NAME,
+        scope: Scope::Project,
+        check: public_api::check,
+        fix: Some(public_api::fix),
+    },
+];
+
+#[derive(Debug)]
+pub struct CheckCommand {
+    search_path: PathBuf,
+    follow_incoming_project_references: bool,
+    follow_outgoing_project_references: bool,
+    rules: Vec<String>,
+    fix: bool,
+    since: Option<String>,
+    binlog: Option<PathBuf>,
+    plan_json: Option<PathBuf>,
+    format: OutputFormat,
+    links: Links,
+}
+
+impl CheckCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+        use clap::SubCommand;
+
+        SubCommand::with_name(CMD_CHECK)
+            .about("Check projects for common problems")
+            .arg(cli::arg_search())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(cli::arg_do_not_follow_incoming_project_references())
+            .arg(
+                Arg::with_name(ARG_RULE)
+                    .long("rule")
+                    .value_name("RULE")
+                    .help("Only run the given rule, can be passed multiple times")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name(ARG_FIX)
+                    .long("fix")
+                    .takes_value(false)
+                    .help("Fix the findings of rules that support it"),
+            )
+            .arg(
+                Arg::with_name(ARG_CHANGED_ONLY)
+                    .long("changed-only")
+                    .takes_value(false)
+                    .help("Only check the projects that changed and the projects affected by them"),
+            )
+            .arg(
+                Arg::with_name(ARG_SINCE)
+                    .long("since")
+                    .value_name("REV")
+                    .help("The revision that --changed-only compares against")
+                    .default_value("origin/main")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_BINLOG)
+                    .long("binlog")
+                    .value_name("BINLOG_PATH")
+                    .help("Compare the declared project references and target frameworks with those of the build in an MSBuild binary log")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_plan_json())
+            .arg(cli::arg_format())
+            .arg(cli::arg_url_template())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_CHECK)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            follow_incoming_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES),
+            follow_outgoing_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            rules: matches
+                .values_of(ARG_RULE)
+                .map(|values| values.map(str::to_owned).collect())
+                .unwrap_or_default(),
+            fix: matches.is_present(ARG_FIX),
+            since: if matches.is_present(ARG_CHANGED_ONLY) {
+                matches.value_of(ARG_SINCE).map(str::to_owned)
+            } else {
+                None
+            },
+            binlog: matches.value_of_os(ARG_BINLOG).map(PathBuf::from),
+            plan_json: cli::get_plan_json(matches),
+            format: cli::get_format(matches),
+            links: cli::get_links(matches),
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        if self.format == OutputFormat::Sarif {
+            // The log is json as well, messages about fixes have to go to stderr.
+            output::init(true);
+        }
+
+        for name in self.rules.iter() {
+            if !RULES.iter().any(|rule| rule.name == name) {
+                return Err(Error::user(format!(
+                    "Unknown rule {}, expected one of: {}",
+                    name,
+                    RULES
+                        .iter()
+                        .map(|rule| rule.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        }
+
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: self.follow_incoming_project_references,
+            follow_outgoing_project_references: self.follow_outgoing_project_references,
+        });
+
+        // The projects that changed themselves and those that changed with their references.
+        let changed = self.since.as_ref().map(|since| -> Result<_> {
+            let search_path = self.search_path.simplified_absolute().unwrap();
+            let files = git::changed_files(
+                if search_path.is_file() {
+                    search_path.parent().unwrap()
+                } else {
+                    &search_path
+                },
+                since,
+            )?;
+            let changed = affected_by(&projects, &files, false);
+            let dependents = affected_by(&projects, &files, true);
+            debug!(
+                "{} changed files affect {} projects",
+                files.len(),
+                dependents.len()
+            );
+            Ok((files, changed, dependents))
+        });
+        let changed = changed.transpose()?;
+
+        let mut effects = Effects::new(CMD_CHECK, self.plan_json.as_deref());
+        let mut findings = Vec::new();
+
+        for rule in RULES
+            .iter()
+            .filter(|rule| self.rules.is_empty() || self.rules.iter().any(|name| name == rule.name))
+        {
+            let rule_projects = match (&changed, rule.scope) {
+                (None, _) => projects.clone(),
+                (Some((_, changed, _)), Scope::Repository) if changed.is_empty() => Vec::new(),
+                (Some(_), Scope::Repository) => projects.clone(),
+                (Some((_, affected, _)), Scope::Project)
+                | (Some((_, _, affected)), Scope::Dependencies) => projects
+                    .iter()
+                    .filter(|project| affected.contains(&project.path))
+                    .cloned()
+                    .collect(),
+            };
+            if rule_projects.is_empty() {
+                debug!(
+                    "Skipping {} because no project it checks changed",
+                    rule.name
+                );
+                continue;
+            }
+
+            let mut rule_findings = match rule.fix {
+                Some(fix) if self.fix => fix(&rule_projects, &mut effects),
+                _ => (rule.check)(&rule_projects),
+            };
+
+            // Rules that compare projects are given every project, only the findings that
+            // involve a change are relevant.
+            if let (Some((files, changed, _)), Scope::Repository) = (&changed, rule.scope) {
+                rule_findings.retain(|finding| {
+                    finding
+                        .paths
+                        .iter()
+                        .any(|path| changed.contains(path) || files.contains(path))
+                });
+            }
+
+            findings.extend(rule_findings);
+        }
+
+        effects.finish();
+
+        if let Some(binlog_path) = self.binlog.as_ref() {
+            let build = binlog::Build::read(binlog_path);
+            debug!(
+                "{} built {} projects",
+                binlog_path.display(),
+                build.projects.len()
+            );
+            findings.extend(binlog::compare(&projects, &build));
+        }
+
+        match self.format {
+            format if format.reports_issues() => print_issues(
+                format,
+                &findings.iter().map(sarif::Issue::from).collect::<Vec<_>>(),
+                &self.links.root,
+            ),
+            _ if output::is_json() => output::print_json(&findings),
+            OutputFormat::Text => {
+                let current_dir = std::env::current_dir().unwrap();
+                for finding in findings.iter() {
+                    println!("[{}] {}", finding.rule, finding.message);
+                    for path in finding.paths.iter() {
+                        println!("  {}", relative_path(&current_dir, path).display());
+                    }
+                }
+            }
+            format => print!(
+                "{}",
+                render_summary(format, "csprojtool check", &findings, &self.links)
+            ),
+        }
+
+        if !findings.is_empty() {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+        Ok(())
+    }
+}
+
+/// Runs every rule without fixing anything and returns their findings.
+pub fn check_all(projects: &[Project]) -> Vec<Finding> {
+    check_all_until(projects, &|| false).unwrap()
+}
+
+/// Runs every rule like `check_all`, but gives up between rules once `cancelled` returns true.
+pub fn check_all_until(projects: &[Project], cancelled: &dyn Fn() -> bool) -> Option<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for rule in RULES.iter() {
+        if cancelled() {
+            return None;
+        }
+        findings.extend((rule.check)(projects));
+    }
+    Some(findings)
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use log::warn;
+
+use crate::check::Finding;
+use crate::csproj::Project;
+use crate::encoding;
+use crate::fmt::merge_adjacent_groups;
+use crate::output::status;
+use crate::path_extensions::relative_path;
+use crate::plan::Effects;
+use crate::xml_extensions::parse_xml_str;
+
+pub const NAME: &'static str = "adjacent-groups";
+
+/// Flags ItemGroups and PropertyGroups that directly follow a group with the same condition,
+/// which the IDE and NuGet leave behind when they add items one at a time.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    projects
+        .iter()
+        .filter_map(|project| {
+            let count = mergeable_count(&project.path)?;
+            Some(Finding {
+                rule: NAME,
+                message: format!("{} groups can be merged into the group before them", count),
+                paths: vec![project.path.clone()],
+            })
+        })
+        .collect()
+}
+
+/// Merges the adjacent groups, every finding can be fixed.
+pub fn fix(projects: &[Project], effects: &mut Effects) -> Vec<Finding> {
+    let current_dir = std::env::current_dir().unwrap();
+
+    for project in projects.iter() {
+        if mergeable_count(&project.path).is_none() {
+            continue;
+        }
+
+        effects
+            .transform_xml_file(&project.path, |mut root| {
+                let count = merge_adjacent_groups(&mut root);
+                status!(
+                    "Merged {} groups in {}",
+                    count,
+                    relative_path(&current_dir, &project.path).display()
+                );
+                Some(root)
+            })
+            .unwrap();
+    }
+
+    Vec::new()
+}
+
+fn mergeable_count(project_path: &Path) -> Option<usize> {
+    let contents = match encoding::read_to_string(project_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Failed to read {}: {}", project_path.display(), e);
+            return None;
+        }
+    };
+    let mut root = match parse_xml_str(&contents) {
+        Ok(root) => root,
+        Err(e) => {
+            warn!("Failed to parse {}: {:?}", project_path.display(), e);
+            return None;
+        }
+    };
+
+    match merge_adjacent_groups(&mut root) {
+        0 => None,
+        count => Some(count),
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::artifacts::artifacts_layout;
+use crate::check::Finding;
+use crate::csproj::Project;
+use crate::evaluation::{global_properties, Evaluator};
+use crate::path_extensions::relative_path;
+
+pub const NAME: &'static str = "output-paths";
+
+/// The configurations in which the output paths are compared, unless properties are given with
+/// `--msbuild-property`. Projects only race when they are built in the same configuration.
+const CONFIGURATIONS: [&'static str; 2] = ["Debug", "Release"];
+
+/// Flags projects that share an output directory, which makes parallel builds race.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    let configurations = match global_properties() {
+        Some(properties) => vec![properties],
+        None => CONFIGURATIONS
+            .iter()
+            .map(|configuration| {
+                vec![
+                    ("Configuration".to_owned(), (*configuration).to_owned()),
+                    ("Platform".to_owned(), "AnyCPU".to_owned()),
+                ]
+            })
+            .collect(),
+    };
+
+    // Keyed by the lowercase path because the file system is usually case insensitive on Windows,
+    // then by the configuration.
+    let mut clusters: BTreeMap<String, (String, BTreeMap<String, BTreeSet<PathBuf>>)> =
+        BTreeMap::new();
+
+    for project in projects.iter() {
+        // The artifacts layout separates projects by name rather than by directory, so projects
+        // with the same name in different directories collide.
+        let artifacts_output_dir = artifacts_layout(&project.path)
+            .map(|layout| layout.output_dir(&project.path).display().to_string());
+
+        for properties in configurations.iter() {
+            let configuration = properties
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("Configuration"))
+                .map_or("", |(_, value)| value.as_str());
+
+            let mut output_paths = output_paths(project, properties);
+            if output_paths.is_empty() {
+                output_paths.extend(artifacts_output_dir.clone());
+            }
+
+            for output_path in output_paths {
+                clusters
+                    .entry(output_path.to_lowercase())
+                    .or_insert_with(|| (output_path, BTreeMap::new()))
+                    .1
+                    .entry(configuration.to_owned())
+                    .or_default()
+                    .insert(project.path.clone());
+            }
+        }
+    }
+
+    let current_dir = std::env::current_dir().unwrap();
+
+    clusters
+        .into_iter()
+        .filter_map(|(_, (output_path, configurations))| {
+            let configurations = configurations
+                .into_iter()
+                .filter(|(_, project_paths)| project_paths.len() > 1)
+                .collect::<Vec<_>>();
+            if configurations.is_empty() {
+                return None;
+            }
+            let project_paths = configurations
+                .iter()
+                .flat_map(|(_, project_paths)| project_paths.iter().cloned())
+                .collect::<BTreeSet<_>>();
+            let names = configurations
+                .iter()
+                .map(|(configuration, _)| configuration.as_str())
+                .filter(|configuration| !configuration.is_empty())
+                .collect::<Vec<_>>();
+
+            let display = if Path::new(&output_path).is_absolute() {
+                relative_path(&current_dir, Path::new(&output_path))
+                    .display()
+                    .to_string()
+            } else {
+                output_path
+            };
+            let mut message = format!(
+                "{} projects write to the output path {}",
+                project_paths.len(),
+                display
+            );
+            if !names.is_empty() {
+                message.push_str(&format!(" in {}", names.join(" and ")));
+            }
+            Some(Finding {
+                rule: NAME,
+                message,
+                paths: project_paths.into_iter().collect(),
+            })
+        })
+        .collect()
+}
+
+/// The resolved OutputPath and OutDir of the project when it is built with the properties. Values
+/// that still depend on unknown properties are returned as written.
+fn output_paths(project: &Project, properties: &[(String, String)]) -> Vec<String> {
+    let mut evaluator = Evaluator::new(&project.path, properties);
+    // Defined by the common targets, which are imported after the project, but commonly used in
+    // output paths anyway.
+    evaluator.set_property("ProjectDir", "$(MSBuildThisFileDirectory)");
+    evaluator.set_property("ProjectName", "$(MSBuildProjectName)");
+
+    let mut values = BTreeMap::new();
+    for property in project.properties.iter() {
+        if !property
+            .condition
+            .as_deref()
+            .map_or(true, |condition| evaluator.condition(condition))
+        {
+            continue;
+        }
+        if property.name == "OutputPath" || property.name == "OutDir" {
+            values.insert(property.name.as_str(), property.value.as_str());
+        }
+        evaluator.set_property(&property.name, &property.value);
+    }
+
+    values
+        .values()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| {
+            if !evaluator.defines_all(value) {
+                return value.to_string();
+            }
+            match evaluator.resolve_path(value) {
+                Some(path) => path.display().to_string(),
+                None => value.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csproj::Property;
+
+    fn project(path: &str, properties: &[(&str, &str, Option<&str>)]) -> Project {
+        Project {
+            properties: properties
+                .iter()
+                .map(|&(name, value, condition)| Property {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                    condition: condition.map(str::to_owned),
+                })
+                .collect(),
+            ..Project::new(path)
+        }
+    }
+
+    #[test]
+    fn output_paths_are_compared_per_configuration() {
+        let debug = Some("'$(Configuration)|$(Platform)' == 'Debug|AnyCPU'");
+        let release = Some("'$(Configuration)' == 'Release'");
+        let projects = [
+            project("/repo/A/A.csproj", &[("OutputPath", r"..\out\", debug)]),
+            project("/repo/B/B.csproj", &[("OutputPath", r"..\out\", release)]),
+            project(
+                "/repo/C/C.csproj",
+                &[
+                    ("OutputPath", r"..\shared\", None),
+                    ("OutputPath", r"bin\$(Configuration)\", release),
+                ],
+            ),
+            project("/repo/D/D.csproj", &[("OutputPath", r"..\shared\", None)]),
+        ];
+
+        let findings = check(&projects);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.ends_with(" in Debug"));
+        assert_eq!(
+            findings[0].paths,
+            vec![
+                PathBuf::from("/repo/C/C.csproj"),
+                PathBuf::from("/repo/D/D.csproj")
+            ]
+        );
+    }
+}
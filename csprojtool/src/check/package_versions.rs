@@ -0,0 +1,35 @@
+use crate::check::Finding;
+use crate::csproj::Project;
+use crate::package_version::{cmp_version_str, package_usages, PackageUsage};
+
+pub const NAME: &'static str = "package-versions";
+
+/// Returns the packages that are referenced with more than one version.
+pub fn conflicts(projects: &[Project]) -> Vec<PackageUsage> {
+    package_usages(projects)
+        .into_values()
+        .filter(|usage| usage.versions.len() > 1)
+        .collect()
+}
+
+/// Flags packages that are referenced with more than one version.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    conflicts(projects)
+        .into_iter()
+        .map(|usage| {
+            let mut versions = usage.versions.keys().cloned().collect::<Vec<_>>();
+            versions.sort_by(|a, b| cmp_version_str(a, b));
+            let mut paths = usage.versions.into_values().flatten().collect::<Vec<_>>();
+            paths.sort();
+            Finding {
+                rule: NAME,
+                message: format!(
+                    "{} is referenced with versions {}",
+                    usage.name,
+                    versions.join(", ")
+                ),
+                paths,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use xmltree::XMLNode;
+
+use crate::check::Finding;
+use crate::csproj::Project;
+use crate::output::status;
+use crate::path_extensions::{from_msbuild_path, relative_path, to_msbuild_path, PathExt};
+use crate::plan::Effects;
+use crate::xml_extensions::{depth_first_visit_nodes, parse_xml_str, process_tree, xml_to_string};
+
+pub const NAME: &'static str = "one-project-per-directory";
+
+/// Item attributes that hold paths relative to the project.
+const PATH_ATTRIBUTES: &[&str] = &["Include", "Update", "Remove", "Project"];
+
+/// Flags directories that contain more than one project. The projects share their source files
+/// through the default globs and the directory can not be moved as a whole.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    shared_directories(projects)
+        .into_iter()
+        .map(|(dir, projects)| finding(&dir, &projects))
+        .collect()
+}
+
+/// Moves each project out of a shared directory into a subdirectory named after it, so
+/// `Foo/A.csproj` becomes `Foo/A/A.csproj`. A project named after the directory stays. Source
+/// files are not moved because they can not be attributed to a project reliably, the moved
+/// projects are printed so that their files can be moved by hand.
+pub fn fix(projects: &[Project], effects: &mut Effects) -> Vec<Finding> {
+    let current_dir = std::env::current_dir().unwrap();
+    let mut findings = Vec::new();
+    // The current location of projects that have been moved.
+    let mut moved = HashMap::<PathBuf, PathBuf>::new();
+
+    for (dir, shared) in shared_directories(projects) {
+        let dir_name = dir.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let mut remaining = shared.len();
+
+        for old_file in shared.iter() {
+            let stem = old_file.file_stem().unwrap().to_string_lossy();
+            if stem.eq_ignore_ascii_case(dir_name) {
+                continue;
+            }
+
+            let new_dir = dir.join(stem.as_ref());
+            if new_dir.exists() {
+                warn!(
+                    "Not moving {} because {} already exists",
+                    relative_path(&current_dir, old_file).display(),
+                    relative_path(&current_dir, &new_dir).display()
+                );
+                continue;
+            }
+            let new_file = new_dir.join(old_file.file_name().unwrap());
+
+            move_project(effects, old_file, &new_file);
+            moved.insert(old_file.clone(), new_file.clone());
+            remaining -= 1;
+
+            for project in projects
+                .iter()
+                .filter(|project| project.project_references.contains(old_file))
+            {
+                let path = moved.get(&project.path).unwrap_or(&project.path);
+                rewrite_references(effects, path, old_file, &new_file);
+            }
+
+            status!(
+                "Moved {} to {}, move its source files along",
+                relative_path(&current_dir, old_file).display(),
+                relative_path(&current_dir, &new_file).display()
+            );
+        }
+
+        if remaining > 1 {
+            findings.push(finding(&dir, &shared));
+        }
+    }
+
+    findings
+}
+
+fn shared_directories(projects: &[Project]) -> BTreeMap<PathBuf, Vec<PathBuf>> {
+    let mut dirs = BTreeMap::<PathBuf, Vec<PathBuf>>::new();
+    for project in projects.iter() {
+        dirs.entry(project.path.parent().unwrap().to_owned())
+            .or_default()
+            .push(project.path.clone());
+    }
+    dirs.retain(|_, projects| projects.len() > 1);
+    dirs
+}
+
+fn finding(dir: &Path, projects: &[PathBuf]) -> Finding {
+    let current_dir = std::env::current_dir().unwrap();
+    Finding {
+        rule: NAME,
+        message: format!(
+            "{} projects share the directory {}",
+            projects.len(),
+            relative_path(&current_dir, dir).display()
+        ),
+        paths: projects.to_vec(),
+    }
+}
+
+/// Writes the project to its new location with its relative paths rebased and removes the old
+/// file.
+fn move_project(effects: &mut Effects, old_file: &Path, new_file: &Path) {
+    let old_dir = old_file.parent().unwrap();
+    let new_dir = new_file.parent().unwrap();
+
+    let contents = effects.read_to_string(old_file).unwrap();
+    let root = parse_xml_str(&contents).unwrap();
+    let mut root_node = XMLNode::Element(root);
+
+    depth_first_visit_nodes(&mut root_node, |node| match node {
+        XMLNode::Element(element) => {
+            for (name, value) in element.attributes.iter_mut() {
+                if PATH_ATTRIBUTES.contains(&name.as_str()) {
+                    if let Some(rebased) = rebase_path(effects, value, old_dir, new_dir) {
+                        *value = rebased;
+                    }
+                }
+            }
+        }
+        XMLNode::Text(text) => {
+            if let Some(rebased) = rebase_path(effects, text.trim(), old_dir, new_dir) {
+                *text = rebased;
+            }
+        }
+        _ => {}
+    });
+
+    let root = match root_node {
+        XMLNode::Element(root) => root,
+        _ => unreachable!(),
+    };
+
+    effects
+        .write_file(new_file, xml_to_string(&root), Vec::new())
+        .unwrap();
+    effects.remove_file(old_file).unwrap();
+    effects.git_add(new_file).unwrap();
+}
+
+/// Returns the path relative to the new directory if the value is a relative path to a file that
+/// exists relative to the old directory. Wildcards and properties are left alone.
+fn rebase_path(effects: &Effects, value: &str, old_dir: &Path, new_dir: &Path) -> Option<String> {
+    if value.is_empty() || value.contains("$(") || value.contains('*') || value.contains(';') {
+        return None;
+    }
+    let path = from_msbuild_path(value);
+    if path.has_root() {
+        return None;
+    }
+
+    let abs_path = old_dir.join(&path).simplify();
+    if !effects.is_file(&abs_path) && !abs_path.is_dir() {
+        return None;
+    }
+    Some(to_msbuild_path(&relative_path(new_dir, &abs_path)))
+}
+
+fn rewrite_references(
+    effects: &mut Effects,
+    project_path: &Path,
+    old_file: &Path,
+    new_file: &Path,
+) {
+    let project_dir = project_path.parent().unwrap();
+    let mut edited = false;
+
+    effects
+        .transform_xml_file(project_path, |mut root| {
+            process_tree(&mut root, |element| {
+                if element.name != "ProjectReference" {
+                    return;
+                }
+                if let Some(include) = element.attributes.get_mut("Include") {
+                    if project_dir.join(from_msbuild_path(include)).simplify() == old_file {
+                        *include = to_msbuild_path(&relative_path(project_dir, new_file));
+                        edited = true;
+                    }
+                }
+            });
+
+            if edited {
+                Some(root)
+            } else {
+                None
+            }
+        })
+        .unwrap();
+}
@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+use log::warn;
+use uuid::Uuid;
+
+use crate::check::{rooted_project_references, Finding};
+use crate::csproj::Project;
+use crate::encoding;
+use crate::output::status;
+use crate::path_extensions::{from_msbuild_path, relative_path, to_msbuild_path, PathExt};
+use crate::plan::Effects;
+use crate::utils::path_extension_is_project;
+use crate::xml_extensions::process_tree;
+
+pub const NAME: &'static str = "project-references";
+
+/// A ProjectReference Include that does not match the canonical path of its target.
+#[derive(Debug)]
+struct Drift {
+    include: String,
+    canonical: Option<String>,
+}
+
+/// Flags project references whose Include differs from the relative path to the target as it
+/// exists on disk, or whose target no longer exists.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    drifts(projects)
+        .into_iter()
+        .flat_map(|(project_path, drifts)| {
+            drifts
+                .into_iter()
+                .map(move |drift| finding(&project_path, &drift))
+        })
+        .collect()
+}
+
+/// Rewrites every ProjectReference Include to the canonical path of its target and returns the
+/// findings for references that could not be resolved.
+pub fn fix(projects: &[Project], effects: &mut Effects) -> Vec<Finding> {
+    let current_dir = std::env::current_dir().unwrap();
+    let mut findings = Vec::new();
+
+    for (project_path, drifts) in drifts(projects) {
+        let rewrites = drifts
+            .iter()
+            .filter_map(|drift| Some((drift.include.as_str(), drift.canonical.as_deref()?)))
+            .collect::<HashMap<_, _>>();
+
+        if !rewrites.is_empty() {
+            effects
+                .transform_xml_file(&project_path, |mut root| {
+                    process_tree(&mut root, |element| {
+                        if element.name == "ProjectReference" {
+                            if let Some(include) = element.attributes.get_mut("Include") {
+                                if let Some(&canonical) = rewrites.get(include.as_str()) {
+                                    *include = canonical.to_owned();
+                                }
+                            }
+                        }
+                    });
+                    Some(root)
+                })
+                .unwrap();
+            status!(
+                "Rewrote {} project references in {}",
+                rewrites.len(),
+                relative_path(&current_dir, &project_path).display()
+            );
+        }
+
+        findings.extend(
+            drifts
+                .iter()
+                .filter(|drift| drift.canonical.is_none())
+                .map(|drift| finding(&project_path, drift)),
+        );
+    }
+
+    findings
+}
+
+fn finding(project_path: &Path, drift: &Drift) -> Finding {
+    Finding {
+        rule: NAME,
+        message: match &drift.canonical {
+            Some(canonical) => format!(
+                "Project reference {} should be written as {}",
+                drift.include, canonical
+            ),
+            None => format!(
+                "Project reference {} does not resolve to a project",
+                drift.include
+            ),
+        },
+        paths: vec![project_path.to_owned()],
+    }
+}
+
+fn drifts(projects: &[Project]) -> BTreeMap<PathBuf, Vec<Drift>> {
+    let mut dirs = DirCache::default();
+    let mut result = BTreeMap::new();
+
+    for project in projects.iter() {
+        let project_dir = project.path.parent().unwrap();
+
+        let contents = match encoding::read_to_string(&project.path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to read {}: {}", project.path.display(), e);
+                continue;
+            }
+        };
+        let document = match roxmltree::Document::parse(&contents) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!("Failed to parse {}: {}", project.path.display(), e);
+                continue;
+            }
+        };
+
+        let mut drifts = Vec::new();
+        for node in document
+            .descendants()
+            .filter(|node| node.tag_name().name() == "ProjectReference")
+        {
+            let include = match node.attribute("Include") {
+                Some(include) if !include.contains("$(") => include,
+                _ => continue,
+            };
+            // These are reported and fixed by their own rule.
+            if rooted_project_references::is_rooted(include) {
+                continue;
+            }
+
+            let path = project_dir.join(from_msbuild_path(include)).simplify();
+            let guid = node
+                .children()
+                .find(|child| child.tag_name().name() == "Project")
+                .and_then(|child| child.text())
+                .and_then(|text| Uuid::parse_str(text.trim().trim_matches(&['{', '}'][..])).ok());
+
+            let canonical = dirs
+                .actual_path(&path)
+                .or_else(|| find_renamed(&mut dirs, &path, guid, projects))
+                .map(|target| to_msbuild_path(&relative_path(project_dir, &target)));
+
+            if canonical.as_deref() != Some(include) {
+                drifts.push(Drift {
+                    include: include.to_owned(),
+                    canonical,
+                });
+            }
+        }
+
+        if !drifts.is_empty() {
+            result.insert(project.path.clone(), drifts);
+        }
+    }
+
+    result
+}
+
+/// Finds the project a reference pointed to before it was moved or renamed, by the project GUID
+/// that old style references carry, by the only project left in the referenced directory, or by
+/// the only project in the repository with the same file name.
+fn find_renamed(
+    dirs: &mut DirCache,
+    path: &Path,
+    guid: Option<Uuid>,
+    projects: &[Project],
+) -> Option<PathBuf> {
+    if let Some(guid) = guid {
+        if let Some(project) = projects
+            .iter()
+            .find(|project| project.project_guid == Some(guid))
+        {
+            return Some(project.path.clone());
+        }
+    }
+
+    if let Some(dir) = path.parent().and_then(|dir| dirs.actual_path(dir)) {
+        let mut csproj_paths = dirs
+            .entries(&dir)
+            .iter()
+            .map(|name| dir.join(name))
+            .filter(|path| path_extension_is_project(path) && path.is_file());
+        if let (Some(csproj_path), None) = (csproj_paths.next(), csproj_paths.next()) {
+            return Some(csproj_path);
+        }
+    }
+
+    let file_name = path.file_name()?.to_str()?;
+    let mut candidates = projects.iter().filter(|project| {
+        project
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.eq_ignore_ascii_case(file_name))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(project), None) => Some(project.path.clone()),
+        _ => None,
+    }
+}
+
+/// Caches directory listings to look up the casing of paths as they exist on disk.
+#[derive(Default)]
+struct DirCache {
+    entries: HashMap<PathBuf, Vec<OsString>>,
+}
+
+impl DirCache {
+    fn entries(&mut self, dir: &Path) -> &[OsString] {
+        self.entries.entry(dir.to_owned()).or_insert_with(|| {
+            std::fs::read_dir(dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.file_name())
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    /// Returns the path with the casing used on disk, matching components case insensitively
+    /// when there is no exact match, or None if the path does not exist.
+    fn actual_path(&mut self, path: &Path) -> Option<PathBuf> {
+        let mut actual = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => {
+                    let entries = self.entries(&actual);
+                    let name = entries
+                        .iter()
+                        .find(|entry| entry.as_os_str() == name)
+                        .or_else(|| {
+                            let name = name.to_str()?.to_lowercase();
+                            entries.iter().find(|entry| {
+                                entry.to_str().map(str::to_lowercase).as_ref() == Some(&name)
+                            })
+                        })?
+                        .clone();
+                    actual.push(name);
+                }
+                component => actual.push(component),
+            }
+        }
+        Some(actual)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_file(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "<Project />").unwrap();
+    }
+
+    #[test]
+    fn actual_path_restores_the_casing_on_disk() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        create_file(&dir.join("Src").join("Lib").join("Lib.csproj"));
+
+        let mut dirs = DirCache::default();
+        assert_eq!(
+            dirs.actual_path(&dir.join("src").join("LIB").join("lib.csproj")),
+            Some(dir.join("Src").join("Lib").join("Lib.csproj"))
+        );
+        assert_eq!(
+            dirs.actual_path(&dir.join("Src").join("Other").join("Lib.csproj")),
+            None
+        );
+    }
+
+    #[test]
+    fn find_renamed_matches_the_guid_the_lone_project_or_the_file_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        create_file(&dir.join("Lone").join("Renamed.csproj"));
+
+        let guid = Uuid::parse_str("5b1d2a44-6c0e-4d5f-9f7a-2f1c3e4b5a69").unwrap();
+        let projects = [
+            Project {
+                project_guid: Some(guid),
+                ..Project::new(dir.join("Moved").join("ByGuid.csproj"))
+            },
+            Project::new(dir.join("Moved").join("Unique.csproj")),
+            Project::new(dir.join("A").join("Twice.csproj")),
+            Project::new(dir.join("B").join("Twice.csproj")),
+        ];
+
+        let mut dirs = DirCache::default();
+        let gone = dir.join("Gone");
+        assert_eq!(
+            find_renamed(&mut dirs, &gone.join("Old.csproj"), Some(guid), &projects),
+            Some(projects[0].path.clone())
+        );
+        assert_eq!(
+            find_renamed(
+                &mut dirs,
+                &dir.join("Lone").join("Old.csproj"),
+                None,
+                &projects
+            ),
+            Some(dir.join("Lone").join("Renamed.csproj"))
+        );
+        assert_eq!(
+            find_renamed(&mut dirs, &gone.join("unique.csproj"), None, &projects),
+            Some(projects[1].path.clone())
+        );
+        assert_eq!(
+            find_renamed(&mut dirs, &gone.join("Twice.csproj"), None, &projects),
+            None
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use xmltree::{Element, XMLNode};
+
+use crate::add_package::package_element;
+use crate::check::Finding;
+use crate::cpm;
+use crate::csproj::Project;
+use crate::output::status;
+use crate::package_version::{cmp_version_str, package_usages};
+use crate::path_extensions::relative_path;
+use crate::plan::Effects;
+use crate::xml_extensions::add_item;
+
+pub const NAME: &'static str = "public-api";
+
+const SHIPPED_FILE_NAME: &'static str = "PublicAPI.Shipped.txt";
+const UNSHIPPED_FILE_NAME: &'static str = "PublicAPI.Unshipped.txt";
+const ANALYZER_PACKAGE: &'static str = "Microsoft.CodeAnalysis.PublicApiAnalyzers";
+
+/// Used when no project references the analyzer yet.
+const ANALYZER_VERSION: &'static str = "3.3.4";
+
+/// What a packable project is missing to track its public API.
+#[derive(Debug, Default)]
+struct Missing {
+    files: Vec<PathBuf>,
+    analyzer: bool,
+}
+
+/// Flags packable projects that lack the PublicAPI files or the analyzer that checks them.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    projects
+        .iter()
+        .filter_map(|project| Some((project, missing(project)?)))
+        .flat_map(|(project, missing)| findings(project, &missing))
+        .collect()
+}
+
+/// Creates empty PublicAPI files and adds the analyzer to packable projects.
+pub fn fix(projects: &[Project], effects: &mut Effects) -> Vec<Finding> {
+    let current_dir = std::env::current_dir().unwrap();
+
+    // Prefer the version other projects already use so that the references stay consistent.
+    let version = package_usages(projects)
+        .get(&ANALYZER_PACKAGE.to_lowercase())
+        .and_then(|usage| {
+            usage
+                .versions
+                .keys()
+                .max_by(|a, b| cmp_version_str(a, b))
+                .cloned()
+        })
+        .unwrap_or_else(|| ANALYZER_VERSION.to_owned());
+    let mut central_versions = BTreeMap::new();
+
+    for project in projects.iter() {
+        let missing = match missing(project) {
+            Some(missing) => missing,
+            None => continue,
+        };
+        let rel_path = relative_path(&current_dir, &project.path);
+
+        for path in missing.files.iter() {
+            effects
+                .write_file(path, "#nullable enable\n".to_owned(), Vec::new())
+                .unwrap();
+            effects.git_add(path).unwrap();
+            status!("Created {}", relative_path(&current_dir, path).display());
+        }
+
+        if missing.analyzer {
+            let mut reference = match cpm::find_packages_props(project.path.parent().unwrap()) {
+                Some(props_path) => {
+                    add_central_version(effects, &mut central_versions, &props_path, &version);
+                    let mut reference = Element::new("PackageReference");
+                    reference
+                        .attributes
+                        .insert("Include".to_owned(), ANALYZER_PACKAGE.to_owned());
+                    reference
+                }
+                None => package_element("PackageReference", ANALYZER_PACKAGE, &version),
+            };
+            let mut private_assets = Element::new("PrivateAssets");
+            private_assets
+                .children
+                .push(XMLNode::Text("all".to_owned()));
+            reference.children.push(XMLNode::Element(private_assets));
+
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    add_item(&mut root, reference);
+                    Some(root)
+                })
+                .unwrap();
+            status!("Added {} to {}", ANALYZER_PACKAGE, rel_path.display());
+        }
+    }
+
+    Vec::new()
+}
+
+fn add_central_version(
+    effects: &mut Effects,
+    central_versions: &mut BTreeMap<PathBuf, BTreeMap<String, String>>,
+    props_path: &Path,
+    version: &str,
+) {
+    let key = ANALYZER_PACKAGE.to_lowercase();
+    let versions = central_versions
+        .entry(props_path.to_owned())
+        .or_insert_with(|| {
+            cpm::read_package_versions(props_path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", props_path.display(), e))
+        });
+    if versions.contains_key(&key) {
+        return;
+    }
+
+    effects
+        .transform_xml_file(props_path, |mut root| {
+            add_item(
+                &mut root,
+                package_element("PackageVersion", ANALYZER_PACKAGE, version),
+            );
+            Some(root)
+        })
+        .unwrap();
+    let current_dir = std::env::current_dir().unwrap();
+    status!(
+        "Added {} {} to {}",
+        ANALYZER_PACKAGE,
+        version,
+        relative_path(&current_dir, props_path).display()
+    );
+    versions.insert(key, version.to_owned());
+}
+
+/// Returns what the project is missing, or None if it is not packable or is complete.
+fn missing(project: &Project) -> Option<Missing> {
+    if !is_packable(project) {
+        return None;
+    }
+
+    let project_dir = project.path.parent().unwrap();
+    let missing = Missing {
+        files: [SHIPPED_FILE_NAME, UNSHIPPED_FILE_NAME]
+            .iter()
+            .map(|file_name| project_dir.join(file_name))
+            .filter(|path| !path.is_file())
+            .collect(),
+        analyzer: !project
+            .package_references
+            .iter()
+            .any(|package| package.name.eq_ignore_ascii_case(ANALYZER_PACKAGE)),
+    };
+
+    if missing.files.is_empty() && !missing.analyzer {
+        None
+    } else {
+        Some(missing)
+    }
+}
+
+fn is_packable(project: &Project) -> bool {
+    project.properties.iter().any(|property| {
+        property.condition.is_none()
+            && property.name == "IsPackable"
+            && property.value.eq_ignore_ascii_case("true")
+    })
+}
+
+fn findings(project: &Project, missing: &Missing) -> Vec<Finding> {
+    let mut findings = missing
+        .files
+        .iter()
+        .map(|path| Finding {
+            rule: NAME,
+            message: format!(
+                "Packable project is missing {}",
+                path.file_name().unwrap().to_string_lossy()
+            ),
+            paths: vec![project.path.clone()],
+        })
+        .collect::<Vec<_>>();
+    if missing.analyzer {
+        findings.push(Finding {
+            rule: NAME,
+            message: format!("Packable project does not reference {}", ANALYZER_PACKAGE),
+            paths: vec![project.path.clone()],
+        });
+    }
+    findings
+}
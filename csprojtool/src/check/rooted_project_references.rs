@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::check::Finding;
+use crate::csproj::Project;
+use crate::encoding;
+use crate::output::status;
+use crate::path_extensions::{from_msbuild_path, relative_path, to_msbuild_path, PathExt};
+use crate::plan::Effects;
+use crate::xml_extensions::process_tree;
+
+pub const NAME: &'static str = "rooted-project-references";
+
+/// A ProjectReference Include that is absolute or rooted, with the relative Include to replace it
+/// with if the target could be found.
+#[derive(Debug)]
+struct Rooted {
+    include: String,
+    relative: Option<String>,
+}
+
+/// Flags project references written as absolute or rooted paths, which Visual Studio writes when
+/// a reference is added by browsing to the project and which only resolve on the machine that
+/// added it.
+pub fn check(projects: &[Project]) -> Vec<Finding> {
+    rooted_references(projects)
+        .into_iter()
+        .flat_map(|(project_path, references)| {
+            references
+                .into_iter()
+                .map(move |reference| finding(&project_path, &reference))
+        })
+        .collect()
+}
+
+/// Rewrites the rooted includes relative to the project and returns the findings for references
+/// whose target could not be found.
+pub fn fix(projects: &[Project], effects: &mut Effects) -> Vec<Finding> {
+    let current_dir = std::env::current_dir().unwrap();
+    let mut findings = Vec::new();
+
+    for (project_path, references) in rooted_references(projects) {
+        let rewrites = references
+            .iter()
+            .filter_map(|reference| {
+                Some((reference.include.as_str(), reference.relative.as_deref()?))
+            })
+            .collect::<HashMap<_, _>>();
+
+        if !rewrites.is_empty() {
+            effects
+                .transform_xml_file(&project_path, |mut root| {
+                    process_tree(&mut root, |element| {
+                        if element.name == "ProjectReference" {
+                            if let Some(include) = element.attributes.get_mut("Include") {
+                                if let Some(&relative) = rewrites.get(include.as_str()) {
+                                    *include = relative.to_owned();
+                                }
+                            }
+                        }
+                    });
+                    Some(root)
+                })
+                .unwrap();
+            status!(
+                "Made {} project references relative in {}",
+                rewrites.len(),
+                relative_path(&current_dir, &project_path).display()
+            );
+        }
+
+        findings.extend(
+            references
+                .iter()
+                .filter(|reference| reference.relative.is_none())
+                .map(|reference| finding(&project_path, reference)),
+        );
+    }
+
+    findings
+}
+
+fn finding(project_path: &Path, reference: &Rooted) -> Finding {
+    Finding {
+        rule: NAME,
+        message: match &reference.relative {
+            Some(relative) => format!(
+                "Project reference {} is rooted, write it as {}",
+                reference.include, relative
+            ),
+            None => format!(
+                "Project reference {} is rooted and does not resolve to a project",
+                reference.include
+            ),
+        },
+        paths: vec![project_path.to_owned()],
+    }
+}
+
+/// Returns whether the Include starts at a root, like `C:\src\A\A.csproj`, `\src\A\A.csproj`,
+/// `/src/A/A.csproj` or `\\server\share\A.csproj`, rather than at the project directory.
+pub fn is_rooted(include: &str) -> bool {
+    let bytes = include.as_bytes();
+    let drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    drive || include.starts_with('\\') || include.starts_with('/')
+}
+
+fn rooted_references(projects: &[Project]) -> Vec<(PathBuf, Vec<Rooted>)> {
+    let mut result = Vec::new();
+
+    for project in projects.iter() {
+        let project_dir = project.path.parent().unwrap();
+
+        let contents = match encoding::read_to_string(&project.path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to read {}: {}", project.path.display(), e);
+                continue;
+            }
+        };
+        let document = match roxmltree::Document::parse(&contents) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!("Failed to parse {}: {}", project.path.display(), e);
+                continue;
+            }
+        };
+
+        let references = document
+            .descendants()
+            .filter(|node| node.tag_name().name() == "ProjectReference")
+            .filter_map(|node| node.attribute("Include"))
+            .filter(|include| is_rooted(include))
+            .map(|include| Rooted {
+                include: include.to_owned(),
+                relative: find_target(include, projects)
+                    .map(|target| to_msbuild_path(&relative_path(project_dir, &target))),
+            })
+            .collect::<Vec<_>>();
+
+        if !references.is_empty() {
+            result.push((project.path.clone(), references));
+        }
+    }
+
+    result
+}
+
+/// Resolves the Include if it exists on this machine, otherwise looks for the only project with
+/// the same file name, since the path usually comes from another checkout.
+fn find_target(include: &str, projects: &[Project]) -> Option<PathBuf> {
+    let path = from_msbuild_path(include);
+    if path.is_absolute() && path.is_file() {
+        return Some(std::fs::canonicalize(&path).unwrap_or_else(|_| path.simplify()));
+    }
+
+    let file_name = include.rsplit(&['\\', '/'][..]).next()?;
+    let mut candidates = projects.iter().filter(|project| {
+        project
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.eq_ignore_ascii_case(file_name))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(project), None) => Some(project.path.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_rooted_detects_absolute_and_rooted_includes() {
+        assert!(is_rooted("C:\\src\\A\\A.csproj"));
+        assert!(is_rooted("d:/src/A/A.csproj"));
+        assert!(is_rooted("\\src\\A\\A.csproj"));
+        assert!(is_rooted("\\\\server\\share\\A\\A.csproj"));
+        assert!(is_rooted("/src/A/A.csproj"));
+        assert!(!is_rooted("..\\A\\A.csproj"));
+        assert!(!is_rooted("A.csproj"));
+        assert!(!is_rooted("$(SolutionDir)A\\A.csproj"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use crate::{
+    check::package_versions,
+    cli, list,
+    output_format::{print_issues, render_summary, Links, OutputFormat},
+    package_version::cmp_version_str,
+    path_extensions::relative_path,
+    sarif,
+};
+
+const CMD_CHECK_PACKAGES: &'static str = "check-packages";
+
+#[derive(Debug)]
+pub struct CheckPackagesCommand {
+    search_path: PathBuf,
+    follow_incoming_project_references: bool,
+    follow_outgoing_project_references: bool,
+    format: OutputFormat,
+    links: Links,
+}
+
+impl CheckPackagesCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_CHECK_PACKAGES)
+            .about("Report packages that are referenced with more than one version")
+            .arg(cli::arg_search())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(cli::arg_do_not_follow_incoming_project_references())
+            .arg(cli::arg_format())
+            .arg(cli::arg_url_template())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_CHECK_PACKAGES)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            follow_incoming_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES),
+            follow_outgoing_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            format: cli::get_format(matches),
+            links: cli::get_links(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: self.follow_incoming_project_references,
+            follow_outgoing_project_references: self.follow_outgoing_project_references,
+        });
+
+        if self.format != OutputFormat::Text {
+            let findings = package_versions::check(&projects);
+            if self.format.reports_issues() {
+                print_issues(
+                    self.format,
+                    &findings.iter().map(sarif::Issue::from).collect::<Vec<_>>(),
+                    &self.links.root,
+                );
+            } else {
+                print!(
+                    "{}",
+                    render_summary(
+                        self.format,
+                        "csprojtool check-packages",
+                        &findings,
+                        &self.links
+                    )
+                );
+            }
+            if !findings.is_empty() {
+                std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+            }
+            return;
+        }
+
+        let conflicts = package_versions::conflicts(&projects);
+
+        let current_dir = std::env::current_dir().unwrap();
+        for usage in conflicts.iter() {
+            println!("{}", usage.name);
+
+            let mut versions = usage.versions.iter().collect::<Vec<_>>();
+            versions.sort_by(|a, b| cmp_version_str(a.0, b.0));
+
+            for (version, project_paths) in versions {
+                println!("  {}", version);
+                let mut project_paths = project_paths.clone();
+                project_paths.sort();
+                for project_path in project_paths.iter() {
+                    println!(
+                        "    {}",
+                        relative_path(&current_dir, project_path).display()
+                    );
+                }
+            }
+        }
+
+        if !conflicts.is_empty() {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+    }
+}
@@ -1,14 +1,57 @@
 use clap::*;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::limits;
+use crate::list::TestFilter;
+use crate::output_format::{Links, OutputFormat};
+use crate::path_extensions::PathExt;
+use crate::tools::Tool;
+use crate::utils::find_git_root;
+use crate::walk;
 
 pub const ARG_CLEAN_APP_CONFIG: &'static str = "clean-app-config";
+pub const ARG_COMMIT: &'static str = "commit";
+pub const ARG_CONFIGURATION: &'static str = "configuration";
+pub const ARG_DEPTH_FROM: &'static str = "depth-from";
+pub const ARG_DETAILS: &'static str = "details";
 pub const ARG_DOT: &'static str = "dot";
+pub const ARG_DRY_RUN: &'static str = "dry-run";
+pub const ARG_EXCLUDE: &'static str = "exclude";
+pub const ARG_DOTNET: &'static str = "dotnet";
 pub const ARG_EXCLUDE_SDK: &'static str = "exclude-sdk";
 pub const ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES: &'static str = "no-follow";
 pub const ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES: &'static str = "no-follow-incoming";
+pub const ARG_FOLLOW_LINKS: &'static str = "follow-links";
+pub const ARG_FORCE_WRITABLE: &'static str = "force-writable";
+pub const ARG_FORMAT: &'static str = "format";
+pub const ARG_GIT: &'static str = "git";
 pub const ARG_GLOB: &'static str = "glob";
+pub const ARG_HIDDEN: &'static str = "hidden";
+pub const ARG_IGNORE_FILE: &'static str = "ignore-file";
 pub const ARG_JSON: &'static str = "json";
+pub const ARG_JSON_FILE: &'static str = "json-file";
+pub const ARG_MAX_MEMORY: &'static str = "max-memory";
+pub const ARG_MSBUILD: &'static str = "msbuild";
+pub const ARG_MSBUILD_PROPERTY: &'static str = "msbuild-property";
+pub const ARG_NO_CACHE: &'static str = "no-cache";
+pub const ARG_NO_IGNORE: &'static str = "no-ignore";
+pub const ARG_NO_TESTS: &'static str = "no-tests";
+pub const ARG_OFFLINE: &'static str = "offline";
+pub const ARG_ONLY_TESTS: &'static str = "only-tests";
+pub const ARG_PLAN_JSON: &'static str = "plan-json";
+pub const ARG_PLATFORM: &'static str = "platform";
+pub const ARG_PROPERTY: &'static str = "property";
+pub const ARG_RETRY: &'static str = "retry";
+pub const ARG_SDK: &'static str = "sdk";
 pub const ARG_SEARCH_PATH: &'static str = "search";
+pub const ARG_SEARCH_PATHS: &'static str = "search-path";
 pub const ARG_SLN_PATH: &'static str = "sln-file-path";
+pub const ARG_TFM: &'static str = "tfm";
+pub const ARG_THREADS: &'static str = "threads";
+pub const ARG_UPDATE: &'static str = "update";
+pub const ARG_URL_TEMPLATE: &'static str = "url-template";
 pub const CMD_DEPENDENCY_GRAPH: &'static str = "dependency-graph";
 pub const CMD_LIST_PROJECTS: &'static str = "list-projects";
 pub const CMD_LIST: &'static str = "list";
@@ -16,42 +59,351 @@ pub const CMD_POST_MIGRATION_CLEANUP: &'static str = "post-migration-cleanup";
 pub const CMD_SLN: &'static str = "sln";
 
 #[cfg(windows)]
-const DEFAULT_GLOB: &'static str = "**\\*.csproj";
+const DEFAULT_GLOB: &'static str = "**\\*.{csproj,fsproj,vbproj,shproj}";
 #[cfg(not(windows))]
-const DEFAULT_GLOB: &'static str = "**/*.csproj";
+const DEFAULT_GLOB: &'static str = "**/*.{csproj,fsproj,vbproj,shproj}";
 
 #[cfg(windows)]
 const DEFAULT_SEARCH: &'static str = ".\\";
 #[cfg(not(windows))]
 const DEFAULT_SEARCH: &'static str = "./";
 
-pub fn build_cli() -> App<'static, 'static> {
-    let arg_glob = &Arg::with_name(ARG_GLOB)
+pub fn arg_glob() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_GLOB)
         .short("g")
         .long("glob")
+        .alias("filter")
         .value_name("GLOB")
         .help("Specifies the glob pattern for which files to include")
         .takes_value(true)
-        .default_value(DEFAULT_GLOB);
+        .default_value(DEFAULT_GLOB)
+}
 
-    let arg_search = &Arg::with_name(ARG_SEARCH_PATH)
+pub fn arg_search() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_SEARCH_PATH)
         .value_name("SEARCH_PATH")
         .help("Sets the file to process or directory to search")
-        .default_value(DEFAULT_SEARCH);
+        .default_value(DEFAULT_SEARCH)
+}
 
-    let arg_do_not_follow_outgoing_project_references =
-        &Arg::with_name(ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES)
-            .short("F")
-            .long("no-follow")
-            .takes_value(false)
-            .help("Do not follow outgoing project references when searching for projects");
+pub fn arg_search_paths() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_SEARCH_PATHS)
+        .long("search-path")
+        .value_name("SEARCH_PATH")
+        .help("Adds a file to process or directory to search, can be passed multiple times")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+}
+
+pub fn arg_only_tests() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_ONLY_TESTS)
+        .long("only-tests")
+        .takes_value(false)
+        .conflicts_with(ARG_NO_TESTS)
+        .help("Only include test projects, which set IsTestProject or reference a test framework")
+}
+
+pub fn arg_no_tests() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_NO_TESTS)
+        .long("no-tests")
+        .takes_value(false)
+        .help("Leave out test projects, which set IsTestProject or reference a test framework")
+}
+
+pub fn arg_do_not_follow_outgoing_project_references() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES)
+        .short("F")
+        .long("no-follow")
+        .takes_value(false)
+        .help("Do not follow outgoing project references when searching for projects")
+}
+
+pub fn arg_do_not_follow_incoming_project_references() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES)
+        .short("I")
+        .long("no-follow-incoming")
+        .takes_value(false)
+        .help("Do not follow incoming project references when searching for projects")
+}
+
+pub fn arg_plan_json() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_PLAN_JSON)
+        .long("plan-json")
+        .value_name("PLAN_PATH")
+        .takes_value(true)
+        .help("Writes the planned changes to a json file instead of applying them")
+}
+
+/// Commits the touched files once a mutating command is done, the command gives the default
+/// message and the placeholders it fills in.
+pub fn arg_commit(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(ARG_COMMIT)
+        .long("commit")
+        .value_name("MESSAGE_TEMPLATE")
+        .takes_value(true)
+        .min_values(0)
+        .require_equals(true)
+        .help(help)
+}
+
+pub fn arg_format() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_FORMAT)
+        .long("format")
+        .value_name("FORMAT")
+        .help("Sets the output format, markdown and slack produce a short summary, sarif a SARIF log for code scanning and github annotations for pull requests")
+        .takes_value(true)
+        .possible_values(OutputFormat::NAMES)
+        .default_value("text")
+}
+
+/// The output format of commands that do not have a summary, with the same name as `arg_format`
+/// so that `get_format` reads both.
+pub fn arg_issues_format() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_FORMAT)
+        .long("format")
+        .value_name("FORMAT")
+        .help("Sets the output format, sarif produces a SARIF log for code scanning and github annotations for pull requests")
+        .takes_value(true)
+        .possible_values(&["text", "sarif", "github"])
+        .default_value("text")
+}
+
+pub fn arg_url_template() -> Arg<'static, 'static> {
+    Arg::with_name(ARG_URL_TEMPLATE)
+        .long("url-template")
+        .value_name("URL_TEMPLATE")
+        .help("Links files in summaries to this URL, {path} is replaced by the path in the repository")
+        .takes_value(true)
+}
+
+pub fn get_threads(matches: &ArgMatches) -> Option<usize> {
+    matches
+        .value_of(ARG_THREADS)
+        .map(|value| value.parse().unwrap())
+}
+
+pub fn get_max_memory(matches: &ArgMatches) -> Option<usize> {
+    matches
+        .value_of(ARG_MAX_MEMORY)
+        .map(|value| limits::parse_size(value).unwrap())
+}
+
+pub fn get_retries(matches: &ArgMatches) -> Option<usize> {
+    matches
+        .value_of(ARG_RETRY)
+        .map(|value| value.parse().unwrap())
+}
 
+pub fn get_json(matches: &ArgMatches) -> bool {
+    matches.is_present(ARG_JSON)
+}
+
+pub fn get_dry_run(matches: &ArgMatches) -> bool {
+    matches.is_present(ARG_DRY_RUN)
+}
+
+/// The properties that Condition attributes are evaluated with.
+pub fn get_msbuild_properties(matches: &ArgMatches) -> Vec<(String, String)> {
+    get_values(matches, ARG_MSBUILD_PROPERTY)
+        .iter()
+        .map(|assignment| {
+            let (name, value) = crate::set_property::parse_assignment(assignment).unwrap();
+            (name.to_owned(), value.to_owned())
+        })
+        .collect()
+}
+
+pub fn get_no_cache(matches: &ArgMatches) -> bool {
+    matches.is_present(ARG_NO_CACHE)
+}
+
+pub fn get_force_writable(matches: &ArgMatches) -> bool {
+    matches.is_present(ARG_FORCE_WRITABLE)
+}
+
+/// Returns the paths of the tools given on the command line.
+pub fn get_tool_paths(matches: &ArgMatches) -> Vec<(Tool, PathBuf)> {
+    [
+        (Tool::Git, ARG_GIT),
+        (Tool::Dotnet, ARG_DOTNET),
+        (Tool::Msbuild, ARG_MSBUILD),
+    ]
+    .iter()
+    .filter_map(|(tool, name)| Some((*tool, PathBuf::from(matches.value_of_os(name)?))))
+    .collect()
+}
+
+pub fn get_offline(matches: &ArgMatches) -> bool {
+    matches.is_present(ARG_OFFLINE)
+}
+
+/// Returns which files the walker skips, see `walk::Options`.
+pub fn get_walk_options(matches: &ArgMatches) -> walk::Options {
+    walk::Options {
+        ignore: !matches.is_present(ARG_NO_IGNORE),
+        hidden: matches.is_present(ARG_HIDDEN),
+        ignore_files: matches
+            .values_of_os(ARG_IGNORE_FILE)
+            .map(|values| values.map(PathBuf::from).collect())
+            .unwrap_or_default(),
+        follow_links: matches.is_present(ARG_FOLLOW_LINKS) || Config::current().search.follow_links,
+    }
+}
+
+pub fn get_test_filter(matches: &ArgMatches) -> TestFilter {
+    if matches.is_present(ARG_ONLY_TESTS) {
+        TestFilter::OnlyTests
+    } else if matches.is_present(ARG_NO_TESTS) {
+        TestFilter::NoTests
+    } else {
+        TestFilter::All
+    }
+}
+
+pub fn get_glob_matcher(matches: &ArgMatches) -> globset::GlobMatcher {
+    let glob_pattern = matches.value_of(ARG_GLOB).unwrap();
+    globset::Glob::new(glob_pattern).unwrap().compile_matcher()
+}
+
+/// Returns the search path given on the command line, or else the one in the configuration.
+pub fn get_search_path(matches: &ArgMatches) -> PathBuf {
+    let config = Config::current();
+    if matches.occurrences_of(ARG_SEARCH_PATH) == 0 {
+        if let Some(search_path) = config.search.path.as_ref() {
+            return config.dir.join(search_path).simplify();
+        }
+    }
+    let search_path = matches.value_of(ARG_SEARCH_PATH).unwrap();
+    Path::new(search_path).components().collect()
+}
+
+/// Returns the search paths given on the command line, the positional one first, or else the ones
+/// in the configuration. Falls back to `get_search_path` when neither gives several.
+pub fn get_search_paths(matches: &ArgMatches) -> Vec<PathBuf> {
+    let additional = get_values(matches, ARG_SEARCH_PATHS)
+        .iter()
+        .map(|search_path| Path::new(search_path).components().collect::<PathBuf>())
+        .collect::<Vec<_>>();
+    let config = Config::current();
+    if matches.occurrences_of(ARG_SEARCH_PATH) == 0 {
+        if !additional.is_empty() {
+            return additional;
+        }
+        if !config.search.paths.is_empty() {
+            return config
+                .search
+                .paths
+                .iter()
+                .map(|search_path| config.dir.join(search_path).simplify())
+                .collect();
+        }
+    }
+    Some(get_search_path(matches))
+        .into_iter()
+        .chain(additional)
+        .collect()
+}
+
+pub fn get_values(matches: &ArgMatches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .map(|values| values.map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+pub fn get_plan_json(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.value_of_os(ARG_PLAN_JSON).map(PathBuf::from)
+}
+
+/// The message template given with `--commit`, or the default when it is given without one.
+pub fn get_commit(matches: &ArgMatches, default_template: &str) -> Option<String> {
+    if !matches.is_present(ARG_COMMIT) {
+        return None;
+    }
+    Some(
+        matches
+            .value_of(ARG_COMMIT)
+            .unwrap_or(default_template)
+            .to_owned(),
+    )
+}
+
+pub fn get_format(matches: &ArgMatches) -> OutputFormat {
+    OutputFormat::from_name(matches.value_of(ARG_FORMAT).unwrap()).unwrap()
+}
+
+/// Links paths relative to the repository that contains the search path.
+pub fn get_links(matches: &ArgMatches) -> Links {
+    Links {
+        root: get_repository_root(matches),
+        template: matches.value_of(ARG_URL_TEMPLATE).map(str::to_owned),
+    }
+}
+
+/// The repository that contains the search path, or the current directory outside of one.
+pub fn get_repository_root(matches: &ArgMatches) -> PathBuf {
+    let search_path = get_search_path(matches).simplified_absolute().unwrap();
+    let current_dir = std::env::current_dir().unwrap();
+    find_git_root(&search_path)
+        .unwrap_or(&current_dir)
+        .to_owned()
+}
+
+/// Replaces `--json JSON_PATH` of dependency-graph, which clashes with the global `--json` flag,
+/// with `--json-file JSON_PATH` so that existing scripts keep working. Only values that end in
+/// `.json` or are not an existing directory count as JSON_PATH, anything else is the search path
+/// after the global flag.
+pub fn replace_deprecated_args(args: Vec<OsString>) -> Vec<OsString> {
+    let subcommand = match args.iter().position(|arg| arg == CMD_DEPENDENCY_GRAPH) {
+        Some(index) => index,
+        None => return args,
+    };
+
+    let mut result = Vec::with_capacity(args.len());
+    let mut args = args.into_iter().enumerate().peekable();
+    while let Some((index, arg)) = args.next() {
+        let is_path = |(_, next): &(usize, OsString)| {
+            !next.to_string_lossy().starts_with('-')
+                && (Path::new(next)
+                    .extension()
+                    .map_or(false, |extension| extension.eq_ignore_ascii_case("json"))
+                    || !Path::new(next).is_dir())
+        };
+        if index > subcommand && arg == "--json" && args.peek().map_or(false, is_path) {
+            log::warn!(
+                "--json JSON_PATH of {} is deprecated, use --json-file JSON_PATH instead",
+                CMD_DEPENDENCY_GRAPH
+            );
+            result.push(OsString::from("--json-file"));
+        } else if let Some(path) = arg
+            .to_str()
+            .filter(|_| index > subcommand)
+            .and_then(|arg| arg.strip_prefix("--json="))
+        {
+            log::warn!(
+                "--json=JSON_PATH of {} is deprecated, use --json-file=JSON_PATH instead",
+                CMD_DEPENDENCY_GRAPH
+            );
+            result.push(OsString::from(format!("--json-file={}", path)));
+        } else {
+            result.push(arg);
+        }
+    }
+    result
+}
+
+pub fn build_cli() -> App<'static, 'static> {
+    let arg_glob = &arg_glob();
+    let arg_search = &arg_search();
+    let arg_do_not_follow_outgoing_project_references =
+        &arg_do_not_follow_outgoing_project_references();
     let arg_do_not_follow_incoming_project_references =
-        &Arg::with_name(ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES)
-            .short("I")
-            .long("no-follow-incoming")
-            .takes_value(false)
-            .help("Do not follow incoming project references when searching for projects");
+        &arg_do_not_follow_incoming_project_references();
+    let arg_plan_json = &arg_plan_json();
+    let arg_search_paths = &arg_search_paths();
+    let arg_only_tests = &arg_only_tests();
+    let arg_no_tests = &arg_no_tests();
 
     let arg_clean_app_config = &Arg::with_name(ARG_CLEAN_APP_CONFIG)
         .long("clean-app-config")
@@ -65,11 +417,151 @@ pub fn build_cli() -> App<'static, 'static> {
         .author("Mick van Gelderen <mick@logiqs.nl>")
         .about("Manages csproj files")
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name(ARG_THREADS)
+                .long("threads")
+                .value_name("COUNT")
+                .env("CSPROJTOOL_THREADS")
+                .global(true)
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(count) if count > 0 => Ok(()),
+                    _ => Err(String::from("expected a positive number")),
+                })
+                .help("Limits the number of threads used to find and parse projects, defaults to one per core"),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_MEMORY)
+                .long("max-memory")
+                .value_name("SIZE")
+                .env("CSPROJTOOL_MAX_MEMORY")
+                .global(true)
+                .validator(|value| match limits::parse_size(&value) {
+                    Some(_) => Ok(()),
+                    None => Err(String::from("expected a size like 512M or 2G")),
+                })
+                .help("Fails instead of allocating more than SIZE bytes, accepts K, M and G suffixes"),
+        )
+        .arg(
+            Arg::with_name(ARG_RETRY)
+                .long("retry")
+                .value_name("COUNT")
+                .global(true)
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(_) => Ok(()),
+                    _ => Err(String::from("expected a number")),
+                })
+                .help("Checks files that are locked by another process again COUNT times, a second apart, before giving up"),
+        )
+        .arg(
+            Arg::with_name(ARG_DRY_RUN)
+                .long("dry-run")
+                .global(true)
+                .takes_value(false)
+                .help("Prints the changes of commands that write files as a unified diff instead of writing them"),
+        )
+        .arg(
+            Arg::with_name(ARG_JSON)
+                .long("json")
+                .global(true)
+                .takes_value(false)
+                .help("Prints the results as json on stdout, other messages go to stderr"),
+        )
+        .arg(
+            Arg::with_name(ARG_MSBUILD_PROPERTY)
+                .long("msbuild-property")
+                .short("p")
+                .value_name("NAME=VALUE")
+                .global(true)
+                .help("Evaluates Condition attributes with the property, like TargetFramework=net8.0, so that only what applies is read, can be passed multiple times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|value| crate::set_property::parse_assignment(&value).map(|_| ())),
+        )
+        .arg(
+            Arg::with_name(ARG_NO_CACHE)
+                .long("no-cache")
+                .global(true)
+                .takes_value(false)
+                .help("Parses every project instead of reusing the projects cached in .git/csprojtool-cache"),
+        )
+        .arg(
+            Arg::with_name(ARG_FORCE_WRITABLE)
+                .long("force-writable")
+                .global(true)
+                .takes_value(false)
+                .help("Clears the read-only attribute of files that have to be changed"),
+        )
+        .arg(
+            Arg::with_name(ARG_GIT)
+                .long("git")
+                .value_name("PATH")
+                .global(true)
+                .takes_value(true)
+                .help("The git executable to run, overrides [tools] in .csprojtool.toml"),
+        )
+        .arg(
+            Arg::with_name(ARG_DOTNET)
+                .long("dotnet")
+                .value_name("PATH")
+                .global(true)
+                .takes_value(true)
+                .help("The dotnet executable to run, overrides [tools] in .csprojtool.toml"),
+        )
+        .arg(
+            Arg::with_name(ARG_MSBUILD)
+                .long("msbuild")
+                .value_name("PATH")
+                .global(true)
+                .takes_value(true)
+                .help("Runs this MSBuild executable instead of dotnet msbuild"),
+        )
+        .arg(
+            Arg::with_name(ARG_OFFLINE)
+                .long("offline")
+                .global(true)
+                .takes_value(false)
+                .help("Disables all network access, like querying package feeds"),
+        )
+        .arg(
+            Arg::with_name(ARG_NO_IGNORE)
+                .long("no-ignore")
+                .global(true)
+                .takes_value(false)
+                .help("Finds projects in directories that .gitignore and the git excludes ignore, .csprojtoolignore files still apply"),
+        )
+        .arg(
+            Arg::with_name(ARG_FOLLOW_LINKS)
+                .long("follow-links")
+                .global(true)
+                .takes_value(false)
+                .help("Finds projects in symbolically linked directories, projects reachable through several links are listed once"),
+        )
+        .arg(
+            Arg::with_name(ARG_HIDDEN)
+                .long("hidden")
+                .global(true)
+                .takes_value(false)
+                .help("Finds projects in hidden directories, whose name starts with a dot"),
+        )
+        .arg(
+            Arg::with_name(ARG_IGNORE_FILE)
+                .long("ignore-file")
+                .value_name("PATH")
+                .global(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Skips the paths that match the patterns in this file, in the format of .gitignore, can be passed multiple times"),
+        )
         .subcommands(vec![
             clap::SubCommand::with_name(CMD_DEPENDENCY_GRAPH)
                 .about("Generate dependency graph of project references")
                 .arg(arg_search)
+                .arg(arg_search_paths)
                 .arg(arg_glob)
+                .arg(arg_only_tests)
+                .arg(arg_no_tests)
                 .arg(
                     Arg::with_name(ARG_DOT)
                         .long("dot")
@@ -77,22 +569,77 @@ pub fn build_cli() -> App<'static, 'static> {
                         .help("Writes the output to a dot file"),
                 )
                 .arg(
-                    Arg::with_name(ARG_JSON)
-                        .long("json")
+                    Arg::with_name(ARG_JSON_FILE)
+                        .long("json-file")
                         .value_name("JSON_PATH")
-                        .help("Writes the output to a json file"),
+                        .help("Writes the output to a json file, was --json JSON_PATH before"),
                 ),
             clap::SubCommand::with_name(CMD_POST_MIGRATION_CLEANUP)
                 .about("Perform post csproj migration cleanup")
                 .arg(arg_search)
                 .arg(arg_glob)
                 .arg(arg_do_not_follow_outgoing_project_references)
-                .arg(arg_clean_app_config),
+                .arg(arg_clean_app_config)
+                .arg(arg_plan_json),
             clap::SubCommand::with_name(CMD_LIST)
                 .about("List all projects and their dependencies")
                 .arg(arg_search)
+                .arg(arg_search_paths)
                 .arg(arg_do_not_follow_outgoing_project_references)
-                .arg(arg_do_not_follow_incoming_project_references),
+                .arg(arg_do_not_follow_incoming_project_references)
+                .arg(arg_glob)
+                .arg(arg_only_tests)
+                .arg(arg_no_tests)
+                .arg(
+                    Arg::with_name(ARG_TFM)
+                        .long("tfm")
+                        .value_name("TFM")
+                        .help("Only list projects targeting this framework, can be passed multiple times")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name(ARG_PROPERTY)
+                        .long("property")
+                        .value_name("NAME=VALUE")
+                        .help("Only list projects setting the property to the value, can be passed multiple times")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .validator(|value| {
+                            crate::set_property::parse_assignment(&value).map(|_| ())
+                        }),
+                )
+                .arg(
+                    Arg::with_name(ARG_SDK)
+                        .long("sdk")
+                        .value_name("SDK")
+                        .help("Only list projects using this SDK, like Microsoft.NET.Sdk.Web")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_DEPTH_FROM)
+                        .long("depth-from")
+                        .value_name("PROJECT_PATH")
+                        .help("Annotate every project with its shortest reference distance from this project")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_DETAILS)
+                        .long("details")
+                        .takes_value(false)
+                        .help("Include the AssemblyName, RootNamespace, OutputType, target frameworks and references of every project"),
+                )
+                .arg(
+                    Arg::with_name(ARG_FORMAT)
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Sets the output format, json includes the GUID, target frameworks and references")
+                        .takes_value(true)
+                        .possible_values(crate::list::Format::NAMES)
+                        .default_value("text"),
+                ),
             clap::SubCommand::with_name(CMD_SLN)
                 .about("Generate a solution file")
                 .arg(
@@ -102,8 +649,151 @@ pub fn build_cli() -> App<'static, 'static> {
                         .help("Path to the solution file"),
                 )
                 .arg(arg_search)
+                .arg(arg_search_paths)
                 .arg(arg_do_not_follow_outgoing_project_references)
-                .arg(arg_do_not_follow_incoming_project_references),
+                .arg(arg_do_not_follow_incoming_project_references)
+                .arg(arg_only_tests)
+                .arg(arg_no_tests)
+                .arg(
+                    Arg::with_name(ARG_EXCLUDE)
+                        .long("exclude")
+                        .value_name("GLOB")
+                        .help("Leave out projects whose path matches the glob, can be passed multiple times")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name(ARG_UPDATE)
+                        .long("update")
+                        .takes_value(false)
+                        .help("Add new and remove deleted projects, keeping the rest of an existing solution"),
+                )
+                .arg(
+                    Arg::with_name(ARG_CONFIGURATION)
+                        .long("configuration")
+                        .value_name("CONFIGURATION")
+                        .help("A solution configuration like Debug, can be passed multiple times")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name(ARG_PLATFORM)
+                        .long("platform")
+                        .value_name("PLATFORM")
+                        .help("A solution platform like x64, can be passed multiple times")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(arg_plan_json),
             crate::move_command::MoveCommand::subcommand(),
+            crate::migrate_packages::MigratePackagesCommand::subcommand(),
+            crate::cpm::CpmCommand::subcommand(),
+            crate::check::CheckCommand::subcommand(),
+            crate::check_packages::CheckPackagesCommand::subcommand(),
+            crate::artifacts::ArtifactsCommand::subcommand(),
+            crate::apply::ApplyCommand::subcommand(),
+            crate::outdated::OutdatedCommand::subcommand(),
+            crate::add_package::AddPackageCommand::subcommand(),
+            crate::remove_package::RemovePackageCommand::subcommand(),
+            crate::tfms::TfmsCommand::subcommand(),
+            crate::retarget::RetargetCommand::subcommand(),
+            crate::unreachable::UnreachableCommand::subcommand(),
+            crate::tfm::TfmCommand::subcommand(),
+            crate::rdeps::RdepsCommand::subcommand(),
+            crate::slnf::SlnfCommand::subcommand(),
+            crate::graph::GraphCommand::subcommand(),
+            crate::stats::StatsCommand::subcommand(),
+            crate::sln_diff::SlnDiffCommand::subcommand(),
+            crate::package_diff::PackageDiffCommand::subcommand(),
+            crate::sln_merge::SlnMergeCommand::subcommand(),
+            crate::validate::ValidateCommand::subcommand(),
+            crate::report::ReportCommand::subcommand(),
+            crate::fmt::FmtCommand::subcommand(),
+            crate::sort::SortCommand::subcommand(),
+            crate::serve::ServeCommand::subcommand(),
+            crate::dedupe::DedupeCommand::subcommand(),
+            crate::lint::LintCommand::subcommand(),
+            crate::reaches::ReachesCommand::subcommand(),
+            crate::query::QueryCommand::subcommand(),
+            crate::propose_layout::ProposeLayoutCommand::subcommand(),
+            crate::set_property::SetPropertyCommand::subcommand(),
+            crate::remove_property::RemovePropertyCommand::subcommand(),
+            crate::watch::WatchCommand::subcommand(),
+            crate::ui::UiCommand::subcommand(),
+            crate::affected::AffectedCommand::subcommand(),
+            crate::which_sln::WhichSlnCommand::subcommand(),
+            crate::slns::SlnsCommand::subcommand(),
+            crate::normalize_paths::NormalizePathsCommand::subcommand(),
+            crate::convert_refs::ConvertRefsCommand::subcommand(),
+            crate::analyze_redirects::AnalyzeRedirectsCommand::subcommand(),
+            crate::analyzers::AnalyzersCommand::subcommand(),
+            crate::rollout::RolloutCommand::subcommand(),
+            crate::langversion::LangVersionCommand::subcommand(),
+            crate::chains::ChainsCommand::subcommand(),
+            crate::traversal::TraversalCommand::subcommand(),
         ])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn replace_deprecated_args_renames_the_json_path_of_dependency_graph() {
+        assert_eq!(
+            replace_deprecated_args(args(&[
+                "csprojtool",
+                "dependency-graph",
+                "--json",
+                "graph.json"
+            ])),
+            args(&[
+                "csprojtool",
+                "dependency-graph",
+                "--json-file",
+                "graph.json"
+            ])
+        );
+        assert_eq!(
+            replace_deprecated_args(args(&[
+                "csprojtool",
+                "dependency-graph",
+                "--json=graph.json"
+            ])),
+            args(&["csprojtool", "dependency-graph", "--json-file=graph.json"])
+        );
+        assert_eq!(
+            replace_deprecated_args(args(&[
+                "csprojtool",
+                "--json",
+                "dependency-graph",
+                "--json",
+                "--dot",
+                "graph.dot"
+            ])),
+            args(&[
+                "csprojtool",
+                "--json",
+                "dependency-graph",
+                "--json",
+                "--dot",
+                "graph.dot"
+            ])
+        );
+        assert_eq!(
+            replace_deprecated_args(args(&["csprojtool", "list", "--json", "src"])),
+            args(&["csprojtool", "list", "--json", "src"])
+        );
+        assert_eq!(
+            replace_deprecated_args(args(&["csprojtool", "dependency-graph", "--json", "src"])),
+            args(&["csprojtool", "dependency-graph", "--json", "src"])
+        );
+    }
+}
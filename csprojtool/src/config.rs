@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::Deserialize;
+
+use crate::path_extensions::relative_path;
+
+pub const CONFIG_FILE_NAME: &'static str = ".csprojtool.toml";
+
+/// The repository configuration read from `.csprojtool.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// The directory of the configuration file, paths in the configuration are relative to it.
+    #[serde(skip)]
+    pub dir: PathBuf,
+    pub search: SearchConfig,
+    pub sln: SlnConfig,
+    pub lint: LintConfig,
+    pub tools: ToolsConfig,
+    pub analyzers: AnalyzersConfig,
+    pub mv: MvConfig,
+}
+
+/// ```toml
+/// [search]
+/// path = "src"
+/// paths = ["src", "tools"]
+/// exclude = ["samples/**", "**/*.Legacy.csproj"]
+/// follow-links = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SearchConfig {
+    /// The search path of commands that are not given one.
+    pub path: Option<PathBuf>,
+    /// The search paths of commands that merge the projects of several, used instead of `path`
+    /// by those commands.
+    pub paths: Vec<PathBuf>,
+    /// Projects whose path relative to the configuration file matches one of these globs are
+    /// never listed.
+    pub exclude: Vec<String>,
+    /// Walks into symbolically linked directories like `--follow-links`.
+    pub follow_links: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SlnConfig {
+    /// Places projects in solution folders regardless of their directory, the first matching
+    /// mapping is used.
+    pub folders: Vec<FolderMapping>,
+    /// The solution configurations, like Debug and Release.
+    pub configurations: Vec<String>,
+    /// The solution platforms, like Any CPU and x64.
+    pub platforms: Vec<String>,
+    /// Overrides the platforms of projects, the first matching override is used.
+    pub project_platforms: Vec<PlatformOverride>,
+    /// How GUIDs are written in solutions.
+    pub guids: GuidStyle,
+    /// Projects whose path relative to the configuration file matches one of these globs are
+    /// left out of generated solutions, like `["samples/**", "benchmarks/**"]`.
+    pub exclude: Vec<String>,
+}
+
+/// ```toml
+/// [[sln.folders]]
+/// glob = "src/Services/**"
+/// folder = "Services"
+///
+/// [[sln.folders]]
+/// test-projects = true
+/// folder = "Tests"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FolderMapping {
+    /// The solution folder, nested folders are separated by slashes.
+    pub folder: String,
+    /// Matches projects by their path relative to the configuration file.
+    #[serde(default)]
+    pub glob: Option<String>,
+    /// Matches only test projects.
+    #[serde(default)]
+    pub test_projects: bool,
+}
+
+/// ```toml
+/// [[sln.project-platforms]]
+/// glob = "src/Legacy/**"
+/// platforms = ["x86"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlatformOverride {
+    /// Matches projects by their path relative to the configuration file.
+    pub glob: String,
+    /// The platforms the projects can build.
+    pub platforms: Vec<String>,
+}
+
+/// ```toml
+/// [sln.guids]
+/// case = "lower"
+/// braces = false
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GuidStyle {
+    pub case: GuidCase,
+    pub braces: bool,
+}
+
+/// Visual Studio writes uppercase GUIDs in braces.
+impl Default for GuidStyle {
+    fn default() -> Self {
+        Self {
+            case: GuidCase::Upper,
+            braces: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuidCase {
+    #[default]
+    Upper,
+    Lower,
+}
+
+/// ```toml
+/// [lint]
+/// test-frameworks = ["xunit"]
+/// project-name-pattern = "Acme\\.[A-Z][A-Za-z]*(\\.[A-Z][A-Za-z]*)*"
+///
+/// [lint.rules]
+/// floating-versions = "error"
+/// absolute-paths = "off"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LintConfig {
+    /// Overrides the severity of lint rules by name.
+    pub rules: BTreeMap<String, Severity>,
+    /// The packages of which a test project has to reference at least one.
+    pub test_frameworks: Vec<String>,
+    /// A regular expression the project names have to match completely.
+    pub project_name_pattern: Option<String>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            rules: BTreeMap::new(),
+            test_frameworks: vec![
+                String::from("xunit"),
+                String::from("NUnit"),
+                String::from("MSTest.TestFramework"),
+            ],
+            project_name_pattern: None,
+        }
+    }
+}
+
+/// ```toml
+/// [analyzers]
+/// required = ["StyleCop.Analyzers", "Contoso.Analyzers"]
+/// packages = ["Contoso.*.Analyzers"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct AnalyzersConfig {
+    /// The analyzer packages every project has to reference.
+    pub required: Vec<String>,
+    /// Globs for the names of analyzer packages that are not recognized by their name.
+    pub packages: Vec<String>,
+}
+
+/// ```toml
+/// [mv]
+/// dockerfiles = ["**/Dockerfile", "docker/*.dockerfile"]
+/// files = ["azure-pipelines.yml", "**/*.ps1", "build.cake"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MvConfig {
+    /// Globs for the Dockerfiles relative to the configuration file whose paths to a moved
+    /// project are rewritten.
+    pub dockerfiles: Vec<String>,
+    /// Globs for other text files, like build scripts and pipelines, whose paths to a moved
+    /// project are rewritten the same way.
+    pub files: Vec<String>,
+}
+
+impl Default for MvConfig {
+    fn default() -> Self {
+        Self {
+            dockerfiles: vec![
+                String::from("**/Dockerfile"),
+                String::from("**/Dockerfile.*"),
+                String::from("**/*.Dockerfile"),
+            ],
+            files: Vec::new(),
+        }
+    }
+}
+
+/// ```toml
+/// [tools]
+/// git = "/opt/git/bin/git"
+/// dotnet = "tools/dotnet/dotnet"
+/// offline = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ToolsConfig {
+    pub git: Option<PathBuf>,
+    pub dotnet: Option<PathBuf>,
+    /// Runs MSBuild directly instead of through `dotnet msbuild`.
+    pub msbuild: Option<PathBuf>,
+    pub handle: Option<PathBuf>,
+    /// Disables network access, like querying package feeds.
+    pub offline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Off,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Off => "off",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CURRENT: Config = Config::discover(&std::env::current_dir().unwrap());
+}
+
+impl Config {
+    /// The configuration of the repository the tool is run in.
+    pub fn current() -> &'static Self {
+        &CURRENT
+    }
+
+    /// Reads the configuration file from the directory or the closest ancestor that has one,
+    /// without leaving the git repository. Returns the default configuration when there is none.
+    pub fn discover(dir: &Path) -> Self {
+        for dir in dir.ancestors() {
+            let path = dir.join(CONFIG_FILE_NAME);
+            if path.is_file() {
+                debug!("Reading configuration from {}", path.display());
+                return Self::read(&path);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+
+        Self {
+            dir: dir.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    /// Matches the paths of the projects excluded by `[search] exclude`.
+    pub fn exclude_matcher(&self) -> globset::GlobSet {
+        let mut builder = globset::GlobSetBuilder::new();
+        for glob in self.search.exclude.iter() {
+            builder.add(
+                globset::Glob::new(glob).unwrap_or_else(|e| {
+                    panic!("Invalid glob {} in the configuration: {}", glob, e)
+                }),
+            );
+        }
+        builder.build().unwrap()
+    }
+
+    /// Matches the paths of the files of `[mv] dockerfiles` and `[mv] files`.
+    pub fn mv_file_matcher(&self) -> globset::GlobSet {
+        let mut builder = globset::GlobSetBuilder::new();
+        for glob in self.mv.dockerfiles.iter().chain(self.mv.files.iter()) {
+            builder.add(
+                globset::GlobBuilder::new(glob)
+                    .case_insensitive(true)
+                    .build()
+                    .unwrap_or_else(|e| {
+                        panic!("Invalid glob {} in the configuration: {}", glob, e)
+                    }),
+            );
+        }
+        builder.build().unwrap()
+    }
+
+    /// Returns true if the path relative to the configuration file matches.
+    pub fn is_match(&self, matcher: &globset::GlobSet, path: &Path) -> bool {
+        matcher.is_match(
+            relative_path(&self.dir, path)
+                .to_string_lossy()
+                .replace('\\', "/"),
+        )
+    }
+
+    /// Returns true if the project is excluded by `[search] exclude`.
+    pub fn is_excluded(&self, exclude_matcher: &globset::GlobSet, project_path: &Path) -> bool {
+        !self.search.exclude.is_empty()
+            && exclude_matcher.is_match(
+                relative_path(&self.dir, project_path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            )
+    }
+
+    pub fn read(path: &Path) -> Self {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+        let mut config: Self = toml::from_str(&contents)
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", path.display(), e));
+        config.dir = path.parent().unwrap().to_owned();
+        config
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+use serde::Deserialize;
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    add_package::package_element,
+    cli, cpm, encoding, list,
+    package_version::packages_folder_package,
+    path_extensions::relative_path,
+    plan::Effects,
+    xml_extensions::{add_item, all_children_whitespace, process_tree},
+};
+
+const ARG_MAPPING: &'static str = "mapping";
+const CMD_CONVERT_REFS: &'static str = "convert-refs";
+
+#[derive(Debug)]
+pub struct ConvertRefsCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    mapping: Option<PathBuf>,
+    plan_json: Option<PathBuf>,
+}
+
+/// The packages that provide assemblies which can not be matched otherwise, for example:
+///
+/// ```toml
+/// [[assembly]]
+/// name = "Contoso.Core"
+/// package = "Contoso.Core.Legacy"
+/// version = "2.1.0"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Mapping {
+    assembly: Vec<AssemblyMapping>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssemblyMapping {
+    name: String,
+    package: String,
+    version: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Package {
+    id: String,
+    version: String,
+}
+
+/// A Reference to an assembly by HintPath.
+#[derive(Debug)]
+struct AssemblyReference {
+    /// The Include as written, like `Newtonsoft.Json, Version=12.0.0.0, Culture=neutral`.
+    include: String,
+    name: String,
+    version: Option<String>,
+    hint_path: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    Package(Package),
+    Ambiguous(Vec<Package>),
+    Unknown,
+}
+
+impl ConvertRefsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_CONVERT_REFS)
+            .about("Replace references to assemblies by HintPath with PackageReference items for the packages that provide them")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(
+                Arg::with_name(ARG_MAPPING)
+                    .long("mapping")
+                    .value_name("PATH")
+                    .help("Sets a TOML file with [[assembly]] tables that map the name of an assembly to a package and version, these take precedence")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_CONVERT_REFS)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            mapping: matches.value_of(ARG_MAPPING).map(PathBuf::from),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mapping = match &self.mapping {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+                toml::from_str::<Mapping>(&contents)
+                    .unwrap_or_else(|e| panic!("Failed to parse {}: {}", path.display(), e))
+            }
+            None => Mapping::default(),
+        };
+
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_CONVERT_REFS, self.plan_json.as_deref());
+        // The global packages folder is only indexed when a reference needs it.
+        let mut installed = None;
+        let mut central_versions = BTreeMap::new();
+
+        for project in projects.iter() {
+            let rel_path = relative_path(&current_dir, &project.path);
+            let references = read_assembly_references(&project.path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", project.path.display(), e));
+
+            let mut converted = Vec::new();
+            let mut packages = Vec::<Package>::new();
+            for reference in references {
+                let resolution = match resolve(&reference, &mapping, &HashMap::new()) {
+                    Resolution::Unknown => resolve(
+                        &reference,
+                        &mapping,
+                        installed.get_or_insert_with(installed_packages),
+                    ),
+                    resolution => resolution,
+                };
+                match resolution {
+                    Resolution::Package(package) => {
+                        if !packages
+                            .iter()
+                            .any(|p| p.id.eq_ignore_ascii_case(&package.id))
+                        {
+                            packages.push(package);
+                        }
+                        converted.push(reference.include);
+                    }
+                    Resolution::Ambiguous(candidates) => warn!(
+                        "{} in {} is provided by {}, add it to the mapping file",
+                        reference.name,
+                        rel_path.display(),
+                        candidates
+                            .iter()
+                            .map(|package| format!("{} {}", package.id, package.version))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Resolution::Unknown => warn!(
+                        "No package provides {} in {}, add it to the mapping file",
+                        reference.name,
+                        rel_path.display()
+                    ),
+                }
+            }
+            if converted.is_empty() {
+                continue;
+            }
+
+            // Packages that are already referenced the new way should not be added twice.
+            packages.retain(|package| {
+                !project
+                    .package_references
+                    .iter()
+                    .any(|reference| reference.name.eq_ignore_ascii_case(&package.id))
+            });
+
+            let props_path = cpm::find_packages_props(project.path.parent().unwrap());
+            if let Some(props_path) = &props_path {
+                let versions = central_versions
+                    .entry(props_path.clone())
+                    .or_insert_with(|| {
+                        cpm::read_package_versions(props_path).unwrap_or_else(|e| {
+                            panic!("Failed to read {}: {}", props_path.display(), e)
+                        })
+                    });
+                let added = packages
+                    .iter()
+                    .filter(|package| {
+                        let key = package.id.to_lowercase();
+                        if versions.contains_key(&key) {
+                            return false;
+                        }
+                        versions.insert(key, package.version.clone());
+                        true
+                    })
+                    .collect::<Vec<_>>();
+                if !added.is_empty() {
+                    effects
+                        .transform_xml_file(props_path, |mut root| {
+                            for package in added {
+                                add_item(
+                                    &mut root,
+                                    package_element(
+                                        "PackageVersion",
+                                        &package.id,
+                                        &package.version,
+                                    ),
+                                );
+                            }
+                            Some(root)
+                        })
+                        .unwrap();
+                }
+            }
+
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    remove_references(&mut root, &converted);
+                    for package in packages.iter() {
+                        let reference = if props_path.is_some() {
+                            let mut reference = Element::new("PackageReference");
+                            reference
+                                .attributes
+                                .insert("Include".to_owned(), package.id.clone());
+                            reference
+                        } else {
+                            package_element("PackageReference", &package.id, &package.version)
+                        };
+                        add_item(&mut root, reference);
+                    }
+                    Some(root)
+                })
+                .unwrap();
+            status!(
+                "Converted {} references to {} packages in {}",
+                converted.len(),
+                packages.len(),
+                rel_path.display()
+            );
+        }
+
+        effects.finish();
+    }
+}
+
+fn read_assembly_references(
+    project_path: &Path,
+) -> Result<Vec<AssemblyReference>, crate::csproj::Error> {
+    let contents = encoding::read_to_string(project_path)?;
+    let document = roxmltree::Document::parse(&contents)?;
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.has_tag_name("Reference"))
+        .filter_map(|node| {
+            let include = node.attribute("Include")?;
+            let hint_path = node
+                .children()
+                .find(|child| child.has_tag_name("HintPath"))?
+                .text()?
+                .trim();
+            let (name, version) = parse_include(include);
+            Some(AssemblyReference {
+                include: include.to_owned(),
+                name,
+                version,
+                hint_path: hint_path.to_owned(),
+            })
+        })
+        .collect())
+}
+
+/// Splits an assembly Include like `Newtonsoft.Json, Version=12.0.0.0, Culture=neutral` into the
+/// name and the version.
+fn parse_include(include: &str) -> (String, Option<String>) {
+    let mut parts = include.split(',').map(str::trim);
+    let name = parts.next().unwrap_or_default().to_owned();
+    let version = parts
+        .find_map(|part| part.strip_prefix("Version="))
+        .map(str::to_owned);
+    (name, version)
+}
+
+/// Finds the package that provides the assembly, from the mapping, from a HintPath into a
+/// packages folder or from the installed packages by assembly name. When several versions of the
+/// package provide it, the one that matches the assembly version is used.
+fn resolve(
+    reference: &AssemblyReference,
+    mapping: &Mapping,
+    installed: &HashMap<String, Vec<Package>>,
+) -> Resolution {
+    if let Some(mapping) = mapping
+        .assembly
+        .iter()
+        .find(|mapping| mapping.name.eq_ignore_ascii_case(&reference.name))
+    {
+        return Resolution::Package(Package {
+            id: mapping.package.clone(),
+            version: mapping.version.clone(),
+        });
+    }
+
+    if let Some((id, version)) = packages_folder_package(&reference.hint_path) {
+        return Resolution::Package(Package {
+            id: id.to_owned(),
+            version: version.to_owned(),
+        });
+    }
+
+    let candidates = match installed.get(&reference.name.to_lowercase()) {
+        Some(candidates) => candidates,
+        None => return Resolution::Unknown,
+    };
+    if candidates
+        .iter()
+        .all(|package| package.id.eq_ignore_ascii_case(&candidates[0].id))
+    {
+        let matching = reference.version.as_ref().and_then(|version| {
+            candidates
+                .iter()
+                .find(|package| trim_version(&package.version) == trim_version(version))
+        });
+        match (matching, candidates.len()) {
+            (Some(package), _) => return Resolution::Package(package.clone()),
+            (None, 1) => return Resolution::Package(candidates[0].clone()),
+            _ => {}
+        }
+    }
+    Resolution::Ambiguous(candidates.clone())
+}
+
+/// Drops trailing zeros so that assembly version 12.0.0.0 equals package version 12.0.0.
+fn trim_version(version: &str) -> &str {
+    let mut version = version;
+    while let Some(trimmed) = version.strip_suffix(".0") {
+        version = trimmed;
+    }
+    version
+}
+
+/// Indexes the global packages folder by the lowercase names of the assemblies in `lib`.
+fn installed_packages() -> HashMap<String, Vec<Package>> {
+    let mut installed = HashMap::<String, Vec<Package>>::new();
+    let packages_dir = match global_packages_dir() {
+        Some(packages_dir) => packages_dir,
+        None => return installed,
+    };
+    debug!("indexing {}", packages_dir.display());
+
+    for id_dir in read_dirs(&packages_dir) {
+        for version_dir in read_dirs(&id_dir) {
+            let version = version_dir
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            let id = nuspec_id(&version_dir)
+                .unwrap_or_else(|| id_dir.file_name().unwrap().to_string_lossy().into_owned());
+            let mut names = read_dirs(&version_dir.join("lib"))
+                .into_iter()
+                .flat_map(|framework_dir| std::fs::read_dir(framework_dir).into_iter().flatten())
+                .filter_map(|entry| {
+                    let path = entry.ok()?.path();
+                    if path.extension()?.eq_ignore_ascii_case("dll") {
+                        Some(path.file_stem()?.to_string_lossy().to_lowercase())
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            for name in names {
+                installed.entry(name).or_default().push(Package {
+                    id: id.clone(),
+                    version: version.clone(),
+                });
+            }
+        }
+    }
+    installed
+}
+
+/// The folder is named in lowercase, the nuspec has the id as published.
+fn nuspec_id(version_dir: &Path) -> Option<String> {
+    let nuspec_path = std::fs::read_dir(version_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "nuspec")
+        })?;
+    let contents = encoding::read_to_string(&nuspec_path).ok()?;
+    let document = roxmltree::Document::parse(&contents).ok()?;
+    let id = document
+        .descendants()
+        .find(|node| node.has_tag_name("id"))?
+        .text()?
+        .trim()
+        .to_owned();
+    Some(id)
+}
+
+fn read_dirs(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+fn global_packages_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("NUGET_PACKAGES") {
+        return Some(PathBuf::from(dir));
+    }
+    let home = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })?;
+    Some(Path::new(&home).join(".nuget").join("packages"))
+}
+
+fn remove_references(root: &mut Element, includes: &[String]) {
+    process_tree(root, |element| {
+        element.children.retain(|node| match node {
+            XMLNode::Element(child) if child.name == "Reference" => !child
+                .attributes
+                .get("Include")
+                .map_or(false, |include| includes.contains(include)),
+            _ => true,
+        });
+
+        // Omit item groups that only contained the removed references
+        element.children.retain(|node| match node {
+            XMLNode::Element(child) if child.name == "ItemGroup" => !all_children_whitespace(child),
+            _ => true,
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(id: &str, version: &str) -> Package {
+        Package {
+            id: String::from(id),
+            version: String::from(version),
+        }
+    }
+
+    fn reference(include: &str, hint_path: &str) -> AssemblyReference {
+        let (name, version) = parse_include(include);
+        AssemblyReference {
+            include: String::from(include),
+            name,
+            version,
+            hint_path: String::from(hint_path),
+        }
+    }
+
+    #[test]
+    fn resolve_prefers_the_mapping_and_matches_assembly_versions() {
+        let mapping = Mapping {
+            assembly: vec![AssemblyMapping {
+                name: String::from("Contoso.Core"),
+                package: String::from("Contoso.Core.Legacy"),
+                version: String::from("2.1.0"),
+            }],
+        };
+        let installed = [
+            ("contoso.core", vec![package("Contoso.Core", "3.0.0")]),
+            (
+                "newtonsoft.json",
+                vec![
+                    package("Newtonsoft.Json", "11.0.2"),
+                    package("Newtonsoft.Json", "12.0.0"),
+                ],
+            ),
+            (
+                "shared",
+                vec![package("Shared.A", "1.0.0"), package("Shared.B", "1.0.0")],
+            ),
+        ]
+        .iter()
+        .map(|(name, packages)| (String::from(*name), packages.clone()))
+        .collect();
+
+        assert_eq!(
+            resolve(
+                &reference("Contoso.Core", r"..\lib\Contoso.Core.dll"),
+                &mapping,
+                &installed
+            ),
+            Resolution::Package(package("Contoso.Core.Legacy", "2.1.0"))
+        );
+        assert_eq!(
+            resolve(
+                &reference(
+                    "Newtonsoft.Json, Version=12.0.0.0, Culture=neutral",
+                    r"..\lib\Newtonsoft.Json.dll"
+                ),
+                &mapping,
+                &installed
+            ),
+            Resolution::Package(package("Newtonsoft.Json", "12.0.0"))
+        );
+        assert_eq!(
+            resolve(
+                &reference("Shared", r"..\lib\Shared.dll"),
+                &mapping,
+                &installed
+            ),
+            Resolution::Ambiguous(vec![
+                package("Shared.A", "1.0.0"),
+                package("Shared.B", "1.0.0")
+            ])
+        );
+        assert_eq!(
+            resolve(&reference("Gone", r"..\lib\Gone.dll"), &mapping, &installed),
+            Resolution::Unknown
+        );
+    }
+}
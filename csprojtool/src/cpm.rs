@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli, csproj, encoding,
+    error::{Error, Result},
+    list,
+    package_version::{package_usages, PackageUsage, PackageVersion},
+    path_extensions::{relative_path, PathExt},
+    plan::Effects,
+    xml_extensions::process_tree,
+};
+
+const CMD_CPM: &'static str = "cpm";
+const CMD_INIT: &'static str = "init";
+const PROPS_FILE_NAME: &'static str = "Directory.Packages.props";
+
+#[derive(Debug)]
+pub enum CpmCommand {
+    Init(InitCommand),
+}
+
+#[derive(Debug)]
+pub struct InitCommand {
+    search_path: PathBuf,
+    plan_json: Option<PathBuf>,
+}
+
+impl CpmCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::SubCommand;
+
+        SubCommand::with_name(CMD_CPM)
+            .about("Manage central package management")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(CMD_INIT)
+                    .about("Move all package versions into a Directory.Packages.props file")
+                    .arg(cli::arg_search())
+                    .arg(cli::arg_plan_json()),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_CPM)?;
+
+        matches.subcommand_matches(CMD_INIT).map(|matches| {
+            Self::Init(InitCommand {
+                search_path: cli::get_search_path(matches),
+                plan_json: cli::get_plan_json(matches),
+            })
+        })
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            Self::Init(command) => command.execute(),
+        }
+    }
+}
+
+/// The version that ends up in Directory.Packages.props for a single package.
+#[derive(Debug)]
+enum Resolution {
+    /// All projects can use this version.
+    Resolved(String),
+    /// Projects that use a different version keep it through a VersionOverride.
+    Conflict(String),
+}
+
+impl Resolution {
+    fn version(&self) -> &str {
+        match self {
+            Self::Resolved(version) | Self::Conflict(version) => version,
+        }
+    }
+}
+
+impl InitCommand {
+    pub fn execute(&self) -> Result<()> {
+        let search_dir = self.search_path.simplified_absolute().unwrap();
+        if !search_dir.is_dir() {
+            return Err(Error::user(format!(
+                "The search path {} must be a directory",
+                search_dir.display()
+            )));
+        }
+
+        let props_path = search_dir.join(PROPS_FILE_NAME);
+        if props_path.exists() {
+            return Err(Error::user(format!(
+                "{} already exists",
+                props_path.display()
+            )));
+        }
+
+        let projects = list::list(list::Options {
+            search_path: &search_dir,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+
+        let packages = package_usages(&projects);
+
+        let resolutions = packages
+            .iter()
+            .map(|(key, usage)| (key.clone(), resolve(usage)))
+            .collect::<BTreeMap<_, _>>();
+
+        let current_dir = std::env::current_dir().unwrap();
+
+        for (key, usage) in packages.iter() {
+            match &resolutions[key] {
+                Resolution::Resolved(version) if usage.versions.len() > 1 => {
+                    status!(
+                        "Consolidated {} to {} (was {})",
+                        usage.name,
+                        version,
+                        usage
+                            .versions
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                Resolution::Resolved(_) => {}
+                Resolution::Conflict(version) => {
+                    status!(
+                        "Could not resolve {}, using {} with VersionOverride for:",
+                        usage.name,
+                        version
+                    );
+                    for (other_version, project_paths) in usage.versions.iter() {
+                        if other_version == version {
+                            continue;
+                        }
+                        for project_path in project_paths.iter() {
+                            status!(
+                                "  {} {}",
+                                other_version,
+                                relative_path(&current_dir, project_path).display()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut effects = Effects::new(CMD_CPM, self.plan_json.as_deref());
+
+        let props = create_props(
+            packages
+                .iter()
+                .map(|(key, usage)| (usage.name.as_str(), resolutions[key].version())),
+        );
+        effects.write_xml_file(&props_path, &props)?;
+        status!(
+            "Wrote {} package versions to {}",
+            packages.len(),
+            relative_path(&current_dir, &props_path).display()
+        );
+
+        for project in projects.iter() {
+            let mut edited = false;
+            effects.transform_xml_file(&project.path, |mut root| {
+                process_tree(&mut root, |element| {
+                    if element.name == "PackageReference" {
+                        edited |= strip_version(element, &resolutions);
+                    }
+                });
+
+                if edited {
+                    Some(root)
+                } else {
+                    None
+                }
+            })?;
+
+            if edited {
+                debug!("removed package versions from {}", project.path.display());
+            }
+        }
+
+        effects.finish();
+        Ok(())
+    }
+}
+
+/// Finds the Directory.Packages.props that applies to projects in the directory.
+pub fn find_packages_props(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROPS_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads the centrally managed versions, keyed by the lowercase package name.
+pub fn read_package_versions(
+    props_path: &Path,
+) -> std::result::Result<BTreeMap<String, String>, csproj::Error> {
+    let contents = encoding::read_to_string(props_path)?;
+    let document = roxmltree::Document::parse(&contents)?;
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "PackageVersion")
+        .filter_map(|node| {
+            Some((
+                node.attribute("Include")?.to_lowercase(),
+                node.attribute("Version")?.to_owned(),
+            ))
+        })
+        .collect())
+}
+
+fn resolve(usage: &PackageUsage) -> Resolution {
+    let mut versions = usage.versions.keys();
+    if usage.versions.len() == 1 {
+        return Resolution::Resolved(versions.next().unwrap().clone());
+    }
+
+    let parsed = versions
+        .map(|version| PackageVersion::parse(version).map(|parsed| (parsed, version)))
+        .collect::<Option<Vec<_>>>();
+
+    match parsed {
+        Some(parsed) => {
+            let (_, highest) = parsed.into_iter().max_by(|a, b| a.0.cmp(&b.0)).unwrap();
+            Resolution::Resolved(highest.clone())
+        }
+        None => {
+            // Floating versions, ranges and properties can not be compared, pick the version
+            // used by most projects to minimize the number of overrides.
+            let (most_used, _) = usage
+                .versions
+                .iter()
+                .max_by(|a, b| a.1.len().cmp(&b.1.len()).then_with(|| b.0.cmp(a.0)))
+                .unwrap();
+            Resolution::Conflict(most_used.clone())
+        }
+    }
+}
+
+fn strip_version(element: &mut Element, resolutions: &BTreeMap<String, Resolution>) -> bool {
+    let resolution = match element
+        .attributes
+        .get("Include")
+        .and_then(|name| resolutions.get(&name.to_lowercase()))
+    {
+        Some(resolution) => resolution,
+        None => return false,
+    };
+
+    let version = element.attributes.shift_remove("Version").or_else(|| {
+        let index = element
+            .children
+            .iter()
+            .position(|node| matches!(node, XMLNode::Element(child) if child.name == "Version"))?;
+        match element.children.remove(index) {
+            XMLNode::Element(child) => child.get_text().map(|text| text.into_owned()),
+            _ => unreachable!(),
+        }
+    });
+
+    match (version, resolution) {
+        (Some(version), Resolution::Conflict(central)) if &version != central => {
+            element
+                .attributes
+                .insert("VersionOverride".to_owned(), version);
+            true
+        }
+        (Some(_), _) => true,
+        (None, _) => false,
+    }
+}
+
+fn create_props<'a>(package_versions: impl Iterator<Item = (&'a str, &'a str)>) -> Element {
+    let mut manage = Element::new("ManagePackageVersionsCentrally");
+    manage.children.push(XMLNode::Text("true".to_owned()));
+
+    let mut property_group = Element::new("PropertyGroup");
+    property_group.children.push(XMLNode::Element(manage));
+
+    let mut item_group = Element::new("ItemGroup");
+    for (name, version) in package_versions {
+        let mut package_version = Element::new("PackageVersion");
+        package_version
+            .attributes
+            .insert("Include".to_owned(), name.to_owned());
+        package_version
+            .attributes
+            .insert("Version".to_owned(), version.to_owned());
+        item_group.children.push(XMLNode::Element(package_version));
+    }
+
+    let mut root = Element::new("Project");
+    root.children.push(XMLNode::Element(property_group));
+    root.children.push(XMLNode::Element(item_group));
+    root
+}
@@ -1,12 +1,32 @@
+use crate::artifacts::{find_directory_build_props, find_directory_build_targets};
+use crate::encoding;
+use crate::evaluation::{is_property, Evaluator};
+use crate::internals_visible_to::Friend;
 use crate::path_extensions::*;
+use crate::utils::{SHARED_ITEMS_EXTENSION, SHARED_PROJECT_EXTENSION};
 use lazy_static::lazy_static;
+use log::{debug, warn};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::path::Path;
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
 use uuid::Uuid;
 
+/// Packages that only test projects reference, the test SDK and the xUnit, NUnit and MSTest
+/// frameworks.
+const TEST_PACKAGES: &[&str] = &[
+    "Microsoft.NET.Test.Sdk",
+    "xunit",
+    "xunit.core",
+    "NUnit",
+    "MSTest.TestFramework",
+    "MSTest",
+];
+
 pub fn search_for_projects(glob_pattern: &str) -> HashMap<PathBuf, Option<Result<Project, Error>>> {
     glob::glob(glob_pattern)
         .unwrap()
@@ -26,23 +46,86 @@ pub fn search_for_projects(glob_pattern: &str) -> HashMap<PathBuf, Option<Result
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonRoot {
     pub projects: Vec<Project>,
+    /// The assemblies that the projects make their internals visible to, by project.
+    #[serde(default)]
+    pub internals_visible_to: BTreeMap<PathBuf, Vec<Friend>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub path: PathBuf,
     pub is_sdk: bool,
+    /// The Sdk attribute of the Project element as written, like `Microsoft.NET.Sdk.Web`.
+    pub sdk: Option<String>,
     pub is_exe: bool,
     pub project_guid: Option<Uuid>,
     pub target_frameworks: Vec<String>,
     pub project_references: Vec<PathBuf>,
     pub package_references: Vec<PackageReference>,
+    pub properties: Vec<Property>,
+    /// The files imported by the project in evaluation order, including Directory.Build.props
+    /// and Directory.Build.targets. What they define is part of the project.
+    #[serde(default)]
+    pub imports: Vec<PathBuf>,
+    /// The shared projects whose .projitems the project imports.
+    #[serde(default)]
+    pub shared_projects: Vec<PathBuf>,
+}
+
+impl Project {
+    /// An SDK-style project at the path without target frameworks, references or properties.
+    #[cfg(test)]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            is_sdk: true,
+            sdk: Some("Microsoft.NET.Sdk".to_owned()),
+            is_exe: false,
+            project_guid: None,
+            target_frameworks: Vec::new(),
+            project_references: Vec::new(),
+            package_references: Vec::new(),
+            properties: Vec::new(),
+            imports: Vec::new(),
+            shared_projects: Vec::new(),
+        }
+    }
+
+    /// Test projects set IsTestProject or reference the test SDK or a test framework package.
+    pub fn is_test_project(&self) -> bool {
+        self.properties.iter().any(|property| {
+            property.condition.is_none()
+                && property.name == "IsTestProject"
+                && property.value.eq_ignore_ascii_case("true")
+        }) || self.package_references.iter().any(|package| {
+            TEST_PACKAGES
+                .iter()
+                .any(|name| package.name.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// The value of the last definition of the property outside of any condition.
+    pub fn last_unconditional_property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .rfind(|property| property.condition.is_none() && property.name == name)
+            .map(|property| property.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Property {
+    pub name: String,
+    pub value: String,
+    /// The condition on the property or its PropertyGroup, combined if both have one.
+    pub condition: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageReference {
     pub name: String,
-    pub version: String,
+    /// Absent when the version is managed centrally.
+    pub version: Option<String>,
 }
 
 #[derive(Debug)]
@@ -128,9 +211,7 @@ pub fn parse_projects(
     let mut new_todo = vec![];
 
     while !todo.is_empty() {
-        for project_path in todo.drain(..) {
-            let result = read_and_parse_project(project_path.clone());
-
+        for (project_path, result) in read_and_parse_projects(std::mem::take(&mut todo)) {
             if let Ok(project) = &result {
                 if follow_project_references {
                     for project_path in project.project_references.iter() {
@@ -149,7 +230,6 @@ pub fn parse_projects(
                 .is_none());
         }
 
-        todo.clear();
         std::mem::swap(&mut todo, &mut new_todo);
     }
 
@@ -160,7 +240,7 @@ fn find_files<'a>(
     search_path: &Path,
     glob_matcher: &'a globset::GlobMatcher,
 ) -> impl Iterator<Item = PathBuf> + 'a {
-    let walk_builder = ignore::WalkBuilder::new(search_path);
+    let walk_builder = crate::walk::builder(search_path);
     let cwd = std::fs::canonicalize(std::env::current_dir().unwrap()).unwrap();
     walk_builder
         .build()
@@ -176,26 +256,209 @@ fn find_files<'a>(
             }
         })
 }
+/// Reads and parses the projects on a thread per core, see `limits::worker_count`. The results
+/// are in the order of the paths, regardless of which thread finished first.
+pub fn read_and_parse_projects(
+    project_paths: Vec<PathBuf>,
+) -> Vec<(PathBuf, Result<Project, Error>)> {
+    let (job_sender, job_receiver) = crossbeam_channel::unbounded();
+    let (result_sender, result_receiver) = crossbeam_channel::unbounded();
+
+    let count = project_paths.len();
+    for job in project_paths.into_iter().enumerate() {
+        job_sender.send(job).unwrap();
+    }
+    drop(job_sender);
+
+    let workers = (0..crate::limits::worker_count().min(count))
+        .map(|_| {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            std::thread::spawn(move || {
+                for (index, project_path) in job_receiver.iter() {
+                    let result = read_and_parse_project(project_path.clone());
+                    result_sender.send((index, project_path, result)).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(result_sender);
+
+    let mut results = (0..count).map(|_| None).collect::<Vec<_>>();
+    for (index, project_path, result) in result_receiver.iter() {
+        results[index] = Some((project_path, result));
+    }
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    results.into_iter().map(Option::unwrap).collect()
+}
+
 pub fn read_and_parse_project(project_path: PathBuf) -> Result<Project, Error> {
-    let contents = std::fs::read_to_string(&project_path)?;
+    let contents = encoding::read_to_string(&project_path)?;
+    parse_project(project_path, &contents)
+}
+
+/// Returns the Directory.Build.props and Directory.Build.targets the SDK imports before and after
+/// the project.
+pub fn implicit_imports(project_path: &Path) -> (Option<PathBuf>, Option<PathBuf>) {
+    let project_dir = project_path.parent().unwrap();
+    (
+        find_directory_build_props(project_dir),
+        find_directory_build_targets(project_dir),
+    )
+}
+
+/// The files that were read while evaluating a project and which file each followed Import
+/// element refers to. A file is imported at most once, like MSBuild does.
+#[derive(Default)]
+struct Imports {
+    order: Vec<PathBuf>,
+    contents: HashMap<PathBuf, String>,
+    followed: HashMap<(PathBuf, roxmltree::NodeId), PathBuf>,
+    visited: HashSet<PathBuf>,
+}
+
+impl Imports {
+    /// Reads and evaluates the file, files that can not be read or parsed are skipped.
+    fn import(&mut self, path: &Path, evaluator: &mut Evaluator) -> bool {
+        if !self.visited.insert(path.to_owned()) {
+            debug!("Skipping {} which was imported before", path.display());
+            return false;
+        }
+        let contents = match encoding::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                debug!("Skipping import {}: {}", path.display(), e);
+                return false;
+            }
+        };
+        let document = match roxmltree::Document::parse(&contents) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!("Skipping import {}: {}", path.display(), e);
+                return false;
+            }
+        };
+        self.order.push(path.to_owned());
+        self.evaluate(path, &document, evaluator);
+        drop(document);
+        self.contents.insert(path.to_owned(), contents);
+        true
+    }
+
+    /// Defines the properties of the file in order and follows its imports. Conditions are only
+    /// evaluated with --msbuild-property, otherwise only the unconditional properties are known
+    /// when expanding paths.
+    fn evaluate(&mut self, path: &Path, document: &roxmltree::Document, evaluator: &mut Evaluator) {
+        evaluator.set_this_file(path);
+        for node in document.descendants() {
+            if is_property(node) {
+                if evaluator.defines(node) {
+                    evaluator
+                        .set_property(node.tag_name().name(), node.text().unwrap_or("").trim());
+                }
+            } else if node.has_tag_name("Import") && evaluator.imports(node) {
+                // Sdk imports and wildcards point outside of the repository.
+                let project = match node.attribute("Project") {
+                    Some(project) if node.attribute("Sdk").is_none() => project,
+                    _ => continue,
+                };
+                let import_path = match evaluator.expand(project) {
+                    Some(expanded) if !expanded.contains('*') => path
+                        .parent()
+                        .unwrap()
+                        .join(from_msbuild_path(expanded.trim()))
+                        .simplify(),
+                    _ => {
+                        debug!("Not following the import of {}", project);
+                        continue;
+                    }
+                };
+                if self.import(&import_path, evaluator) {
+                    self.followed
+                        .insert((path.to_owned(), node.id()), import_path);
+                }
+                evaluator.set_this_file(path);
+            }
+        }
+    }
+}
 
-    let document = roxmltree::Document::parse(&contents)?;
+/// Appends the nodes of the document with the nodes of the followed imports in place of their
+/// Import elements, which is the order in which MSBuild evaluates them.
+fn push_nodes<'a, 'input>(
+    path: &'a Path,
+    document: &'a roxmltree::Document<'input>,
+    documents: &'a HashMap<&'a Path, roxmltree::Document<'input>>,
+    imports: &'a Imports,
+    nodes: &mut Vec<(&'a Path, roxmltree::Node<'a, 'input>)>,
+) {
+    for node in document.descendants() {
+        nodes.push((path, node));
+        if let Some(import_path) = imports.followed.get(&(path.to_owned(), node.id())) {
+            let document = &documents[import_path.as_path()];
+            push_nodes(import_path, document, documents, imports, nodes);
+        }
+    }
+}
+
+/// Parses the contents of the project file at the path, the path is needed to resolve the
+/// project references and imports.
+pub fn parse_project(project_path: PathBuf, contents: &str) -> Result<Project, Error> {
+    let document = roxmltree::Document::parse(contents)?;
 
     let project_dir = project_path
         .parent()
         .expect("Failed to compute project directory path!");
 
-    let is_sdk = document
+    let mut evaluator = Evaluator::for_project(&project_path);
+    let mut imports = Imports::default();
+    imports.visited.insert(project_path.clone());
+    let (props, targets) = implicit_imports(&project_path);
+    let props = props.filter(|path| imports.import(path, &mut evaluator));
+    imports.evaluate(&project_path, &document, &mut evaluator);
+    let targets = targets.filter(|path| imports.import(path, &mut evaluator));
+    evaluator.set_this_file(&project_path);
+
+    // The imported files parsed fine while evaluating.
+    let documents = imports
+        .contents
+        .iter()
+        .map(|(path, contents)| {
+            (
+                path.as_path(),
+                roxmltree::Document::parse(contents).unwrap(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let mut nodes = Vec::new();
+    if let Some(path) = props.as_deref() {
+        push_nodes(path, &documents[path], &documents, &imports, &mut nodes);
+    }
+    push_nodes(&project_path, &document, &documents, &imports, &mut nodes);
+    if let Some(path) = targets.as_deref() {
+        push_nodes(path, &documents[path], &documents, &imports, &mut nodes);
+    }
+
+    let applies = |node: &roxmltree::Node| evaluator.applies(*node);
+    let descendants = || nodes.iter().map(|&(_, node)| node);
+
+    let sdk = document
         .root()
         .children()
         .find(|node| node.tag_name().name() == "Project")
         .unwrap()
         .attribute("Sdk")
-        .is_some();
+        .map(str::to_owned);
+    let is_sdk = sdk.is_some();
 
-    let is_exe = document
-        .descendants()
-        .find(|node| node.tag_name().name() == "OutputType")
+    // The project overrides what the imports before it define.
+    let is_exe = descendants()
+        .filter(applies)
+        .rfind(|node| node.tag_name().name() == "OutputType")
         .map_or(false, |node| {
             matches!(node.text(), Some("Exe") | Some("WinExe"))
         });
@@ -204,8 +467,8 @@ pub fn read_and_parse_project(project_path: PathBuf) -> Result<Project, Error> {
         static ref GUID_REGEX: Regex = Regex::new(r#"[a-fA-F0-9\-]+"#).unwrap();
     }
 
-    let project_guid = document
-        .descendants()
+    let project_guid = descendants()
+        .filter(applies)
         .find(|node| node.tag_name().name() == "ProjectGuid")
         .map(|node| {
             let captures = GUID_REGEX
@@ -215,83 +478,122 @@ pub fn read_and_parse_project(project_path: PathBuf) -> Result<Project, Error> {
                 .expect("ProjectGuid element does not contain a valid guid!")
         });
 
-    let target_frameworks = {
-        let target_frameworks_iter = document
-            .descendants()
-            .filter_map(|node| {
-                if node.tag_name().name() == "TargetFrameworks" {
-                    node.text()
-                } else {
-                    None
-                }
+    // Like MSBuild the last definition that applies wins and a TargetFramework, which may be set
+    // to build a single framework of TargetFrameworks, turns off multi-targeting.
+    let property = |name: &str| {
+        descendants()
+            .filter(|node| is_property(*node) && node.has_tag_name(name))
+            .rfind(applies)
+            .map(|node| {
+                let text = node.text().unwrap_or("").trim();
+                evaluator.expand(text).unwrap_or_else(|| text.to_owned())
             })
-            .flat_map(|text| text.split(';'))
-            .map(str::to_owned);
-
-        let target_framework_iter = document
-            .descendants()
-            .filter_map(|node| {
-                if node.tag_name().name() == "TargetFramework" {
-                    node.text()
-                } else {
-                    None
-                }
-            })
-            .map(str::to_owned);
-
-        // Old style
-        let target_framework_version_iter = document.descendants().filter_map(|node| {
-            if node.tag_name().name() == "TargetFrameworkVersion" {
-                node.text()
-                    .map(parse_target_framework_version)
-                    .expect("Failed to parse framework version!")
-            } else {
-                None
-            }
-        });
-
-        let mut target_frameworks = target_frameworks_iter
-            .chain(target_framework_iter)
-            .chain(target_framework_version_iter)
+            .filter(|value| !value.trim().is_empty())
+    };
+    let target_frameworks = if let Some(target_framework) = property("TargetFramework") {
+        vec![target_framework.trim().to_owned()]
+    } else if let Some(target_frameworks) = property("TargetFrameworks") {
+        let mut target_frameworks = target_frameworks
+            .split(';')
+            .map(str::trim)
+            .filter(|target_framework| !target_framework.is_empty())
+            .map(str::to_owned)
             .collect::<Vec<_>>();
-
         target_frameworks.sort();
         target_frameworks.dedup();
         target_frameworks
+    } else {
+        // Old style
+        property("TargetFrameworkVersion")
+            .and_then(|version| parse_target_framework_version(&version))
+            .into_iter()
+            .collect()
     };
 
-    let project_references = document
-        .descendants()
-        .filter_map(|node| -> Option<std::io::Result<PathBuf>> {
+    // References to missing projects are kept as written so that they can be reported and fixed
+    // instead of hiding the referencing project.
+    // Relative includes are relative to the project, also in imported files.
+    let project_references = nodes
+        .iter()
+        .filter(|(_, node)| applies(node))
+        .filter_map(|&(path, node)| -> Option<PathBuf> {
             if node.tag_name().name() != "ProjectReference" {
                 return None;
             }
-            let ref_path = PathBuf::from(node.attribute("Include")?);
-            let ref_path = project_dir.join(&ref_path).simplify();
-            Some(std::fs::canonicalize(ref_path))
+            let include = node.attribute("Include")?;
+            let mut evaluator = evaluator.clone();
+            evaluator.set_this_file(path);
+            let ref_path = evaluator
+                .resolve_path(include)
+                .unwrap_or_else(|| project_dir.join(from_msbuild_path(include)).simplify());
+            Some(std::fs::canonicalize(&ref_path).unwrap_or(ref_path))
         })
-        .collect::<Result<Vec<PathBuf>, std::io::Error>>()?;
+        .collect::<Vec<PathBuf>>();
 
-    let package_references = document
-        .descendants()
+    let package_references = descendants()
+        .filter(applies)
         .filter_map(|node| -> Option<PackageReference> {
             if node.tag_name().name() != "PackageReference" {
                 return None;
             }
             Some(PackageReference {
                 name: node.attribute("Include")?.to_string(),
-                version: node.attribute("Version")?.to_string(),
+                version: node
+                    .attribute("Version")
+                    .or_else(|| {
+                        node.children()
+                            .find(|child| child.tag_name().name() == "Version")
+                            .and_then(|child| child.text())
+                    })
+                    .map(str::to_owned),
             })
         })
         .collect::<Vec<_>>();
+
+    // Evaluated properties apply unconditionally, the others keep their condition.
+    let properties = descendants()
+        .filter(|node| is_property(*node) && applies(node))
+        .map(|node| Property {
+            name: node.tag_name().name().to_owned(),
+            value: node.text().unwrap_or("").trim().to_owned(),
+            condition: if evaluator.evaluates_conditions() {
+                None
+            } else {
+                let group = node.parent_element().unwrap();
+                match (group.attribute("Condition"), node.attribute("Condition")) {
+                    (Some(a), Some(b)) => Some(format!("({}) and ({})", a.trim(), b.trim())),
+                    (a, b) => a.or(b).map(|c| c.trim().to_owned()),
+                }
+            },
+        })
+        .collect::<Vec<_>>();
+
+    // The .projitems of a shared project sits next to its .shproj.
+    let shared_projects = imports
+        .order
+        .iter()
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == SHARED_ITEMS_EXTENSION)
+        })
+        .filter_map(|path| {
+            std::fs::canonicalize(path.with_extension(SHARED_PROJECT_EXTENSION)).ok()
+        })
+        .filter(|path| path != &project_path)
+        .collect();
+
     Ok(Project {
         path: project_path,
         is_sdk,
+        sdk,
         is_exe,
         project_guid,
         target_frameworks,
         project_references,
         package_references,
+        properties,
+        imports: imports.order,
+        shared_projects,
     })
 }
 
@@ -311,7 +613,7 @@ fn parse_target_framework_version(text: &str) -> Option<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_target_framework_version;
+    use super::*;
 
     #[test]
     fn parse_target_framework_version_works() {
@@ -324,4 +626,84 @@ mod tests {
             Some(String::from("net471"))
         );
     }
+
+    #[test]
+    fn parse_project_keeps_references_to_missing_projects() {
+        let project = parse_project(
+            PathBuf::from("/does/not/exist/App/App.csproj"),
+            r#"<Project Sdk="Microsoft.NET.Sdk">
+  <ItemGroup>
+    <ProjectReference Include="..\Lib\Lib.csproj" />
+  </ItemGroup>
+</Project>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            project.project_references,
+            vec![PathBuf::from("/does/not/exist/Lib/Lib.csproj")]
+        );
+    }
+
+    #[test]
+    fn parse_project_takes_the_last_target_framework_like_msbuild() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("Directory.Build.props"),
+            r#"<Project>
+  <PropertyGroup>
+    <TargetFramework>net6.0</TargetFramework>
+    <DefaultTargetFrameworks>net48;net8.0</DefaultTargetFrameworks>
+  </PropertyGroup>
+</Project>"#,
+        )
+        .unwrap();
+        let parse = |contents: &str| {
+            parse_project(dir.join("App").join("App.csproj"), contents)
+                .unwrap()
+                .target_frameworks
+        };
+
+        assert_eq!(
+            parse(
+                r#"<Project Sdk="Microsoft.NET.Sdk">
+  <PropertyGroup>
+    <TargetFramework>net8.0</TargetFramework>
+  </PropertyGroup>
+</Project>"#
+            ),
+            vec![String::from("net8.0")]
+        );
+        assert_eq!(
+            parse(
+                r#"<Project Sdk="Microsoft.NET.Sdk">
+  <PropertyGroup>
+    <TargetFramework></TargetFramework>
+    <TargetFrameworks>$(DefaultTargetFrameworks)</TargetFrameworks>
+  </PropertyGroup>
+</Project>"#
+            ),
+            vec![String::from("net48"), String::from("net8.0")]
+        );
+        assert_eq!(
+            parse(r#"<Project Sdk="Microsoft.NET.Sdk" />"#),
+            vec![String::from("net6.0")]
+        );
+    }
+
+    #[test]
+    fn read_and_parse_projects_keeps_the_order_of_the_paths() {
+        let paths = (0..20)
+            .map(|index| PathBuf::from(format!("/does/not/exist/{}.csproj", index)))
+            .collect::<Vec<_>>();
+        let results = read_and_parse_projects(paths.clone());
+        assert_eq!(
+            results
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>(),
+            paths
+        );
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+    }
 }
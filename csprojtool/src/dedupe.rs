@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli, list,
+    path_extensions::{from_msbuild_path, relative_path, PathExt},
+    plan::Effects,
+    xml_extensions::all_children_whitespace,
+};
+
+const CMD_DEDUPE: &'static str = "dedupe";
+
+#[derive(Debug)]
+pub struct DedupeCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+impl DedupeCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_DEDUPE)
+            .about("Remove duplicate ProjectReference and PackageReference items")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_DEDUPE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_DEDUPE, self.plan_json.as_deref());
+
+        let mut deduped_count = 0;
+        for project in projects.iter() {
+            let project_dir = project.path.parent().unwrap();
+            let mut removed = Vec::new();
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    removed = remove_duplicates(project_dir, &mut root);
+                    if removed.is_empty() {
+                        None
+                    } else {
+                        Some(root)
+                    }
+                })
+                .unwrap();
+
+            if !removed.is_empty() {
+                status!("{}", relative_path(&current_dir, &project.path).display());
+                for item in removed.iter() {
+                    status!("  removed duplicate {}", item);
+                }
+                deduped_count += 1;
+            }
+        }
+
+        if deduped_count == 0 {
+            status!("No duplicate references in {} projects", projects.len());
+        }
+
+        effects.finish();
+    }
+}
+
+/// Removes every ProjectReference that resolves to the same path as an earlier one and every
+/// PackageReference with the same id as an earlier one, compared case insensitively. Items under
+/// different conditions are not duplicates. Item groups that become empty are removed as well.
+/// Returns a description of each removed item.
+pub fn remove_duplicates(project_dir: &Path, project: &mut Element) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut removed = Vec::new();
+
+    let children = std::mem::take(&mut project.children);
+    for mut node in children {
+        if let XMLNode::Element(group) = &mut node {
+            if group.name == "ItemGroup" {
+                let group_condition = condition(group);
+                let count = group.children.len();
+                group.children.retain(|node| {
+                    let item = match node {
+                        XMLNode::Element(item) => item,
+                        _ => return true,
+                    };
+                    let include = match item.attributes.get("Include") {
+                        Some(include) => include,
+                        None => return true,
+                    };
+                    let key = match reference_key(project_dir, &item.name, include) {
+                        Some(key) => key,
+                        None => return true,
+                    };
+                    if seen.insert((group_condition.clone(), condition(item), key)) {
+                        true
+                    } else {
+                        removed.push(format!("{} {}", item.name, include));
+                        false
+                    }
+                });
+
+                if group.children.len() != count && all_children_whitespace(group) {
+                    continue;
+                }
+            }
+        }
+        project.children.push(node);
+    }
+
+    removed
+}
+
+fn condition(element: &Element) -> String {
+    element
+        .attributes
+        .get("Condition")
+        .map(|condition| condition.trim().to_owned())
+        .unwrap_or_default()
+}
+
+/// Identifies the target of a reference: the resolved path of a ProjectReference and the id of a
+/// PackageReference, both lowercase.
+fn reference_key(project_dir: &Path, name: &str, include: &str) -> Option<(&'static str, String)> {
+    match name {
+        "ProjectReference" => Some((
+            "ProjectReference",
+            project_dir
+                .join(from_msbuild_path(include))
+                .simplify()
+                .display()
+                .to_string()
+                .to_lowercase(),
+        )),
+        "PackageReference" => Some(("PackageReference", include.to_lowercase())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_extensions::{element, project};
+
+    #[test]
+    fn remove_duplicates_compares_resolved_paths_and_package_ids() {
+        let reference =
+            |include: &str| element("ProjectReference", &[("Include", include)], vec![]);
+        let package = |include: &str| element("PackageReference", &[("Include", include)], vec![]);
+        let condition = [("Condition", "'$(TargetFramework)' == 'net48'")];
+
+        let mut actual = project(vec![
+            element(
+                "ItemGroup",
+                &[],
+                vec![reference("..\\B\\B.csproj"), package("Serilog")],
+            ),
+            element(
+                "ItemGroup",
+                &[],
+                vec![reference("..\\A\\..\\b\\B.csproj"), package("serilog")],
+            ),
+            element("ItemGroup", &condition, vec![package("Serilog")]),
+        ]);
+
+        let removed = remove_duplicates(Path::new("/src/A"), &mut actual);
+        assert_eq!(
+            removed,
+            vec![
+                "ProjectReference ..\\A\\..\\b\\B.csproj",
+                "PackageReference serilog",
+            ]
+        );
+        assert_eq!(
+            actual,
+            project(vec![
+                element(
+                    "ItemGroup",
+                    &[],
+                    vec![reference("..\\B\\B.csproj"), package("Serilog")],
+                ),
+                element("ItemGroup", &condition, vec![package("Serilog")]),
+            ])
+        );
+    }
+}
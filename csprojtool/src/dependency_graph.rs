@@ -1,31 +1,51 @@
 use crate::csproj::*;
+use crate::internals_visible_to::{friends, Friend};
+use crate::list::TestFilter;
+use crate::output;
 use crate::path_extensions::*;
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-pub fn dependency_graph(glob: &str, search: &str, dot: Option<&str>, json: Option<&str>) {
+pub fn dependency_graph(
+    glob: &str,
+    searches: &[PathBuf],
+    tests: TestFilter,
+    dot: Option<&str>,
+    json: Option<&str>,
+) {
     // if we pass a file path, projects should contain that file
     // if we pass a directory path, projects should glob that directory
     // if we don't pass a path, projects should glob the current directory
+    // with several paths, the projects of all of them are graphed relative to their common directory
 
-    let (search_dir, mut projects) = {
-        let search_path =
-            std::fs::canonicalize(search).expect("Failed to canonicalize path, does it exist?");
-        let meta = std::fs::metadata(&search_path).unwrap();
-        if meta.is_file() {
-            let search_dir = search_path.parent().unwrap().to_path_buf();
-            let projects = Some((search_path, None)).into_iter().collect();
-            (search_dir, projects)
-        } else if meta.is_dir() {
-            let search_dir = search_path;
-            let original_current_dir = std::env::current_dir().unwrap();
-            std::env::set_current_dir(&search_dir).unwrap();
-            let projects = search_for_projects(glob);
-            std::env::set_current_dir(original_current_dir).unwrap();
-            (search_dir, projects)
-        } else {
-            panic!("Specified path is not a file nor a directory!")
-        }
-    };
+    let mut search_dirs = Vec::new();
+    let mut projects = HashMap::new();
+    for search in searches.iter() {
+        let (search_dir, search_projects) = {
+            let search_path =
+                std::fs::canonicalize(search).expect("Failed to canonicalize path, does it exist?");
+            let meta = std::fs::metadata(&search_path).unwrap();
+            if meta.is_file() {
+                let search_dir = search_path.parent().unwrap().to_path_buf();
+                let projects = Some((search_path, None)).into_iter().collect();
+                (search_dir, projects)
+            } else if meta.is_dir() {
+                let search_dir = search_path;
+                let original_current_dir = std::env::current_dir().unwrap();
+                std::env::set_current_dir(&search_dir).unwrap();
+                let projects = search_for_projects(glob);
+                std::env::set_current_dir(original_current_dir).unwrap();
+                (search_dir, projects)
+            } else {
+                panic!("Specified path is not a file nor a directory!")
+            }
+        };
+        search_dirs.push(search_dir);
+        projects.extend(search_projects);
+    }
+    let search_dir = common_dir(&search_dirs);
 
     loop {
         let todo = projects
@@ -43,9 +63,7 @@ pub fn dependency_graph(glob: &str, search: &str, dot: Option<&str>, json: Optio
             break;
         }
 
-        for project_path in todo {
-            let project = read_and_parse_project(project_path.clone());
-
+        for (project_path, project) in read_and_parse_projects(todo) {
             if let Ok(project) = &project {
                 for path in project.project_references.iter().cloned() {
                     projects.entry(path).or_insert(None);
@@ -56,6 +74,26 @@ pub fn dependency_graph(glob: &str, search: &str, dot: Option<&str>, json: Optio
         }
     }
 
+    // InternalsVisibleTo couples projects without a project reference, they are drawn apart.
+    let parsed = projects
+        .values()
+        .filter_map(|project| project.as_ref().unwrap().as_ref().ok().cloned())
+        .collect::<Vec<_>>();
+    let mut internals_visible_to = parsed
+        .iter()
+        .map(|project| {
+            let friends = friends(project, &parsed)
+                .into_iter()
+                .map(|friend| Friend {
+                    project: friend.project.map(|path| relative_path(&search_dir, &path)),
+                    ..friend
+                })
+                .collect::<Vec<_>>();
+            (relative_path(&search_dir, &project.path), friends)
+        })
+        .filter(|(_, friends)| !friends.is_empty())
+        .collect::<BTreeMap<_, _>>();
+
     let mut projects = projects
         .into_iter()
         .map(|(project_path, maybe_project)| {
@@ -73,26 +111,60 @@ pub fn dependency_graph(glob: &str, search: &str, dot: Option<&str>, json: Optio
         .collect::<Vec<_>>();
     projects.sort_by(|a, b| a.0.cmp(&b.0));
 
+    // Projects that failed to parse cannot be classified, so they are only kept with all projects.
+    // References to left out projects are left out of the graph as well.
+    projects.retain(|(_, project)| match project {
+        Ok(project) => tests.matches(project),
+        Err(_) => tests == TestFilter::All,
+    });
+    let kept = projects
+        .iter()
+        .map(|(path, _)| path.clone())
+        .collect::<HashSet<_>>();
+    for (_, project) in projects.iter_mut() {
+        if let Ok(project) = project {
+            project
+                .project_references
+                .retain(|reference| kept.contains(reference));
+        }
+    }
+    internals_visible_to.retain(|path, _| kept.contains(path));
+    for friends in internals_visible_to.values_mut() {
+        friends.retain(|friend| {
+            friend
+                .project
+                .as_ref()
+                .map_or(true, |path| kept.contains(path))
+        });
+    }
+
     if let Some(path) = dot {
         let mut file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
-        serialize_dot(&mut file, &projects).unwrap();
+        serialize_dot(&mut file, &projects, &internals_visible_to).unwrap();
     }
 
-    if let Some(path) = json {
-        let mut file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+    if json.is_some() || output::is_json() {
         let root = JsonRoot {
             projects: projects
                 .iter()
                 .filter_map(|(_, project)| project.as_ref().ok().cloned())
                 .collect(),
+            internals_visible_to,
         };
-        serde_json::to_writer_pretty(&mut file, &root).unwrap();
+        if let Some(path) = json {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+            serde_json::to_writer_pretty(&mut file, &root).unwrap();
+        }
+        if output::is_json() {
+            output::print_json(&root);
+        }
     }
 }
 
 fn serialize_dot<W: std::io::Write>(
     writer: &mut W,
     projects: &[(PathBuf, Result<Project, Error>)],
+    internals_visible_to: &BTreeMap<PathBuf, Vec<Friend>>,
 ) -> std::io::Result<()> {
     writeln!(writer, "// {} projects", projects.iter().len())?;
 
@@ -225,6 +297,19 @@ fn serialize_dot<W: std::io::Write>(
         }
     }
 
+    // Friends depend on the internals of the project, so they point at it like a reference.
+    for (path, friends) in internals_visible_to.iter() {
+        for friend in friends.iter() {
+            if let Some(friend_path) = &friend.project {
+                writeln!(
+                    writer,
+                    "  n{} -> n{} [style = dashed, color = \"#8da0cb\"];",
+                    nodes[friend_path], nodes[path]
+                )?;
+            }
+        }
+    }
+
     writeln!(writer, "}}")?;
 
     Ok(())
@@ -250,3 +335,14 @@ fn compatible_dependency(s: Option<&Project>, t: Option<&Project>) -> bool {
         true
     }
 }
+
+/// Returns the deepest directory that contains all of the directories.
+fn common_dir(dirs: &[PathBuf]) -> PathBuf {
+    let mut common = dirs[0].as_path();
+    for dir in dirs[1..].iter() {
+        while !dir.starts_with(common) {
+            common = common.parent().unwrap_or_else(|| Path::new(""));
+        }
+    }
+    common.to_owned()
+}
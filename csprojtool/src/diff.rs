@@ -0,0 +1,175 @@
+/// The number of unchanged lines shown around every change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line {
+    /// The indices of a line that is in both texts.
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Renders the differences between two texts as a unified diff like `diff -u`, empty if the
+/// texts have the same lines. None stands for a file that does not exist.
+pub fn unified_diff(
+    old_name: &str,
+    new_name: &str,
+    old: Option<&str>,
+    new: Option<&str>,
+) -> String {
+    let old_lines = old
+        .map(|old| old.lines().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let new_lines = new
+        .map(|new| new.lines().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(..)))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!(
+        "--- {}\n+++ {}\n",
+        old.map_or("/dev/null", |_| old_name),
+        new.map_or("/dev/null", |_| new_name)
+    );
+
+    // Changes that are close enough share a hunk so that context lines are not repeated.
+    let mut hunks = Vec::<(usize, usize)>::new();
+    for &index in changes.iter() {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        // The lines before the hunk, a hunk that is empty on one side starts at the line before.
+        let old_before = lines[..start]
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_before = lines[..start]
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_before, old_count),
+            range(new_before, new_count)
+        ));
+        for line in hunk.iter() {
+            match *line {
+                Line::Same(i, _) => out.push_str(&format!(" {}\n", old_lines[i])),
+                Line::Removed(i) => out.push_str(&format!("-{}\n", old_lines[i])),
+                Line::Added(j) => out.push_str(&format!("+{}\n", new_lines[j])),
+            }
+        }
+    }
+
+    out
+}
+
+fn range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
+
+/// Aligns the lines using the longest common subsequence. The common prefix and suffix are
+/// skipped first, which keeps the table small for the typical edit of a few lines.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Line> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lengths = vec![vec![0u32; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = (0..prefix)
+        .map(|index| Line::Same(index, index))
+        .collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            lines.push(Line::Same(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if j == new_middle.len()
+            || (i < old_middle.len() && lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            lines.push(Line::Removed(prefix + i));
+            i += 1;
+        } else {
+            lines.push(Line::Added(prefix + j));
+            j += 1;
+        }
+    }
+    lines.extend(
+        (0..suffix).map(|index| Line::Same(old.len() - suffix + index, new.len() - suffix + index)),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_shows_changes_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff("a/x", "b/x", Some(old), Some(new)),
+            "--- a/x\n+++ b/x\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_of_new_and_equal_files() {
+        assert_eq!(
+            unified_diff("a/x", "b/x", None, Some("a\n")),
+            "--- /dev/null\n+++ b/x\n@@ -0,0 +1 @@\n+a\n"
+        );
+        assert_eq!(unified_diff("a/x", "b/x", Some("a\n"), Some("a\n")), "");
+    }
+}
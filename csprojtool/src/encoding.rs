@@ -0,0 +1,178 @@
+use std::path::Path;
+
+use crate::git;
+use crate::path_extensions::to_extended_length;
+
+/// How a file is encoded on disk, edited files are written back the way they were read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Detects the encoding by the byte order mark, files without one are UTF-8.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Self::Utf8Bom,
+            [0xFF, 0xFE, ..] => Self::Utf16Le,
+            [0xFE, 0xFF, ..] => Self::Utf16Be,
+            _ => Self::Utf8,
+        }
+    }
+
+    /// The encoding of the file on disk, UTF-8 for files that do not exist yet.
+    pub fn of_file(path: &Path) -> Self {
+        let mut bom = [0u8; 3];
+        let read = std::fs::File::open(to_extended_length(path))
+            .and_then(|mut file| std::io::Read::read(&mut file, &mut bom))
+            .unwrap_or(0);
+        Self::detect(&bom[..read])
+    }
+
+    /// Decodes the contents without the byte order mark.
+    pub fn decode(self, bytes: &[u8]) -> std::io::Result<String> {
+        let utf16 = |to_unit: fn([u8; 2]) -> u16| {
+            let units = bytes[2..]
+                .chunks_exact(2)
+                .map(|pair| to_unit([pair[0], pair[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16(&units).map_err(invalid_data)
+        };
+        match self {
+            Self::Utf8 => String::from_utf8(bytes.to_vec()).map_err(invalid_data),
+            Self::Utf8Bom => String::from_utf8(bytes[3..].to_vec()).map_err(invalid_data),
+            Self::Utf16Le => utf16(u16::from_le_bytes),
+            Self::Utf16Be => utf16(u16::from_be_bytes),
+        }
+    }
+
+    /// Encodes the contents with the byte order mark. The XML declaration written by xmltree
+    /// always claims UTF-8, which is corrected for UTF-16.
+    pub fn encode(self, contents: &str) -> Vec<u8> {
+        let utf16 = |bom: [u8; 2], to_bytes: fn(u16) -> [u8; 2]| {
+            let contents = declare_utf16(contents);
+            bom.iter()
+                .copied()
+                .chain(contents.encode_utf16().flat_map(to_bytes))
+                .collect()
+        };
+        match self {
+            Self::Utf8 => contents.as_bytes().to_vec(),
+            Self::Utf8Bom => [0xEF, 0xBB, 0xBF]
+                .iter()
+                .chain(contents.as_bytes())
+                .copied()
+                .collect(),
+            Self::Utf16Le => utf16([0xFF, 0xFE], u16::to_le_bytes),
+            Self::Utf16Be => utf16([0xFE, 0xFF], u16::to_be_bytes),
+        }
+    }
+}
+
+/// The line breaks of a file, edited files keep theirs instead of the ones xmltree writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The line ending of most lines, None for contents without line breaks.
+    pub fn detect(contents: &str) -> Option<Self> {
+        let crlf = contents.matches("\r\n").count();
+        let lf = contents.matches('\n').count() - crlf;
+        match (crlf, lf) {
+            (0, 0) => None,
+            (crlf, lf) if crlf >= lf => Some(Self::CrLf),
+            _ => Some(Self::Lf),
+        }
+    }
+
+    /// The `eol` attribute that .gitattributes assigns to the file, git converts the line
+    /// endings on checkout so the file should be written with them too.
+    pub fn of_attributes(path: &Path) -> Option<Self> {
+        match git::attribute(path, "eol")?.as_str() {
+            "lf" => Some(Self::Lf),
+            "crlf" => Some(Self::CrLf),
+            _ => None,
+        }
+    }
+
+    /// Converts every line break in the contents to this line ending.
+    pub fn apply(self, contents: &str) -> String {
+        let contents = contents.replace("\r\n", "\n");
+        match self {
+            Self::Lf => contents,
+            Self::CrLf => contents.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// Reads the file in whatever encoding it has, see `Encoding::detect`.
+pub fn read_to_string(path: &Path) -> std::io::Result<String> {
+    let bytes = std::fs::read(to_extended_length(path))?;
+    Encoding::detect(&bytes).decode(&bytes)
+}
+
+fn invalid_data<E>(e: E) -> std::io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+}
+
+fn declare_utf16(contents: &str) -> String {
+    let declaration_end = match contents.find("?>") {
+        Some(end) if contents.starts_with("<?xml") => end,
+        _ => return contents.to_owned(),
+    };
+    let (declaration, rest) = contents.split_at(declaration_end);
+    let declaration = declaration
+        .replace("encoding=\"utf-8\"", "encoding=\"utf-16\"")
+        .replace("encoding=\"UTF-8\"", "encoding=\"utf-16\"");
+    declaration + rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contents_are_written_back_in_the_encoding_they_were_read_in() {
+        let contents = "<Project>\r\n  <PropertyGroup />\r\n</Project>";
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Utf8Bom,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+        ] {
+            let bytes = encoding.encode(contents);
+            assert_eq!(Encoding::detect(&bytes), encoding);
+            assert_eq!(encoding.decode(&bytes).unwrap(), contents);
+        }
+    }
+
+    #[test]
+    fn line_endings_follow_the_majority_of_lines() {
+        assert_eq!(LineEnding::detect("<Project />"), None);
+        assert_eq!(LineEnding::detect("a\nb\r\nc\n"), Some(LineEnding::Lf));
+        assert_eq!(
+            LineEnding::Lf.apply("<Project>\r\n  <A />\n</Project>\r\n"),
+            "<Project>\n  <A />\n</Project>\n"
+        );
+        assert_eq!(LineEnding::CrLf.apply("a\r\nb\n"), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn utf16_files_declare_their_encoding() {
+        let bytes =
+            Encoding::Utf16Le.encode("<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n<Project />");
+        assert_eq!(
+            Encoding::Utf16Le.decode(&bytes).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"utf-16\"?>\r\n<Project />"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+/// A check or validation found problems.
+pub const EXIT_VALIDATION_FAILED: i32 = 1;
+/// The command can not be run with the given arguments or on the repository as it is.
+pub const EXIT_USER_ERROR: i32 = 2;
+/// Something failed that should not have, like an unreadable file or a bug.
+pub const EXIT_INTERNAL_ERROR: i32 = 3;
+
+/// Why a command failed, printed as a diagnostic by `exit`.
+#[derive(Debug)]
+pub enum Error {
+    User(String),
+    Internal(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn user(message: impl Into<String>) -> Self {
+        Self::User(message.into())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::Internal(message.into())
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::User(_) => EXIT_USER_ERROR,
+            Error::Internal(_) => EXIT_INTERNAL_ERROR,
+        }
+    }
+
+    /// Prints the diagnostic and exits with the matching exit code.
+    pub fn exit(&self) -> ! {
+        eprintln!("error: {}", self);
+        std::process::exit(self.exit_code())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::User(message) | Error::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+impl From<crate::csproj::Error> for Error {
+    fn from(err: crate::csproj::Error) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+/// Makes the remaining panics print a one line diagnostic and exit with `EXIT_INTERNAL_ERROR`
+/// instead of the exit code of a panic. The backtrace is still printed when RUST_BACKTRACE is
+/// set.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::env::var_os("RUST_BACKTRACE").is_some() {
+            default_hook(info);
+        } else {
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown error"));
+            match info.location() {
+                Some(location) => eprintln!("error: {} ({})", message, location),
+                None => eprintln!("error: {}", message),
+            }
+        }
+        std::process::exit(EXIT_INTERNAL_ERROR);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_distinct_exit_codes() {
+        let codes = [
+            EXIT_VALIDATION_FAILED,
+            Error::user("no csproj found").exit_code(),
+            Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).exit_code(),
+        ];
+        assert_eq!(codes, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use log::{debug, warn};
+
+use crate::encoding;
+use crate::path_extensions::{from_msbuild_path, PathExt};
+
+lazy_static! {
+    /// The properties given with `--msbuild-property`, None when conditions are not evaluated.
+    static ref GLOBAL_PROPERTIES: RwLock<Option<Vec<(String, String)>>> = RwLock::new(None);
+}
+
+/// Applies the options given on the command line, must be called before any project is parsed.
+/// Conditions are only evaluated when properties are given, otherwise everything in a project
+/// applies like before.
+pub fn init(properties: Vec<(String, String)>) {
+    *GLOBAL_PROPERTIES.write().unwrap() = if properties.is_empty() {
+        None
+    } else {
+        Some(properties)
+    };
+}
+
+/// The properties given on the command line as `NAME=VALUE`, for keys like the cache version.
+pub fn global_properties() -> Option<Vec<(String, String)>> {
+    GLOBAL_PROPERTIES.read().unwrap().clone()
+}
+
+/// Expands properties and evaluates MSBuild conditions with the global properties, the reserved
+/// properties like `MSBuildProjectDirectory` and the properties of a project, which are set in the
+/// order in which the project defines them. Global and reserved properties can not be overridden
+/// by the project, like in MSBuild.
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    project_dir: PathBuf,
+    /// The values by lowercase name, MSBuild property names are case insensitive.
+    properties: HashMap<String, String>,
+    global: Vec<String>,
+    /// Whether conditions are evaluated, otherwise everything applies.
+    conditions: bool,
+}
+
+impl Evaluator {
+    /// Returns an evaluator for the project file that only evaluates conditions when global
+    /// properties were given on the command line.
+    pub fn for_project(project_path: &Path) -> Self {
+        match global_properties() {
+            Some(properties) => Self::new(project_path, &properties),
+            None => Self {
+                conditions: false,
+                ..Self::new(project_path, &[])
+            },
+        }
+    }
+
+    pub fn new(project_path: &Path, global_properties: &[(String, String)]) -> Self {
+        let project_dir = project_path.parent().unwrap();
+        let file_name = project_path.file_name().unwrap().to_string_lossy();
+        let reserved = [
+            ("MSBuildProjectFullPath", project_path.display().to_string()),
+            ("MSBuildProjectDirectory", project_dir.display().to_string()),
+            ("MSBuildProjectFile", file_name.to_string()),
+            (
+                "MSBuildProjectName",
+                project_path
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            ("MSBuildThisFile", file_name.to_string()),
+            (
+                "MSBuildThisFileDirectory",
+                format!("{}{}", project_dir.display(), std::path::MAIN_SEPARATOR),
+            ),
+        ];
+        let properties = reserved
+            .iter()
+            .map(|(name, value)| (name.to_lowercase(), value.clone()))
+            .chain(
+                global_properties
+                    .iter()
+                    .map(|(name, value)| (name.to_lowercase(), value.clone())),
+            )
+            .collect::<HashMap<_, _>>();
+        Self {
+            project_dir: project_dir.to_owned(),
+            global: properties.keys().cloned().collect(),
+            properties,
+            conditions: true,
+        }
+    }
+
+    /// Returns an evaluator with the properties that the file defines itself, without following
+    /// its imports, or None when the file can not be read.
+    pub fn for_file(path: &Path) -> Option<Self> {
+        let contents = match encoding::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let document = match roxmltree::Document::parse(&contents) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!("Failed to parse {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        let mut evaluator = Self::for_project(path);
+        for node in document.descendants().filter(|node| is_property(*node)) {
+            if evaluator.defines(node) {
+                evaluator.set_property(node.tag_name().name(), node.text().unwrap_or("").trim());
+            }
+        }
+        Some(evaluator)
+    }
+
+    pub fn evaluates_conditions(&self) -> bool {
+        self.conditions
+    }
+
+    /// Points `MSBuildThisFile` and `MSBuildThisFileDirectory` to the file that is evaluated,
+    /// which differs from the project in imported files.
+    pub fn set_this_file(&mut self, path: &Path) {
+        self.properties.insert(
+            "msbuildthisfile".to_owned(),
+            path.file_name().unwrap().to_string_lossy().to_string(),
+        );
+        self.properties.insert(
+            "msbuildthisfiledirectory".to_owned(),
+            format!(
+                "{}{}",
+                path.parent().unwrap().display(),
+                std::path::MAIN_SEPARATOR
+            ),
+        );
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .get(&name.to_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Defines a property of the project, the value can refer to earlier properties.
+    pub fn set_property(&mut self, name: &str, value: &str) {
+        let name = name.to_lowercase();
+        if self.global.contains(&name) {
+            return;
+        }
+        let value = self.expand(value).unwrap_or_else(|| value.to_owned());
+        self.properties.insert(name, value);
+    }
+
+    /// Whether every property that the text refers to is defined, `SolutionDir` is expanded to
+    /// the directory of the closest solution.
+    pub fn defines_all(&self, text: &str) -> bool {
+        text.split("$(").skip(1).all(|rest| {
+            let name = rest.split(')').next().unwrap_or(rest);
+            self.property(name).is_some() || name.eq_ignore_ascii_case("SolutionDir")
+        })
+    }
+
+    /// Replaces the `$(Name)` references in the text with the values of the properties, undefined
+    /// properties are empty. Returns None for property functions and item references, which can
+    /// not be expanded.
+    pub fn expand(&self, text: &str) -> Option<String> {
+        if text.contains("@(") || text.contains("%(") {
+            return None;
+        }
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("$(") {
+            out.push_str(&rest[..start]);
+            let end = rest[start..].find(')')? + start;
+            let name = &rest[start + 2..end];
+            if !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return None;
+            }
+            match self.property(name) {
+                Some(value) => out.push_str(value),
+                // Only set when building through a solution, the closest solution is the best
+                // guess.
+                None if name.eq_ignore_ascii_case("SolutionDir") => {
+                    if let Some(dir) = solution_dir(&self.project_dir) {
+                        out.push_str(&format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR));
+                    }
+                }
+                None => {}
+            }
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+
+    /// Expands the properties in a path relative to the project directory and resolves it.
+    pub fn resolve_path(&self, text: &str) -> Option<PathBuf> {
+        let expanded = self.expand(text)?;
+        Some(
+            self.project_dir
+                .join(from_msbuild_path(expanded.trim()))
+                .simplify(),
+        )
+    }
+
+    /// Whether the condition holds. Conditions that can not be evaluated, because they use
+    /// property functions or are malformed, are assumed to hold so that nothing is hidden that
+    /// might apply.
+    pub fn condition(&self, condition: &str) -> bool {
+        if condition.trim().is_empty() {
+            return true;
+        }
+        match self.evaluate(condition) {
+            Some(value) => value,
+            None => {
+                debug!("Assuming that the condition {} holds", condition);
+                true
+            }
+        }
+    }
+
+    fn evaluate(&self, condition: &str) -> Option<bool> {
+        let tokens = tokenize(condition)?;
+        let mut parser = Parser {
+            evaluator: self,
+            tokens: &tokens,
+            position: 0,
+        };
+        let value = parser.or()?;
+        if parser.position != tokens.len() {
+            return None;
+        }
+        value.to_bool()
+    }
+
+    /// Whether a node applies, which requires the conditions of the node and of all its ancestors
+    /// to hold. An Otherwise element only applies when none of the When elements before it do.
+    pub fn applies(&self, node: roxmltree::Node) -> bool {
+        !self.conditions || self.holds(node)
+    }
+
+    /// Whether the property element defines its property. Without evaluating conditions only the
+    /// properties outside of any condition are known.
+    pub fn defines(&self, node: roxmltree::Node) -> bool {
+        if self.conditions {
+            self.holds(node)
+        } else {
+            node.ancestors()
+                .all(|node| node.attribute("Condition").is_none())
+        }
+    }
+
+    /// Whether the Import element is followed. Its conditions are evaluated even without global
+    /// properties because a file is either imported or not, most check whether the file exists.
+    pub fn imports(&self, node: roxmltree::Node) -> bool {
+        self.holds(node)
+    }
+
+    fn holds(&self, node: roxmltree::Node) -> bool {
+        node.ancestors()
+            .filter(|node| node.is_element())
+            .all(|node| match node.tag_name().name() {
+                "Otherwise" => {
+                    std::iter::successors(node.prev_sibling(), |node| node.prev_sibling())
+                        .filter(|sibling| sibling.has_tag_name("When"))
+                        .all(|sibling| {
+                            !self.condition(sibling.attribute("Condition").unwrap_or(""))
+                        })
+                }
+                _ => self.condition(node.attribute("Condition").unwrap_or("")),
+            })
+    }
+}
+
+/// Whether the node defines a property, which are the elements in a PropertyGroup.
+pub fn is_property(node: roxmltree::Node) -> bool {
+    node.is_element()
+        && node
+            .parent_element()
+            .map_or(false, |parent| parent.has_tag_name("PropertyGroup"))
+}
+
+/// Returns the closest directory that contains a solution.
+fn solution_dir(project_dir: &Path) -> Option<&Path> {
+    project_dir.ancestors().find(|dir| {
+        std::fs::read_dir(dir).map_or(false, |entries| {
+            entries.filter_map(Result::ok).any(|entry| {
+                entry
+                    .path()
+                    .extension()
+                    .map_or(false, |extension| extension.eq_ignore_ascii_case("sln"))
+            })
+        })
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A quoted string, or an unquoted word like `true` or `$(Configuration)`.
+    Text(String),
+    Function(String),
+    Comparison(&'static str),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(condition: &str) -> Option<Vec<Token>> {
+    let chars = condition.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+        match c {
+            _ if c.is_whitespace() => index += 1,
+            '\'' => {
+                let length = chars[index + 1..].iter().position(|&c| c == '\'')?;
+                let text = chars[index + 1..index + 1 + length].iter().collect();
+                tokens.push(Token::Text(text));
+                index += length + 2;
+            }
+            '=' | '!' | '<' | '>' if next == Some('=') => {
+                tokens.push(Token::Comparison(match c {
+                    '=' => "==",
+                    '!' => "!=",
+                    '<' => "<=",
+                    _ => ">=",
+                }));
+                index += 2;
+            }
+            '<' => {
+                tokens.push(Token::Comparison("<"));
+                index += 1;
+            }
+            '>' => {
+                tokens.push(Token::Comparison(">"));
+                index += 1;
+            }
+            '!' => {
+                tokens.push(Token::Not);
+                index += 1;
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                index += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                index += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                index += 1;
+            }
+            '$' if next == Some('(') => {
+                let length = chars[index..].iter().position(|&c| c == ')')? + 1;
+                tokens.push(Token::Text(chars[index..index + length].iter().collect()));
+                index += length;
+            }
+            _ if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let length = chars[index..]
+                    .iter()
+                    .position(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-'))
+                    .unwrap_or(chars.len() - index);
+                let word = chars[index..index + length].iter().collect::<String>();
+                index += length;
+                tokens.push(match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    _ if chars.get(index) == Some(&'(') => Token::Function(word),
+                    _ => Token::Text(word),
+                });
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn to_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            Value::Text(text) => match text.to_lowercase().as_str() {
+                "true" | "on" | "yes" => Some(true),
+                "false" | "off" | "no" => Some(false),
+                _ => None,
+            },
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Value::Text(text) => text.clone(),
+            Value::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+/// A recursive descent parser that evaluates while it parses, every method returns None when the
+/// condition can not be evaluated.
+struct Parser<'a> {
+    evaluator: &'a Evaluator,
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(token)
+    }
+
+    fn expect(&mut self, token: &Token) -> Option<()> {
+        if self.next()? == token {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn or(&mut self) -> Option<Value> {
+        let mut value = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            let right = self.and()?;
+            value = Value::Bool(value.to_bool()? || right.to_bool()?);
+        }
+        Some(value)
+    }
+
+    fn and(&mut self) -> Option<Value> {
+        let mut value = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            let right = self.not()?;
+            value = Value::Bool(value.to_bool()? && right.to_bool()?);
+        }
+        Some(value)
+    }
+
+    fn not(&mut self) -> Option<Value> {
+        if self.peek() == Some(&Token::Not) {
+            self.position += 1;
+            return Some(Value::Bool(!self.not()?.to_bool()?));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Option<Value> {
+        let left = self.operand()?;
+        let operator = match self.peek() {
+            Some(Token::Comparison(operator)) => *operator,
+            _ => return Some(left),
+        };
+        self.position += 1;
+        let right = self.operand()?;
+        compare(operator, &left.to_text(), &right.to_text()).map(Value::Bool)
+    }
+
+    fn operand(&mut self) -> Option<Value> {
+        match self.next()? {
+            Token::Open => {
+                let value = self.or()?;
+                self.expect(&Token::Close)?;
+                Some(value)
+            }
+            Token::Text(text) => Some(Value::Text(self.evaluator.expand(text)?)),
+            Token::Function(name) => {
+                self.expect(&Token::Open)?;
+                let argument = match self.next()? {
+                    Token::Text(text) => self.evaluator.expand(text)?,
+                    _ => return None,
+                };
+                self.expect(&Token::Close)?;
+                self.function(name, &argument).map(Value::Bool)
+            }
+            _ => None,
+        }
+    }
+
+    fn function(&self, name: &str, argument: &str) -> Option<bool> {
+        match name.to_lowercase().as_str() {
+            "exists" => Some(
+                !argument.trim().is_empty()
+                    && self
+                        .evaluator
+                        .project_dir
+                        .join(from_msbuild_path(argument.trim()))
+                        .simplify()
+                        .exists(),
+            ),
+            "hastrailingslash" => Some(argument.ends_with('/') || argument.ends_with('\\')),
+            _ => None,
+        }
+    }
+}
+
+/// Compares numbers as numbers and everything else as case insensitive text, which only
+/// supports equality.
+fn compare(operator: &str, left: &str, right: &str) -> Option<bool> {
+    if let (Ok(left), Ok(right)) = (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        return Some(match operator {
+            "==" => left == right,
+            "!=" => left != right,
+            "<" => left < right,
+            "<=" => left <= right,
+            ">" => left > right,
+            _ => left >= right,
+        });
+    }
+    let equal = left.eq_ignore_ascii_case(right);
+    match operator {
+        "==" => Some(equal),
+        "!=" => Some(!equal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluator(properties: &[(&str, &str)]) -> Evaluator {
+        Evaluator::new(
+            Path::new("/does/not/exist/A.csproj"),
+            &properties
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn conditions_compare_expanded_properties() {
+        let evaluator = evaluator(&[("TargetFramework", "net8.0"), ("Configuration", "Release")]);
+        assert!(evaluator.condition("'$(TargetFramework)' == 'NET8.0'"));
+        assert!(!evaluator.condition("'$(TargetFramework)' == 'net48'"));
+        assert!(evaluator.condition(
+            " '$(Configuration)|$(Platform)' == 'Release|' and !('$(TargetFramework)' == 'net48') "
+        ));
+        assert!(evaluator.condition("'$(Undefined)' == '' or false"));
+        assert!(!evaluator.condition("Exists('missing.props')"));
+        assert!(evaluator.condition("'9' < '10' AND '$(Nullable)' == ''"));
+    }
+
+    #[test]
+    fn unsupported_conditions_hold() {
+        let evaluator = evaluator(&[("TargetFramework", "net8.0")]);
+        assert!(evaluator
+            .condition("$([MSBuild]::IsTargetFrameworkCompatible('$(TargetFramework)', 'net48'))"));
+        assert!(evaluator.condition("'$(TargetFramework)' < 'net48'"));
+        assert!(evaluator.condition("'unterminated == ''"));
+    }
+
+    #[test]
+    fn global_properties_win_over_project_properties() {
+        let mut evaluator = evaluator(&[("TargetFramework", "net8.0")]);
+        evaluator.set_property("targetframework", "net48");
+        evaluator.set_property("Root", "src");
+        evaluator.set_property("Output", "$(Root)/bin/$(TargetFramework)");
+        assert_eq!(evaluator.property("TargetFramework"), Some("net8.0"));
+        assert_eq!(evaluator.property("output"), Some("src/bin/net8.0"));
+    }
+
+    #[test]
+    fn paths_are_resolved_with_reserved_properties() {
+        let mut evaluator = evaluator(&[]);
+        evaluator.set_property("MSBuildProjectDirectory", "/elsewhere");
+        evaluator.set_property("SharedDir", "$(MSBuildThisFileDirectory)../Shared/");
+        assert_eq!(
+            evaluator.resolve_path("$(MSBuildProjectDirectory)/../B/B.csproj"),
+            Some(PathBuf::from("/does/not/B/B.csproj"))
+        );
+        assert_eq!(
+            evaluator.resolve_path("$(SharedDir)Shared.csproj"),
+            Some(PathBuf::from("/does/not/Shared/Shared.csproj"))
+        );
+        assert_eq!(evaluator.resolve_path("$(Root.Trim())B.csproj"), None);
+    }
+
+    #[test]
+    fn this_file_points_to_the_imported_file() {
+        let mut evaluator = evaluator(&[]);
+        evaluator.set_this_file(Path::new("/does/Directory.Build.props"));
+        assert_eq!(
+            evaluator.resolve_path("$(MSBuildThisFileDirectory)B/B.csproj"),
+            Some(PathBuf::from("/does/B/B.csproj"))
+        );
+        assert_eq!(evaluator.property("MSBuildProjectFile"), Some("A.csproj"));
+    }
+}
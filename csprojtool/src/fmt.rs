@@ -0,0 +1,410 @@
+use std::path::PathBuf;
+
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli, list,
+    path_extensions::relative_path,
+    plan::Effects,
+    xml_extensions::{child_elements, parse_xml_str, xml_to_string},
+};
+
+const ARG_CHECK: &'static str = "check";
+const CMD_FMT: &'static str = "fmt";
+
+/// The items that `fmt` sorts by Include.
+const SORTED_ITEMS: &[&str] = &["PackageReference", "ProjectReference"];
+
+/// The attributes that are written first, in this order, the others keep their order after them.
+const LEADING_ATTRIBUTES: &[&str] = &[
+    "Sdk",
+    "Include",
+    "Update",
+    "Remove",
+    "Exclude",
+    "Version",
+    "Condition",
+];
+
+#[derive(Debug)]
+pub struct FmtCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+    check: bool,
+}
+
+impl FmtCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_FMT)
+            .about("Rewrite projects in a canonical format to reduce merge conflicts")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_plan_json())
+            .arg(
+                clap::Arg::with_name(ARG_CHECK)
+                    .long("check")
+                    .help("List the projects that are not formatted and exit with 1 if there are any, without writing anything"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_FMT).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+            check: matches.is_present(ARG_CHECK),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_FMT, self.plan_json.as_deref());
+
+        let mut formatted_count = 0;
+        for project in projects.iter() {
+            let contents = effects
+                .read_to_string(&project.path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", project.path.display(), e));
+            let mut root = parse_xml_str(&contents)
+                .unwrap_or_else(|e| panic!("Failed to parse {}: {:?}", project.path.display(), e));
+            format_project(&mut root);
+
+            // Compared with the line endings the file would be written with.
+            let formatted = xml_to_string(&root);
+            let formatted = match effects.line_ending(&project.path) {
+                Some(line_ending) => line_ending.apply(&formatted),
+                None => formatted,
+            };
+            if formatted != contents && self.check {
+                println!("{}", relative_path(&current_dir, &project.path).display());
+                formatted_count += 1;
+            } else if formatted != contents {
+                effects
+                    .write_file(&project.path, formatted, Vec::new())
+                    .unwrap();
+                status!(
+                    "Formatted {}",
+                    relative_path(&current_dir, &project.path).display()
+                );
+                formatted_count += 1;
+            }
+        }
+
+        if formatted_count == 0 {
+            status!("All {} projects are formatted", projects.len());
+        }
+
+        if self.check {
+            if formatted_count > 0 {
+                status!(
+                    "{} of {} projects are not formatted, run fmt to format them",
+                    formatted_count,
+                    projects.len()
+                );
+                std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+            }
+            return;
+        }
+
+        effects.finish();
+    }
+}
+
+/// Brings a project into the canonical shape. The indentation is left to the writer, so the
+/// whitespace between elements is dropped here.
+pub fn format_project(project: &mut Element) {
+    strip_whitespace(project);
+    merge_adjacent_groups(project);
+    merge_duplicate_item_groups(project);
+    sort_items(project, SORTED_ITEMS);
+    order_attributes(project);
+}
+
+fn strip_whitespace(element: &mut Element) {
+    element.children.retain(|node| match node {
+        XMLNode::Text(text) => !text.trim().is_empty(),
+        _ => true,
+    });
+    for child in element.children.iter_mut() {
+        if let XMLNode::Element(child) = child {
+            strip_whitespace(child);
+        }
+    }
+}
+
+/// Merges each ItemGroup or PropertyGroup into the group right before it if both have the same
+/// name and attributes. The items keep their order. Returns the number of groups merged away.
+pub fn merge_adjacent_groups(project: &mut Element) -> usize {
+    let mut merged = 0;
+    let mut result = Vec::<XMLNode>::with_capacity(project.children.len());
+
+    for node in std::mem::take(&mut project.children) {
+        let group = match node {
+            XMLNode::Element(group)
+                if group.name == "ItemGroup" || group.name == "PropertyGroup" =>
+            {
+                group
+            }
+            node => {
+                result.push(node);
+                continue;
+            }
+        };
+
+        // Whitespace between the groups does not separate them, comments do.
+        let previous = result
+            .iter_mut()
+            .rev()
+            .find(|node| !matches!(node, XMLNode::Text(text) if text.trim().is_empty()));
+        match previous {
+            Some(XMLNode::Element(previous))
+                if previous.name == group.name && previous.attributes == group.attributes =>
+            {
+                previous.children.extend(group.children);
+                merged += 1;
+            }
+            _ => result.push(XMLNode::Element(group)),
+        }
+    }
+
+    project.children = result;
+    merged
+}
+
+/// Merges ItemGroups that hold a single kind of item into the first ItemGroup with the same kind
+/// of item and the same attributes, like the condition. Returns true if a group was merged.
+pub fn merge_duplicate_item_groups(project: &mut Element) -> bool {
+    let mut merged = false;
+    let mut result = Vec::<XMLNode>::with_capacity(project.children.len());
+
+    for node in std::mem::take(&mut project.children) {
+        let group = match node {
+            XMLNode::Element(group) if item_kind(&group).is_some() => group,
+            node => {
+                result.push(node);
+                continue;
+            }
+        };
+
+        let target = result.iter_mut().find_map(|node| match node {
+            XMLNode::Element(target)
+                if item_kind(target) == item_kind(&group)
+                    && target.attributes == group.attributes =>
+            {
+                Some(target)
+            }
+            _ => None,
+        });
+        match target {
+            Some(target) => {
+                target.children.extend(group.children);
+                merged = true;
+            }
+            None => result.push(XMLNode::Element(group)),
+        }
+    }
+
+    project.children = result;
+    merged
+}
+
+/// Returns the name of the items in an ItemGroup if they all have the same name.
+fn item_kind(group: &Element) -> Option<&str> {
+    if group.name != "ItemGroup" {
+        return None;
+    }
+    let mut names = child_elements(group).map(|item| item.name.as_str());
+    let first = names.next()?;
+    if names.all(|name| name == first) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+/// Orders the items with the given names by Include, case insensitively, within each ItemGroup.
+/// Other elements and comments keep their position. Returns true if an item moved.
+pub fn sort_items(project: &mut Element, names: &[&str]) -> bool {
+    let mut sorted = false;
+
+    for group in project.children.iter_mut() {
+        let group = match group {
+            XMLNode::Element(group) if group.name == "ItemGroup" => group,
+            _ => continue,
+        };
+
+        for name in names.iter() {
+            let positions = group
+                .children
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| matches!(node, XMLNode::Element(item) if item.name == *name))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+
+            let mut items = positions
+                .iter()
+                .map(|&index| group.children[index].clone())
+                .collect::<Vec<_>>();
+            items.sort_by_key(|node| match node {
+                XMLNode::Element(item) => item
+                    .attributes
+                    .get("Include")
+                    .map(|include| include.to_lowercase())
+                    .unwrap_or_default(),
+                _ => unreachable!(),
+            });
+
+            for (index, item) in positions.into_iter().zip(items) {
+                if group.children[index] != item {
+                    group.children[index] = item;
+                    sorted = true;
+                }
+            }
+        }
+    }
+
+    sorted
+}
+
+fn order_attributes(element: &mut Element) {
+    let rank = |name: &str| {
+        LEADING_ATTRIBUTES
+            .iter()
+            .position(|leading| *leading == name)
+            .unwrap_or(LEADING_ATTRIBUTES.len())
+    };
+
+    let mut attributes = std::mem::take(&mut element.attributes)
+        .into_iter()
+        .collect::<Vec<_>>();
+    attributes.sort_by_key(|(name, _)| rank(name));
+    element.attributes = attributes.into_iter().collect();
+
+    for child in element.children.iter_mut() {
+        if let XMLNode::Element(child) = child {
+            order_attributes(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_extensions::{element, project};
+
+    fn package(name: &str) -> XMLNode {
+        element(
+            "PackageReference",
+            &[("Version", "1.0.0"), ("Include", name)],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn format_project_merges_sorts_and_orders_attributes() {
+        let whitespace = || XMLNode::Text(String::from("\n  "));
+        let mut actual = project(vec![
+            whitespace(),
+            element("ItemGroup", &[], vec![whitespace(), package("Serilog")]),
+            element("PropertyGroup", &[], vec![]),
+            element("ItemGroup", &[], vec![package("Polly")]),
+            element(
+                "ItemGroup",
+                &[("Condition", "'$(TargetFramework)' == 'net48'")],
+                vec![package("System.Memory")],
+            ),
+        ]);
+        format_project(&mut actual);
+
+        let sorted = |name: &str| {
+            element(
+                "PackageReference",
+                &[("Include", name), ("Version", "1.0.0")],
+                vec![],
+            )
+        };
+        assert_eq!(
+            actual,
+            project(vec![
+                element("ItemGroup", &[], vec![sorted("Polly"), sorted("Serilog")]),
+                element("PropertyGroup", &[], vec![]),
+                element(
+                    "ItemGroup",
+                    &[("Condition", "'$(TargetFramework)' == 'net48'")],
+                    vec![sorted("System.Memory")],
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_adjacent_groups_only_merges_neighbours_with_the_same_condition() {
+        let condition = [("Condition", "'$(Configuration)' == 'Debug'")];
+        let property = |name: &str| element(name, &[], vec![XMLNode::Text(String::from("x"))]);
+        let mut actual = project(vec![
+            element("PropertyGroup", &[], vec![property("A")]),
+            element("PropertyGroup", &[], vec![property("B")]),
+            element("PropertyGroup", &condition, vec![property("C")]),
+            element("ItemGroup", &[], vec![package("Serilog")]),
+            XMLNode::Comment(String::from(" tests ")),
+            element("ItemGroup", &[], vec![package("Polly")]),
+            element("ItemGroup", &[], vec![package("Moq")]),
+        ]);
+
+        assert_eq!(merge_adjacent_groups(&mut actual), 2);
+        assert_eq!(
+            actual,
+            project(vec![
+                element("PropertyGroup", &[], vec![property("A"), property("B")]),
+                element("PropertyGroup", &condition, vec![property("C")]),
+                element("ItemGroup", &[], vec![package("Serilog")]),
+                XMLNode::Comment(String::from(" tests ")),
+                element("ItemGroup", &[], vec![package("Polly"), package("Moq")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn sort_items_keeps_other_elements_in_place() {
+        let reference =
+            |include: &str| element("ProjectReference", &[("Include", include)], vec![]);
+        let comment = || XMLNode::Comment(String::from(" analyzers "));
+        let mut actual = project(vec![element(
+            "ItemGroup",
+            &[],
+            vec![
+                reference("..\\B\\B.csproj"),
+                comment(),
+                reference("..\\a\\A.csproj"),
+            ],
+        )]);
+
+        assert!(sort_items(&mut actual, &["ProjectReference"]));
+        assert_eq!(
+            actual,
+            project(vec![element(
+                "ItemGroup",
+                &[],
+                vec![
+                    reference("..\\a\\A.csproj"),
+                    comment(),
+                    reference("..\\B\\B.csproj")
+                ],
+            )])
+        );
+    }
+}
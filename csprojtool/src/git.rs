@@ -0,0 +1,144 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::error::{Error, Result};
+use crate::path_extensions::strip_extended_length;
+use crate::tools::{self, Tool};
+
+/// Runs git in the directory and returns its output. A failing git is a user error because it
+/// explains what is wrong, like a directory outside of any repository or an unknown revision.
+fn git<I, S>(dir: &Path, args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = tools::command(Tool::Git);
+    command.current_dir(strip_extended_length(dir)).args(args);
+    debug!("{:?}", &command);
+
+    let output = command
+        .output()
+        .map_err(|e| Error::internal(format!("Failed to run {:?}: {}", command, e)))?;
+    if !output.status.success() {
+        return Err(Error::user(format!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|e| Error::internal(format!("{:?} printed invalid UTF-8: {}", command, e)))
+}
+
+/// Returns the root of the repository that contains the directory.
+pub fn root(dir: &Path) -> Result<PathBuf> {
+    let root = PathBuf::from(git(dir, ["rev-parse", "--show-toplevel"])?.trim());
+    // Project paths are canonical, so paths in the repository must be too.
+    Ok(std::fs::canonicalize(&root).unwrap_or(root))
+}
+
+/// Returns the value of a git attribute of the file, None when the attribute is unspecified or
+/// the file is not in a repository. The file does not have to exist.
+pub fn attribute(path: &Path, name: &str) -> Option<String> {
+    let dir = path.ancestors().skip(1).find(|dir| dir.is_dir())?;
+    let path = strip_extended_length(path);
+    let stdout = try_git(
+        dir,
+        [
+            OsStr::new("check-attr"),
+            OsStr::new(name),
+            OsStr::new("--"),
+            path.as_os_str(),
+        ],
+    )?;
+    match stdout.trim_end().rsplit(": ").next()? {
+        "unspecified" | "unset" | "" => None,
+        value => Some(value.to_owned()),
+    }
+}
+
+/// Returns the date of the last commit that changed the file as `YYYY-MM-DD`, None when the file
+/// was never committed or is not in a repository.
+pub fn last_commit_date(path: &Path) -> Option<String> {
+    let dir = path.ancestors().skip(1).find(|dir| dir.is_dir())?;
+    let path = strip_extended_length(path);
+    let stdout = try_git(
+        dir,
+        [
+            OsStr::new("log"),
+            OsStr::new("-1"),
+            OsStr::new("--format=%cd"),
+            OsStr::new("--date=short"),
+            OsStr::new("--"),
+            path.as_os_str(),
+        ],
+    )?;
+    Some(stdout.trim().to_owned()).filter(|date| !date.is_empty())
+}
+
+/// Runs git like `git` but returns None when it fails.
+fn try_git<I, S>(dir: &Path, args: I) -> Option<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = tools::command(Tool::Git);
+    command.current_dir(strip_extended_length(dir)).args(args);
+    debug!("{:?}", &command);
+
+    let output = command
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Returns the commit where the current branch forked off the revision.
+pub fn merge_base(root: &Path, rev: &str) -> Result<String> {
+    Ok(git(root, ["merge-base", rev, "HEAD"])?.trim().to_owned())
+}
+
+/// Returns the paths of the files in the revision relative to the root, with forward slashes.
+pub fn files_at(root: &Path, rev: &str) -> Result<Vec<String>> {
+    Ok(git(root, ["ls-tree", "-r", "--name-only", rev])?
+        .lines()
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Returns the contents of a file in the revision, the path is relative to the root.
+pub fn show(root: &Path, rev: &str, path: &str) -> Result<String> {
+    git(root, ["show", format!("{}:{}", rev, path).as_str()])
+}
+
+/// Returns the absolute paths of the files that changed since the merge base with the revision,
+/// including uncommitted, untracked and deleted files. Renamed files are returned under both
+/// names. The paths are separated by NUL so that git prints them unquoted.
+pub fn changed_files(dir: &Path, since: &str) -> Result<Vec<PathBuf>> {
+    let root = root(dir)?;
+
+    let committed = git(
+        &root,
+        [
+            "diff",
+            "--name-only",
+            "--no-renames",
+            "-z",
+            format!("{}...HEAD", since).as_str(),
+        ],
+    )?;
+    let uncommitted = git(&root, ["diff", "--name-only", "--no-renames", "-z", "HEAD"])?;
+    let untracked = git(&root, ["ls-files", "--others", "--exclude-standard", "-z"])?;
+
+    let mut paths = [committed, uncommitted, untracked]
+        .iter()
+        .flat_map(|output| output.split('\0'))
+        .filter(|path| !path.is_empty())
+        .map(|path| root.join(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
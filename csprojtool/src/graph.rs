@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::{
+    cli,
+    error::Result,
+    git, list,
+    path_extensions::{from_msbuild_path, relative_path, PathExt},
+    utils::path_extension_is_project,
+};
+
+const ARG_BASE: &'static str = "base";
+const CMD_GRAPH: &'static str = "graph";
+const CMD_DIFF: &'static str = "diff";
+
+/// A project reference from one project to another, both relative to the repository root with
+/// forward slashes.
+type Edge = (String, String);
+
+#[derive(Debug)]
+pub enum GraphCommand {
+    Diff(DiffCommand),
+}
+
+#[derive(Debug)]
+pub struct DiffCommand {
+    base: String,
+    search_path: PathBuf,
+}
+
+impl GraphCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::{Arg, SubCommand};
+
+        SubCommand::with_name(CMD_GRAPH)
+            .about("Inspect the project reference graph")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(CMD_DIFF)
+                    .about("List the project references added and removed since a base revision")
+                    .arg(
+                        Arg::with_name(ARG_BASE)
+                            .long("base")
+                            .value_name("REV")
+                            .help("The revision to compare with, the merge base with the current branch is used")
+                            .default_value("origin/main")
+                            .takes_value(true),
+                    )
+                    .arg(cli::arg_search()),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_GRAPH)?;
+
+        match matches.subcommand() {
+            (CMD_DIFF, Some(matches)) => Some(Self::Diff(DiffCommand {
+                base: matches.value_of(ARG_BASE).unwrap().to_owned(),
+                search_path: cli::get_search_path(matches),
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            Self::Diff(command) => command.execute(),
+        }
+    }
+}
+
+impl DiffCommand {
+    fn execute(&self) -> Result<()> {
+        let search_path = std::fs::canonicalize(self.search_path.simplified_absolute().unwrap())
+            .expect("Failed to find the search path!");
+        let root = git::root(if search_path.is_file() {
+            search_path.parent().unwrap()
+        } else {
+            &search_path
+        })?;
+        let rel_search_path = relative_path(&root, &search_path);
+        let in_search_path = |(from, _): &Edge| Path::new(from).starts_with(&rel_search_path);
+
+        let base = git::merge_base(&root, &self.base)?;
+        let base_edges = base_edges(&root, &base)?
+            .into_iter()
+            .filter(in_search_path)
+            .map(keyed)
+            .collect::<BTreeMap<_, _>>();
+
+        let head_edges = list::list(list::Options {
+            search_path: &root,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        })
+        .into_iter()
+        .flat_map(|project| {
+            let from = repo_path(&root, &project.path);
+            project
+                .project_references
+                .iter()
+                .map(|reference| (from.clone(), repo_path(&root, reference)))
+                .collect::<Vec<_>>()
+        })
+        .filter(in_search_path)
+        .map(keyed)
+        .collect::<BTreeMap<_, _>>();
+
+        let added = head_edges
+            .iter()
+            .filter(|(key, _)| !base_edges.contains_key(*key))
+            .map(|(_, edge)| edge)
+            .collect::<Vec<_>>();
+        let removed = base_edges
+            .iter()
+            .filter(|(key, _)| !head_edges.contains_key(*key))
+            .map(|(_, edge)| edge)
+            .collect::<Vec<_>>();
+
+        for (title, edges) in [("Added", &added), ("Removed", &removed)] {
+            if edges.is_empty() {
+                continue;
+            }
+            println!("{} project references:", title);
+            for (from, to) in edges.iter() {
+                println!("  {} -> {}", from, to);
+            }
+        }
+
+        if added.is_empty() && removed.is_empty() {
+            println!("No project references changed since {}", self.base);
+        }
+        Ok(())
+    }
+}
+
+/// Keys the edge by its lowercase paths, because paths in project files often differ in casing
+/// from the files on disk.
+fn keyed(edge: Edge) -> (Edge, Edge) {
+    ((edge.0.to_lowercase(), edge.1.to_lowercase()), edge)
+}
+
+fn repo_path(root: &Path, path: &Path) -> String {
+    relative_path(root, path)
+        .to_str()
+        .unwrap()
+        .replace('\\', "/")
+}
+
+/// Reads the project references of every project in the revision from git.
+fn base_edges(root: &Path, rev: &str) -> Result<Vec<Edge>> {
+    let mut edges = Vec::new();
+    for path in git::files_at(root, rev)?
+        .into_iter()
+        .filter(|path| path_extension_is_project(Path::new(path)))
+    {
+        let contents = git::show(root, rev, &path)?;
+        edges.extend(
+            project_references(&path, &contents)
+                .into_iter()
+                .map(|reference| (path.clone(), reference)),
+        );
+    }
+    Ok(edges)
+}
+
+/// Returns the project references of the project at the path relative to the root, relative to
+/// the root as well. References that use properties are skipped because they can not be
+/// resolved without evaluating the project.
+fn project_references(project_path: &str, contents: &str) -> Vec<String> {
+    let document = match roxmltree::Document::parse(contents) {
+        Ok(document) => document,
+        Err(e) => {
+            warn!("Failed to parse {}: {}", project_path, e);
+            return Vec::new();
+        }
+    };
+    let project_dir = Path::new(project_path).parent().unwrap();
+
+    document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "ProjectReference")
+        .filter_map(|node| node.attribute("Include"))
+        .filter(|include| !include.contains("$("))
+        .map(|include| {
+            project_dir
+                .join(from_msbuild_path(include))
+                .simplify()
+                .to_str()
+                .unwrap()
+                .replace('\\', "/")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_references_are_relative_to_the_root() {
+        let contents = r#"<Project Sdk="Microsoft.NET.Sdk">
+  <ItemGroup>
+    <ProjectReference Include="..\Data\Data.csproj" />
+    <ProjectReference Include="$(RepoRoot)\src\Core\Core.csproj" />
+  </ItemGroup>
+</Project>"#;
+
+        assert_eq!(
+            project_references("src/Web/Web.csproj", contents),
+            vec![String::from("src/Data/Data.csproj")]
+        );
+    }
+}
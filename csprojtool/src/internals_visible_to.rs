@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::encoding;
+use crate::{csproj::Project, utils::path_extension_is_project};
+
+/// An assembly that a project makes its internals visible to, with InternalsVisibleTo items in
+/// the project or its imports or with `[assembly: InternalsVisibleTo("...")]` in its sources.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Friend {
+    /// The name of the assembly as declared, without the public key.
+    pub assembly: String,
+    /// The project that builds the assembly, absent for assemblies that no project builds, like
+    /// the DynamicProxyGenAssembly2 of mocking libraries.
+    pub project: Option<PathBuf>,
+}
+
+/// The AssemblyName of the project, which defaults to the name of the project file.
+pub fn assembly_name(project: &Project) -> String {
+    project
+        .last_unconditional_property("AssemblyName")
+        .filter(|name| !name.contains("$("))
+        .map(str::to_owned)
+        .unwrap_or_else(|| project_name(&project.path))
+}
+
+/// Returns the friends of the project ordered by assembly, matched with the projects by their
+/// AssemblyName.
+pub fn friends(project: &Project, projects: &[Project]) -> Vec<Friend> {
+    let mut friends = declared(project)
+        .into_iter()
+        .map(|assembly| Friend {
+            project: projects
+                .iter()
+                .find(|other| assembly_name(other).eq_ignore_ascii_case(&assembly))
+                .map(|other| other.path.clone()),
+            assembly,
+        })
+        .collect::<Vec<_>>();
+    friends.sort();
+    friends.dedup();
+    friends
+}
+
+/// Returns the assemblies that the project declares as friends, from the items and the sources.
+fn declared(project: &Project) -> Vec<String> {
+    let assembly_name = assembly_name(project);
+    let project_name = project_name(&project.path);
+
+    let mut declared = Vec::new();
+    for path in Some(&project.path)
+        .into_iter()
+        .chain(project.imports.iter())
+    {
+        let contents = match encoding::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let document = match roxmltree::Document::parse(&contents) {
+            Ok(document) => document,
+            Err(_) => continue,
+        };
+        declared.extend(
+            document
+                .descendants()
+                .filter(|node| node.has_tag_name("InternalsVisibleTo"))
+                .filter_map(|node| node.attribute("Include"))
+                .filter_map(|include| item_friend(include, &assembly_name, &project_name)),
+        );
+    }
+
+    for path in source_files(project.path.parent().unwrap()) {
+        if let Ok(contents) = encoding::read_to_string(&path) {
+            declared.extend(source_friends(&contents));
+        }
+    }
+    declared
+}
+
+fn project_name(project_path: &Path) -> String {
+    project_path
+        .file_stem()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Resolves the Include of an InternalsVisibleTo item, which is often written relative to the
+/// assembly like `$(AssemblyName).Tests`. Includes with other properties are skipped.
+fn item_friend(include: &str, assembly_name: &str, project_name: &str) -> Option<String> {
+    let include = include
+        .replace("$(AssemblyName)", assembly_name)
+        .replace("$(MSBuildProjectName)", project_name);
+    if include.contains("$(") {
+        return None;
+    }
+    Some(strip_public_key(&include))
+}
+
+/// Returns the assemblies in the InternalsVisibleTo attributes of C# or Visual Basic source.
+fn source_friends(contents: &str) -> Vec<String> {
+    lazy_static! {
+        static ref ATTRIBUTE_REGEX: Regex = Regex::new(
+            r#"(?i)[\[<]\s*assembly\s*:\s*(?:System\.Runtime\.CompilerServices\.)?InternalsVisibleTo(?:Attribute)?\s*\(\s*"([^"]+)""#
+        )
+        .unwrap();
+    }
+
+    ATTRIBUTE_REGEX
+        .captures_iter(contents)
+        .map(|captures| strip_public_key(&captures[1]))
+        .collect()
+}
+
+fn strip_public_key(assembly: &str) -> String {
+    assembly.split(',').next().unwrap().trim().to_owned()
+}
+
+/// The C# and Visual Basic files of the project, without those in bin and obj and in the
+/// directories of other projects.
+fn source_files(project_dir: &Path) -> Vec<PathBuf> {
+    crate::walk::builder(project_dir)
+        .filter_entry(|entry| {
+            if entry.depth() == 0 || !entry.file_type().map_or(false, |t| t.is_dir()) {
+                return true;
+            }
+            let name = entry.file_name().to_string_lossy();
+            !name.eq_ignore_ascii_case("bin")
+                && !name.eq_ignore_ascii_case("obj")
+                && !std::fs::read_dir(entry.path())
+                    .into_iter()
+                    .flatten()
+                    .any(|child| {
+                        child.map_or(false, |child| path_extension_is_project(&child.path()))
+                    })
+        })
+        .build()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.extension().map_or(false, |extension| {
+                extension.eq_ignore_ascii_case("cs") || extension.eq_ignore_ascii_case("vb")
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_friends_reads_csharp_and_visual_basic_attributes() {
+        let contents = r#"using System.Runtime.CompilerServices;
+
+[assembly: InternalsVisibleTo("Core.Tests")]
+[assembly: System.Runtime.CompilerServices.InternalsVisibleToAttribute("Signed, PublicKey=0024000004800000")]
+<Assembly: InternalsVisibleTo("Legacy.Tests")>
+// InternalsVisibleTo("NotAnAttribute")
+"#;
+
+        assert_eq!(
+            source_friends(contents),
+            vec![
+                String::from("Core.Tests"),
+                String::from("Signed"),
+                String::from("Legacy.Tests"),
+            ]
+        );
+    }
+
+    #[test]
+    fn item_friend_resolves_the_assembly_name() {
+        assert_eq!(
+            item_friend("$(AssemblyName).Tests", "Contoso.Core", "Core"),
+            Some(String::from("Contoso.Core.Tests"))
+        );
+        assert_eq!(
+            item_friend("$(MSBuildProjectName).Tests", "Contoso.Core", "Core"),
+            Some(String::from("Core.Tests"))
+        );
+        assert_eq!(item_friend("$(TestAssembly)", "Contoso.Core", "Core"), None);
+    }
+}
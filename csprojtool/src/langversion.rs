@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::output::{self, status};
+use crate::{
+    cli,
+    csproj::Project,
+    evaluation::Evaluator,
+    list,
+    path_extensions::relative_path,
+    plan::Effects,
+    tfms::{default_lang_version, group_by_target_framework},
+    xml_extensions::{remove_property, set_property},
+};
+
+const ARG_FORMAT: &'static str = "format";
+const ARG_VERSION: &'static str = "version";
+const CMD_LANGVERSION: &'static str = "langversion";
+const CMD_REPORT: &'static str = "report";
+const CMD_SET: &'static str = "set";
+const CMD_CLEAN: &'static str = "clean";
+
+const LANG_VERSION: &'static str = "LangVersion";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Report(Format),
+    Set(String),
+    Clean,
+}
+
+#[derive(Debug)]
+pub struct LangVersionCommand {
+    action: Action,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Finding {
+    /// The project pins a language version older than the default of its target frameworks.
+    Older,
+    /// The project sets the language version that it would get without setting it.
+    Redundant,
+}
+
+#[derive(Debug, Serialize)]
+struct ProjectLangVersion {
+    path: PathBuf,
+    /// The LangVersion of the project, absent when neither the project nor its imports set it.
+    lang_version: Option<String>,
+    /// The default language version of the target framework with the oldest default.
+    default: Option<String>,
+    finding: Option<Finding>,
+}
+
+/// What the LangVersion of a project is and where it comes from.
+#[derive(Debug)]
+struct Setting {
+    /// As set in the project file itself.
+    own: Option<String>,
+    /// As set in the files that the project imports.
+    inherited: Option<String>,
+    /// The defaults of the target frameworks of the project.
+    defaults: Vec<(u32, u32)>,
+}
+
+impl LangVersionCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::{Arg, SubCommand};
+
+        SubCommand::with_name(CMD_LANGVERSION)
+            .about("Report and edit the C# language version of projects")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(CMD_REPORT)
+                    .about("List the LangVersion of projects and flag those pinning an older version than the default of their target frameworks or setting a redundant one")
+                    .arg(cli::arg_search())
+                    .arg(cli::arg_glob())
+                    .arg(
+                        Arg::with_name(ARG_FORMAT)
+                            .long("format")
+                            .value_name("FORMAT")
+                            .help("Sets the output format")
+                            .takes_value(true)
+                            .possible_values(&["text", "json"])
+                            .default_value("text"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_SET)
+                    .about("Set LangVersion in projects")
+                    .arg(
+                        Arg::with_name(ARG_VERSION)
+                            .value_name("VERSION")
+                            .help("The language version, like 12.0 or latest")
+                            .required(true)
+                            .takes_value(true),
+                    )
+                    .arg(cli::arg_search())
+                    .arg(cli::arg_glob())
+                    .arg(cli::arg_plan_json()),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_CLEAN)
+                    .about("Remove LangVersion from projects that set the version they would get without it")
+                    .arg(cli::arg_search())
+                    .arg(cli::arg_glob())
+                    .arg(cli::arg_plan_json()),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_LANGVERSION)?;
+
+        let (action, matches) = match matches.subcommand() {
+            (CMD_REPORT, Some(matches)) => (
+                Action::Report(match matches.value_of(ARG_FORMAT).unwrap() {
+                    "json" => Format::Json,
+                    _ => Format::Text,
+                }),
+                matches,
+            ),
+            (CMD_SET, Some(matches)) => (
+                Action::Set(matches.value_of(ARG_VERSION).unwrap().to_owned()),
+                matches,
+            ),
+            (CMD_CLEAN, Some(matches)) => (Action::Clean, matches),
+            _ => return None,
+        };
+
+        Some(Self {
+            action,
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        })
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+        // LangVersion of Visual Basic projects counts differently.
+        projects.retain(|project| {
+            project
+                .path
+                .extension()
+                .map_or(false, |extension| extension.eq_ignore_ascii_case("csproj"))
+        });
+
+        match &self.action {
+            Action::Report(format) => self.report(&projects, *format),
+            Action::Set(version) => self.set(&projects, version),
+            Action::Clean => self.clean(&projects),
+        }
+    }
+
+    fn report(&self, projects: &[Project], format: Format) {
+        let current_dir = std::env::current_dir().unwrap();
+        let settings = settings(projects);
+        let reports = projects
+            .iter()
+            .map(|project| {
+                let setting = &settings[project.path.as_path()];
+                ProjectLangVersion {
+                    path: relative_path(&current_dir, &project.path),
+                    lang_version: setting.own.clone().or_else(|| setting.inherited.clone()),
+                    default: setting
+                        .defaults
+                        .iter()
+                        .min()
+                        .map(|(major, minor)| format!("{}.{}", major, minor)),
+                    finding: finding(setting),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            format
+        };
+        match format {
+            Format::Text => {
+                for report in reports.iter() {
+                    println!(
+                        "{}  {} (default {}){}",
+                        report.path.display(),
+                        report.lang_version.as_deref().unwrap_or("-"),
+                        report.default.as_deref().unwrap_or("unknown"),
+                        match report.finding {
+                            Some(Finding::Older) => "  older than the default",
+                            Some(Finding::Redundant) => "  redundant",
+                            None => "",
+                        }
+                    );
+                }
+                let count = |expected: Finding| {
+                    reports
+                        .iter()
+                        .filter(|report| report.finding == Some(expected))
+                        .count()
+                };
+                println!(
+                    "{} of {} projects pin an older version than the default, {} set a redundant one",
+                    count(Finding::Older),
+                    reports.len(),
+                    count(Finding::Redundant)
+                );
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &reports).unwrap();
+                println!();
+            }
+        }
+    }
+
+    fn set(&self, projects: &[Project], version: &str) {
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_LANGVERSION, self.plan_json.as_deref());
+
+        let mut changed_count = 0;
+        for project in projects.iter() {
+            let mut changed = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    changed = set_property(&mut root, LANG_VERSION, version);
+                    if changed {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if changed {
+                changed_count += 1;
+                status!(
+                    "Set LangVersion to {} in {}",
+                    version,
+                    relative_path(&current_dir, &project.path).display()
+                );
+            }
+        }
+
+        if changed_count == 0 {
+            status!(
+                "All {} projects already set LangVersion to {}",
+                projects.len(),
+                version
+            );
+        }
+
+        effects.finish();
+    }
+
+    fn clean(&self, projects: &[Project]) {
+        let current_dir = std::env::current_dir().unwrap();
+        let settings = settings(projects);
+        let mut effects = Effects::new(CMD_LANGVERSION, self.plan_json.as_deref());
+
+        let mut removed_count = 0;
+        for project in projects.iter() {
+            if !is_redundant(&settings[project.path.as_path()]) {
+                continue;
+            }
+
+            let mut removed = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    removed = remove_property(&mut root, LANG_VERSION, None);
+                    if removed {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if removed {
+                removed_count += 1;
+                status!(
+                    "Removed the redundant LangVersion from {}",
+                    relative_path(&current_dir, &project.path).display()
+                );
+            }
+        }
+
+        if removed_count == 0 {
+            status!(
+                "None of the {} projects set a redundant LangVersion",
+                projects.len()
+            );
+        }
+
+        effects.finish();
+    }
+}
+
+/// Reads where the projects set LangVersion and the defaults of their target frameworks.
+fn settings(projects: &[Project]) -> HashMap<&Path, Setting> {
+    let mut target_frameworks = HashMap::<&Path, Vec<String>>::new();
+    for (target_framework, project_paths) in group_by_target_framework(projects) {
+        for project_path in project_paths {
+            target_frameworks
+                .entry(project_path)
+                .or_default()
+                .push(target_framework.clone());
+        }
+    }
+
+    let mut imported = HashMap::new();
+    projects
+        .iter()
+        .map(|project| {
+            let mut lang_version = |path: &Path| {
+                imported
+                    .entry(path.to_owned())
+                    .or_insert_with(|| {
+                        Evaluator::for_file(path).and_then(|evaluator| {
+                            evaluator.property("LangVersion").map(str::to_owned)
+                        })
+                    })
+                    .clone()
+            };
+            let own = lang_version(&project.path);
+            let inherited = project
+                .imports
+                .iter()
+                .rev()
+                .find_map(|path| lang_version(path));
+            let defaults = target_frameworks
+                .get(project.path.as_path())
+                .into_iter()
+                .flatten()
+                .filter_map(|target_framework| default_lang_version(target_framework))
+                .collect();
+            (
+                project.path.as_path(),
+                Setting {
+                    own,
+                    inherited,
+                    defaults,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Parses a LangVersion into major and minor. `default` and `latest` and the like have no
+/// version, `ISO-1` and `ISO-2` are C# 1 and 2.
+fn parse_lang_version(value: &str) -> Option<(u32, u32)> {
+    let value = value.trim().to_lowercase();
+    if let Some(major) = value.strip_prefix("iso-") {
+        return Some((major.parse().ok()?, 0));
+    }
+    let mut parts = value.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+    Some((major, minor))
+}
+
+fn finding(setting: &Setting) -> Option<Finding> {
+    if is_redundant(setting) {
+        return Some(Finding::Redundant);
+    }
+    let version = parse_lang_version(setting.own.as_ref().or(setting.inherited.as_ref())?)?;
+    if setting
+        .defaults
+        .iter()
+        .min()
+        .map_or(false, |default| version < *default)
+    {
+        Some(Finding::Older)
+    } else {
+        None
+    }
+}
+
+/// A LangVersion in the project file is redundant when removing it does not change the language
+/// version: it repeats the inherited value, or without one it asks for the default or the default
+/// version of every target framework.
+fn is_redundant(setting: &Setting) -> bool {
+    let own = match &setting.own {
+        Some(own) => own,
+        None => return false,
+    };
+    match &setting.inherited {
+        Some(inherited) => own.trim().eq_ignore_ascii_case(inherited.trim()),
+        None => {
+            own.trim().eq_ignore_ascii_case("default")
+                || parse_lang_version(own).map_or(false, |version| {
+                    !setting.defaults.is_empty()
+                        && setting.defaults.iter().all(|default| *default == version)
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setting(own: Option<&str>, inherited: Option<&str>, defaults: &[(u32, u32)]) -> Setting {
+        Setting {
+            own: own.map(String::from),
+            inherited: inherited.map(String::from),
+            defaults: defaults.to_vec(),
+        }
+    }
+
+    #[test]
+    fn finding_flags_older_and_redundant_lang_versions() {
+        assert_eq!(
+            finding(&setting(Some("10.0"), None, &[(12, 0)])),
+            Some(Finding::Older)
+        );
+        assert_eq!(
+            finding(&setting(None, Some("7.3"), &[(7, 3), (12, 0)])),
+            None
+        );
+        assert_eq!(
+            finding(&setting(Some("12"), None, &[(12, 0)])),
+            Some(Finding::Redundant)
+        );
+        assert_eq!(
+            finding(&setting(Some("12.0"), None, &[(7, 3), (12, 0)])),
+            None
+        );
+        assert_eq!(
+            finding(&setting(Some("latest"), Some("Latest"), &[(12, 0)])),
+            Some(Finding::Redundant)
+        );
+        assert_eq!(
+            finding(&setting(Some("default"), None, &[])),
+            Some(Finding::Redundant)
+        );
+        assert_eq!(finding(&setting(Some("preview"), None, &[(12, 0)])), None);
+        assert_eq!(
+            finding(&setting(Some("ISO-2"), None, &[(7, 3)])),
+            Some(Finding::Older)
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Parses MSBuild projects and solutions and rewrites them, the library behind the
+//! `csprojtool` binary.
+//!
+//! The modules listed in the documentation are the library API. The other public modules
+//! implement the commands of the binary and change without notice.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use csprojtool::list;
+//!
+//! let projects = list::list(list::Options {
+//!     search_path: Path::new("src"),
+//!     follow_incoming_project_references: false,
+//!     follow_outgoing_project_references: true,
+//! });
+//! for project in projects.iter() {
+//!     println!(
+//!         "{} references {} projects",
+//!         project.path.display(),
+//!         project.project_references.len()
+//!     );
+//! }
+//! ```
+
+/// Reuses parsed projects between runs.
+pub mod cache;
+/// The `.csprojtool.toml` configuration of a repository.
+pub mod config;
+/// Reads and parses project files.
+pub mod csproj;
+/// The errors of fallible operations and the exit codes of the binary.
+pub mod error;
+/// Evaluates the Condition attributes of projects.
+pub mod evaluation;
+/// Finds the projects of a repository and follows their project references.
+pub mod list;
+/// Moves projects and directories and rewrites the paths that point at them.
+pub mod move_command;
+/// Path manipulation that works the same for Windows and MSBuild paths.
+pub mod path_extensions;
+/// Records, prints and applies the file changes of mutating operations.
+pub mod plan;
+/// Reads, writes and updates solution files.
+pub mod sln;
+
+mod annotations;
+mod binlog;
+mod diff;
+mod encoding;
+mod git;
+mod nuget;
+mod nuspec;
+mod output_format;
+mod package_version;
+mod sarif;
+mod utils;
+mod xml_extensions;
+
+#[doc(hidden)]
+pub mod add_package;
+#[doc(hidden)]
+pub mod affected;
+#[doc(hidden)]
+pub mod analyze_redirects;
+#[doc(hidden)]
+pub mod analyzers;
+#[doc(hidden)]
+pub mod apply;
+#[doc(hidden)]
+pub mod artifacts;
+#[doc(hidden)]
+pub mod chains;
+#[doc(hidden)]
+pub mod check;
+#[doc(hidden)]
+pub mod check_packages;
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod convert_refs;
+#[doc(hidden)]
+pub mod cpm;
+#[doc(hidden)]
+pub mod dedupe;
+#[doc(hidden)]
+pub mod dependency_graph;
+#[doc(hidden)]
+pub mod fmt;
+#[doc(hidden)]
+pub mod graph;
+#[doc(hidden)]
+pub mod internals_visible_to;
+#[doc(hidden)]
+pub mod langversion;
+#[doc(hidden)]
+pub mod limits;
+#[doc(hidden)]
+pub mod lint;
+#[doc(hidden)]
+pub mod migrate_packages;
+#[doc(hidden)]
+pub mod normalize_paths;
+#[doc(hidden)]
+pub mod outdated;
+#[doc(hidden)]
+pub mod output;
+#[doc(hidden)]
+pub mod package_diff;
+#[doc(hidden)]
+pub mod post_migration_cleanup;
+#[doc(hidden)]
+pub mod propose_layout;
+#[doc(hidden)]
+pub mod query;
+#[doc(hidden)]
+pub mod rdeps;
+#[doc(hidden)]
+pub mod reaches;
+#[doc(hidden)]
+pub mod remove_package;
+#[doc(hidden)]
+pub mod remove_property;
+#[doc(hidden)]
+pub mod report;
+#[doc(hidden)]
+pub mod retarget;
+#[doc(hidden)]
+pub mod rollout;
+#[doc(hidden)]
+pub mod serve;
+#[doc(hidden)]
+pub mod set_property;
+#[doc(hidden)]
+pub mod sln_diff;
+#[doc(hidden)]
+pub mod sln_merge;
+#[doc(hidden)]
+pub mod slnf;
+#[doc(hidden)]
+pub mod slns;
+#[doc(hidden)]
+pub mod sort;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod tfm;
+#[doc(hidden)]
+pub mod tfms;
+#[doc(hidden)]
+pub mod tools;
+#[doc(hidden)]
+pub mod traversal;
+#[doc(hidden)]
+pub mod ui;
+#[doc(hidden)]
+pub mod unreachable;
+#[doc(hidden)]
+pub mod validate;
+#[doc(hidden)]
+pub mod walk;
+#[doc(hidden)]
+pub mod watch;
+#[doc(hidden)]
+pub mod which_sln;
+#[doc(hidden)]
+pub mod writable;
@@ -0,0 +1,132 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of threads the parallel subsystems may use, 0 means one per core.
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// The maximum number of bytes allocated at any time, 0 means unlimited.
+static MAX_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts allocations so that `--max-memory` can fail allocations instead of letting the
+/// process grow until a container kills it. The binary installs it as the global allocator.
+pub struct LimitedAllocator;
+
+unsafe impl GlobalAlloc for LimitedAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if !self.reserve(layout.size()) {
+            return std::ptr::null_mut();
+        }
+        let ptr = System.alloc(layout);
+        if ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if !self.reserve(layout.size()) {
+            return std::ptr::null_mut();
+        }
+        let ptr = System.alloc_zeroed(layout);
+        if ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size > layout.size() {
+            if !self.reserve(new_size - layout.size()) {
+                return std::ptr::null_mut();
+            }
+        } else {
+            ALLOCATED.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+        }
+
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if new_ptr.is_null() {
+            // The original allocation is still in use, undo the change in size.
+            if new_size > layout.size() {
+                ALLOCATED.fetch_sub(new_size - layout.size(), Ordering::Relaxed);
+            } else {
+                ALLOCATED.fetch_add(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+impl LimitedAllocator {
+    fn reserve(&self, size: usize) -> bool {
+        let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+        let max_memory = MAX_MEMORY.load(Ordering::Relaxed);
+        if max_memory != 0 && allocated > max_memory {
+            ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+            false
+        } else {
+            true
+        }
+    }
+}
+
+/// Applies the limits given on the command line, must be called before any work is started.
+pub fn init(threads: Option<usize>, max_memory: Option<usize>) {
+    THREADS.store(threads.unwrap_or(0), Ordering::Relaxed);
+    MAX_MEMORY.store(max_memory.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// The number of threads to use for CPU bound work, in the format of
+/// `ignore::WalkBuilder::threads` where 0 lets the walker pick one per core.
+pub fn threads() -> usize {
+    THREADS.load(Ordering::Relaxed)
+}
+
+/// The number of threads to spawn for CPU bound work that does not go through the walker.
+pub fn worker_count() -> usize {
+    match threads() {
+        0 => std::thread::available_parallelism().map_or(1, |count| count.get()),
+        threads => threads,
+    }
+}
+
+/// Caps the number of threads for work that is not CPU bound, like network requests.
+pub fn cap_threads(preferred: usize) -> usize {
+    match threads() {
+        0 => preferred,
+        threads => preferred.min(threads),
+    }
+}
+
+/// Parses sizes like `512M`, `2G` or `1048576`, the suffixes are powers of 1024.
+pub fn parse_size(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let (digits, factor) = match value.char_indices().last()? {
+        (index, 'k') | (index, 'K') => (&value[..index], 1 << 10),
+        (index, 'm') | (index, 'M') => (&value[..index], 1 << 20),
+        (index, 'g') | (index, 'G') => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+    digits.trim().parse::<usize>().ok()?.checked_mul(factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_works() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("4k"), Some(4096));
+        assert_eq!(parse_size("512M"), Some(512 << 20));
+        assert_eq!(parse_size("2G"), Some(2 << 30));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("lots"), None);
+    }
+}
@@ -0,0 +1,417 @@
+use std::path::PathBuf;
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    check::rooted_project_references::is_rooted,
+    cli,
+    config::{Config, LintConfig, Severity},
+    csproj::Project,
+    encoding, list,
+    output::{self, status},
+    output_format::{print_issues, OutputFormat},
+    path_extensions::{relative_path, PathExt},
+    sarif,
+};
+
+const CMD_LINT: &'static str = "lint";
+
+/// A hygiene rule. The severity can be changed per rule in the `[lint.rules]` table of
+/// `.csprojtool.toml`, `off` disables the rule.
+struct LintRule {
+    name: &'static str,
+    severity: Severity,
+    check: fn(&Context) -> Vec<Violation>,
+}
+
+const LINT_RULES: &[LintRule] = &[
+    LintRule {
+        name: "floating-versions",
+        severity: Severity::Error,
+        check: floating_versions,
+    },
+    LintRule {
+        name: "absolute-paths",
+        severity: Severity::Warning,
+        check: absolute_paths,
+    },
+    LintRule {
+        name: "test-framework",
+        severity: Severity::Warning,
+        check: test_framework,
+    },
+    LintRule {
+        name: "project-names",
+        severity: Severity::Warning,
+        check: project_names,
+    },
+];
+
+/// What a rule gets to look at for a single project.
+struct Context<'a, 'input> {
+    project: &'a Project,
+    document: &'a roxmltree::Document<'input>,
+    config: &'a LintConfig,
+}
+
+/// A violation of a rule at a line of the project file, starting at 1.
+#[derive(Debug, PartialEq, Eq)]
+struct Violation {
+    line: u32,
+    message: String,
+}
+
+/// A violation as printed with `--json`, the path is relative to the current directory.
+#[derive(Debug, Serialize)]
+struct JsonViolation {
+    path: PathBuf,
+    line: u32,
+    severity: &'static str,
+    rule: &'static str,
+    message: String,
+}
+
+impl Violation {
+    fn at(node: roxmltree::Node, message: String) -> Self {
+        Self {
+            line: node.document().text_pos_at(node.range().start).row,
+            message,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LintCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    format: OutputFormat,
+    repository_root: PathBuf,
+}
+
+impl LintCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_LINT)
+            .about("Check projects against the hygiene rules configured in .csprojtool.toml")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_issues_format())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_LINT).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            format: cli::get_format(matches),
+            repository_root: cli::get_repository_root(matches),
+        }
+    }
+
+    /// Prints the violations and exits with 1 if any rule with the error severity is violated.
+    pub fn execute(&self) {
+        if self.format == OutputFormat::Sarif {
+            // The log is json as well, the summary has to go to stderr.
+            output::init(true);
+        }
+
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let search_path = self.search_path.simplified_absolute().unwrap();
+        let config_dir = if search_path.is_file() {
+            search_path.parent().unwrap()
+        } else {
+            &search_path
+        };
+        let config = Config::discover(config_dir);
+        for name in config.lint.rules.keys() {
+            if !LINT_RULES.iter().any(|rule| rule.name == name) {
+                warn!("Unknown lint rule {} in the configuration", name);
+            }
+        }
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut counts = [0; 3];
+        let mut results = Vec::new();
+        let mut issues = Vec::new();
+        for project in projects.iter() {
+            for (severity, rule, violation) in lint_project(project, &config.lint) {
+                let path = relative_path(&current_dir, &project.path);
+                counts[severity as usize] += 1;
+                if self.format.reports_issues() {
+                    issues.push(sarif::Issue {
+                        rule,
+                        level: match severity {
+                            Severity::Error => sarif::Level::Error,
+                            _ => sarif::Level::Warning,
+                        },
+                        message: violation.message,
+                        locations: vec![sarif::Location {
+                            path: project.path.clone(),
+                            line: Some(violation.line),
+                        }],
+                    });
+                } else if output::is_json() {
+                    results.push(JsonViolation {
+                        path,
+                        line: violation.line,
+                        severity: severity.name(),
+                        rule,
+                        message: violation.message,
+                    });
+                } else {
+                    println!(
+                        "{}:{}: {}[{}]: {}",
+                        path.display(),
+                        violation.line,
+                        severity.name(),
+                        rule,
+                        violation.message
+                    );
+                }
+            }
+        }
+        if self.format.reports_issues() {
+            print_issues(self.format, &issues, &self.repository_root);
+        } else if output::is_json() {
+            output::print_json(&results);
+        }
+
+        let (warnings, errors) = (
+            counts[Severity::Warning as usize],
+            counts[Severity::Error as usize],
+        );
+        if warnings + errors == 0 {
+            status!("No lint violations in {} projects", projects.len());
+        } else {
+            status!("{} errors, {} warnings", errors, warnings);
+        }
+        if errors > 0 {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+    }
+}
+
+fn lint_project(
+    project: &Project,
+    config: &LintConfig,
+) -> Vec<(Severity, &'static str, Violation)> {
+    let contents = match encoding::read_to_string(&project.path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Failed to read {}: {}", project.path.display(), e);
+            return Vec::new();
+        }
+    };
+    let document = match roxmltree::Document::parse(&contents) {
+        Ok(document) => document,
+        Err(e) => {
+            warn!("Failed to parse {}: {}", project.path.display(), e);
+            return Vec::new();
+        }
+    };
+
+    let context = Context {
+        project,
+        document: &document,
+        config,
+    };
+    let mut violations = Vec::new();
+    for rule in LINT_RULES.iter() {
+        let severity = config
+            .rules
+            .get(rule.name)
+            .copied()
+            .unwrap_or(rule.severity);
+        if severity == Severity::Off {
+            continue;
+        }
+        violations.extend(
+            (rule.check)(&context)
+                .into_iter()
+                .map(|violation| (severity, rule.name, violation)),
+        );
+    }
+    violations.sort_by_key(|(_, _, violation)| violation.line);
+    violations
+}
+
+/// Floating versions like `1.*` make restores pick up whatever was published last.
+fn floating_versions(context: &Context) -> Vec<Violation> {
+    context
+        .document
+        .descendants()
+        .filter(|node| node.has_tag_name("PackageReference") || node.has_tag_name("PackageVersion"))
+        .filter_map(|node| {
+            let version = node.attribute("Version").or_else(|| {
+                node.children()
+                    .find(|child| child.has_tag_name("Version"))
+                    .and_then(|child| child.text())
+            })?;
+            if !version.contains('*') {
+                return None;
+            }
+            let name = node
+                .attribute("Include")
+                .or_else(|| node.attribute("Update"))
+                .unwrap_or_default();
+            Some(Violation::at(
+                node,
+                format!("{} uses the floating version {}", name, version.trim()),
+            ))
+        })
+        .collect()
+}
+
+/// Absolute paths only resolve on the machine that wrote them.
+fn absolute_paths(context: &Context) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for node in context
+        .document
+        .descendants()
+        .filter(|node| node.is_element())
+    {
+        for attribute in ["Include", "Update", "Project"].iter() {
+            if let Some(value) = node.attribute(*attribute).filter(|value| is_rooted(value)) {
+                violations.push(Violation::at(
+                    node,
+                    format!("{} {} is an absolute path", node.tag_name().name(), value),
+                ));
+            }
+        }
+
+        let is_leaf = node.children().all(|child| child.is_text());
+        if let Some(text) = node
+            .text()
+            .map(str::trim)
+            .filter(|text| is_leaf && is_rooted(text))
+        {
+            violations.push(Violation::at(
+                node,
+                format!("{} {} is an absolute path", node.tag_name().name(), text),
+            ));
+        }
+    }
+    violations
+}
+
+/// Test projects have to reference one of the configured test frameworks.
+fn test_framework(context: &Context) -> Vec<Violation> {
+    let frameworks = &context.config.test_frameworks;
+    if !context.project.is_test_project()
+        || frameworks.is_empty()
+        || context.project.package_references.iter().any(|package| {
+            frameworks
+                .iter()
+                .any(|framework| framework.eq_ignore_ascii_case(&package.name))
+        })
+    {
+        return Vec::new();
+    }
+
+    vec![Violation::at(
+        context.document.root_element(),
+        format!(
+            "Test project does not reference {}",
+            frameworks.join(" or ")
+        ),
+    )]
+}
+
+/// Project names have to match the configured pattern, if any.
+fn project_names(context: &Context) -> Vec<Violation> {
+    let pattern = match context.config.project_name_pattern.as_ref() {
+        Some(pattern) => pattern,
+        None => return Vec::new(),
+    };
+    let regex = regex::Regex::new(&format!("^(?:{})$", pattern)).unwrap_or_else(|e| {
+        panic!(
+            "Invalid project-name-pattern {} in the configuration: {}",
+            pattern, e
+        )
+    });
+
+    let name = context.project.path.file_stem().unwrap().to_string_lossy();
+    if regex.is_match(&name) {
+        return Vec::new();
+    }
+
+    vec![Violation::at(
+        context.document.root_element(),
+        format!("Project name {} does not match {}", name, pattern),
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(contents: &str, check: fn(&Context) -> Vec<Violation>) -> Vec<Violation> {
+        lint_with(contents, &LintConfig::default(), check)
+    }
+
+    fn lint_with(
+        contents: &str,
+        config: &LintConfig,
+        check: fn(&Context) -> Vec<Violation>,
+    ) -> Vec<Violation> {
+        let document = roxmltree::Document::parse(contents).unwrap();
+        let project = Project::new("/src/A/A.csproj");
+        check(&Context {
+            project: &project,
+            document: &document,
+            config,
+        })
+    }
+
+    #[test]
+    fn floating_versions_reports_the_line_of_the_reference() {
+        let contents = "<Project>\n  <ItemGroup>\n    <PackageReference Include=\"Serilog\" Version=\"2.*\" />\n    <PackageReference Include=\"Polly\" Version=\"7.2.2\" />\n  </ItemGroup>\n</Project>\n";
+        assert_eq!(
+            lint(contents, floating_versions),
+            vec![Violation {
+                line: 3,
+                message: String::from("Serilog uses the floating version 2.*"),
+            }]
+        );
+    }
+
+    #[test]
+    fn absolute_paths_checks_includes_and_values() {
+        let contents = "<Project>\n  <PropertyGroup>\n    <OutputPath>C:\\out</OutputPath>\n    <RootNamespace>A</RootNamespace>\n  </PropertyGroup>\n  <ItemGroup>\n    <ProjectReference Include=\"..\\B\\B.csproj\" />\n    <Reference Include=\"Legacy\">\n      <HintPath>\\\\server\\libs\\Legacy.dll</HintPath>\n    </Reference>\n  </ItemGroup>\n</Project>\n";
+        assert_eq!(
+            lint(contents, absolute_paths)
+                .into_iter()
+                .map(|violation| violation.line)
+                .collect::<Vec<_>>(),
+            vec![3, 9]
+        );
+    }
+
+    #[test]
+    fn project_names_match_the_whole_name() {
+        let contents = "<Project Sdk=\"Microsoft.NET.Sdk\">\n</Project>\n";
+        assert!(lint(contents, project_names).is_empty());
+
+        let config = |pattern: &str| LintConfig {
+            project_name_pattern: Some(pattern.to_owned()),
+            ..LintConfig::default()
+        };
+        assert!(lint_with(contents, &config("[A-Z]"), project_names).is_empty());
+        assert_eq!(
+            lint_with(contents, &config("Acme\\..+|B"), project_names),
+            vec![Violation {
+                line: 1,
+                message: String::from("Project name A does not match Acme\\..+|B"),
+            }]
+        );
+    }
+}
@@ -3,13 +3,19 @@ use ignore::ParallelVisitor;
 use ignore::ParallelVisitorBuilder;
 use log::debug;
 use log::warn;
+use serde::Serialize;
+use uuid::Uuid;
 
+use crate::cache::Cache;
+use crate::config::Config;
 use crate::csproj::*;
+use crate::evaluation::Evaluator;
+use crate::output;
 use crate::path_extensions::*;
-use crate::utils::entry_is_csproj;
+use crate::utils::entry_is_project;
 use crate::utils::find_git_root;
-use std::collections::BTreeMap;
-use std::path::Path;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Options<'a> {
@@ -18,12 +24,290 @@ pub struct Options<'a> {
     pub follow_outgoing_project_references: bool,
 }
 
-pub fn run(options: Options) {
-    let projects = list(options);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["text", "json"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RunOptions<'a> {
+    /// The projects of all search paths are listed together.
+    pub search_paths: &'a [PathBuf],
+    pub follow_incoming_project_references: bool,
+    pub follow_outgoing_project_references: bool,
+    pub glob_matcher: &'a globset::GlobMatcher,
+    pub filters: Filters,
+    pub format: Format,
+    pub details: bool,
+    /// The project from which the reference distance of every listed project is annotated.
+    pub depth_from: Option<PathBuf>,
+}
+
+/// Selects the listed projects, a project has to pass every given filter.
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// Target frameworks of which the project has to target at least one.
+    pub target_frameworks: Vec<String>,
+    /// Properties the project has to define with exactly this value.
+    pub properties: Vec<(String, String)>,
+    /// The SDK the project has to use.
+    pub sdk: Option<String>,
+    /// Whether to keep test projects, other projects or both.
+    pub tests: TestFilter,
+}
+
+/// Selects projects by whether they are test projects, see `Project::is_test_project`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TestFilter {
+    #[default]
+    All,
+    OnlyTests,
+    NoTests,
+}
+
+impl TestFilter {
+    pub fn matches(self, project: &Project) -> bool {
+        match self {
+            Self::All => true,
+            Self::OnlyTests => project.is_test_project(),
+            Self::NoTests => !project.is_test_project(),
+        }
+    }
+}
+
+impl Filters {
+    pub fn matches(&self, project: &Project) -> bool {
+        let target_framework_matches = self.target_frameworks.is_empty()
+            || self.target_frameworks.iter().any(|tfm| {
+                project
+                    .target_frameworks
+                    .iter()
+                    .any(|target_framework| target_framework.eq_ignore_ascii_case(tfm))
+            });
+
+        let properties_match = self.properties.iter().all(|(name, value)| {
+            project.properties.iter().any(|property| {
+                property.name.eq_ignore_ascii_case(name) && &property.value == value
+            })
+        });
+
+        // The attribute may name several SDKs and pin versions, as in `A/1.0;B`.
+        let sdk_matches = self.sdk.as_ref().map_or(true, |sdk| {
+            project.sdk.as_ref().map_or(false, |attribute| {
+                attribute.split(';').any(|part| {
+                    part.split('/')
+                        .next()
+                        .unwrap()
+                        .trim()
+                        .eq_ignore_ascii_case(sdk)
+                })
+            })
+        });
+
+        target_framework_matches && properties_match && sdk_matches && self.tests.matches(project)
+    }
+}
+
+/// A project as printed by `list --format json`, paths are relative to the current directory.
+#[derive(Debug, Serialize)]
+struct ListedProject {
+    path: PathBuf,
+    project_guid: Option<Uuid>,
+    target_frameworks: Vec<String>,
+    project_references: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    imports: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Details>,
+    /// The reference distance from the `--depth-from` project, absent when it does not depend on
+    /// the project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<usize>,
+}
+
+/// The properties of a project as MSBuild would default them when they are not set.
+#[derive(Debug, Serialize)]
+struct Details {
+    assembly_name: String,
+    root_namespace: String,
+    output_type: String,
+    package_references: Vec<PackageReference>,
+}
+
+impl Details {
+    fn new(project: &Project) -> Self {
+        let name = project.path.file_stem().unwrap().to_string_lossy();
+        // The imports can set the properties too, and the values can refer to other properties.
+        let mut evaluator = Evaluator::for_project(&project.path);
+        for property in project
+            .properties
+            .iter()
+            .filter(|property| property.condition.is_none())
+        {
+            evaluator.set_property(&property.name, &property.value);
+        }
+        let property = |name: &str, default: &str| {
+            evaluator
+                .property(name)
+                .filter(|value| !value.trim().is_empty())
+                .unwrap_or(default)
+                .to_owned()
+        };
+        Self {
+            assembly_name: property("AssemblyName", &name),
+            root_namespace: property("RootNamespace", &name),
+            output_type: property("OutputType", "Library"),
+            package_references: project.package_references.clone(),
+        }
+    }
+}
+
+pub fn run(options: RunOptions) {
+    let mut projects = list_each(
+        options.search_paths,
+        options.follow_incoming_project_references,
+        options.follow_outgoing_project_references,
+    );
+    retain_glob_matches(&mut projects, options.glob_matcher);
+    projects.retain(|project| options.filters.matches(project));
+
+    let depths = options.depth_from.as_ref().map(|root| {
+        let root = std::fs::canonicalize(root)
+            .unwrap_or_else(|e| panic!("Failed to find {}: {}", root.display(), e));
+        // The projects the root depends on may have been filtered out of the listed projects.
+        let graph = list(Options {
+            search_path: &root,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: true,
+        });
+        reference_depths(
+            &root,
+            graph.iter().map(|project| {
+                (
+                    project.path.as_path(),
+                    project.project_references.as_slice(),
+                )
+            }),
+        )
+    });
+    let depth = |project: &Project| {
+        depths
+            .as_ref()
+            .and_then(|depths| depths.get(&project.path).copied())
+    };
 
     let current_dir = std::env::current_dir().unwrap();
-    for project in &projects {
-        println!("{}", relative_path(&current_dir, &project.path).display());
+    let format = if output::is_json() {
+        Format::Json
+    } else {
+        options.format
+    };
+    match format {
+        Format::Text => {
+            for project in &projects {
+                let path = relative_path(&current_dir, &project.path);
+                match (&depths, depth(project)) {
+                    (None, _) => println!("{}", path.display()),
+                    (Some(_), Some(depth)) => println!("{} (depth {})", path.display(), depth),
+                    (Some(_), None) => println!("{} (unreachable)", path.display()),
+                }
+                if options.details {
+                    print_details(project, &current_dir);
+                }
+            }
+        }
+        Format::Json => {
+            let listed = projects
+                .iter()
+                .map(|project| ListedProject {
+                    path: relative_path(&current_dir, &project.path),
+                    project_guid: project.project_guid,
+                    target_frameworks: project.target_frameworks.clone(),
+                    project_references: project
+                        .project_references
+                        .iter()
+                        .map(|path| relative_path(&current_dir, path))
+                        .collect(),
+                    imports: project
+                        .imports
+                        .iter()
+                        .map(|path| relative_path(&current_dir, path))
+                        .collect(),
+                    details: if options.details {
+                        Some(Details::new(project))
+                    } else {
+                        None
+                    },
+                    depth: depth(project),
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(std::io::stdout(), &listed).unwrap();
+            println!();
+        }
+    }
+}
+
+/// Returns the length of the shortest chain of references from the root to every project it
+/// depends on, the root itself has depth 0.
+fn reference_depths<'a>(
+    root: &Path,
+    projects: impl Iterator<Item = (&'a Path, &'a [PathBuf])>,
+) -> BTreeMap<PathBuf, usize> {
+    let references = projects.collect::<BTreeMap<_, _>>();
+
+    let mut depths = BTreeMap::new();
+    depths.insert(root.to_owned(), 0);
+    let mut queue = VecDeque::new();
+    queue.push_back((root, 0));
+
+    while let Some((path, depth)) = queue.pop_front() {
+        for reference in references.get(path).copied().unwrap_or_default() {
+            if !depths.contains_key(reference) {
+                depths.insert(reference.to_owned(), depth + 1);
+                queue.push_back((reference, depth + 1));
+            }
+        }
+    }
+
+    depths
+}
+
+fn print_details(project: &Project, current_dir: &Path) {
+    let details = Details::new(project);
+    println!("  AssemblyName: {}", details.assembly_name);
+    println!("  RootNamespace: {}", details.root_namespace);
+    println!("  OutputType: {}", details.output_type);
+    println!(
+        "  TargetFrameworks: {}",
+        project.target_frameworks.join(";")
+    );
+    for package in details.package_references.iter() {
+        match &package.version {
+            Some(version) => println!("  PackageReference: {} {}", package.name, version),
+            None => println!("  PackageReference: {}", package.name),
+        }
+    }
+    for reference in project.project_references.iter() {
+        println!(
+            "  ProjectReference: {}",
+            relative_path(current_dir, reference).display()
+        );
+    }
+    for import in project.imports.iter() {
+        println!("  Import: {}", relative_path(current_dir, import).display());
     }
 }
 
@@ -57,19 +341,43 @@ pub fn list(options: Options) -> Vec<Project> {
         }
     };
 
+    let config = Config::discover(if search_meta.is_file() {
+        search_path.parent().unwrap()
+    } else {
+        &search_path
+    });
+    let exclude_matcher = config.exclude_matcher();
+    let cache = Cache::open(root_dir);
+
     let (sender, receiver) = crossbeam_channel::unbounded();
 
-    let mut visitor_builder = CollectorBuilder { sender };
+    let mut visitor_builder = CollectorBuilder {
+        sender,
+        config: &config,
+        exclude_matcher: &exclude_matcher,
+        cache: &cache,
+    };
 
-    let walk_builder = ignore::WalkBuilder::new(root_dir);
+    let mut walk_builder = crate::walk::builder(root_dir);
+    walk_builder.threads(crate::limits::threads());
     walk_builder.build_parallel().visit(&mut visitor_builder);
 
     drop(visitor_builder);
 
-    let projects = receiver
-        .into_iter()
-        .flat_map(|projects| projects)
-        .collect::<Vec<_>>();
+    // The walker threads finish in any order, sorting keeps the output the same between runs.
+    // When symbolic links are followed a project can be found through several paths, it is
+    // listed once under its canonical path and is in the search path if any of them is.
+    let mut walked_paths = BTreeMap::<PathBuf, Vec<PathBuf>>::new();
+    let mut projects = Vec::new();
+    for (walked_path, project) in receiver.into_iter().flat_map(|projects| projects) {
+        let paths = walked_paths.entry(project.path.clone()).or_default();
+        if paths.is_empty() {
+            projects.push(project);
+        }
+        paths.push(walked_path);
+    }
+    projects.sort_by(|a, b| a.path.cmp(&b.path));
+    cache.save();
 
     let path_to_project_index = projects
         .iter()
@@ -82,8 +390,10 @@ pub fn list(options: Options) -> Vec<Project> {
     let mut included = projects
         .iter()
         .map(|project| {
-            let rel_path = relative_path(&current_dir, &project.path);
-            rel_path.starts_with(&rel_search_path)
+            Some(&project.path)
+                .into_iter()
+                .chain(walked_paths[&project.path].iter())
+                .any(|path| relative_path(&current_dir, path).starts_with(&rel_search_path))
         })
         .collect::<Vec<_>>();
 
@@ -94,6 +404,7 @@ pub fn list(options: Options) -> Vec<Project> {
             project
                 .project_references
                 .iter()
+                .chain(project.shared_projects.iter())
                 .filter_map(|to_path| {
                     if let Some(to_index) = path_to_project_index.get(to_path).copied() {
                         Some((from_index, to_index))
@@ -111,8 +422,18 @@ pub fn list(options: Options) -> Vec<Project> {
         })
         .collect::<Vec<_>>();
 
-    // Follow incoming references
+    // Follow incoming references, imported files are part of the graph as well so a project
+    // that imports a file in the search path depends on it.
     if follow_incoming_project_references {
+        for (index, project) in projects.iter().enumerate() {
+            if project
+                .imports
+                .iter()
+                .any(|path| relative_path(&current_dir, path).starts_with(&rel_search_path))
+            {
+                included[index] = true;
+            }
+        }
         while let Some((from, _)) = edges
             .iter()
             .copied()
@@ -148,27 +469,87 @@ pub fn list(options: Options) -> Vec<Project> {
         .collect()
 }
 
-struct Collector {
-    projects: Vec<Project>,
-    sender: Sender<Vec<Project>>,
+/// Lists the projects of each search path like `list` does, a project found through more than
+/// one search path is listed once.
+pub fn list_each(
+    search_paths: &[PathBuf],
+    follow_incoming_project_references: bool,
+    follow_outgoing_project_references: bool,
+) -> Vec<Project> {
+    if let [search_path] = search_paths {
+        return list(Options {
+            search_path,
+            follow_incoming_project_references,
+            follow_outgoing_project_references,
+        });
+    }
+    search_paths
+        .iter()
+        .flat_map(|search_path| {
+            list(Options {
+                search_path,
+                follow_incoming_project_references,
+                follow_outgoing_project_references,
+            })
+        })
+        .map(|project| (project.path.clone(), project))
+        .collect::<BTreeMap<_, _>>()
+        .into_values()
+        .collect()
+}
+
+/// Keeps the projects whose path relative to the current directory matches the glob, the same
+/// way `parse_projects` matches files.
+pub fn retain_glob_matches(projects: &mut Vec<Project>, glob_matcher: &globset::GlobMatcher) {
+    let current_dir = std::fs::canonicalize(std::env::current_dir().unwrap()).unwrap();
+    projects.retain(|project| glob_matcher.is_match(relative_path(&current_dir, &project.path)));
+}
+
+/// Collects the projects with the path through which the walker found them, which differs from
+/// the canonical project path below symbolic links.
+struct Collector<'a> {
+    projects: Vec<(PathBuf, Project)>,
+    sender: Sender<Vec<(PathBuf, Project)>>,
+    config: &'a Config,
+    exclude_matcher: &'a globset::GlobSet,
+    cache: &'a Cache,
 }
 
-impl Collector {
-    pub fn new(sender: Sender<Vec<Project>>) -> Self {
+impl<'a> Collector<'a> {
+    pub fn new(
+        sender: Sender<Vec<(PathBuf, Project)>>,
+        config: &'a Config,
+        exclude_matcher: &'a globset::GlobSet,
+        cache: &'a Cache,
+    ) -> Self {
         Self {
             projects: Default::default(),
             sender,
+            config,
+            exclude_matcher,
+            cache,
         }
     }
 }
 
-impl ParallelVisitor for Collector {
+impl<'a> ParallelVisitor for Collector<'a> {
     fn visit(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> ignore::WalkState {
-        let entry = entry.unwrap();
-        if entry_is_csproj(&entry) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // Like a symbolic link that points at one of its parent directories.
+            Err(e) => {
+                warn!("Skipping a path the walker could not read: {}", e);
+                return ignore::WalkState::Continue;
+            }
+        };
+        if entry_is_project(&entry) {
             let path = std::fs::canonicalize(entry.path()).unwrap();
-            match read_and_parse_project(path.clone()) {
-                Ok(project) => self.projects.push(project),
+            if self.config.is_excluded(self.exclude_matcher, &path) {
+                debug!("Skipping {} excluded by the configuration", path.display());
+                return ignore::WalkState::Continue;
+            }
+            match self.cache.read_and_parse_project(path.clone()) {
+                Ok(project) => self.projects.push((entry.into_path(), project)),
                 Err(e) => {
                     warn!(
                         "Ignoring project at {} due parsing failure: {}",
@@ -183,19 +564,137 @@ impl ParallelVisitor for Collector {
     }
 }
 
-impl Drop for Collector {
+impl<'a> Drop for Collector<'a> {
     fn drop(&mut self) {
         let projects = std::mem::take(&mut self.projects);
         self.sender.send(projects).unwrap();
     }
 }
 
-struct CollectorBuilder {
-    sender: Sender<Vec<Project>>,
+struct CollectorBuilder<'a> {
+    sender: Sender<Vec<(PathBuf, Project)>>,
+    config: &'a Config,
+    exclude_matcher: &'a globset::GlobSet,
+    cache: &'a Cache,
 }
 
-impl<'s> ParallelVisitorBuilder<'s> for CollectorBuilder {
+impl<'s, 'a: 's> ParallelVisitorBuilder<'s> for CollectorBuilder<'a> {
     fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
-        Box::new(Collector::new(self.sender.clone()))
+        Box::new(Collector::new(
+            self.sender.clone(),
+            self.config,
+            self.exclude_matcher,
+            self.cache,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(sdk: &str, target_frameworks: &[&str], properties: &[(&str, &str)]) -> Project {
+        Project {
+            sdk: Some(sdk.to_owned()),
+            target_frameworks: target_frameworks
+                .iter()
+                .map(|&tfm| tfm.to_owned())
+                .collect(),
+            properties: properties
+                .iter()
+                .map(|&(name, value)| Property {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                    condition: None,
+                })
+                .collect(),
+            ..Project::new("/src/A/A.csproj")
+        }
+    }
+
+    #[test]
+    fn filters_require_every_given_filter() {
+        let web = project(
+            "Microsoft.NET.Sdk.Web",
+            &["net6.0", "net8.0"],
+            &[("Nullable", "enable")],
+        );
+        let library = project("Microsoft.NET.Sdk/6.0.100", &["net8.0"], &[]);
+
+        let filters = Filters {
+            target_frameworks: vec!["NET8.0".to_owned()],
+            ..Filters::default()
+        };
+        assert!(filters.matches(&web) && filters.matches(&library));
+
+        let filters = Filters {
+            sdk: Some("Microsoft.NET.Sdk".to_owned()),
+            ..Filters::default()
+        };
+        assert!(!filters.matches(&web) && filters.matches(&library));
+
+        let filters = Filters {
+            target_frameworks: vec!["net6.0".to_owned()],
+            properties: vec![("nullable".to_owned(), "enable".to_owned())],
+            sdk: None,
+            tests: TestFilter::All,
+        };
+        assert!(filters.matches(&web) && !filters.matches(&library));
+    }
+
+    #[test]
+    fn test_filter_recognizes_test_framework_references() {
+        let library = project("Microsoft.NET.Sdk", &["net8.0"], &[]);
+        let mut tests = project("Microsoft.NET.Sdk", &["net8.0"], &[]);
+        tests.package_references.push(PackageReference {
+            name: "NUnit".to_owned(),
+            version: Some("3.14.0".to_owned()),
+        });
+        let marked = project(
+            "Microsoft.NET.Sdk",
+            &["net8.0"],
+            &[("IsTestProject", "true")],
+        );
+
+        assert!(!TestFilter::OnlyTests.matches(&library));
+        assert!(TestFilter::OnlyTests.matches(&tests) && TestFilter::OnlyTests.matches(&marked));
+        assert!(TestFilter::NoTests.matches(&library) && !TestFilter::NoTests.matches(&tests));
+        assert!(TestFilter::All.matches(&library) && TestFilter::All.matches(&tests));
+    }
+
+    #[test]
+    fn reference_depths_are_shortest_distances() {
+        let a = PathBuf::from("/a");
+        let b = PathBuf::from("/b");
+        let c = PathBuf::from("/c");
+        let d = PathBuf::from("/d");
+        let graph = [
+            (a.clone(), vec![b.clone(), c.clone()]),
+            (b.clone(), vec![c.clone()]),
+            (c.clone(), vec![a.clone()]),
+            (d.clone(), vec![a.clone()]),
+        ];
+        let depths = reference_depths(
+            &a,
+            graph
+                .iter()
+                .map(|(path, references)| (path.as_path(), references.as_slice())),
+        );
+        assert_eq!(
+            depths.into_iter().collect::<Vec<_>>(),
+            vec![(a, 0), (b, 1), (c, 1)]
+        );
+    }
+
+    #[test]
+    fn details_default_to_the_project_name() {
+        let details = Details::new(&project(
+            "Microsoft.NET.Sdk",
+            &["net8.0"],
+            &[("OutputType", "Exe"), ("RootNamespace", "Acme.A")],
+        ));
+        assert_eq!(details.assembly_name, "A");
+        assert_eq!(details.root_namespace, "Acme.A");
+        assert_eq!(details.output_type, "Exe");
     }
 }
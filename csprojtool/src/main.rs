@@ -1,40 +1,41 @@
-mod cli;
-mod csproj;
-mod dependency_graph;
-mod list;
-mod move_command;
-mod path_extensions;
-mod post_migration_cleanup;
-mod utils;
-mod xml_extensions;
-pub use dependency_graph::*;
-pub use post_migration_cleanup::*;
-mod sln;
-
-use std::path::{Path, PathBuf};
-
-fn get_glob_matcher(matches: &clap::ArgMatches) -> globset::GlobMatcher {
-    let glob_pattern = matches.value_of(cli::ARG_GLOB).unwrap();
-    globset::Glob::new(glob_pattern).unwrap().compile_matcher()
-}
+use csprojtool::*;
 
-fn get_search_path(matches: &clap::ArgMatches) -> PathBuf {
-    let search_path = matches.value_of(cli::ARG_SEARCH_PATH).unwrap();
-    Path::new(search_path).components().collect()
-}
+use cli::{get_glob_matcher, get_plan_json, get_search_path};
+use dependency_graph::dependency_graph;
+use post_migration_cleanup::{post_migration_cleanup, PostMigrationCleanupOptions};
+
+#[global_allocator]
+static ALLOCATOR: limits::LimitedAllocator = limits::LimitedAllocator;
 
 fn main() {
     ::pretty_env_logger::init();
+    error::install_panic_hook();
 
     let app = cli::build_cli();
-    let matches = app.get_matches();
+    let matches = app.get_matches_from(cli::replace_deprecated_args(std::env::args_os().collect()));
+
+    limits::init(cli::get_threads(&matches), cli::get_max_memory(&matches));
+    writable::init(
+        cli::get_retries(&matches),
+        cli::get_force_writable(&matches),
+    );
+    output::init(cli::get_json(&matches));
+    plan::init(cli::get_dry_run(&matches));
+    cache::init(!cli::get_no_cache(&matches));
+    walk::init(cli::get_walk_options(&matches));
+    evaluation::init(cli::get_msbuild_properties(&matches));
+    tools::init(
+        config::Config::current(),
+        cli::get_tool_paths(&matches),
+        cli::get_offline(&matches),
+    );
 
     if let Some(matches) = matches.subcommand_matches(cli::CMD_DEPENDENCY_GRAPH) {
         let glob = matches.value_of(cli::ARG_GLOB).unwrap();
-        let search = matches.value_of(cli::ARG_SEARCH_PATH).unwrap();
+        let searches = cli::get_search_paths(&matches);
         let dot = matches.value_of(cli::ARG_DOT);
-        let json = matches.value_of(cli::ARG_JSON);
-        dependency_graph(glob, search, dot, json);
+        let json = matches.value_of(cli::ARG_JSON_FILE);
+        dependency_graph(glob, &searches, cli::get_test_filter(&matches), dot, json);
     }
 
     if let Some(matches) = matches.subcommand_matches(cli::CMD_POST_MIGRATION_CLEANUP) {
@@ -44,31 +45,253 @@ fn main() {
             follow_project_references: !matches
                 .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
             clean_app_configs: matches.is_present(cli::ARG_CLEAN_APP_CONFIG),
+            plan_json: get_plan_json(&matches),
         });
     }
 
     if let Some(matches) = matches.subcommand_matches(cli::CMD_LIST) {
-        list::run(list::Options {
-            search_path: &get_search_path(&matches),
+        list::run(list::RunOptions {
+            search_paths: &cli::get_search_paths(&matches),
             follow_incoming_project_references: !matches
                 .is_present(cli::ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES),
             follow_outgoing_project_references: !matches
                 .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            glob_matcher: &get_glob_matcher(&matches),
+            filters: list::Filters {
+                target_frameworks: cli::get_values(&matches, cli::ARG_TFM),
+                properties: cli::get_values(&matches, cli::ARG_PROPERTY)
+                    .iter()
+                    .map(|assignment| {
+                        let (name, value) = set_property::parse_assignment(assignment).unwrap();
+                        (name.to_owned(), value.to_owned())
+                    })
+                    .collect(),
+                sdk: matches.value_of(cli::ARG_SDK).map(str::to_owned),
+                tests: cli::get_test_filter(&matches),
+            },
+            format: list::Format::from_name(matches.value_of(cli::ARG_FORMAT).unwrap()).unwrap(),
+            details: matches.is_present(cli::ARG_DETAILS),
+            depth_from: matches
+                .value_of_os(cli::ARG_DEPTH_FROM)
+                .map(std::path::PathBuf::from),
         });
     }
 
     if let Some(matches) = matches.subcommand_matches(cli::CMD_SLN) {
-        sln::sln(sln::Options {
+        let result = sln::sln(sln::Options {
             sln_path: &std::path::PathBuf::from(matches.value_of(cli::ARG_SLN_PATH).unwrap()),
-            search_path: &get_search_path(&matches),
+            search_paths: &cli::get_search_paths(&matches),
             follow_incoming_project_references: !matches
                 .is_present(cli::ARG_DO_NOT_FOLLOW_INCOMING_PROJECT_REFERENCES),
             follow_outgoing_project_references: !matches
                 .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            tests: cli::get_test_filter(&matches),
+            exclude: &cli::get_values(&matches, cli::ARG_EXCLUDE),
+            update: matches.is_present(cli::ARG_UPDATE),
+            configurations: &cli::get_values(&matches, cli::ARG_CONFIGURATION),
+            platforms: &cli::get_values(&matches, cli::ARG_PLATFORM),
+            plan_json: get_plan_json(&matches).as_deref(),
         });
+        if let Err(e) = result {
+            e.exit();
+        }
     }
 
     if let Some(command) = move_command::MoveCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = migrate_packages::MigratePackagesCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = cpm::CpmCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = check::CheckCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = check_packages::CheckPackagesCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = artifacts::ArtifactsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = apply::ApplyCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = outdated::OutdatedCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = add_package::AddPackageCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = remove_package::RemovePackageCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = tfms::TfmsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = retarget::RetargetCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = unreachable::UnreachableCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = tfm::TfmCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = rdeps::RdepsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = slnf::SlnfCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = graph::GraphCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = stats::StatsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = sln_diff::SlnDiffCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = package_diff::PackageDiffCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = sln_merge::SlnMergeCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = validate::ValidateCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = report::ReportCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = fmt::FmtCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = sort::SortCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = serve::ServeCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = dedupe::DedupeCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = lint::LintCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = reaches::ReachesCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = query::QueryCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = propose_layout::ProposeLayoutCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = set_property::SetPropertyCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = remove_property::RemovePropertyCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = watch::WatchCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = ui::UiCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = affected::AffectedCommand::try_from_matches(&matches) {
+        if let Err(e) = command.execute() {
+            e.exit();
+        }
+    }
+
+    if let Some(command) = which_sln::WhichSlnCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = slns::SlnsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = normalize_paths::NormalizePathsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = convert_refs::ConvertRefsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = analyze_redirects::AnalyzeRedirectsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = analyzers::AnalyzersCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = rollout::RolloutCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = langversion::LangVersionCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = chains::ChainsCommand::try_from_matches(&matches) {
+        command.execute();
+    }
+
+    if let Some(command) = traversal::TraversalCommand::try_from_matches(&matches) {
         command.execute();
     }
 }
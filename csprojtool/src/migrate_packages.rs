@@ -0,0 +1,286 @@
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli, csproj::Error, encoding, list, package_version::packages_folder_package,
+    path_extensions::relative_path, plan::Effects, xml_extensions::all_children_whitespace,
+};
+
+const CMD_MIGRATE_PACKAGES: &'static str = "migrate-packages";
+
+#[derive(Debug)]
+pub struct MigratePackagesCommand {
+    search_path: PathBuf,
+    follow_project_references: bool,
+    plan_json: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PackageEntry {
+    pub id: String,
+    pub version: String,
+    pub development_dependency: bool,
+}
+
+impl MigratePackagesCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_MIGRATE_PACKAGES)
+            .about("Convert packages.config files to PackageReference items")
+            .arg(cli::arg_search())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_MIGRATE_PACKAGES)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            follow_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: self.follow_project_references,
+        });
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_MIGRATE_PACKAGES, self.plan_json.as_deref());
+
+        for project in projects {
+            let packages_config_path = project.path.parent().unwrap().join("packages.config");
+            if !packages_config_path.is_file() {
+                continue;
+            }
+
+            let rel_path = relative_path(&current_dir, &project.path);
+
+            let packages = read_packages_config(&packages_config_path).unwrap_or_else(|e| {
+                panic!("Failed to read {}: {}", packages_config_path.display(), e)
+            });
+
+            // Packages that are already referenced the new way should not be added twice.
+            let new_packages = packages
+                .iter()
+                .filter(|package| {
+                    !project
+                        .package_references
+                        .iter()
+                        .any(|reference| reference.name.eq_ignore_ascii_case(&package.id))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            status!(
+                "Migrating {} packages in {}",
+                new_packages.len(),
+                rel_path.display()
+            );
+
+            if let Err(e) = migrate_csproj(&mut effects, &project.path, &packages, &new_packages) {
+                panic!("Failed to migrate {}: {}", project.path.display(), e)
+            }
+
+            debug!("removing {}", packages_config_path.display());
+            effects.remove_file(&packages_config_path).unwrap();
+        }
+
+        effects.finish();
+    }
+}
+
+pub fn read_packages_config(path: &Path) -> Result<Vec<PackageEntry>, Error> {
+    let contents = encoding::read_to_string(path)?;
+    let document = roxmltree::Document::parse(&contents)?;
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "package")
+        .filter_map(|node| {
+            Some(PackageEntry {
+                id: node.attribute("id")?.to_owned(),
+                version: node.attribute("version")?.to_owned(),
+                development_dependency: node.attribute("developmentDependency") == Some("true"),
+            })
+        })
+        .collect())
+}
+
+/// Removes the artifacts of all the packages in packages.config and adds references to the new
+/// packages.
+fn migrate_csproj(
+    effects: &mut Effects,
+    project_path: &Path,
+    packages: &[PackageEntry],
+    new_packages: &[PackageEntry],
+) -> Result<(), Error> {
+    effects.transform_xml_file(project_path, |mut root| {
+        remove_packages_config_artifacts(&mut root, packages);
+
+        if !new_packages.is_empty() {
+            let mut item_group = Element::new("ItemGroup");
+            item_group.children.extend(
+                new_packages
+                    .iter()
+                    .map(|package| XMLNode::Element(package_reference_element(package))),
+            );
+
+            // Place the package references right after the other items.
+            let index = root
+                .children
+                .iter()
+                .rposition(|node| matches!(node, XMLNode::Element(e) if e.name == "ItemGroup"))
+                .map_or(root.children.len(), |index| index + 1);
+            root.children.insert(index, XMLNode::Element(item_group));
+        }
+
+        Some(root)
+    })
+}
+
+/// Removes the packages.config artifacts below the element and returns whether there were any.
+fn remove_packages_config_artifacts(element: &mut Element, packages: &[PackageEntry]) -> bool {
+    let mut removed = false;
+    for mut node in std::mem::take(&mut element.children) {
+        if let XMLNode::Element(child) = &mut node {
+            if is_packages_config_artifact(child, packages) {
+                removed = true;
+                continue;
+            }
+            if remove_packages_config_artifacts(child, packages) {
+                removed = true;
+                // Omit item groups that only contained the removed references
+                if child.name == "ItemGroup" && all_children_whitespace(child) {
+                    continue;
+                }
+            }
+        }
+        element.children.push(node);
+    }
+    removed
+}
+
+fn is_packages_config_artifact(element: &Element, packages: &[PackageEntry]) -> bool {
+    match element.name.as_str() {
+        "Reference" => element
+            .get_child("HintPath")
+            .and_then(|hint_path| hint_path.get_text())
+            .map_or(false, |text| points_into_packages_folder(&text, packages)),
+        "Import" => element.attributes.get("Project").map_or(false, |project| {
+            points_into_packages_folder(project, packages)
+        }),
+        "Target" => {
+            element.attributes.get("Name").map(String::as_str)
+                == Some("EnsureNuGetPackageBuildImports")
+        }
+        "None" | "Content" => element.attributes.get("Include").map_or(false, |include| {
+            include.eq_ignore_ascii_case("packages.config")
+        }),
+        _ => false,
+    }
+}
+
+/// Checks if the path goes through `packages\<id>.<version>` for one of the given packages.
+fn points_into_packages_folder(path: &str, packages: &[PackageEntry]) -> bool {
+    packages_folder_package(path).map_or(false, |(id, version)| {
+        packages.iter().any(|package| {
+            package.id.eq_ignore_ascii_case(id) && package.version.eq_ignore_ascii_case(version)
+        })
+    })
+}
+
+fn package_reference_element(package: &PackageEntry) -> Element {
+    let mut element = Element::new("PackageReference");
+    element
+        .attributes
+        .insert("Include".to_owned(), package.id.clone());
+    element
+        .attributes
+        .insert("Version".to_owned(), package.version.clone());
+
+    if package.development_dependency {
+        let mut private_assets = Element::new("PrivateAssets");
+        private_assets
+            .children
+            .push(XMLNode::Text("all".to_owned()));
+        element.children.push(XMLNode::Element(private_assets));
+    }
+
+    element
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_extensions::{child_elements, element, project};
+
+    #[test]
+    fn points_into_packages_folder_matches_id_and_version() {
+        let packages = [PackageEntry {
+            id: String::from("Newtonsoft.Json"),
+            version: String::from("12.0.3"),
+            development_dependency: false,
+        }];
+
+        assert!(points_into_packages_folder(
+            r"..\packages\Newtonsoft.Json.12.0.3\lib\net45\Newtonsoft.Json.dll",
+            &packages
+        ));
+        assert!(points_into_packages_folder(
+            "../../Packages/newtonsoft.json.12.0.3/build/Newtonsoft.Json.targets",
+            &packages
+        ));
+        assert!(!points_into_packages_folder(
+            r"..\packages\Newtonsoft.Json.11.0.1\lib\net45\Newtonsoft.Json.dll",
+            &packages
+        ));
+        assert!(!points_into_packages_folder(
+            r"..\lib\Newtonsoft.Json.12.0.3\Newtonsoft.Json.dll",
+            &packages
+        ));
+    }
+
+    #[test]
+    fn remove_packages_config_artifacts_only_omits_the_item_groups_it_emptied() {
+        let mut root = project(vec![
+            element("ItemGroup", &[], vec![]),
+            element(
+                "ItemGroup",
+                &[],
+                vec![element("None", &[("Include", "packages.config")], vec![])],
+            ),
+            element(
+                "ItemGroup",
+                &[],
+                vec![
+                    element("None", &[("Include", "packages.config")], vec![]),
+                    element("Compile", &[("Include", "Program.cs")], vec![]),
+                ],
+            ),
+        ]);
+
+        assert!(remove_packages_config_artifacts(&mut root, &[]));
+        let groups = child_elements(&root).collect::<Vec<_>>();
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].children.is_empty());
+        assert_eq!(
+            child_elements(groups[1])
+                .map(|child| child.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Compile"]
+        );
+        assert!(!remove_packages_config_artifacts(&mut root, &[]));
+    }
+}
@@ -1,19 +1,29 @@
 use std::{
+    collections::HashSet,
     ffi::{OsStr, OsString},
     path::{Path, PathBuf},
-    process::Command,
 };
 
-use log::{debug, info};
+use log::{debug, info, warn};
 use xmltree::{Element, XMLNode};
 
 use crate::{
-    path_extensions::{relative_path, PathExt},
-    utils::{find_dir_csproj, find_git_root},
-    xml_extensions::{child_elements, depth_first_visit_nodes, process_tree, transform_xml_file},
+    artifacts::artifacts_layout,
+    cli,
+    config::Config,
+    error::{Error, Result},
+    evaluation::Evaluator,
+    path_extensions::{from_msbuild_path, relative_path, to_msbuild_path, PathExt},
+    plan::Effects,
+    utils::{
+        find_dir_projects, find_git_root, find_nuspec_files, path_extension_is_project,
+        SHARED_PROJECT_EXTENSION,
+    },
+    xml_extensions::{child_elements, depth_first_visit_nodes, process_tree},
 };
 
 const ARG_FROM: &'static str = "from";
+const DEFAULT_COMMIT_TEMPLATE: &'static str = "Move {old} to {new}";
 const ARG_TO: &'static str = "to";
 const CMD_MOVE: &'static str = "mv";
 
@@ -21,6 +31,9 @@ const CMD_MOVE: &'static str = "mv";
 pub struct MoveCommand {
     old: PathBuf,
     new: PathBuf,
+    plan_json: Option<PathBuf>,
+    /// The message template of the commit to create afterwards.
+    commit: Option<String>,
 }
 
 impl MoveCommand {
@@ -46,6 +59,10 @@ impl MoveCommand {
                     .takes_value(true)
                     .index(2),
             )
+            .arg(cli::arg_plan_json())
+            .arg(cli::arg_commit(
+                "Commits the moved and edited files, {old} and {new} are replaced by the paths [default: Move {old} to {new}]",
+            ))
     }
 
     pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
@@ -56,41 +73,65 @@ impl MoveCommand {
         Self {
             old: matches.value_of_os(ARG_FROM).unwrap().into(),
             new: matches.value_of_os(ARG_TO).unwrap().into(),
+            plan_json: cli::get_plan_json(matches),
+            commit: cli::get_commit(matches, DEFAULT_COMMIT_TEMPLATE),
         }
     }
 
-    pub fn execute(&self) {
+    /// Moves the project file or directory at the old path to the new path once executed, the
+    /// changes are written to the plan instead when a plan path is given.
+    pub fn new(old: PathBuf, new: PathBuf, plan_json: Option<PathBuf>) -> Self {
+        Self {
+            old,
+            new,
+            plan_json,
+            commit: None,
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
         info!("moving {0} to {1}", self.old.display(), self.new.display());
 
         let (old_dir, old_file) = {
-            let old = std::fs::canonicalize(&self.old).unwrap();
-            let meta = std::fs::metadata(&old).unwrap();
+            let old = std::fs::canonicalize(&self.old).map_err(|e| {
+                Error::user(format!("Failed to find {}: {}", self.old.display(), e))
+            })?;
+            let meta = std::fs::metadata(&old)?;
             if meta.is_file() {
                 (old.parent().unwrap().to_owned(), old)
             } else if meta.is_dir() {
-                let mut csprojs_in_dir = find_dir_csproj(&old);
+                let mut csprojs_in_dir = find_dir_projects(&old);
                 let first = csprojs_in_dir.next();
 
                 let second = csprojs_in_dir.next();
                 if second.is_some() {
-                    panic!("More than one csproj found in {}", old.display());
+                    return Err(Error::user(format!(
+                        "More than one project found in {}, pass the project file instead",
+                        old.display()
+                    )));
                 }
 
                 if let Some(first) = first {
                     (old, first)
                 } else {
-                    panic!("No csproj found in {}", old.display());
+                    return Err(Error::user(format!(
+                        "No project found in {}",
+                        old.display()
+                    )));
                 }
             } else {
-                panic!(
+                return Err(Error::user(format!(
                     "The path {} does not point to a file nor to a directory",
                     old.display()
-                );
+                )));
             }
         };
 
         debug!("determined old path to be {}", old_file.display());
 
+        // Paths into the artifacts folder are rewritten even if nothing has been built yet.
+        let artifacts_path = artifacts_layout(&old_file).map(|layout| layout.artifacts_path);
+
         let cur_dir = std::fs::canonicalize(std::env::current_dir().unwrap()).unwrap();
 
         let (new_dir, new_file) = {
@@ -99,10 +140,15 @@ impl MoveCommand {
 
             let path = [&cur_dir, &new].iter().collect::<PathBuf>().simplify();
 
-            if path.extension() == Some(OsStr::new("csproj")) {
+            if path_extension_is_project(&path) {
                 (path.parent().unwrap().to_owned(), path)
             } else {
-                let name = [path.file_name().unwrap(), OsStr::new(".csproj")]
+                // The project keeps its language when only the directory is given.
+                let extension = [OsStr::new("."), old_file.extension().unwrap()]
+                    .iter()
+                    .copied()
+                    .collect::<OsString>();
+                let name = [path.file_name().unwrap(), &extension]
                     .iter()
                     .copied()
                     .collect::<OsString>();
@@ -111,13 +157,20 @@ impl MoveCommand {
             }
         };
 
-        {
+        // On case-insensitive file systems the target of a case-only rename is the old directory.
+        let case_only = new_dir != old_dir
+            && std::fs::canonicalize(&new_dir).map_or(false, |existing| existing == old_dir);
+
+        if !case_only {
             match std::fs::metadata(&new_dir) {
                 Ok(_) => {
-                    panic!("Target directory {} already exists", new_dir.display());
+                    return Err(Error::user(format!(
+                        "Target directory {} already exists",
+                        new_dir.display()
+                    )));
                 }
                 Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
-                Err(e) => Err(e).unwrap(),
+                Err(e) => return Err(e.into()),
             }
         }
 
@@ -127,16 +180,12 @@ impl MoveCommand {
 
         debug!("root: {}", root.display());
 
-        let csproj_matcher = globset::GlobBuilder::new("*.csproj")
-            .build()
-            .unwrap()
-            .compile_matcher();
-        let csproj_paths = ignore::WalkBuilder::new(root)
+        let csproj_paths = crate::walk::builder(root)
             .build()
             .filter_map(|entry| match entry {
                 Ok(e) => {
                     if e.file_type().map(|t| t.is_file()).unwrap_or_default()
-                        && csproj_matcher.is_match(e.path())
+                        && path_extension_is_project(e.path())
                     {
                         Some(Ok(e.path().to_owned()))
                     } else {
@@ -145,8 +194,8 @@ impl MoveCommand {
                 }
                 Err(e) => Some(Err(e)),
             })
-            .collect::<Result<Vec<_>, ignore::Error>>()
-            .unwrap();
+            .collect::<std::result::Result<Vec<_>, ignore::Error>>()
+            .map_err(|e| Error::Internal(e.to_string()))?;
 
         // Check for nested projects
         let nested = csproj_paths
@@ -154,29 +203,30 @@ impl MoveCommand {
             .filter(|&p| p.starts_with(&old_dir) && p != &old_file)
             .collect::<Vec<_>>();
         if !nested.is_empty() {
-            panic!(
-                "The to-be-moved project contains nested projects: {:#?}",
+            return Err(Error::user(format!(
+                "The to-be-moved project contains nested projects: {}",
                 nested
-            );
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
         }
 
+        let config = Config::current();
+        let text_file_paths = find_matching_files(root, config, &config.mv_file_matcher());
+        let nuspec_paths = find_nuspec_files(root);
+        let publish_profile_paths = find_publish_profiles(&old_dir);
+
+        let mut effects = Effects::new(CMD_MOVE, self.plan_json.as_deref());
+
         // Move the files
-        let mut mv_dir = Command::new("git");
-        mv_dir.args(&[OsStr::new("mv"), old_dir.as_os_str(), new_dir.as_os_str()]);
-        debug!("{:?}", &mv_dir);
-        mv_dir.output().expect("failed to move files");
+        git_move(&mut effects, &old_dir, &new_dir)?;
 
         {
             let current_path = new_dir.join(old_file.file_name().unwrap());
             if &current_path != &new_file {
-                let mut mv_file = Command::new("git");
-                mv_file.args(&[
-                    OsStr::new("mv"),
-                    current_path.as_os_str(),
-                    new_file.as_os_str(),
-                ]);
-                debug!("{:?}", &mv_file);
-                mv_file.output().expect("failed to move files");
+                git_move(&mut effects, &current_path, &new_file)?;
             }
         }
 
@@ -188,29 +238,41 @@ impl MoveCommand {
             let csproj_dir = csproj_path.parent().unwrap();
 
             let mut edited = false;
-            transform_xml_file(csproj_path, |mut root| {
-                process_tree(&mut root, |element| match element.name.as_ref() {
-                    "ProjectReference" => {
-                        if let Some(include) = element.attributes.get_mut("Include") {
-                            let ref_path = [csproj_dir, Path::new(include)]
+            effects.transform_xml_file(csproj_path, |mut root| {
+                let evaluator = path_evaluator(csproj_path, &root);
+                process_tree(&mut root, |element| {
+                    let attribute = match element.name.as_ref() {
+                        "ProjectReference" => "Include",
+                        "Import" => "Project",
+                        _ => return,
+                    };
+                    if let Some(include) = element.attributes.get_mut(attribute) {
+                        let ref_path = evaluator.resolve_path(include).unwrap_or_else(|| {
+                            [csproj_dir, Path::new(include)]
                                 .iter()
                                 .collect::<PathBuf>()
-                                .simplify();
-
-                            if ref_path == old_file {
-                                let new_ref = relative_path(csproj_dir, &new_file);
-                                debug!(
-                                    "replacing project reference {} with {} in {}",
-                                    include,
-                                    new_ref.display(),
-                                    csproj_path.display()
-                                );
-                                *include = new_ref.to_str().unwrap().to_owned();
-                                edited = true;
-                            }
-                        }
+                                .simplify()
+                        });
+
+                        let new_ref = if ref_path == old_file {
+                            new_file.clone()
+                        } else if let Ok(rest) = ref_path.strip_prefix(&old_dir) {
+                            new_dir.join(rest)
+                        } else {
+                            return;
+                        };
+                        let new_include =
+                            rewrite_include(include, &evaluator, csproj_dir, &new_ref);
+                        debug!(
+                            "replacing {} {} with {} in {}",
+                            element.name,
+                            include,
+                            new_include,
+                            csproj_path.display()
+                        );
+                        *include = new_include;
+                        edited = true;
                     }
-                    _ => {}
                 });
 
                 if edited {
@@ -218,36 +280,45 @@ impl MoveCommand {
                 } else {
                     None
                 }
-            })
-            .unwrap();
+            })?;
 
             if edited {
-                let mut add_file = Command::new("git");
-                add_file.args(&[OsStr::new("add"), csproj_path.as_os_str()]);
-                debug!("{:?}", &add_file);
-                add_file.output().expect("failed to add file");
+                effects.git_add(csproj_path)?;
             }
         }
 
         let mut edited = false;
+        let is_shared_project = new_file.extension() == Some(OsStr::new(SHARED_PROJECT_EXTENSION));
 
-        transform_xml_file(&new_file, |root| {
+        effects.transform_xml_file(&new_file, |root| {
             let mut root_node = XMLNode::Element(root);
 
             depth_first_visit_nodes(&mut root_node, |node| match node {
                 XMLNode::Element(element) => match element.name.as_ref() {
-                    "Project" => {
-                        let name = old_file.file_stem().unwrap().to_str().unwrap();
-                        edited |= ensure_root_namespace_and_assembly_name(element, name);
+                    // Shared projects are compiled into the projects that import them.
+                    "Project" if !is_shared_project => {
+                        let name = old_file.file_stem().unwrap().to_string_lossy();
+                        edited |= ensure_root_namespace_and_assembly_name(element, &name);
                     }
+                    "Project" => {}
                     _ => {
                         for (_, val) in element.attributes.iter_mut() {
-                            edited |= try_rewrite_relative_path(val, &old_dir, &new_dir);
+                            edited |= try_rewrite_relative_path(
+                                val,
+                                &old_dir,
+                                &new_dir,
+                                artifacts_path.as_deref(),
+                            );
                         }
                     }
                 },
                 XMLNode::Text(text) => {
-                    edited |= try_rewrite_relative_path(text, &old_dir, &new_dir);
+                    edited |= try_rewrite_relative_path(
+                        text,
+                        &old_dir,
+                        &new_dir,
+                        artifacts_path.as_deref(),
+                    );
                 }
                 _ => {}
             });
@@ -262,40 +333,457 @@ impl MoveCommand {
             } else {
                 None
             }
-        })
-        .unwrap();
+        })?;
 
         if edited {
-            let mut add_file = Command::new("git");
-            add_file.args(&[OsStr::new("add"), new_file.as_os_str()]);
-            debug!("{:?}", &add_file);
-            add_file.output().expect("failed to add file");
+            effects.git_add(&new_file)?;
+        }
+
+        let moves = [
+            (old_file.clone(), new_file.clone()),
+            (old_dir.clone(), new_dir.clone()),
+        ];
+        // Publish profiles are evaluated as part of their project, their paths are relative to it.
+        for (path, old_project_dir) in publish_profile_paths.iter() {
+            let current_path = new_dir.join(path.strip_prefix(&old_dir).unwrap());
+            let new_project_dir = new_dir.join(old_project_dir.strip_prefix(&old_dir).unwrap());
+
+            let mut edited = false;
+            effects.transform_xml_file(&current_path, |root| {
+                let mut rewrite = |value: &mut String| {
+                    if let Some(rewritten) = rewrite_publish_profile_path(
+                        value,
+                        old_project_dir,
+                        &new_project_dir,
+                        &moves,
+                    ) {
+                        debug!(
+                            "replacing {} with {} in {}",
+                            value,
+                            rewritten,
+                            current_path.display()
+                        );
+                        *value = rewritten;
+                        edited = true;
+                    }
+                };
+                let mut root_node = XMLNode::Element(root);
+                depth_first_visit_nodes(&mut root_node, |node| match node {
+                    XMLNode::Element(element) => {
+                        for (_, value) in element.attributes.iter_mut() {
+                            rewrite(value);
+                        }
+                    }
+                    XMLNode::Text(text) => rewrite(text),
+                    _ => {}
+                });
+
+                match root_node {
+                    XMLNode::Element(root) if edited => Some(root),
+                    _ => None,
+                }
+            })?;
+
+            if edited {
+                effects.git_add(&current_path)?;
+            }
+        }
+
+        // The files of package manifests are relative to the manifest, which may move along.
+        for path in nuspec_paths.iter() {
+            let old_nuspec_dir = path.parent().unwrap();
+            let current_path = match path.strip_prefix(&old_dir) {
+                Ok(rest) => new_dir.join(rest),
+                Err(_) => path.clone(),
+            };
+            let new_nuspec_dir = current_path.parent().unwrap().to_owned();
+
+            let mut edited = false;
+            effects.transform_xml_file(&current_path, |mut root| {
+                process_tree(&mut root, |element| {
+                    if element.name != "file" {
+                        return;
+                    }
+                    for attribute in ["src", "exclude"].iter() {
+                        if let Some(value) = element.attributes.get_mut(*attribute) {
+                            // Exclude holds several patterns separated by semicolons.
+                            let rewritten = value
+                                .split(';')
+                                .map(|src| {
+                                    rewrite_moved_path(src, old_nuspec_dir, &new_nuspec_dir, &moves)
+                                        .unwrap_or_else(|| src.to_owned())
+                                })
+                                .collect::<Vec<_>>()
+                                .join(";");
+                            if rewritten != *value {
+                                debug!(
+                                    "replacing file {} {} with {} in {}",
+                                    attribute,
+                                    value,
+                                    rewritten,
+                                    current_path.display()
+                                );
+                                *value = rewritten;
+                                edited = true;
+                            }
+                        }
+                    }
+                });
+
+                if edited {
+                    Some(root)
+                } else {
+                    None
+                }
+            })?;
+
+            if edited {
+                effects.git_add(&current_path)?;
+            }
+        }
+
+        // Dockerfiles and the text files of the configuration, like build scripts.
+        for path in text_file_paths.iter() {
+            // The bases are where relative paths may start, like the build context of a
+            // Dockerfile or the working directory of a script, directories that move along do
+            // not point to the project.
+            let bases = path
+                .parent()
+                .unwrap()
+                .ancestors()
+                .take_while(|dir| dir.starts_with(root))
+                .filter(|dir| !dir.starts_with(&old_dir))
+                .collect::<Vec<_>>();
+            let current_path = match path.strip_prefix(&old_dir) {
+                Ok(rest) => new_dir.join(rest),
+                Err(_) => path.clone(),
+            };
+            let contents = match effects.read_to_string(&current_path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    warn!("Skipping {}: {}", current_path.display(), e);
+                    continue;
+                }
+            };
+            if let Some(contents) =
+                rewrite_text_paths(&contents, bases.iter().rev().copied(), &moves)
+            {
+                debug!("rewriting paths in {}", current_path.display());
+                effects.write_file(&current_path, contents, Vec::new())?;
+                effects.git_add(&current_path)?;
+            }
+        }
+
+        if let Some(template) = &self.commit {
+            let message = template
+                .replace("{old}", &self.old.display().to_string())
+                .replace("{new}", &self.new.display().to_string());
+            effects.git_commit(&message)?;
+        }
+
+        effects.finish();
+        Ok(())
+    }
+}
+
+/// Moves the file or directory, a case-only rename goes through a temporary name because git and
+/// case-insensitive file systems consider the new path to exist already.
+fn git_move(effects: &mut Effects, from: &Path, to: &Path) -> Result<()> {
+    if !from.as_os_str().eq_ignore_ascii_case(to.as_os_str()) {
+        return effects.git_move(from, to);
+    }
+    let mut temp_name = from.file_name().unwrap().to_owned();
+    temp_name.push(".csprojtool-mv");
+    let temp = from.with_file_name(temp_name);
+    debug!("renaming {} through {}", from.display(), temp.display());
+    effects.git_move(from, &temp)?;
+    effects.git_move(&temp, to)
+}
+
+/// Returns the relative path, like the src of a file in a package manifest, that pointed into the
+/// moved directory or that has to be relative to the new directory of its base, with the slashes
+/// as written.
+fn rewrite_moved_path(
+    src: &str,
+    old_nuspec_dir: &Path,
+    new_nuspec_dir: &Path,
+    moves: &[(PathBuf, PathBuf)],
+) -> Option<String> {
+    let trimmed = src.trim();
+    if trimmed.is_empty() || from_msbuild_path(trimmed).has_root() {
+        return None;
+    }
+
+    let old_path = old_nuspec_dir.join(from_msbuild_path(trimmed)).simplify();
+    let new_path = moves
+        .iter()
+        .find_map(|(old, new)| {
+            if &old_path == old {
+                Some(new.clone())
+            } else {
+                old_path.strip_prefix(old).ok().map(|rest| new.join(rest))
+            }
+        })
+        .unwrap_or_else(|| old_path.clone());
+    if new_path == old_path && new_nuspec_dir == old_nuspec_dir {
+        return None;
+    }
+
+    let new_rel = relative_path(new_nuspec_dir, &new_path);
+    let new_src = if trimmed.contains('\\') {
+        to_msbuild_path(&new_rel)
+    } else {
+        new_rel.to_string_lossy().replace('\\', "/")
+    };
+    if new_src == trimmed {
+        None
+    } else {
+        Some(new_src)
+    }
+}
+
+/// Returns the value of a publish profile that points out of the directory of its project, after
+/// an optional property for the project directory, rewritten like `rewrite_moved_path`.
+fn rewrite_publish_profile_path(
+    value: &str,
+    old_project_dir: &Path,
+    new_project_dir: &Path,
+    moves: &[(PathBuf, PathBuf)],
+) -> Option<String> {
+    if !looks_like_out_of_tree_relative_path(value) {
+        return None;
+    }
+
+    let prefix = PROJECT_DIRECTORY_PROPERTIES
+        .iter()
+        .find(|prefix| value.starts_with(*prefix))
+        .map_or("", |prefix| *prefix);
+    let path = &value[prefix.len()..];
+    // Directories like PublishUrl often end with a separator, which has to stay.
+    let separator = path.chars().last().filter(|&c| c == '/' || c == '\\');
+    let path = path.trim_end_matches(&['/', '\\'][..]);
+
+    let rewritten = rewrite_moved_path(path, old_project_dir, new_project_dir, moves)?;
+    Some(format!(
+        "{}{}{}",
+        prefix,
+        rewritten,
+        separator.map(String::from).unwrap_or_default()
+    ))
+}
+
+/// Finds the publish profiles below the directory, like `Properties/PublishProfiles/Azure.pubxml`,
+/// with the directory of the project they belong to, the nearest one above them.
+fn find_publish_profiles(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let paths = crate::walk::builder(dir)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map_or(false, |t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .collect::<Vec<_>>();
+    let project_dirs = paths
+        .iter()
+        .filter(|path| path_extension_is_project(path))
+        .filter_map(|path| path.parent())
+        .collect::<HashSet<_>>();
+
+    paths
+        .iter()
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension.eq_ignore_ascii_case("pubxml"))
+        })
+        .map(|path| {
+            let project_dir = path
+                .ancestors()
+                .skip(1)
+                .take_while(|ancestor| ancestor.starts_with(dir))
+                .find(|ancestor| project_dirs.contains(ancestor))
+                .unwrap_or(dir);
+            (path.clone(), project_dir.to_owned())
+        })
+        .collect()
+}
+
+/// Finds the files below the root whose path relative to the configuration file matches, project
+/// files are edited as projects instead.
+fn find_matching_files(root: &Path, config: &Config, matcher: &globset::GlobSet) -> Vec<PathBuf> {
+    crate::walk::builder(root)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map_or(false, |t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| !path_extension_is_project(path) && config.is_match(matcher, path))
+        .collect()
+}
+
+/// Rewrites the paths to the moved file and directory in the text, written relative to one of
+/// the bases with forward or backward slashes. The outermost base should come first, so that its
+/// longer paths are rewritten before the shorter ones of the bases within it.
+fn rewrite_text_paths<'a>(
+    text: &str,
+    bases: impl Iterator<Item = &'a Path>,
+    moves: &[(PathBuf, PathBuf)],
+) -> Option<String> {
+    let mut text = text.to_owned();
+    let mut edited = false;
+    for base in bases {
+        for (old, new) in moves.iter().filter(|(old, _)| old.starts_with(base)) {
+            let old_rel = relative_path(base, old)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let new_rel = relative_path(base, new)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if old_rel.is_empty() {
+                continue;
+            }
+            for separator in ["/", "\\"].iter() {
+                if let Some(replaced) = replace_path(
+                    &text,
+                    &old_rel.replace('/', separator),
+                    &new_rel.replace('/', separator),
+                ) {
+                    text = replaced;
+                    edited = true;
+                }
+            }
         }
     }
+    if edited {
+        Some(text)
+    } else {
+        None
+    }
 }
 
-fn try_rewrite_relative_path(val: &mut String, old_dir: &Path, new_dir: &Path) -> bool {
+/// Replaces the occurrences of the path that are not part of a longer path, like `src/Old` in
+/// `COPY src/Old/ ./` and `./src/Old` but not in `src/Old.Tests` or `test/src/Old`.
+fn replace_path(text: &str, from: &str, to: &str) -> Option<String> {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (index, _) in text.match_indices(from) {
+        if index < last {
+            continue;
+        }
+        let before = &text[..index];
+        let before = before
+            .strip_suffix("./")
+            .or_else(|| before.strip_suffix(".\\"))
+            .unwrap_or(before);
+        let starts = before
+            .chars()
+            .next_back()
+            .map_or(true, |c| !is_name_char(c) && c != '/' && c != '\\');
+        let ends = text[index + from.len()..]
+            .chars()
+            .next()
+            .map_or(true, |c| !is_name_char(c));
+        if starts && ends {
+            result.push_str(&text[last..index]);
+            result.push_str(to);
+            last = index + from.len();
+        }
+    }
+    if last == 0 {
+        return None;
+    }
+    result.push_str(&text[last..]);
+    Some(result)
+}
+
+/// Returns an evaluator with the unconditional properties of the project to expand paths.
+fn path_evaluator(csproj_path: &Path, root: &Element) -> Evaluator {
+    let mut evaluator = Evaluator::for_project(csproj_path);
+    let unconditional = |element: &&Element| !element.attributes.contains_key("Condition");
+    for group in child_elements(root)
+        .filter(|e| e.name == "PropertyGroup")
+        .filter(unconditional)
+    {
+        for property in child_elements(group).filter(unconditional) {
+            let value = property.get_text().unwrap_or_default();
+            evaluator.set_property(&property.name, value.trim());
+        }
+    }
+    evaluator
+}
+
+/// Returns the include pointing to the new path. A leading property like
+/// `$(MSBuildThisFileDirectory)` is kept and the rest is made relative to its value.
+fn rewrite_include(
+    include: &str,
+    evaluator: &Evaluator,
+    csproj_dir: &Path,
+    new_path: &Path,
+) -> String {
+    if include.starts_with("$(") {
+        if let Some(end) = include.find(')') {
+            let (prefix, rest) = include.split_at(end + 1);
+            if let Some(base) = evaluator.resolve_path(prefix) {
+                let separator = rest.chars().next().filter(|&c| c == '/' || c == '\\');
+                let new_rel = relative_path(&base, new_path);
+                return format!(
+                    "{}{}{}",
+                    prefix,
+                    separator.map(String::from).unwrap_or_default(),
+                    new_rel.to_str().unwrap()
+                );
+            }
+        }
+    }
+    relative_path(csproj_dir, new_path)
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+/// The properties that point to the directory of the project, paths after them are relative to
+/// the project directory as well.
+const PROJECT_DIRECTORY_PROPERTIES: [&'static str; 3] = [
+    "$(MSBuildThisFileDirectory)",
+    "$(MSBuildProjectDirectory)/",
+    "$(MSBuildProjectDirectory)\\",
+];
+
+fn try_rewrite_relative_path(
+    val: &mut String,
+    old_dir: &Path,
+    new_dir: &Path,
+    artifacts_path: Option<&Path>,
+) -> bool {
     if !looks_like_out_of_tree_relative_path(val) {
         return false;
     }
 
-    let mut edited = true;
+    if let Some(prefix) = PROJECT_DIRECTORY_PROPERTIES
+        .iter()
+        .find(|prefix| val.starts_with(*prefix))
+    {
+        let mut rest = val[prefix.len()..].to_owned();
+        let edited = try_rewrite_relative_path(&mut rest, old_dir, new_dir, artifacts_path);
+        *val = format!("{}{}", prefix, rest);
+        return edited;
+    }
+
+    let mut edited = false;
     let path = Path::new(val);
     if !path.has_root() {
         let path = path.simplify();
         let old_abs_path = old_dir.join(&path).simplify();
-        match std::fs::metadata(&old_abs_path) {
-            Ok(_) => {
-                let new_rel_path = relative_path(&new_dir, &old_abs_path);
-                debug!(
-                    "rewriting relative path from {} to {}",
-                    val,
-                    new_rel_path.display(),
-                );
-                *val = new_rel_path.to_str().unwrap().to_owned();
-                edited = true;
-            }
-            _ => {}
+        let is_artifact = artifacts_path.map_or(false, |artifacts_path| {
+            old_abs_path.starts_with(artifacts_path)
+        });
+        if is_artifact || std::fs::metadata(&old_abs_path).is_ok() {
+            let new_rel_path = relative_path(&new_dir, &old_abs_path);
+            debug!(
+                "rewriting relative path from {} to {}",
+                val,
+                new_rel_path.display(),
+            );
+            *val = new_rel_path.to_str().unwrap().to_owned();
+            edited = true;
         }
     }
     edited
@@ -363,3 +851,138 @@ fn ensure_root_namespace_and_assembly_name(element: &mut xmltree::Element, name:
 
     modified
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_moved_path_follows_the_moved_directory_and_manifest() {
+        let moves = [
+            (
+                PathBuf::from("/repo/src/Old/Old.csproj"),
+                PathBuf::from("/repo/src/New/New.csproj"),
+            ),
+            (
+                PathBuf::from("/repo/src/Old"),
+                PathBuf::from("/repo/src/New"),
+            ),
+        ];
+
+        // A manifest elsewhere that packs the output of the moved project.
+        let nuspec_dir = Path::new("/repo/nuget");
+        assert_eq!(
+            rewrite_moved_path(
+                r"..\src\Old\bin\$configuration$\Old.dll",
+                nuspec_dir,
+                nuspec_dir,
+                &moves
+            ),
+            Some(String::from(r"..\src\New\bin\$configuration$\Old.dll"))
+        );
+        assert_eq!(
+            rewrite_moved_path(r"..\src\Other\Other.dll", nuspec_dir, nuspec_dir, &moves),
+            None
+        );
+
+        // The manifest of the moved project, paths within it move along.
+        let old_dir = Path::new("/repo/src/Old");
+        let new_dir = Path::new("/repo/libs/New");
+        let moves = [
+            (old_dir.join("Old.csproj"), new_dir.join("New.csproj")),
+            (old_dir.to_owned(), new_dir.to_owned()),
+        ];
+        assert_eq!(
+            rewrite_moved_path("bin/**/*.dll", old_dir, new_dir, &moves),
+            None
+        );
+        assert_eq!(
+            rewrite_moved_path(r"..\..\README.md", old_dir, new_dir, &moves),
+            None
+        );
+        assert_eq!(
+            rewrite_moved_path(r"..\Shared\icon.png", old_dir, new_dir, &moves),
+            Some(String::from(r"..\..\src\Shared\icon.png"))
+        );
+    }
+
+    #[test]
+    fn rewrite_publish_profile_path_follows_the_moved_project() {
+        let old_dir = Path::new("/repo/src/Old");
+        let new_dir = Path::new("/repo/libs/web/New");
+        let moves = [
+            (old_dir.join("Old.csproj"), new_dir.join("New.csproj")),
+            (old_dir.to_owned(), new_dir.to_owned()),
+        ];
+
+        assert_eq!(
+            rewrite_publish_profile_path(r"..\..\publish\", old_dir, new_dir, &moves),
+            Some(String::from(r"..\..\..\publish\"))
+        );
+        assert_eq!(
+            rewrite_publish_profile_path(
+                r"$(MSBuildProjectDirectory)\..\..\publish",
+                old_dir,
+                new_dir,
+                &moves
+            ),
+            Some(String::from(r"$(MSBuildProjectDirectory)\..\..\..\publish"))
+        );
+        assert_eq!(
+            rewrite_publish_profile_path(r"..\Old\bin\publish", old_dir, new_dir, &moves),
+            Some(String::from(r"bin\publish"))
+        );
+        assert_eq!(
+            rewrite_publish_profile_path("bin/publish", old_dir, new_dir, &moves),
+            None
+        );
+
+        // The profile of a nested project is relative to that project.
+        assert_eq!(
+            rewrite_publish_profile_path(
+                r"..\..\..\publish",
+                &old_dir.join("Nested"),
+                &new_dir.join("Nested"),
+                &moves
+            ),
+            Some(String::from(r"..\..\..\..\publish"))
+        );
+    }
+
+    #[test]
+    fn rewrite_text_paths_rewrites_paths_relative_to_the_bases() {
+        let dockerfile = r#"FROM mcr.microsoft.com/dotnet/sdk:8.0 AS build
+WORKDIR /src
+COPY ["src/Old/Old.csproj", "src/Old/"]
+COPY src/Old.Tests/Old.Tests.csproj src/Old.Tests/
+COPY ./src/Old/ ./src/Old/
+RUN dotnet build "src\Old\Old.csproj"
+"#;
+        let moves = [
+            (
+                PathBuf::from("/repo/src/Old/Old.csproj"),
+                PathBuf::from("/repo/src/New/New.csproj"),
+            ),
+            (
+                PathBuf::from("/repo/src/Old"),
+                PathBuf::from("/repo/src/New"),
+            ),
+        ];
+        let bases = [Path::new("/repo"), Path::new("/repo/src")];
+
+        assert_eq!(
+            rewrite_text_paths(dockerfile, bases.iter().copied(), &moves).unwrap(),
+            r#"FROM mcr.microsoft.com/dotnet/sdk:8.0 AS build
+WORKDIR /src
+COPY ["src/New/New.csproj", "src/New/"]
+COPY src/Old.Tests/Old.Tests.csproj src/Old.Tests/
+COPY ./src/New/ ./src/New/
+RUN dotnet build "src\New\New.csproj"
+"#
+        );
+        assert_eq!(
+            rewrite_text_paths("COPY src/Other/ ./", bases.iter().copied(), &moves),
+            None
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli, list, path_extensions::relative_path, plan::Effects, xml_extensions::process_tree,
+};
+
+const ARG_SEPARATOR: &'static str = "separator";
+const CMD_NORMALIZE_PATHS: &'static str = "normalize-paths";
+
+#[derive(Debug)]
+pub struct NormalizePathsCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    separator: char,
+    plan_json: Option<PathBuf>,
+}
+
+impl NormalizePathsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_NORMALIZE_PATHS)
+            .about("Write the separators in ProjectReference, Import and HintPath paths the same way in every project and the files it imports")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(
+                Arg::with_name(ARG_SEPARATOR)
+                    .long("separator")
+                    .value_name("SEPARATOR")
+                    .help("Sets the separator, backslashes work with every version of Visual Studio and slashes read better on other platforms")
+                    .takes_value(true)
+                    .possible_values(&["backslash", "slash"])
+                    .default_value("backslash"),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_NORMALIZE_PATHS)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            separator: match matches.value_of(ARG_SEPARATOR).unwrap() {
+                "slash" => '/',
+                _ => '\\',
+            },
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        // Imported files like Directory.Build.props hold references too, each is rewritten once.
+        let paths = projects
+            .iter()
+            .flat_map(|project| {
+                Some(&project.path)
+                    .into_iter()
+                    .chain(project.imports.iter())
+            })
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_NORMALIZE_PATHS, self.plan_json.as_deref());
+
+        let mut normalized_count = 0;
+        for path in paths.iter() {
+            let mut normalized = false;
+            effects
+                .transform_xml_file(path, |mut root| {
+                    normalized = normalize_separators(&mut root, self.separator);
+                    if normalized {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if normalized {
+                status!("Normalized {}", relative_path(&current_dir, path).display());
+                normalized_count += 1;
+            }
+        }
+
+        if normalized_count == 0 {
+            status!("All {} files use the separator", paths.len());
+        }
+
+        effects.finish();
+    }
+}
+
+/// Replaces the other separator in ProjectReference Includes, Import Projects and HintPaths.
+/// Values that look like URLs are left alone. Returns true if a value changed.
+fn normalize_separators(root: &mut Element, separator: char) -> bool {
+    let other = if separator == '/' { '\\' } else { '/' };
+    let normalize = |value: &mut String| {
+        if value.contains(other) && !value.contains("://") {
+            *value = value.replace(other, &separator.to_string());
+            true
+        } else {
+            false
+        }
+    };
+
+    let mut normalized = false;
+    process_tree(root, |element| {
+        let value = match element.name.as_str() {
+            "ProjectReference" => element.attributes.get_mut("Include"),
+            "Import" => element.attributes.get_mut("Project"),
+            _ => None,
+        };
+        if let Some(value) = value {
+            normalized |= normalize(value);
+        }
+
+        if element.name == "HintPath" {
+            for child in element.children.iter_mut() {
+                if let XMLNode::Text(text) = child {
+                    normalized |= normalize(text);
+                }
+            }
+        }
+    });
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_extensions::{child_elements, element, project};
+
+    #[test]
+    fn normalize_separators_rewrites_reference_paths() {
+        let mut root = project(vec![
+            element("Import", &[("Project", "../Shared/Common.props")], vec![]),
+            element(
+                "ItemGroup",
+                &[],
+                vec![
+                    element("ProjectReference", &[("Include", r"..\A/A.csproj")], vec![]),
+                    element("Compile", &[("Include", "Generated/Code.cs")], vec![]),
+                    element(
+                        "Reference",
+                        &[("Include", "Legacy")],
+                        vec![element(
+                            "HintPath",
+                            &[],
+                            vec![XMLNode::Text(String::from("../lib/Legacy.dll"))],
+                        )],
+                    ),
+                ],
+            ),
+        ]);
+
+        assert!(normalize_separators(&mut root, '\\'));
+        let child = |parent: &'_ Element, name: &str| -> Element {
+            child_elements(parent)
+                .find(|element| element.name == name)
+                .unwrap()
+                .clone()
+        };
+        let import = child(&root, "Import");
+        assert_eq!(
+            import.attributes.get("Project").unwrap(),
+            r"..\Shared\Common.props"
+        );
+        let item_group = child(&root, "ItemGroup");
+        let include = |name: &str| {
+            child(&item_group, name)
+                .attributes
+                .get("Include")
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(include("ProjectReference"), r"..\A\A.csproj");
+        assert_eq!(include("Compile"), "Generated/Code.cs");
+        let hint_path = child(&child(&item_group, "Reference"), "HintPath");
+        assert_eq!(hint_path.get_text().unwrap(), r"..\lib\Legacy.dll");
+
+        assert!(!normalize_separators(&mut root, '\\'));
+        assert!(normalize_separators(&mut root, '/'));
+    }
+}
@@ -0,0 +1,438 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::encoding;
+use crate::tools;
+
+pub const NUGET_ORG_NAME: &'static str = "nuget.org";
+pub const NUGET_ORG_URL: &'static str = "https://api.nuget.org/v3/index.json";
+
+const PACKAGE_BASE_ADDRESS: &'static str = "PackageBaseAddress/3.0.0";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSource {
+    pub name: String,
+    pub url: String,
+    pub credentials: Option<Credentials>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Http(Box<ureq::Error>),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    MissingResource(&'static str),
+}
+
+impl From<ureq::Error> for Error {
+    fn from(err: ureq::Error) -> Self {
+        Self::Http(Box::new(err))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "request failed: {}", e),
+            Error::Io(e) => write!(f, "failed to read response: {}", e),
+            Error::Json(e) => write!(f, "failed to parse response: {}", e),
+            Error::MissingResource(resource) => {
+                write!(f, "service index does not provide {}", resource)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Http(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            Error::Json(ref e) => Some(e),
+            Error::MissingResource(_) => None,
+        }
+    }
+}
+
+/// Connects to the package sources for projects in the directory, skipping the ones that are not
+/// available. Panics if none of them are or if network access is disabled.
+pub fn connect_feeds(dir: &Path) -> Vec<Feed> {
+    if tools::is_offline() {
+        panic!("The package sources can not be queried with --offline");
+    }
+
+    let feeds = package_sources(dir)
+        .iter()
+        .filter_map(|source| match Feed::connect(source) {
+            Ok(feed) => Some(feed),
+            Err(e) => {
+                warn!(
+                    "Skipping package source {} ({}): {}",
+                    source.name, source.url, e
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if feeds.is_empty() {
+        panic!("None of the package sources are available");
+    }
+    feeds
+}
+
+/// Determines the package sources for projects in the directory from the NuGet.Config files in
+/// the directory and its ancestors. Like NuGet, closer files take precedence and a `<clear />`
+/// drops the sources defined further up, including nuget.org.
+pub fn package_sources(dir: &Path) -> Vec<PackageSource> {
+    let mut sources = vec![PackageSource {
+        name: NUGET_ORG_NAME.to_owned(),
+        url: NUGET_ORG_URL.to_owned(),
+        credentials: None,
+    }];
+
+    let mut config_paths = dir
+        .ancestors()
+        .filter_map(find_nuget_config)
+        .collect::<Vec<_>>();
+    config_paths.reverse();
+
+    for config_path in config_paths {
+        debug!("reading package sources from {}", config_path.display());
+        let contents = match encoding::read_to_string(&config_path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to read {}: {}", config_path.display(), e);
+                continue;
+            }
+        };
+        let document = match roxmltree::Document::parse(&contents) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!("Failed to parse {}: {}", config_path.display(), e);
+                continue;
+            }
+        };
+        apply_config(&mut sources, &document);
+    }
+
+    sources
+}
+
+fn find_nuget_config(dir: &Path) -> Option<PathBuf> {
+    // The file name is matched case insensitively because both NuGet.Config and nuget.config
+    // are common and not every file system ignores case.
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.eq_ignore_ascii_case("nuget.config"))
+                && path.is_file()
+        })
+}
+
+fn apply_config(sources: &mut Vec<PackageSource>, document: &roxmltree::Document) {
+    let configuration = document.root_element();
+
+    for section in configuration.children().filter(|node| node.is_element()) {
+        match section.tag_name().name() {
+            "packageSources" => {
+                for node in section.children().filter(|node| node.is_element()) {
+                    match (node.tag_name().name(), node.attribute("key")) {
+                        ("clear", _) => sources.clear(),
+                        ("add", Some(key)) => {
+                            let url = match node.attribute("value") {
+                                Some(url) => url.to_owned(),
+                                None => continue,
+                            };
+                            match sources.iter_mut().find(|source| source.name == key) {
+                                Some(source) => source.url = url,
+                                None => sources.push(PackageSource {
+                                    name: key.to_owned(),
+                                    url,
+                                    credentials: None,
+                                }),
+                            }
+                        }
+                        ("remove", Some(key)) => sources.retain(|source| source.name != key),
+                        _ => {}
+                    }
+                }
+            }
+            "disabledPackageSources" => {
+                for node in section.children().filter(|node| node.has_tag_name("add")) {
+                    if node.attribute("value") == Some("true") {
+                        if let Some(key) = node.attribute("key") {
+                            sources.retain(|source| source.name != key);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Credentials can refer to sources from other files so they are applied afterwards.
+    for section in configuration
+        .children()
+        .filter(|node| node.has_tag_name("packageSourceCredentials"))
+    {
+        for node in section.children().filter(|node| node.is_element()) {
+            let name = node.tag_name().name().replace("_x0020_", " ");
+            let setting = |key: &str| {
+                node.children()
+                    .find(|child| child.has_tag_name("add") && child.attribute("key") == Some(key))
+                    .and_then(|child| child.attribute("value"))
+            };
+
+            let source = match sources.iter_mut().find(|source| source.name == name) {
+                Some(source) => source,
+                None => continue,
+            };
+
+            match (setting("Username"), setting("ClearTextPassword")) {
+                (Some(username), Some(password)) => {
+                    source.credentials = Some(Credentials {
+                        username: expand_environment_variables(username),
+                        password: expand_environment_variables(password),
+                    });
+                }
+                _ if setting("Password").is_some() => {
+                    warn!(
+                        "The credentials of {} are encrypted, use ClearTextPassword instead",
+                        name
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Expands `%NAME%` the way NuGet does in config values, unknown variables are left as is.
+fn expand_environment_variables(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        let after_start = &rest[start + 1..];
+        match after_start.find('%') {
+            Some(end) => {
+                let name = &after_start[..end];
+                result.push_str(&rest[..start]);
+                match std::env::var(name) {
+                    Ok(value) if !name.is_empty() => result.push_str(&value),
+                    _ => {
+                        result.push('%');
+                        result.push_str(name);
+                        result.push('%');
+                    }
+                }
+                rest = &after_start[end + 1..];
+            }
+            None => break,
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceIndex {
+    resources: Vec<ServiceResource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceResource {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@type")]
+    resource_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageVersions {
+    versions: Vec<String>,
+}
+
+/// A NuGet v3 feed. Only the flat container (`PackageBaseAddress`) is used because it lists all
+/// versions of a package in a single small request.
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub name: String,
+    agent: ureq::Agent,
+    authorization: Option<String>,
+    package_base_address: String,
+}
+
+impl Feed {
+    pub fn connect(source: &PackageSource) -> Result<Self, Error> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .build();
+
+        let authorization = source.credentials.as_ref().map(|credentials| {
+            format!(
+                "Basic {}",
+                base64::encode(format!("{}:{}", credentials.username, credentials.password))
+            )
+        });
+
+        let mut request = agent.get(&source.url);
+        if let Some(authorization) = &authorization {
+            request = request.set("Authorization", authorization);
+        }
+        let index: ServiceIndex = serde_json::from_str(&request.call()?.into_string()?)?;
+
+        let mut package_base_address = index
+            .resources
+            .into_iter()
+            .find(|resource| resource.resource_type == PACKAGE_BASE_ADDRESS)
+            .ok_or(Error::MissingResource(PACKAGE_BASE_ADDRESS))?
+            .id;
+        if !package_base_address.ends_with('/') {
+            package_base_address.push('/');
+        }
+
+        Ok(Self {
+            name: source.name.clone(),
+            agent,
+            authorization,
+            package_base_address,
+        })
+    }
+
+    /// Lists all published versions of the package, an empty list if the feed does not have it.
+    pub fn versions(&self, package_id: &str) -> Result<Vec<String>, Error> {
+        let url = format!(
+            "{}{}/index.json",
+            self.package_base_address,
+            package_id.to_lowercase()
+        );
+        debug!("requesting {}", url);
+
+        let mut request = self.agent.get(&url);
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        match request.call() {
+            Ok(response) => {
+                let versions: PackageVersions = serde_json::from_str(&response.into_string()?)?;
+                Ok(versions.versions)
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Downloads the nuspec of a published package version, none if the feed does not have it.
+    pub fn nuspec(&self, package_id: &str, version: &str) -> Result<Option<String>, Error> {
+        let package_id = package_id.to_lowercase();
+        let url = format!(
+            "{}{}/{}/{}.nuspec",
+            self.package_base_address,
+            package_id,
+            version.to_lowercase(),
+            package_id
+        );
+        debug!("requesting {}", url);
+
+        let mut request = self.agent.get(&url);
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        match request.call() {
+            Ok(response) => Ok(Some(response.into_string()?)),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(configs: &[&str]) -> Vec<PackageSource> {
+        let mut sources = vec![PackageSource {
+            name: NUGET_ORG_NAME.to_owned(),
+            url: NUGET_ORG_URL.to_owned(),
+            credentials: None,
+        }];
+        for config in configs {
+            apply_config(&mut sources, &roxmltree::Document::parse(config).unwrap());
+        }
+        sources
+    }
+
+    #[test]
+    fn closer_configs_add_and_clear_sources() {
+        let outer = r#"<configuration><packageSources>
+            <add key="Outer" value="https://outer/index.json" />
+        </packageSources></configuration>"#;
+        let inner = r#"<configuration><packageSources>
+            <clear />
+            <add key="Inner" value="https://inner/index.json" />
+        </packageSources></configuration>"#;
+
+        let names = |sources: Vec<PackageSource>| {
+            sources
+                .into_iter()
+                .map(|source| source.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(sources(&[outer])), vec!["nuget.org", "Outer"]);
+        assert_eq!(names(sources(&[outer, inner])), vec!["Inner"]);
+    }
+
+    #[test]
+    fn credentials_are_read_for_sources_with_spaces() {
+        let config = r#"<configuration>
+            <packageSources><add key="My Feed" value="https://feed/index.json" /></packageSources>
+            <packageSourceCredentials><My_x0020_Feed>
+                <add key="Username" value="user" />
+                <add key="ClearTextPassword" value="secret" />
+            </My_x0020_Feed></packageSourceCredentials>
+        </configuration>"#;
+
+        assert_eq!(
+            sources(&[config])[1].credentials,
+            Some(Credentials {
+                username: String::from("user"),
+                password: String::from("secret"),
+            })
+        );
+    }
+
+    #[test]
+    fn expand_environment_variables_keeps_unknown_variables() {
+        assert_eq!(
+            expand_environment_variables("%CSPROJTOOL_SURELY_UNDEFINED%/x"),
+            "%CSPROJTOOL_SURELY_UNDEFINED%/x"
+        );
+        assert_eq!(expand_environment_variables("100%"), "100%");
+    }
+}
@@ -0,0 +1,171 @@
+/// The parts of a package manifest that describe what the package was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nuspec {
+    pub id: String,
+    pub version: String,
+    pub dependency_groups: Vec<DependencyGroup>,
+}
+
+/// The dependencies for a target framework. Old packages list their dependencies without a group,
+/// those end up in a group without a target framework.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGroup {
+    pub target_framework: Option<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub id: String,
+    /// The version range, like `[1.0.0, )` or `1.0.0`.
+    pub version: Option<String>,
+}
+
+impl Nuspec {
+    pub fn parse(contents: &str) -> Result<Self, roxmltree::Error> {
+        let document = roxmltree::Document::parse(contents.trim_start_matches('\u{FEFF}'))?;
+        let metadata = document
+            .descendants()
+            .find(|node| node.tag_name().name() == "metadata");
+
+        let text = |name: &str| {
+            metadata
+                .and_then(|metadata| {
+                    metadata
+                        .children()
+                        .find(|node| node.tag_name().name() == name)
+                })
+                .and_then(|node| node.text())
+                .map(|text| text.trim().to_owned())
+                .unwrap_or_default()
+        };
+
+        let dependency = |node: roxmltree::Node| {
+            Some(Dependency {
+                id: node.attribute("id")?.to_owned(),
+                version: node.attribute("version").map(str::to_owned),
+            })
+        };
+
+        let mut dependency_groups = Vec::new();
+        if let Some(dependencies) = document
+            .descendants()
+            .find(|node| node.tag_name().name() == "dependencies")
+        {
+            let ungrouped = dependencies
+                .children()
+                .filter(|node| node.tag_name().name() == "dependency")
+                .filter_map(dependency)
+                .collect::<Vec<_>>();
+            if !ungrouped.is_empty() {
+                dependency_groups.push(DependencyGroup {
+                    target_framework: None,
+                    dependencies: ungrouped,
+                });
+            }
+
+            for group in dependencies
+                .children()
+                .filter(|node| node.tag_name().name() == "group")
+            {
+                dependency_groups.push(DependencyGroup {
+                    target_framework: group.attribute("targetFramework").map(str::to_owned),
+                    dependencies: group
+                        .children()
+                        .filter(|node| node.tag_name().name() == "dependency")
+                        .filter_map(dependency)
+                        .collect(),
+                });
+            }
+        }
+
+        Ok(Self {
+            id: text("id"),
+            version: text("version"),
+            dependency_groups,
+        })
+    }
+}
+
+/// Returns the short name of a target framework as written by `dotnet pack`, so
+/// `.NETFramework4.7.2` becomes `net472` and `.NETStandard2.0` becomes `netstandard2.0`.
+pub fn short_target_framework(target_framework: &str) -> String {
+    let lower = target_framework.to_lowercase();
+    if let Some(version) = lower.strip_prefix(".netframework") {
+        return format!("net{}", version.replace('.', ""));
+    }
+    if let Some(version) = lower.strip_prefix(".netstandard") {
+        return format!("netstandard{}", version);
+    }
+    if let Some(version) = lower.strip_prefix(".netcoreapp") {
+        return format!("netcoreapp{}", version);
+    }
+    lower
+}
+
+/// Returns the lowest version a range allows, `[1.0.0, )` and `1.0.0` both give `1.0.0`.
+pub fn minimum_version(range: &str) -> &str {
+    range
+        .trim_start_matches(|c| c == '[' || c == '(')
+        .split(',')
+        .next()
+        .unwrap()
+        .trim_end_matches(|c| c == ']' || c == ')')
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_dependency_groups() {
+        let nuspec = Nuspec::parse(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://schemas.microsoft.com/packaging/2013/05/nuspec.xsd">
+  <metadata>
+    <id>Company.Core</id>
+    <version>1.2.0</version>
+    <dependencies>
+      <group targetFramework=".NETFramework4.8">
+        <dependency id="Newtonsoft.Json" version="13.0.1" exclude="Build,Analyzers" />
+      </group>
+      <group targetFramework="net8.0" />
+    </dependencies>
+  </metadata>
+</package>"#,
+        )
+        .unwrap();
+
+        assert_eq!(nuspec.id, "Company.Core");
+        assert_eq!(nuspec.version, "1.2.0");
+        assert_eq!(
+            nuspec.dependency_groups,
+            vec![
+                DependencyGroup {
+                    target_framework: Some(String::from(".NETFramework4.8")),
+                    dependencies: vec![Dependency {
+                        id: String::from("Newtonsoft.Json"),
+                        version: Some(String::from("13.0.1")),
+                    }],
+                },
+                DependencyGroup {
+                    target_framework: Some(String::from("net8.0")),
+                    dependencies: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn short_target_framework_converts_long_names() {
+        assert_eq!(short_target_framework(".NETFramework4.7.2"), "net472");
+        assert_eq!(short_target_framework(".NETStandard2.0"), "netstandard2.0");
+        assert_eq!(
+            short_target_framework("net8.0-windows7.0"),
+            "net8.0-windows7.0"
+        );
+        assert_eq!(minimum_version("[1.0.0, )"), "1.0.0");
+        assert_eq!(minimum_version("2.1.0"), "2.1.0");
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::{
+    cli, cpm, limits, list,
+    nuget::{connect_feeds, Feed},
+    output,
+    package_version::{package_usages, PackageUsage, PackageVersion},
+    path_extensions::PathExt,
+};
+
+const ARG_MAJOR: &'static str = "major";
+const ARG_MINOR: &'static str = "minor";
+const CMD_OUTDATED: &'static str = "outdated";
+
+/// The number of concurrent requests to the feeds, unless `--threads` is lower.
+const WORKER_COUNT: usize = 16;
+
+#[derive(Debug)]
+pub struct OutdatedCommand {
+    search_path: PathBuf,
+    follow_project_references: bool,
+    major: bool,
+    minor: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Update {
+    Major,
+    Minor,
+    Patch,
+}
+
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    name: &'a str,
+    current: &'a str,
+    latest: String,
+    project_count: usize,
+}
+
+impl OutdatedCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_OUTDATED)
+            .about("List package references for which a newer version is available")
+            .arg(cli::arg_search())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(
+                Arg::with_name(ARG_MAJOR)
+                    .long("major")
+                    .takes_value(false)
+                    .help("Only list packages with a new major version"),
+            )
+            .arg(
+                Arg::with_name(ARG_MINOR)
+                    .long("minor")
+                    .takes_value(false)
+                    .help("Only list packages with a new minor version"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_OUTDATED)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            follow_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            major: matches.is_present(ARG_MAJOR),
+            minor: matches.is_present(ARG_MINOR),
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: self.follow_project_references,
+        });
+
+        let mut packages = package_usages(&projects);
+        add_central_package_versions(&mut packages, &projects);
+
+        let search_dir = self.search_path.simplified_absolute().unwrap();
+        let search_dir = if search_dir.is_file() {
+            search_dir.parent().unwrap().to_owned()
+        } else {
+            search_dir
+        };
+
+        let feeds = connect_feeds(&search_dir);
+
+        let published = fetch_versions(&feeds, packages.values().map(|usage| &usage.name));
+
+        let mut rows = Vec::new();
+        for (key, usage) in packages.iter() {
+            let published = &published[key];
+            for (current, project_paths) in usage.versions.iter() {
+                let current_version = match PackageVersion::parse(current) {
+                    Some(version) => version,
+                    None => {
+                        debug!(
+                            "skipping {} {} which is not a plain version",
+                            usage.name, current
+                        );
+                        continue;
+                    }
+                };
+
+                // Pre-releases are only suggested to projects that already use one.
+                let latest = published
+                    .iter()
+                    .filter(|(version, _)| {
+                        current_version.is_pre_release() || !version.is_pre_release()
+                    })
+                    .max_by(|a, b| a.0.cmp(&b.0));
+                let (latest_version, latest) = match latest {
+                    Some(latest) if latest.0 > current_version => latest,
+                    _ => continue,
+                };
+
+                let update = if latest_version.major() > current_version.major() {
+                    Update::Major
+                } else if latest_version.minor() > current_version.minor() {
+                    Update::Minor
+                } else {
+                    Update::Patch
+                };
+                if (self.major || self.minor)
+                    && !(self.major && update == Update::Major
+                        || self.minor && update == Update::Minor)
+                {
+                    continue;
+                }
+
+                rows.push(Row {
+                    name: &usage.name,
+                    current,
+                    latest: latest.clone(),
+                    project_count: project_paths.len(),
+                });
+            }
+        }
+
+        if output::is_json() {
+            output::print_json(&rows);
+        } else {
+            print_table(&rows);
+        }
+    }
+}
+
+/// Adds the versions of centrally managed package references from Directory.Packages.props.
+fn add_central_package_versions(
+    packages: &mut BTreeMap<String, PackageUsage>,
+    projects: &[crate::csproj::Project],
+) {
+    let mut central_versions = BTreeMap::new();
+
+    for project in projects.iter() {
+        let props_path = match cpm::find_packages_props(project.path.parent().unwrap()) {
+            Some(props_path) => props_path,
+            None => continue,
+        };
+        let versions = central_versions
+            .entry(props_path.clone())
+            .or_insert_with(|| {
+                cpm::read_package_versions(&props_path).unwrap_or_else(|e| {
+                    warn!("Failed to read {}: {}", props_path.display(), e);
+                    BTreeMap::new()
+                })
+            });
+
+        for reference in project
+            .package_references
+            .iter()
+            .filter(|reference| reference.version.is_none())
+        {
+            let key = reference.name.to_lowercase();
+            if let Some(version) = versions.get(&key) {
+                packages
+                    .entry(key)
+                    .or_insert_with(|| PackageUsage {
+                        name: reference.name.clone(),
+                        versions: BTreeMap::new(),
+                    })
+                    .versions
+                    .entry(version.clone())
+                    .or_default()
+                    .push(project.path.clone());
+            }
+        }
+    }
+}
+
+/// Queries all feeds for the versions of every package, keyed by the lowercase package name.
+fn fetch_versions<'a>(
+    feeds: &[Feed],
+    names: impl Iterator<Item = &'a String>,
+) -> BTreeMap<String, Vec<(PackageVersion, String)>> {
+    let (job_sender, job_receiver) = crossbeam_channel::unbounded::<String>();
+    let (result_sender, result_receiver) = crossbeam_channel::unbounded();
+
+    for name in names {
+        job_sender.send(name.clone()).unwrap();
+    }
+    drop(job_sender);
+
+    let workers = (0..limits::cap_threads(WORKER_COUNT).min(job_receiver.len()))
+        .map(|_| {
+            let feeds = feeds.to_vec();
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            std::thread::spawn(move || {
+                for name in job_receiver.iter() {
+                    let mut versions = Vec::new();
+                    for feed in feeds.iter() {
+                        match feed.versions(&name) {
+                            Ok(feed_versions) => versions.extend(feed_versions),
+                            Err(e) => warn!("Failed to query {} for {}: {}", feed.name, name, e),
+                        }
+                    }
+                    result_sender.send((name, versions)).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(result_sender);
+
+    let published = result_receiver
+        .iter()
+        .map(|(name, versions)| {
+            let versions = versions
+                .into_iter()
+                .filter_map(|version| {
+                    PackageVersion::parse(&version).map(|parsed| (parsed, version))
+                })
+                .collect();
+            (name.to_lowercase(), versions)
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    published
+}
+
+fn print_table(rows: &[Row]) {
+    let headers = ["Package", "Current", "Latest", "Projects"];
+    let cells = rows
+        .iter()
+        .map(|row| {
+            [
+                row.name.to_owned(),
+                row.current.to_owned(),
+                row.latest.clone(),
+                row.project_count.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = [0; 4];
+    for (width, header) in widths.iter_mut().zip(headers.iter()) {
+        *width = header.len();
+    }
+    for row in cells.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    println!(
+        "{:<w0$}  {:<w1$}  {:<w2$}  {:>w3$}",
+        headers[0],
+        headers[1],
+        headers[2],
+        headers[3],
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+        w3 = widths[3],
+    );
+    for row in cells.iter() {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {:>w3$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+/// Whether commands write structured results to stdout, set by the global `--json` flag.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Applies the options given on the command line, must be called before anything is printed.
+pub fn init(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Writes a result to stdout as pretty printed json.
+pub fn print_json<T: Serialize>(value: &T) {
+    serde_json::to_writer_pretty(std::io::stdout(), value).unwrap();
+    println!();
+}
+
+/// Prints a message about the progress of a command. The message goes to stderr in json mode so
+/// that stdout only contains the results.
+pub fn print_status(message: std::fmt::Arguments) {
+    if is_json() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Like `println!`, but for messages that are not results, see `print_status`.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        $crate::output::print_status(format_args!($($arg)*))
+    };
+}
+
+pub(crate) use status;
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{annotations, check::Finding, path_extensions::relative_path, sarif};
+
+/// The number of files listed as top offenders in a summary.
+const TOP_OFFENDER_COUNT: usize = 10;
+
+/// The number of findings listed in a summary, the remainder is only counted.
+const FINDING_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Markdown,
+    Slack,
+    Sarif,
+    Github,
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &["text", "markdown", "slack", "sarif", "github"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "markdown" => Some(Self::Markdown),
+            "slack" => Some(Self::Slack),
+            "sarif" => Some(Self::Sarif),
+            "github" => Some(Self::Github),
+            _ => None,
+        }
+    }
+
+    /// Whether the format reports every finding at its location, see `print_issues`.
+    pub fn reports_issues(self) -> bool {
+        self == Self::Sarif || self == Self::Github
+    }
+}
+
+/// Prints the issues in a format that `reports_issues`, with paths relative to the repository
+/// root.
+pub fn print_issues(format: OutputFormat, issues: &[sarif::Issue], root: &Path) {
+    match format {
+        OutputFormat::Sarif => sarif::print(issues, root),
+        OutputFormat::Github => annotations::print(issues, root),
+        _ => panic!("The {:?} format does not report issues", format),
+    }
+}
+
+/// Turns file paths into links to a code browser. The template is a URL in which `{path}` is
+/// replaced by the path relative to the repository root, for example
+/// `https://git.example.com/repo/blob/main/{path}`.
+#[derive(Debug, Clone)]
+pub struct Links {
+    pub root: PathBuf,
+    pub template: Option<String>,
+}
+
+impl Links {
+    fn display(&self, path: &Path) -> String {
+        relative_path(&self.root, path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    fn url(&self, path: &Path) -> Option<String> {
+        self.template
+            .as_ref()
+            .map(|template| template.replace("{path}", &self.display(path)))
+    }
+}
+
+/// Renders a compact summary of the findings with counts per rule and the files involved in the
+/// most findings, meant to be posted by nightly jobs.
+pub fn render_summary(
+    format: OutputFormat,
+    title: &str,
+    findings: &[Finding],
+    links: &Links,
+) -> String {
+    if format == OutputFormat::Text || format.reports_issues() {
+        panic!("The {:?} format does not have a summary", format);
+    }
+
+    let mut rule_counts = BTreeMap::<&str, usize>::new();
+    let mut path_counts = BTreeMap::<&Path, usize>::new();
+    for finding in findings.iter() {
+        *rule_counts.entry(finding.rule).or_default() += 1;
+        for path in finding.paths.iter() {
+            *path_counts.entry(path).or_default() += 1;
+        }
+    }
+
+    let mut out = String::new();
+    if findings.is_empty() {
+        out.push_str(&format!("{}: no findings\n", format.bold(title)));
+        return out;
+    }
+
+    out.push_str(&format!(
+        "{}: {} findings in {} files\n\n",
+        format.bold(title),
+        findings.len(),
+        path_counts.len()
+    ));
+
+    match format {
+        OutputFormat::Markdown => {
+            out.push_str("| Rule | Findings |\n| --- | ---: |\n");
+            for (rule, count) in rule_counts.iter() {
+                out.push_str(&format!("| {} | {} |\n", rule, count));
+            }
+        }
+        _ => {
+            for (rule, count) in rule_counts.iter() {
+                out.push_str(&format!("{} {}: {}\n", format.bullet(), rule, count));
+            }
+        }
+    }
+    out.push('\n');
+
+    let mut offenders = path_counts.into_iter().collect::<Vec<_>>();
+    offenders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    out.push_str(&format!("{}\n", format.bold("Top offenders")));
+    for (index, (path, count)) in offenders.iter().take(TOP_OFFENDER_COUNT).enumerate() {
+        out.push_str(&format!(
+            "{}. {} ({})\n",
+            index + 1,
+            format.link(links, path),
+            count
+        ));
+    }
+    out.push('\n');
+
+    out.push_str(&format!("{}\n", format.bold("Findings")));
+    for finding in findings.iter().take(FINDING_LIMIT) {
+        out.push_str(&format!(
+            "{} {} {}: {}\n",
+            format.bullet(),
+            format.bold(finding.rule),
+            format.escape(&finding.message),
+            finding
+                .paths
+                .iter()
+                .map(|path| format.link(links, path))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if findings.len() > FINDING_LIMIT {
+        out.push_str(&format!(
+            "_... and {} more_\n",
+            findings.len() - FINDING_LIMIT
+        ));
+    }
+
+    out
+}
+
+impl OutputFormat {
+    fn bullet(self) -> &'static str {
+        match self {
+            Self::Slack => "•",
+            _ => "-",
+        }
+    }
+
+    fn bold(self, text: &str) -> String {
+        match self {
+            Self::Markdown => format!("**{}**", text),
+            Self::Slack => format!("*{}*", text),
+            Self::Text | Self::Sarif | Self::Github => text.to_owned(),
+        }
+    }
+
+    fn escape(self, text: &str) -> String {
+        match self {
+            Self::Markdown => text
+                .chars()
+                .flat_map(|c| match c {
+                    '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' => vec!['\\', c],
+                    c => vec![c],
+                })
+                .collect(),
+            // Slack only requires these three to be escaped, see
+            // https://api.slack.com/reference/surfaces/formatting#escaping
+            Self::Slack => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;"),
+            Self::Text | Self::Sarif | Self::Github => text.to_owned(),
+        }
+    }
+
+    fn link(self, links: &Links, path: &Path) -> String {
+        let text = links.display(path);
+        match (self, links.url(path)) {
+            (Self::Markdown, Some(url)) => format!("[{}]({})", text, url),
+            (Self::Slack, Some(url)) => format!("<{}|{}>", url, text),
+            (_, Some(url)) => url,
+            (_, None) => format!("`{}`", text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links() -> Links {
+        Links {
+            root: PathBuf::from("/repo"),
+            template: Some(String::from("https://code.example.com/{path}")),
+        }
+    }
+
+    fn findings() -> Vec<Finding> {
+        vec![
+            Finding {
+                rule: "package-versions",
+                message: String::from("A is referenced with versions 1.0.0, 2.0.0"),
+                paths: vec![
+                    PathBuf::from("/repo/src/A/A.csproj"),
+                    PathBuf::from("/repo/src/B/B.csproj"),
+                ],
+            },
+            Finding {
+                rule: "output-paths",
+                message: String::from("Projects share output path <out>"),
+                paths: vec![PathBuf::from("/repo/src/B/B.csproj")],
+            },
+        ]
+    }
+
+    #[test]
+    fn slack_summary_links_and_escapes() {
+        let summary = render_summary(OutputFormat::Slack, "check", &findings(), &links());
+
+        assert!(summary.starts_with("*check*: 2 findings in 2 files\n"));
+        assert!(summary.contains("• output-paths: 1\n• package-versions: 1\n"));
+        assert!(
+            summary.contains("1. <https://code.example.com/src/B/B.csproj|src/B/B.csproj> (2)\n")
+        );
+        assert!(summary.contains("Projects share output path &lt;out&gt;"));
+    }
+
+    #[test]
+    fn markdown_summary_without_template_uses_code_spans() {
+        let links = Links {
+            template: None,
+            ..links()
+        };
+        let summary = render_summary(OutputFormat::Markdown, "check", &findings(), &links);
+
+        assert!(summary.contains("| output-paths | 1 |\n"));
+        assert!(summary.contains("1. `src/B/B.csproj` (2)\n"));
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::{
+    cpm,
+    csproj::{read_and_parse_project, Project},
+    encoding,
+    nuget::connect_feeds,
+    nuspec::{minimum_version, short_target_framework, Nuspec},
+    package_version::PackageVersion,
+    path_extensions::{relative_path, PathExt},
+    tfms,
+};
+
+const ARG_NUSPEC: &'static str = "nuspec";
+const ARG_PROJECT: &'static str = "project";
+const ARG_VERSION: &'static str = "version";
+const CMD_PACKAGE_DIFF: &'static str = "package-diff";
+
+#[derive(Debug)]
+pub struct PackageDiffCommand {
+    project_path: PathBuf,
+    version: Option<String>,
+    nuspec_path: Option<PathBuf>,
+}
+
+/// What a package built from the current source would contain.
+#[derive(Debug)]
+struct SourcePackage {
+    target_frameworks: BTreeSet<String>,
+    /// The dependencies by their lowercase id, with the id as written and the version if known.
+    dependencies: BTreeMap<String, (String, Option<String>)>,
+}
+
+impl PackageDiffCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_PACKAGE_DIFF)
+            .about("Compare the dependencies and target frameworks of a published package with its project")
+            .arg(
+                Arg::with_name(ARG_PROJECT)
+                    .value_name("PROJECT_PATH")
+                    .help("The project the package is built from")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::with_name(ARG_VERSION)
+                    .long("version")
+                    .value_name("VERSION")
+                    .help("The published version to compare with, defaults to the latest stable version")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_NUSPEC)
+                    .long("nuspec")
+                    .value_name("NUSPEC_PATH")
+                    .help("Compare with a local nuspec instead of downloading it from the package sources")
+                    .conflicts_with(ARG_VERSION)
+                    .takes_value(true),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_PACKAGE_DIFF)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            project_path: PathBuf::from(matches.value_of_os(ARG_PROJECT).unwrap()),
+            version: matches.value_of(ARG_VERSION).map(str::to_owned),
+            nuspec_path: matches.value_of_os(ARG_NUSPEC).map(PathBuf::from),
+        }
+    }
+
+    pub fn execute(&self) {
+        let project_path = std::fs::canonicalize(self.project_path.simplified_absolute().unwrap())
+            .expect("Failed to find the project!");
+        let project = read_and_parse_project(project_path.clone())
+            .unwrap_or_else(|e| panic!("Failed to read {}: {:?}", project_path.display(), e));
+        let package_id = package_id(&project);
+
+        let contents = match &self.nuspec_path {
+            Some(nuspec_path) => encoding::read_to_string(nuspec_path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", nuspec_path.display(), e)),
+            None => download_nuspec(&project_path, &package_id, self.version.as_deref()),
+        };
+        let nuspec = Nuspec::parse(&contents)
+            .unwrap_or_else(|e| panic!("Failed to parse the nuspec of {}: {}", package_id, e));
+
+        let differences = differences(&source_package(&project), &nuspec);
+
+        let current_dir = std::env::current_dir().unwrap();
+        if differences.is_empty() {
+            println!(
+                "{} {} matches {}",
+                nuspec.id,
+                nuspec.version,
+                relative_path(&current_dir, &project_path).display()
+            );
+            return;
+        }
+
+        println!(
+            "{} {} differs from {}:",
+            nuspec.id,
+            nuspec.version,
+            relative_path(&current_dir, &project_path).display()
+        );
+        for difference in differences.iter() {
+            println!("  {}", difference);
+        }
+        std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+    }
+}
+
+/// The id of the package built from the project, which defaults to the assembly name and the
+/// project name.
+fn package_id(project: &Project) -> String {
+    ["PackageId", "AssemblyName"]
+        .iter()
+        .find_map(|name| {
+            project
+                .properties
+                .iter()
+                .rfind(|property| property.name == *name && property.condition.is_none())
+                .filter(|property| !property.value.contains("$("))
+                .map(|property| property.value.clone())
+        })
+        .unwrap_or_else(|| {
+            project
+                .path
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        })
+}
+
+fn download_nuspec(project_path: &Path, package_id: &str, version: Option<&str>) -> String {
+    let feeds = connect_feeds(project_path.parent().unwrap());
+
+    let version = match version {
+        Some(version) => version.to_owned(),
+        None => feeds
+            .iter()
+            .flat_map(|feed| {
+                feed.versions(package_id).unwrap_or_else(|e| {
+                    warn!("Failed to list the versions on {}: {}", feed.name, e);
+                    Vec::new()
+                })
+            })
+            .filter_map(|version| PackageVersion::parse(&version).map(|parsed| (parsed, version)))
+            .filter(|(parsed, _)| !parsed.is_pre_release())
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, version)| version)
+            .unwrap_or_else(|| panic!("No stable version of {} is published", package_id)),
+    };
+
+    for feed in feeds.iter() {
+        match feed.nuspec(package_id, &version) {
+            Ok(Some(contents)) => return contents,
+            Ok(None) => {}
+            Err(e) => warn!(
+                "Failed to download {} {} from {}: {}",
+                package_id, version, feed.name, e
+            ),
+        }
+    }
+    panic!("{} {} is not published", package_id, version);
+}
+
+/// Collects the target frameworks and the dependencies `dotnet pack` would write for the project.
+/// Package references with `PrivateAssets="all"` are not dependencies of the package, project
+/// references become dependencies on the packages of the referenced projects.
+fn source_package(project: &Project) -> SourcePackage {
+    let mut package = SourcePackage {
+        target_frameworks: tfms::group_by_target_framework(std::slice::from_ref(project))
+            .keys()
+            .map(|target_framework| target_framework.to_lowercase())
+            .filter(|target_framework| target_framework != "(none)")
+            .collect(),
+        dependencies: BTreeMap::new(),
+    };
+
+    let private = private_package_references(&project.path);
+    let central_versions = cpm::find_packages_props(project.path.parent().unwrap())
+        .map(|props_path| {
+            cpm::read_package_versions(&props_path).unwrap_or_else(|e| {
+                warn!("Failed to read {}: {}", props_path.display(), e);
+                BTreeMap::new()
+            })
+        })
+        .unwrap_or_default();
+
+    for reference in project.package_references.iter() {
+        let key = reference.name.to_lowercase();
+        if private.contains(&key) {
+            continue;
+        }
+        let version = reference
+            .version
+            .clone()
+            .or_else(|| central_versions.get(&key).cloned());
+        package
+            .dependencies
+            .insert(key, (reference.name.clone(), version));
+    }
+
+    for reference_path in project.project_references.iter() {
+        match read_and_parse_project(reference_path.clone()) {
+            Ok(reference) => {
+                let id = package_id(&reference);
+                package.dependencies.insert(id.to_lowercase(), (id, None));
+            }
+            Err(e) => warn!("Failed to read {}: {:?}", reference_path.display(), e),
+        }
+    }
+
+    package
+}
+
+/// The lowercase names of the package references that set PrivateAssets to all, as an attribute
+/// or as a child element.
+fn private_package_references(project_path: &Path) -> BTreeSet<String> {
+    let contents = match encoding::read_to_string(project_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Failed to read {}: {}", project_path.display(), e);
+            return BTreeSet::new();
+        }
+    };
+    let document = match roxmltree::Document::parse(contents.trim_start_matches('\u{FEFF}')) {
+        Ok(document) => document,
+        Err(e) => {
+            warn!("Failed to parse {}: {}", project_path.display(), e);
+            return BTreeSet::new();
+        }
+    };
+
+    document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "PackageReference")
+        .filter(|node| {
+            node.attribute("PrivateAssets")
+                .or_else(|| {
+                    node.children()
+                        .find(|child| child.tag_name().name() == "PrivateAssets")
+                        .and_then(|child| child.text())
+                })
+                .map_or(false, |value| value.trim().eq_ignore_ascii_case("all"))
+        })
+        .filter_map(|node| {
+            node.attribute("Include")
+                .or_else(|| node.attribute("Update"))
+        })
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn differences(source: &SourcePackage, nuspec: &Nuspec) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    let published_target_frameworks = nuspec
+        .dependency_groups
+        .iter()
+        .filter_map(|group| group.target_framework.as_deref())
+        .map(short_target_framework)
+        .collect::<BTreeSet<_>>();
+    // Packages without dependency groups do not record their target frameworks.
+    if !published_target_frameworks.is_empty() {
+        for target_framework in source
+            .target_frameworks
+            .difference(&published_target_frameworks)
+        {
+            differences.push(format!(
+                "Target framework {} is not in the published package",
+                target_framework
+            ));
+        }
+        for target_framework in published_target_frameworks.difference(&source.target_frameworks) {
+            differences.push(format!(
+                "Target framework {} is published but no longer targeted",
+                target_framework
+            ));
+        }
+    }
+
+    let mut published = BTreeMap::new();
+    for dependency in nuspec
+        .dependency_groups
+        .iter()
+        .flat_map(|group| group.dependencies.iter())
+    {
+        published
+            .entry(dependency.id.to_lowercase())
+            .or_insert(dependency);
+    }
+
+    for (key, (id, version)) in source.dependencies.iter() {
+        match published.get(key) {
+            None => differences.push(format!("Dependency {} is not in the published package", id)),
+            Some(dependency) => {
+                let published_version = dependency.version.as_deref().map(minimum_version);
+                if let (Some(version), Some(published_version)) = (version, published_version) {
+                    if !version.eq_ignore_ascii_case(published_version) {
+                        differences.push(format!(
+                            "Dependency {} is {} in the source but {} in the published package",
+                            id, version, published_version
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    for (key, dependency) in published.iter() {
+        if !source.dependencies.contains_key(key) {
+            differences.push(format!(
+                "Dependency {} is published but no longer referenced",
+                dependency.id
+            ));
+        }
+    }
+
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuspec::{Dependency, DependencyGroup};
+
+    fn dependency(id: &str, version: &str) -> Dependency {
+        Dependency {
+            id: id.to_owned(),
+            version: Some(version.to_owned()),
+        }
+    }
+
+    #[test]
+    fn differences_flag_drift_in_both_directions() {
+        let source = SourcePackage {
+            target_frameworks: ["net48", "net8.0"].iter().map(|s| s.to_string()).collect(),
+            dependencies: vec![
+                ("newtonsoft.json", "Newtonsoft.Json", Some("13.0.3")),
+                ("company.data", "Company.Data", None),
+                ("serilog", "Serilog", Some("3.1.1")),
+            ]
+            .into_iter()
+            .map(|(key, id, version)| (key.to_owned(), (id.to_owned(), version.map(str::to_owned))))
+            .collect(),
+        };
+        let nuspec = Nuspec {
+            id: String::from("Company.Core"),
+            version: String::from("1.0.0"),
+            dependency_groups: vec![DependencyGroup {
+                target_framework: Some(String::from(".NETFramework4.8")),
+                dependencies: vec![
+                    dependency("Newtonsoft.Json", "[13.0.1, )"),
+                    dependency("Company.Data", "1.0.0"),
+                    dependency("Polly", "7.2.4"),
+                ],
+            }],
+        };
+
+        assert_eq!(
+            differences(&source, &nuspec),
+            vec![
+                "Target framework net8.0 is not in the published package",
+                "Dependency Newtonsoft.Json is 13.0.3 in the source but 13.0.1 in the published package",
+                "Dependency Serilog is not in the published package",
+                "Dependency Polly is published but no longer referenced",
+            ]
+        );
+    }
+}
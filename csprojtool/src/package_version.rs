@@ -0,0 +1,210 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::csproj::Project;
+
+/// A NuGet package version like `1.2.3`, `4.7.2.1` or `2.0.0-beta.1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackageVersion {
+    pub release: [u64; 4],
+    pub pre_release: Vec<String>,
+}
+
+impl PackageVersion {
+    /// Returns None for anything that is not a plain version, like floating versions (`1.*`),
+    /// version ranges (`[1.0,2.0)`) and MSBuild properties (`$(Version)`).
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.split('+').next().unwrap();
+        let (release_text, pre_release_text) = match text.find('-') {
+            Some(index) => (&text[..index], Some(&text[index + 1..])),
+            None => (text, None),
+        };
+
+        let mut release = [0; 4];
+        for (index, part) in release_text.split('.').enumerate() {
+            if index == release.len()
+                || part.is_empty()
+                || !part.chars().all(|c| c.is_ascii_digit())
+            {
+                return None;
+            }
+            release[index] = part.parse().ok()?;
+        }
+
+        let pre_release = match pre_release_text {
+            Some(pre_release_text) => {
+                let labels = pre_release_text
+                    .split('.')
+                    .map(str::to_lowercase)
+                    .collect::<Vec<_>>();
+                if labels.iter().any(|label| label.is_empty()) {
+                    return None;
+                }
+                labels
+            }
+            None => Vec::new(),
+        };
+
+        Some(Self {
+            release,
+            pre_release,
+        })
+    }
+
+    pub fn major(&self) -> u64 {
+        self.release[0]
+    }
+
+    pub fn minor(&self) -> u64 {
+        self.release[1]
+    }
+
+    pub fn is_pre_release(&self) -> bool {
+        !self.pre_release.is_empty()
+    }
+}
+
+impl Ord for PackageVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.release.cmp(&other.release).then_with(|| {
+            match (self.is_pre_release(), other.is_pre_release()) {
+                (false, false) => Ordering::Equal,
+                (false, true) => Ordering::Greater,
+                (true, false) => Ordering::Less,
+                (true, true) => cmp_pre_release(&self.pre_release, &other.pre_release),
+            }
+        })
+    }
+}
+
+impl PartialOrd for PackageVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The versions with which a package is referenced and by which projects.
+#[derive(Debug)]
+pub struct PackageUsage {
+    pub name: String,
+    pub versions: BTreeMap<String, Vec<PathBuf>>,
+}
+
+/// Groups the versioned package references of the projects by package. The map is keyed by the
+/// lowercase package name because package ids are case insensitive.
+pub fn package_usages(projects: &[Project]) -> BTreeMap<String, PackageUsage> {
+    let mut packages: BTreeMap<String, PackageUsage> = BTreeMap::new();
+    for project in projects.iter() {
+        for reference in project.package_references.iter() {
+            if let Some(version) = &reference.version {
+                packages
+                    .entry(reference.name.to_lowercase())
+                    .or_insert_with(|| PackageUsage {
+                        name: reference.name.clone(),
+                        versions: BTreeMap::new(),
+                    })
+                    .versions
+                    .entry(version.clone())
+                    .or_default()
+                    .push(project.path.clone());
+            }
+        }
+    }
+    packages
+}
+
+/// Returns the id and version of the package directory that the path goes through, which is
+/// `packages\<id>.<version>` for packages restored for packages.config.
+pub fn packages_folder_package(path: &str) -> Option<(&str, &str)> {
+    let components = path.split(|c| c == '/' || c == '\\').collect::<Vec<_>>();
+    components.windows(2).find_map(|window| {
+        if !window[0].eq_ignore_ascii_case("packages") {
+            return None;
+        }
+        // Ids can contain dots and digits, the version is the first suffix that looks like one.
+        window[1]
+            .match_indices('.')
+            .map(|(index, _)| (&window[1][..index], &window[1][index + 1..]))
+            .find(|(_, version)| is_version(version))
+    })
+}
+
+fn is_version(value: &str) -> bool {
+    let release = value.split('-').next().unwrap();
+    let parts = release.split('.').collect::<Vec<_>>();
+    (2..=4).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Orders version strings by their parsed version, unparsable versions go last.
+pub fn cmp_version_str(a: &str, b: &str) -> Ordering {
+    match (PackageVersion::parse(a), PackageVersion::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+fn cmp_pre_release(a: &[String], b: &[String]) -> Ordering {
+    for (a, b) in a.iter().zip(b.iter()) {
+        // Numeric labels have lower precedence than alphanumeric ones.
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{packages_folder_package, PackageVersion};
+
+    fn v(text: &str) -> PackageVersion {
+        PackageVersion::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parse_rejects_non_plain_versions() {
+        assert_eq!(PackageVersion::parse("1.*"), None);
+        assert_eq!(PackageVersion::parse("[1.0,2.0)"), None);
+        assert_eq!(PackageVersion::parse("$(NewtonsoftVersion)"), None);
+        assert_eq!(PackageVersion::parse("1.2.3.4.5"), None);
+    }
+
+    #[test]
+    fn versions_are_ordered_like_nuget() {
+        assert!(v("1.10.0") > v("1.9.0"));
+        assert!(v("1.0") == v("1.0.0.0"));
+        assert!(v("2.0.0") > v("2.0.0-rc.1"));
+        assert!(v("2.0.0-rc.2") > v("2.0.0-rc.1"));
+        assert!(v("2.0.0-rc.10") > v("2.0.0-rc.9"));
+        assert!(v("2.0.0-beta") > v("2.0.0-alpha.5"));
+        assert!(v("1.0.0+abc") == v("1.0.0"));
+    }
+
+    #[test]
+    fn packages_folder_package_splits_id_and_version() {
+        assert_eq!(
+            packages_folder_package(
+                r"..\packages\Newtonsoft.Json.12.0.3\lib\net45\Newtonsoft.Json.dll"
+            ),
+            Some(("Newtonsoft.Json", "12.0.3"))
+        );
+        assert_eq!(
+            packages_folder_package("../packages/Contoso.2D.1.0.0-beta.1/lib/net45/Contoso.2D.dll"),
+            Some(("Contoso.2D", "1.0.0-beta.1"))
+        );
+        assert_eq!(packages_folder_package(r"..\lib\Legacy.dll"), None);
+    }
+}
@@ -50,6 +50,54 @@ pub fn relative_path(abs_src_dir: &Path, abs_dst_path: &Path) -> PathBuf {
         .collect()
 }
 
+/// Returns the path as an extended-length path, which starts with `\\?\`, so that file system
+/// operations on Windows are not limited to MAX_PATH characters. Relative paths and paths on other
+/// platforms are returned unchanged.
+pub fn to_extended_length(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) => prefix,
+        _ => return path.to_owned(),
+    };
+    // Extended-length paths are passed to the file system as is, so `..` has to be resolved.
+    let rest = components.as_path().simplify();
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(disk) => PathBuf::from(format!(r"\\?\{}:\", disk as char)),
+        Prefix::UNC(server, share) => {
+            let mut extended = PathBuf::from(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(share);
+            extended
+        }
+        _ => return path.to_owned(),
+    };
+    extended.push(rest.strip_prefix(Component::RootDir).unwrap_or(&rest));
+    extended
+}
+
+/// Returns the path without the `\\?\` prefix of extended-length paths, for messages, for paths
+/// written to files and for other programs like git that do not understand it.
+pub fn strip_extended_length(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) => prefix,
+        _ => return path.to_owned(),
+    };
+    let mut stripped = match prefix.kind() {
+        Prefix::VerbatimDisk(disk) => PathBuf::from(format!(r"{}:\", disk as char)),
+        Prefix::VerbatimUNC(server, share) => {
+            let mut stripped = PathBuf::from(r"\\");
+            stripped.push(server);
+            stripped.push(share);
+            stripped
+        }
+        _ => return path.to_owned(),
+    };
+    let rest = components.as_path();
+    stripped.push(rest.strip_prefix(Component::RootDir).unwrap_or(rest));
+    stripped
+}
+
 fn join_components<'a, I: Iterator<Item = Component<'a>>>(result: &mut PathBuf, components: I) {
     for current in components {
         match current {
@@ -68,6 +116,21 @@ fn join_components<'a, I: Iterator<Item = Component<'a>>>(result: &mut PathBuf,
     }
 }
 
+/// Converts a path as written in a project file, which usually uses backslashes, to a native path.
+pub fn from_msbuild_path(value: &str) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(value)
+    } else {
+        PathBuf::from(value.replace('\\', "/"))
+    }
+}
+
+/// Converts a native path to the form written in project files, which uses backslashes. Parts
+/// that are not valid unicode are replaced with the replacement character.
+pub fn to_msbuild_path(path: &Path) -> String {
+    path.to_string_lossy().replace('/', "\\")
+}
+
 pub trait PathExt {
     fn simplify(&self) -> PathBuf;
 
@@ -145,4 +208,26 @@ mod tests {
             relative_path(Path::new(r"C:\Users"), Path::new(r"\\?\C:\Users\Mick")),
         );
     }
+
+    #[test]
+    #[cfg(windows)]
+    fn extended_length_paths_round_trip() {
+        assert_eq!(
+            to_extended_length(Path::new(r"C:\Users\Mick\..\Rust\README.md")),
+            Path::new(r"\\?\C:\Users\Rust\README.md"),
+        );
+        assert_eq!(
+            to_extended_length(Path::new(r"\\server\share\src")),
+            Path::new(r"\\?\UNC\server\share\src"),
+        );
+        assert_eq!(
+            strip_extended_length(Path::new(r"\\?\UNC\server\share\src")),
+            Path::new(r"\\server\share\src"),
+        );
+        assert_eq!(
+            strip_extended_length(Path::new(r"\\?\C:\Users")),
+            Path::new(r"C:\Users"),
+        );
+        assert_eq!(to_extended_length(Path::new(r"src\A")), Path::new(r"src\A"));
+    }
 }
@@ -0,0 +1,736 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use xmltree::{Element, XMLNode};
+
+use crate::{
+    csproj::Error,
+    diff::unified_diff,
+    encoding::{self, Encoding, LineEnding},
+    error,
+    output::{self, status},
+    path_extensions::{relative_path, strip_extended_length, to_extended_length, PathExt},
+    tools::{self, Tool},
+    writable,
+    xml_extensions::{
+        child_elements, element_to_string, parse_xml_str, write_file_atomic, xml_to_string,
+    },
+};
+
+/// Whether mutating commands only print the changes they would make.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Applies the options given on the command line, must be called before any effects finish.
+pub fn init(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// The changes a mutating command would make, in the order in which they have to be applied.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    pub command: String,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "kebab-case")]
+pub enum Operation {
+    GitMove {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    GitAdd {
+        path: PathBuf,
+    },
+    WriteFile {
+        path: PathBuf,
+        /// Hash of the contents the plan was made against, absent if the file did not exist.
+        original_hash: Option<String>,
+        /// The changes to the xml for review, the contents are what actually gets written.
+        edits: Vec<Edit>,
+        contents: String,
+    },
+    RemoveFile {
+        path: PathBuf,
+        original_hash: Option<String>,
+    },
+    /// Commits the paths touched by the operations before it, other staged changes are left
+    /// alone.
+    GitCommit {
+        message: String,
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// A single change in an xml file. The location is an XPath-like expression such as
+/// `/Project/ItemGroup[2]/ProjectReference[@Include='..\A\A.csproj']/@Include`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub location: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl Plan {
+    pub fn read(path: &Path) -> error::Result<Self> {
+        let file = std::fs::File::open(path).map_err(|e| {
+            error::Error::user(format!("Failed to open plan {}: {}", path.display(), e))
+        })?;
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| {
+            error::Error::user(format!("Failed to read plan {}: {}", path.display(), e))
+        })
+    }
+
+    /// Applies the operations. The whole plan is replayed without touching the disk first so that
+    /// a plan that no longer matches the files is rejected before anything has been changed.
+    pub fn apply(&self) -> error::Result<()> {
+        let mut replay = Effects::record(&self.command);
+        let mut targets = Vec::new();
+        for operation in self.operations.iter() {
+            targets.extend(replay.disk_targets(operation));
+            replay.perform(operation)?;
+        }
+        writable::ensure_writable(&targets);
+
+        let mut effects = Effects::execute();
+        for operation in self.operations.iter() {
+            effects.perform(operation)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the operations as a unified diff with paths relative to the current directory,
+    /// replaying them without touching the disk.
+    pub fn print_diff(&self) {
+        let current_dir = std::env::current_dir().unwrap();
+        let display = |path: &Path| {
+            relative_path(&current_dir, path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+
+        let mut replay = Effects::record(&self.command);
+        for operation in self.operations.iter() {
+            match operation {
+                Operation::GitMove { from, to } => {
+                    println!("rename from {}", display(from));
+                    println!("rename to {}", display(to));
+                }
+                Operation::GitAdd { .. } | Operation::GitCommit { .. } => {}
+                Operation::WriteFile { path, contents, .. } => {
+                    let old = replay.read_to_string(path).ok();
+                    let name = display(path);
+                    print!(
+                        "{}",
+                        unified_diff(
+                            &format!("a/{}", name),
+                            &format!("b/{}", name),
+                            old.as_deref(),
+                            Some(contents),
+                        )
+                    );
+                }
+                Operation::RemoveFile { path, .. } => {
+                    let old = replay.read_to_string(path).ok();
+                    let name = display(path);
+                    print!(
+                        "{}",
+                        unified_diff(
+                            &format!("a/{}", name),
+                            &format!("b/{}", name),
+                            old.as_deref(),
+                            None,
+                        )
+                    );
+                }
+            }
+            if let Err(e) = replay.perform(operation) {
+                e.exit();
+            }
+        }
+    }
+}
+
+/// Performs the file system and git operations of a mutating command, or records them into a
+/// plan. While recording, reads observe the recorded writes and moves so that a command computes
+/// the same changes as it would while executing.
+#[derive(Debug)]
+pub struct Effects {
+    plan: Option<Plan>,
+    plan_path: Option<PathBuf>,
+    /// The recorded contents of files, None for removed files.
+    files: HashMap<PathBuf, Option<String>>,
+    /// The recorded moves in order.
+    moves: Vec<(PathBuf, PathBuf)>,
+    /// The paths that were moved, added, written or removed, which `git_commit` commits.
+    touched: Vec<PathBuf>,
+    /// The targets of moves, a target that is moved again was only passed through.
+    move_targets: Vec<PathBuf>,
+}
+
+impl Effects {
+    /// Records the operations into a plan at the given path, or applies them on `finish` if there
+    /// is none so that files that can not be written are found before anything is changed.
+    pub fn new(command: &str, plan_path: Option<&Path>) -> Self {
+        Self {
+            plan_path: plan_path.map(Path::to_owned),
+            ..Self::record(command)
+        }
+    }
+
+    fn execute() -> Self {
+        Self {
+            plan: None,
+            plan_path: None,
+            files: HashMap::new(),
+            moves: Vec::new(),
+            touched: Vec::new(),
+            move_targets: Vec::new(),
+        }
+    }
+
+    fn record(command: &str) -> Self {
+        Self {
+            plan: Some(Plan {
+                command: command.to_owned(),
+                operations: Vec::new(),
+            }),
+            ..Self::execute()
+        }
+    }
+
+    /// Writes the plan, or applies the recorded operations if there is no plan path. With
+    /// `--dry-run` the operations are printed as a diff instead. With `--json` the applied or
+    /// intended operations are printed as json.
+    pub fn finish(self) {
+        if DRY_RUN.load(Ordering::Relaxed) {
+            match &self.plan {
+                Some(plan) if output::is_json() => output::print_json(plan),
+                Some(plan) => plan.print_diff(),
+                None => {}
+            }
+            return;
+        }
+        if let (Some(plan), None) = (&self.plan, &self.plan_path) {
+            if let Err(e) = plan.apply() {
+                e.exit();
+            }
+            if output::is_json() {
+                output::print_json(plan);
+            }
+        }
+        if let (Some(plan), Some(plan_path)) = (self.plan, self.plan_path) {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&plan_path).unwrap());
+            serde_json::to_writer_pretty(&mut file, &plan).unwrap();
+
+            let current_dir = std::env::current_dir().unwrap();
+            status!(
+                "Wrote plan with {} operations to {}",
+                plan.operations.len(),
+                relative_path(&current_dir, &plan_path.simplified_absolute().unwrap()).display()
+            );
+        }
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        let path = absolute(path);
+        match self.files.get(&path) {
+            Some(Some(contents)) => Ok(contents.clone()),
+            Some(None) => Err(std::io::ErrorKind::NotFound.into()),
+            None => encoding::read_to_string(&self.disk_path(&path)),
+        }
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        let path = absolute(path);
+        match self.files.get(&path) {
+            Some(contents) => contents.is_some(),
+            None => self.disk_path(&path).is_file(),
+        }
+    }
+
+    pub fn write_file(
+        &mut self,
+        path: &Path,
+        contents: String,
+        edits: Vec<Edit>,
+    ) -> Result<(), Error> {
+        let path = absolute(path);
+        self.touch(&path);
+        let contents = match self.line_ending(&path) {
+            Some(line_ending) => line_ending.apply(&contents),
+            None => contents,
+        };
+        if self.plan.is_none() {
+            // Keeps the byte order mark and encoding of the file that is replaced.
+            let bytes = Encoding::of_file(&path).encode(&contents);
+            return write_file_atomic(&path, &bytes);
+        }
+
+        let original_hash = self.content_hash(&path);
+        self.files.insert(path.clone(), Some(contents.clone()));
+        self.push(Operation::WriteFile {
+            path,
+            original_hash,
+            edits,
+            contents,
+        });
+        Ok(())
+    }
+
+    /// The line ending that `write_file` writes the file with. Files keep their line endings,
+    /// unless .gitattributes says otherwise.
+    pub fn line_ending(&self, path: &Path) -> Option<LineEnding> {
+        LineEnding::of_attributes(path).or_else(|| {
+            self.read_to_string(path)
+                .ok()
+                .and_then(|old| LineEnding::detect(&old))
+        })
+    }
+
+    pub fn remove_file(&mut self, path: &Path) -> Result<(), Error> {
+        let path = absolute(path);
+        self.touch(&path);
+        if self.plan.is_none() {
+            debug!("removing {}", path.display());
+            return Ok(std::fs::remove_file(to_extended_length(&path))?);
+        }
+
+        let original_hash = self.content_hash(&path);
+        self.files.insert(path.clone(), None);
+        self.push(Operation::RemoveFile {
+            path,
+            original_hash,
+        });
+        Ok(())
+    }
+
+    pub fn git_move(&mut self, from: &Path, to: &Path) -> error::Result<()> {
+        let (from, to) = (absolute(from), absolute(to));
+        if self.move_targets.contains(&from) {
+            self.touched.retain(|touched| touched != &from);
+        } else {
+            self.touch(&from);
+        }
+        self.touch(&to);
+        self.move_targets.push(to.clone());
+        if self.plan.is_none() {
+            let mut mv = tools::command(Tool::Git);
+            mv.arg("mv")
+                .arg(strip_extended_length(&from))
+                .arg(strip_extended_length(&to));
+            return run_git(mv);
+        }
+
+        self.files = self
+            .files
+            .drain()
+            .map(|(path, contents)| match path.strip_prefix(&from) {
+                Ok(rest) => (to.join(rest), contents),
+                Err(_) => (path, contents),
+            })
+            .collect();
+        self.moves.push((from.clone(), to.clone()));
+        self.push(Operation::GitMove { from, to });
+        Ok(())
+    }
+
+    pub fn git_add(&mut self, path: &Path) -> error::Result<()> {
+        let path = absolute(path);
+        self.touch(&path);
+        if self.plan.is_none() {
+            let mut add = tools::command(Tool::Git);
+            add.arg("add").arg(strip_extended_length(&path));
+            return run_git(add);
+        }
+
+        self.push(Operation::GitAdd { path });
+        Ok(())
+    }
+
+    /// Commits the paths touched so far with the message.
+    pub fn git_commit(&mut self, message: &str) -> error::Result<()> {
+        let paths = std::mem::take(&mut self.touched);
+        self.commit(message, paths)
+    }
+
+    fn commit(&mut self, message: &str, paths: Vec<PathBuf>) -> error::Result<()> {
+        if self.plan.is_none() {
+            let mut commit = tools::command(Tool::Git);
+            commit
+                .args([OsStr::new("commit"), OsStr::new("-m"), OsStr::new(message)])
+                .arg("--")
+                .args(paths.iter().map(|path| strip_extended_length(path)));
+            return run_git(commit);
+        }
+
+        self.push(Operation::GitCommit {
+            message: message.to_owned(),
+            paths,
+        });
+        Ok(())
+    }
+
+    fn touch(&mut self, path: &Path) {
+        if !self.touched.iter().any(|touched| touched == path) {
+            self.touched.push(path.to_owned());
+        }
+    }
+
+    pub fn read_xml_file(&self, path: &Path) -> Result<Element, Error> {
+        parse_xml_str(&self.read_to_string(path)?)
+    }
+
+    pub fn write_xml_file(&mut self, path: &Path, root: &Element) -> Result<(), Error> {
+        self.write_file(path, xml_to_string(root), Vec::new())
+    }
+
+    /// Reads and parses the file and writes it back if the transform returns an element.
+    pub fn transform_xml_file<F>(&mut self, path: &Path, transform: F) -> Result<(), Error>
+    where
+        F: FnOnce(Element) -> Option<Element>,
+    {
+        let root = self.read_xml_file(path)?;
+        let old_root = self.plan.as_ref().map(|_| root.clone());
+
+        if let Some(root) = transform(root) {
+            let edits = old_root
+                .map(|old_root| diff_elements(&old_root, &root))
+                .unwrap_or_default();
+            self.write_file(path, xml_to_string(&root), edits)?;
+        }
+
+        Ok(())
+    }
+
+    fn perform(&mut self, operation: &Operation) -> error::Result<()> {
+        match operation {
+            Operation::GitMove { from, to } => self.git_move(from, to),
+            Operation::GitAdd { path } => self.git_add(path),
+            Operation::GitCommit { message, paths } => self.commit(message, paths.clone()),
+            Operation::WriteFile {
+                path,
+                original_hash,
+                edits,
+                contents,
+            } => {
+                self.verify(path, original_hash)?;
+                self.write_file(path, contents.clone(), edits.clone())
+                    .map_err(|e| {
+                        error::Error::internal(format!("Failed to write {}: {}", path.display(), e))
+                    })
+            }
+            Operation::RemoveFile {
+                path,
+                original_hash,
+            } => {
+                self.verify(path, original_hash)?;
+                self.remove_file(path).map_err(|e| {
+                    error::Error::internal(format!("Failed to remove {}: {}", path.display(), e))
+                })
+            }
+        }
+    }
+
+    fn verify(&self, path: &Path, original_hash: &Option<String>) -> error::Result<()> {
+        if &self.content_hash(path) != original_hash {
+            return Err(error::Error::user(format!(
+                "{} has changed since the plan was made, create a new plan",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn content_hash(&self, path: &Path) -> Option<String> {
+        let contents = self.read_to_string(path).ok()?;
+        let mut hasher = siphasher::sip::SipHasher::new();
+        hasher.write(contents.as_bytes());
+        Some(format!("{:016x}", hasher.finish()))
+    }
+
+    /// The files on disk that an operation changes, moved directories contribute their files.
+    fn disk_targets(&self, operation: &Operation) -> Vec<PathBuf> {
+        match operation {
+            Operation::GitMove { from, .. } => {
+                let from = self.disk_path(&absolute(from));
+                if from.is_dir() {
+                    ignore::WalkBuilder::new(&from)
+                        .build()
+                        .filter_map(Result::ok)
+                        .filter(|entry| entry.file_type().map_or(false, |t| t.is_file()))
+                        .map(|entry| entry.into_path())
+                        .collect()
+                } else {
+                    vec![from]
+                }
+            }
+            Operation::GitAdd { .. } | Operation::GitCommit { .. } => Vec::new(),
+            Operation::WriteFile { path, .. } | Operation::RemoveFile { path, .. } => {
+                match self.files.get(&absolute(path)) {
+                    // Written earlier in the plan, the first write has been checked.
+                    Some(_) => Vec::new(),
+                    None => vec![self.disk_path(&absolute(path))],
+                }
+            }
+        }
+    }
+
+    /// Maps a path to where the file is on disk before the recorded moves.
+    fn disk_path(&self, path: &Path) -> PathBuf {
+        let mut path = path.to_owned();
+        for (from, to) in self.moves.iter().rev() {
+            if let Ok(rest) = path.strip_prefix(to) {
+                path = from.join(rest);
+            }
+        }
+        path
+    }
+
+    fn push(&mut self, operation: Operation) {
+        self.plan.as_mut().unwrap().operations.push(operation);
+    }
+}
+
+/// Runs the git command, a failing git is a user error because it explains what is wrong with the
+/// repository.
+fn run_git(mut command: std::process::Command) -> error::Result<()> {
+    debug!("{:?}", &command);
+    let output = command
+        .output()
+        .map_err(|e| error::Error::internal(format!("Failed to run {:?}: {}", command, e)))?;
+    if !output.status.success() {
+        return Err(error::Error::user(format!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    path.simplified_absolute().unwrap()
+}
+
+/// Lists the differences between two versions of an xml document.
+pub fn diff_elements(old: &Element, new: &Element) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    diff_element(&format!("/{}", old.name), old, new, &mut edits);
+    edits
+}
+
+fn diff_element(location: &str, old: &Element, new: &Element, edits: &mut Vec<Edit>) {
+    for (name, old_value) in old.attributes.iter() {
+        let new_value = new.attributes.get(name);
+        if new_value != Some(old_value) {
+            edits.push(Edit {
+                location: format!("{}/@{}", location, name),
+                old: Some(old_value.clone()),
+                new: new_value.cloned(),
+            });
+        }
+    }
+    for (name, new_value) in new.attributes.iter() {
+        if !old.attributes.contains_key(name) {
+            edits.push(Edit {
+                location: format!("{}/@{}", location, name),
+                old: None,
+                new: Some(new_value.clone()),
+            });
+        }
+    }
+
+    let (old_text, new_text) = (direct_text(old), direct_text(new));
+    if old_text != new_text {
+        edits.push(Edit {
+            location: format!("{}/text()", location),
+            old: old_text,
+            new: new_text,
+        });
+    }
+
+    let old_children = child_elements(old).collect::<Vec<_>>();
+    let new_children = child_elements(new).collect::<Vec<_>>();
+    let old_steps = location_steps(&old_children);
+    let new_steps = location_steps(&new_children);
+
+    let mut old_index = 0;
+    let mut new_index = 0;
+    for (matched_old, matched_new) in align(&old_children, &new_children)
+        .into_iter()
+        .chain(std::iter::once((old_children.len(), new_children.len())))
+    {
+        // Elements between the aligned pairs that have the same name are treated as modified,
+        // this turns a rewritten Include into a single attribute edit.
+        let mut old_gap = (old_index..matched_old).peekable();
+        let mut new_gap = (new_index..matched_new).peekable();
+        loop {
+            match (old_gap.peek().copied(), new_gap.peek().copied()) {
+                (Some(o), Some(n)) if old_children[o].name == new_children[n].name => {
+                    let location = format!("{}/{}", location, old_steps[o]);
+                    diff_element(&location, old_children[o], new_children[n], edits);
+                    old_gap.next();
+                    new_gap.next();
+                }
+                (Some(o), _) => {
+                    edits.push(Edit {
+                        location: format!("{}/{}", location, old_steps[o]),
+                        old: Some(element_to_string(old_children[o])),
+                        new: None,
+                    });
+                    old_gap.next();
+                }
+                (None, Some(n)) => {
+                    edits.push(Edit {
+                        location: format!("{}/{}", location, new_steps[n]),
+                        old: None,
+                        new: Some(element_to_string(new_children[n])),
+                    });
+                    new_gap.next();
+                }
+                (None, None) => break,
+            }
+        }
+
+        if matched_old < old_children.len() {
+            diff_element(
+                &format!("{}/{}", location, old_steps[matched_old]),
+                old_children[matched_old],
+                new_children[matched_new],
+                edits,
+            );
+        }
+        old_index = matched_old + 1;
+        new_index = matched_new + 1;
+    }
+}
+
+fn direct_text(element: &Element) -> Option<String> {
+    let text = element
+        .children
+        .iter()
+        .filter_map(|node| match node {
+            XMLNode::Text(text) | XMLNode::CData(text) => Some(text.trim()),
+            _ => None,
+        })
+        .collect::<String>();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Items are identified by their Include attribute, other elements by their position among the
+/// siblings with the same name.
+fn location_steps(elements: &[&Element]) -> Vec<String> {
+    let mut counts = HashMap::<&str, usize>::new();
+    elements
+        .iter()
+        .map(|element| match element.attributes.get("Include") {
+            Some(include) => format!("{}[@Include='{}']", element.name, include),
+            None => {
+                let count = counts.entry(&element.name).or_default();
+                *count += 1;
+                format!("{}[{}]", element.name, count)
+            }
+        })
+        .collect()
+}
+
+/// Pairs up the children that are the same element in both versions using the longest common
+/// subsequence of their names and Include attributes.
+fn align(old: &[&Element], new: &[&Element]) -> Vec<(usize, usize)> {
+    let same = |a: &Element, b: &Element| {
+        a.name == b.name && a.attributes.get("Include") == b.attributes.get("Include")
+    };
+
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if same(old[i], new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if same(old[i], new[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_extensions::{element, project};
+
+    fn project_with_packages(references: &[(&str, &str)]) -> Element {
+        let references = references
+            .iter()
+            .map(|(include, version)| {
+                element(
+                    "PackageReference",
+                    &[("Include", include), ("Version", version)],
+                    vec![],
+                )
+            })
+            .collect();
+        project(vec![
+            element("PropertyGroup", &[], vec![]),
+            element("ItemGroup", &[], references),
+        ])
+    }
+
+    #[test]
+    fn diff_elements_locates_attribute_edits() {
+        let old = project_with_packages(&[("A", "1.0.0"), ("B", "1.0.0")]);
+        let new = project_with_packages(&[("A", "1.0.0"), ("B", "2.0.0")]);
+
+        assert_eq!(
+            diff_elements(&old, &new),
+            vec![Edit {
+                location: String::from(
+                    "/Project/ItemGroup[1]/PackageReference[@Include='B']/@Version"
+                ),
+                old: Some(String::from("1.0.0")),
+                new: Some(String::from("2.0.0")),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_elements_pairs_renamed_items() {
+        let old = project_with_packages(&[("A", "1.0.0"), ("B", "1.0.0")]);
+        let new = project_with_packages(&[("A", "1.0.0"), ("C", "1.0.0")]);
+
+        assert_eq!(
+            diff_elements(&old, &new),
+            vec![Edit {
+                location: String::from(
+                    "/Project/ItemGroup[1]/PackageReference[@Include='B']/@Include"
+                ),
+                old: Some(String::from("B")),
+                new: Some(String::from("C")),
+            }]
+        );
+    }
+}
@@ -1,12 +1,16 @@
 use crate::csproj::*;
+use crate::output::status;
+use crate::plan::Effects;
 use crate::xml_extensions::*;
 use crate::*;
+use std::path::{Path, PathBuf};
 
 pub struct PostMigrationCleanupOptions {
     pub search_path: PathBuf,
     pub glob_matcher: globset::GlobMatcher,
     pub follow_project_references: bool,
     pub clean_app_configs: bool,
+    pub plan_json: Option<PathBuf>,
 }
 
 pub fn post_migration_cleanup(options: &PostMigrationCleanupOptions) {
@@ -15,22 +19,24 @@ pub fn post_migration_cleanup(options: &PostMigrationCleanupOptions) {
         ref glob_matcher,
         follow_project_references,
         clean_app_configs,
+        ref plan_json,
     } = *options;
 
     // TODO(mickvangelderen): This is inefficient, we're parsing the projects twice.
     let projects = parse_projects(search_path, glob_matcher, follow_project_references);
 
     let cwd = std::fs::canonicalize(std::env::current_dir().unwrap()).unwrap();
+    let mut effects = Effects::new(cli::CMD_POST_MIGRATION_CLEANUP, plan_json.as_deref());
 
     for project_path in projects.into_iter().filter_map(|(path, project)| {
         let rel_path = path_extensions::relative_path(cwd.as_path(), path.as_path());
         match project {
             Ok(project) => {
                 if project.is_sdk {
-                    println!("Migrating sdk project {}", rel_path.display());
+                    status!("Migrating sdk project {}", rel_path.display());
                     Some(path)
                 } else {
-                    println!("Skipping non-sdk project {}", rel_path.display());
+                    status!("Skipping non-sdk project {}", rel_path.display());
                     None
                 }
             }
@@ -47,8 +53,8 @@ pub fn post_migration_cleanup(options: &PostMigrationCleanupOptions) {
                 let app_config_path = app_config_path.unwrap();
                 let rel_path =
                     path_extensions::relative_path(cwd.as_path(), app_config_path.as_path());
-                println!("Cleaning up app config {}", rel_path.display());
-                if let Err(e) = cleanup_app_config(app_config_path.as_path()) {
+                status!("Cleaning up app config {}", rel_path.display());
+                if let Err(e) = cleanup_app_config(&mut effects, app_config_path.as_path()) {
                     panic!(
                         "Failed to clean up app config {}: {}",
                         app_config_path.display(),
@@ -58,10 +64,12 @@ pub fn post_migration_cleanup(options: &PostMigrationCleanupOptions) {
             }
         }
 
-        if let Err(e) = cleanup_csproj(project_path.as_path()) {
+        if let Err(e) = cleanup_csproj(&mut effects, project_path.as_path()) {
             panic!("Failed to migrate {}: {}", project_path.display(), e)
         }
     }
+
+    effects.finish();
 }
 
 fn find_app_configs(
@@ -96,10 +104,10 @@ fn find_app_configs(
     ))
 }
 
-fn cleanup_app_config(path: &Path) -> Result<(), Error> {
+fn cleanup_app_config(effects: &mut Effects, path: &Path) -> Result<(), Error> {
     let mut should_remove = false;
 
-    transform_xml_file(path, |mut root| {
+    effects.transform_xml_file(path, |mut root| {
         process_tree(&mut root, app_config_element_transform);
 
         if all_children_whitespace(&root) {
@@ -111,7 +119,7 @@ fn cleanup_app_config(path: &Path) -> Result<(), Error> {
     })?;
 
     if should_remove {
-        std::fs::remove_file(path)?;
+        effects.remove_file(path)?;
     }
 
     Ok(())
@@ -152,8 +160,8 @@ fn app_config_element_transform(element: &mut xmltree::Element) {
         .collect();
 }
 
-fn cleanup_csproj(project_path: &Path) -> Result<(), Error> {
-    transform_xml_file(project_path, |mut root| {
+fn cleanup_csproj(effects: &mut Effects, project_path: &Path) -> Result<(), Error> {
+    effects.transform_xml_file(project_path, |mut root| {
         process_tree(&mut root, csproj_element_transform);
         Some(root)
     })
@@ -0,0 +1,222 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cli, list,
+    path_extensions::{relative_path, PathExt},
+    utils::find_git_root,
+};
+
+const CMD_PROPOSE_LAYOUT: &'static str = "propose-layout";
+
+/// The cluster of projects that are used by several clusters and belong to none of them.
+const SHARED: &'static str = "Shared";
+
+#[derive(Debug)]
+pub struct ProposeLayoutCommand {
+    search_path: PathBuf,
+}
+
+/// A project as far as the proposal is concerned.
+#[derive(Debug)]
+struct Node<'a> {
+    path: &'a Path,
+    name: Cow<'a, str>,
+    is_test: bool,
+    /// The indices of the projects this project references or is referenced by.
+    neighbours: BTreeSet<usize>,
+}
+
+impl ProposeLayoutCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_PROPOSE_LAYOUT)
+            .about("Propose a directory per project based on the project names and references, printed as mv commands to review")
+            .arg(cli::arg_search())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_PROPOSE_LAYOUT)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+
+        let search_path = self.search_path.simplified_absolute().unwrap();
+        let root = find_git_root(&search_path)
+            .unwrap_or(&search_path)
+            .to_owned();
+
+        let indices = projects
+            .iter()
+            .enumerate()
+            .map(|(index, project)| (project.path.as_path(), index))
+            .collect::<BTreeMap<_, _>>();
+        let mut nodes = projects
+            .iter()
+            .map(|project| Node {
+                path: &project.path,
+                name: project.path.file_stem().unwrap().to_string_lossy(),
+                is_test: project.is_test_project(),
+                neighbours: BTreeSet::new(),
+            })
+            .collect::<Vec<_>>();
+        for (index, project) in projects.iter().enumerate() {
+            for reference in project.project_references.iter() {
+                if let Some(&reference) = indices.get(reference.as_path()) {
+                    nodes[index].neighbours.insert(reference);
+                    nodes[reference].neighbours.insert(index);
+                }
+            }
+        }
+
+        let clusters = clusters(&nodes);
+        let current_dir = std::env::current_dir().unwrap();
+        let display = |path: &Path| relative_path(&current_dir, path).display().to_string();
+
+        let mut moves = BTreeMap::<&str, Vec<(PathBuf, PathBuf)>>::new();
+        for (node, cluster) in nodes.iter().zip(clusters.iter()) {
+            let from = node.path.parent().unwrap().to_owned();
+            let to = root
+                .join(if node.is_test { "tests" } else { "src" })
+                .join(cluster)
+                .join(node.name.as_ref());
+            if from != to {
+                moves.entry(cluster).or_default().push((from, to));
+            }
+        }
+
+        println!(
+            "# {} of {} projects would move",
+            moves.values().map(Vec::len).sum::<usize>(),
+            nodes.len()
+        );
+        for (cluster, moves) in moves.iter() {
+            println!();
+            println!("# {}", cluster);
+            for (from, to) in moves.iter() {
+                println!("csprojtool mv \"{}\" \"{}\"", display(from), display(to));
+            }
+        }
+    }
+}
+
+/// Assigns a cluster to every project. Projects are clustered by the first part of their name
+/// after the prefix all projects share, so `Acme.Billing.Api` and `Acme.Billing.Tests` end up in
+/// `Billing`. Projects whose name does not tell join the cluster of the projects they are
+/// connected to, or `Shared` if those are in different clusters.
+fn clusters(nodes: &[Node]) -> Vec<String> {
+    let segments = nodes
+        .iter()
+        .map(|node| node.name.split('.').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let prefix_len = if nodes.len() > 1 {
+        common_prefix_len(&segments)
+    } else {
+        0
+    };
+
+    let mut clusters = segments
+        .iter()
+        .map(|segments| {
+            let rest = &segments[prefix_len.min(segments.len())..];
+            if rest.len() > 1 {
+                Some(rest[0].to_owned())
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Every round can settle the neighbours of the projects settled in the previous round.
+    loop {
+        let settled = (0..nodes.len())
+            .filter(|&index| clusters[index].is_none())
+            .filter_map(|index| {
+                let neighbours = nodes[index]
+                    .neighbours
+                    .iter()
+                    .filter_map(|&neighbour| clusters[neighbour].as_deref())
+                    .collect::<BTreeSet<_>>();
+                match neighbours.len() {
+                    0 => None,
+                    1 => Some((index, neighbours.into_iter().next().unwrap().to_owned())),
+                    _ => Some((index, SHARED.to_owned())),
+                }
+            })
+            .collect::<Vec<_>>();
+        if settled.is_empty() {
+            break;
+        }
+        for (index, cluster) in settled {
+            clusters[index] = Some(cluster);
+        }
+    }
+
+    clusters
+        .into_iter()
+        .zip(nodes.iter())
+        .map(|(cluster, node)| cluster.unwrap_or_else(|| node.name.to_string()))
+        .collect()
+}
+
+/// The number of leading name segments all names share, leaving at least one segment of each.
+fn common_prefix_len(segments: &[Vec<&str>]) -> usize {
+    let shortest = segments.iter().map(Vec::len).min().unwrap_or(0);
+    (0..shortest.saturating_sub(1))
+        .take_while(|&index| {
+            segments
+                .iter()
+                .all(|name| name[index].eq_ignore_ascii_case(segments[0][index]))
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node<'a>(name: &'a str, neighbours: &[usize]) -> Node<'a> {
+        Node {
+            path: Path::new("/"),
+            name: Cow::Borrowed(name),
+            is_test: name.ends_with(".Tests"),
+            neighbours: neighbours.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn clusters_use_names_and_fall_back_to_references() {
+        let nodes = vec![
+            node("Acme.Billing.Api", &[2, 4]),
+            node("Acme.Billing.Tests", &[]),
+            node("Acme.Invoicing", &[0]),
+            node("Acme.Shipping.Api", &[4]),
+            node("Acme.Logging", &[0, 3]),
+            node("Acme.Tools", &[]),
+        ];
+        assert_eq!(
+            clusters(&nodes),
+            vec![
+                "Billing",
+                "Billing",
+                "Billing",
+                "Shipping",
+                "Shared",
+                "Acme.Tools"
+            ]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use std::path::PathBuf;
+
+use log::warn;
+use serde::Serialize;
+
+use crate::encoding;
+use crate::{cli, list, output, path_extensions::relative_path};
+
+const ARG_EXPRESSION: &'static str = "expression";
+const CMD_QUERY: &'static str = "query";
+
+/// A location path like `//PropertyGroup/CopyLocalLockFileAssemblies`,
+/// `//PackageReference[@Include='Serilog']/@Version` or `/Project/@Sdk`. Names are compared case
+/// insensitively like MSBuild does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expression {
+    steps: Vec<Step>,
+    /// What to select from the elements found by the steps.
+    select: Select,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// Whether the step matches any descendant (`//`) rather than only children (`/`).
+    descendants: bool,
+    /// The element name, None for `*`.
+    name: Option<String>,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Predicate {
+    /// `[@Condition]`
+    HasAttribute(String),
+    /// `[@Include='Serilog']`
+    Attribute(String, String),
+    /// `[OutputType='Exe']`
+    Child(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Select {
+    Elements,
+    /// `/@Version`
+    Attribute(String),
+    /// `/text()`
+    Text,
+}
+
+#[derive(Debug, Serialize)]
+struct Match {
+    path: PathBuf,
+    line: u32,
+    value: String,
+}
+
+#[derive(Debug)]
+pub struct QueryCommand {
+    expression: Expression,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+}
+
+impl QueryCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_QUERY)
+            .about("Print the values an XPath-like expression selects in the projects, exits with 1 if nothing matches")
+            .arg(
+                Arg::with_name(ARG_EXPRESSION)
+                    .value_name("EXPRESSION")
+                    .help("A path like //PropertyGroup/CopyLocalLockFileAssemblies or //PackageReference[@Include='Serilog']/@Version")
+                    .required(true)
+                    .takes_value(true)
+                    .validator(|value| Expression::parse(&value).map(|_| ())),
+            )
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_QUERY)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            expression: Expression::parse(matches.value_of(ARG_EXPRESSION).unwrap()).unwrap(),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+        }
+    }
+
+    /// Prints a line per match with the project, the line in the project and the value, or the
+    /// matches as json.
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut matches = Vec::new();
+        for project in projects.iter() {
+            let contents = match encoding::read_to_string(&project.path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", project.path.display(), e);
+                    continue;
+                }
+            };
+            let document = match roxmltree::Document::parse(&contents) {
+                Ok(document) => document,
+                Err(e) => {
+                    warn!("Failed to parse {}: {}", project.path.display(), e);
+                    continue;
+                }
+            };
+
+            let path = relative_path(&current_dir, &project.path);
+            matches.extend(
+                self.expression
+                    .evaluate(&document)
+                    .into_iter()
+                    .map(|(line, value)| Match {
+                        path: path.clone(),
+                        line,
+                        value,
+                    }),
+            );
+        }
+
+        if output::is_json() {
+            output::print_json(&matches);
+        } else {
+            for m in matches.iter() {
+                println!("{}:{}: {}", m.path.display(), m.line, m.value);
+            }
+        }
+
+        if matches.is_empty() {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+    }
+}
+
+impl Expression {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let expression = expression.trim();
+        if !expression.starts_with('/') {
+            return Err(format!(
+                "expected the expression to start with / or //: {}",
+                expression
+            ));
+        }
+
+        let mut steps = Vec::new();
+        let mut select = Select::Elements;
+        let mut rest = expression;
+        while !rest.is_empty() {
+            if select != Select::Elements {
+                return Err(format!(
+                    "expected @attribute and text() to be the last step: {}",
+                    expression
+                ));
+            }
+
+            let descendants = rest.starts_with("//");
+            rest = rest.trim_start_matches('/');
+            let end = step_end(rest);
+            let step = &rest[..end];
+            rest = &rest[end..];
+
+            if let Some(attribute) = step.strip_prefix('@') {
+                select = Select::Attribute(parse_name(attribute, expression)?);
+            } else if step == "text()" {
+                select = Select::Text;
+            } else {
+                steps.push(parse_step(step, descendants, expression)?);
+            }
+        }
+
+        Ok(Self { steps, select })
+    }
+
+    /// Returns the line and the value of each match.
+    fn evaluate(&self, document: &roxmltree::Document) -> Vec<(u32, String)> {
+        let mut nodes = vec![document.root()];
+        for step in self.steps.iter() {
+            nodes = nodes
+                .iter()
+                .flat_map(|node| {
+                    let candidates: Box<dyn Iterator<Item = roxmltree::Node>> = if step.descendants
+                    {
+                        Box::new(node.descendants().skip(1))
+                    } else {
+                        Box::new(node.children())
+                    };
+                    candidates.filter(|candidate| step.matches(candidate))
+                })
+                .collect();
+            // Nested descendant steps reach the same node from several ancestors.
+            nodes.sort_by_key(|node| node.range().start);
+            nodes.dedup_by_key(|node| node.range().start);
+        }
+
+        let line = |node: &roxmltree::Node| document.text_pos_at(node.range().start).row;
+        nodes
+            .iter()
+            .filter_map(|node| {
+                let value = match &self.select {
+                    Select::Elements => describe(document, node),
+                    Select::Attribute(name) => attribute(node, name)?.to_owned(),
+                    Select::Text => node.text()?.trim().to_owned(),
+                };
+                Some((line(node), value))
+            })
+            .collect()
+    }
+}
+
+impl Step {
+    fn matches(&self, node: &roxmltree::Node) -> bool {
+        node.is_element()
+            && self.name.as_ref().map_or(true, |name| {
+                node.tag_name().name().eq_ignore_ascii_case(name)
+            })
+            && self.predicates.iter().all(|predicate| match predicate {
+                Predicate::HasAttribute(name) => attribute(node, name).is_some(),
+                Predicate::Attribute(name, value) => {
+                    attribute(node, name).map_or(false, |actual| actual.trim() == value)
+                }
+                Predicate::Child(name, value) => node.children().any(|child| {
+                    child.is_element()
+                        && child.tag_name().name().eq_ignore_ascii_case(name)
+                        && child.text().map_or(false, |text| text.trim() == value)
+                }),
+            })
+    }
+}
+
+fn attribute<'a>(node: &roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .iter()
+        .find(|attribute| attribute.name().eq_ignore_ascii_case(name))
+        .map(|attribute| attribute.value())
+}
+
+/// Describes a selected element by its text if it only contains text and by its start tag as
+/// written otherwise.
+fn describe(document: &roxmltree::Document, node: &roxmltree::Node) -> String {
+    if node.children().all(|child| child.is_text()) {
+        if let Some(text) = node.text().map(str::trim).filter(|text| !text.is_empty()) {
+            return text.to_owned();
+        }
+    }
+    let source = &document.input_text()[node.range()];
+    let start_tag = match source.find('>') {
+        Some(end) => &source[..=end],
+        None => source,
+    };
+    start_tag.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Finds the end of the step at the start of the path, slashes inside predicates do not count.
+fn step_end(path: &str) -> usize {
+    let mut in_quotes = None;
+    for (index, c) in path.char_indices() {
+        match (in_quotes, c) {
+            (None, '\'') | (None, '"') => in_quotes = Some(c),
+            (Some(quote), _) if c == quote => in_quotes = None,
+            (None, '/') => return index,
+            _ => {}
+        }
+    }
+    path.len()
+}
+
+fn parse_step(step: &str, descendants: bool, expression: &str) -> Result<Step, String> {
+    let (name, mut predicates) = match step.find('[') {
+        Some(start) => (&step[..start], &step[start..]),
+        None => (step, ""),
+    };
+
+    let mut parsed = Vec::new();
+    while !predicates.is_empty() {
+        let end = predicates
+            .find(']')
+            .filter(|_| predicates.starts_with('['))
+            .ok_or_else(|| format!("expected [predicate] after {}: {}", name, expression))?;
+        parsed.push(parse_predicate(&predicates[1..end], expression)?);
+        predicates = &predicates[end + 1..];
+    }
+
+    Ok(Step {
+        descendants,
+        name: match name {
+            "*" => None,
+            name => Some(parse_name(name, expression)?),
+        },
+        predicates: parsed,
+    })
+}
+
+fn parse_predicate(predicate: &str, expression: &str) -> Result<Predicate, String> {
+    let (left, value) = match predicate.split_once('=') {
+        Some((left, right)) => {
+            let right = right.trim();
+            let unquoted = right
+                .strip_prefix('\'')
+                .and_then(|right| right.strip_suffix('\''))
+                .or_else(|| {
+                    right
+                        .strip_prefix('"')
+                        .and_then(|right| right.strip_suffix('"'))
+                })
+                .ok_or_else(|| {
+                    format!("expected a quoted value in [{}]: {}", predicate, expression)
+                })?;
+            (left.trim(), Some(unquoted.to_owned()))
+        }
+        None => (predicate.trim(), None),
+    };
+
+    match (left.strip_prefix('@'), value) {
+        (Some(name), None) => Ok(Predicate::HasAttribute(parse_name(name, expression)?)),
+        (Some(name), Some(value)) => Ok(Predicate::Attribute(parse_name(name, expression)?, value)),
+        (None, Some(value)) => Ok(Predicate::Child(parse_name(left, expression)?, value)),
+        (None, None) => Err(format!(
+            "expected [@attribute], [@attribute='value'] or [Child='value']: {}",
+            expression
+        )),
+    }
+}
+
+fn parse_name(name: &str, expression: &str) -> Result<String, String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        Ok(name.to_owned())
+    } else {
+        Err(format!(
+            "expected a name instead of '{}': {}",
+            name, expression
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = "<Project Sdk=\"Microsoft.NET.Sdk\">\n  <PropertyGroup>\n    <OutputType>Exe</OutputType>\n    <CopyLocalLockFileAssemblies>true</CopyLocalLockFileAssemblies>\n  </PropertyGroup>\n  <ItemGroup>\n    <PackageReference Include=\"Serilog\" Version=\"2.10.0\" />\n    <PackageReference Include=\"Polly\" Version=\"7.2.2\" />\n  </ItemGroup>\n</Project>\n";
+
+    fn query(expression: &str) -> Vec<(u32, String)> {
+        let document = roxmltree::Document::parse(PROJECT).unwrap();
+        Expression::parse(expression).unwrap().evaluate(&document)
+    }
+
+    #[test]
+    fn parse_reads_steps_predicates_and_the_selection() {
+        assert_eq!(
+            Expression::parse("//ItemGroup/PackageReference[@Include='Serilog']/@Version"),
+            Ok(Expression {
+                steps: vec![
+                    Step {
+                        descendants: true,
+                        name: Some(String::from("ItemGroup")),
+                        predicates: vec![],
+                    },
+                    Step {
+                        descendants: false,
+                        name: Some(String::from("PackageReference")),
+                        predicates: vec![Predicate::Attribute(
+                            String::from("Include"),
+                            String::from("Serilog")
+                        )],
+                    },
+                ],
+                select: Select::Attribute(String::from("Version")),
+            })
+        );
+        assert!(Expression::parse("PropertyGroup").is_err());
+        assert!(Expression::parse("//@Include/Version").is_err());
+        assert!(Expression::parse("//PackageReference[@Include=Serilog]").is_err());
+    }
+
+    #[test]
+    fn evaluate_selects_values_with_their_line() {
+        assert_eq!(
+            query("//copylocallockfileassemblies"),
+            vec![(4, String::from("true"))]
+        );
+        assert_eq!(
+            query("//PackageReference[@Include='Serilog']/@Version"),
+            vec![(7, String::from("2.10.0"))]
+        );
+        assert_eq!(
+            query("/Project/PropertyGroup[OutputType='Exe']"),
+            vec![(2, String::from("<PropertyGroup>"))]
+        );
+        assert_eq!(
+            query("/Project/@Sdk"),
+            vec![(1, String::from("Microsoft.NET.Sdk"))]
+        );
+        assert!(query("//PropertyGroup[@Condition]").is_empty());
+    }
+}
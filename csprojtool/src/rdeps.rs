@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    internals_visible_to::{friends, Friend},
+    list, output,
+    path_extensions::relative_path,
+};
+
+const ARG_DEPTH: &'static str = "depth";
+const ARG_FORMAT: &'static str = "format";
+const ARG_INTERNALS_VISIBLE_TO: &'static str = "internals-visible-to";
+const ARG_PROJECT: &'static str = "project";
+const CMD_RDEPS: &'static str = "rdeps";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct RdepsCommand {
+    project_path: PathBuf,
+    depth: Option<usize>,
+    internals_visible_to: bool,
+    format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    project: PathBuf,
+    dependents: Vec<Dependent>,
+    /// The assemblies that the project makes its internals visible to, with
+    /// `--internals-visible-to`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    internals_visible_to: Option<Vec<Friend>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Dependent {
+    path: PathBuf,
+    /// The number of references between the dependent and the project, 1 for direct references.
+    depth: usize,
+    /// The references of the dependent through which it depends on the project.
+    via: Vec<PathBuf>,
+}
+
+impl RdepsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_RDEPS)
+            .about("List the projects that transitively reference a project or import a file")
+            .arg(
+                Arg::with_name(ARG_PROJECT)
+                    .value_name("PROJECT_PATH")
+                    .help("The project or imported file, like Directory.Build.props, whose dependents to list")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_DEPTH)
+                    .long("depth")
+                    .value_name("N")
+                    .help("Only list dependents up to N references away")
+                    .takes_value(true)
+                    .validator(|value| {
+                        value
+                            .parse::<usize>()
+                            .map(|_| ())
+                            .map_err(|e| e.to_string())
+                    }),
+            )
+            .arg(
+                Arg::with_name(ARG_INTERNALS_VISIBLE_TO)
+                    .long("internals-visible-to")
+                    .help("Also list the assemblies that the project makes its internals visible to, these depend on it without a project reference")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_RDEPS)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            project_path: PathBuf::from(matches.value_of_os(ARG_PROJECT).unwrap()),
+            depth: matches
+                .value_of(ARG_DEPTH)
+                .map(|depth| depth.parse().unwrap()),
+            internals_visible_to: matches.is_present(ARG_INTERNALS_VISIBLE_TO),
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) {
+        let project_path = std::fs::canonicalize(&self.project_path)
+            .unwrap_or_else(|e| panic!("Failed to find {}: {}", self.project_path.display(), e));
+
+        // Listing with the project as search path and following incoming references yields
+        // exactly the project and its dependents, for an imported file its importers and theirs.
+        let projects = list::list(list::Options {
+            search_path: &project_path,
+            follow_incoming_project_references: true,
+            follow_outgoing_project_references: false,
+        });
+
+        let dependents =
+            dependents(
+                &project_path,
+                projects
+                    .iter()
+                    .map(|project| {
+                        (
+                            project.path.as_path(),
+                            project.project_references.as_slice(),
+                        )
+                    })
+                    .chain(
+                        projects
+                            .iter()
+                            .map(|project| (project.path.as_path(), project.imports.as_slice())),
+                    )
+                    .chain(projects.iter().map(|project| {
+                        (project.path.as_path(), project.shared_projects.as_slice())
+                    })),
+                self.depth,
+            );
+
+        let current_dir = std::env::current_dir().unwrap();
+        let friends = if self.internals_visible_to {
+            let friends = projects
+                .iter()
+                .find(|project| project.path == project_path)
+                .map(|project| friends(project, &projects))
+                .unwrap_or_default();
+            Some(
+                friends
+                    .into_iter()
+                    .map(|friend| Friend {
+                        project: friend
+                            .project
+                            .map(|path| relative_path(&current_dir, &path)),
+                        ..friend
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            None
+        };
+
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                for dependent in dependents.iter() {
+                    println!("{}", relative_path(&current_dir, &dependent.path).display());
+                }
+                for friend in friends.iter().flatten() {
+                    match &friend.project {
+                        Some(path) => println!("{} (InternalsVisibleTo)", path.display()),
+                        None => println!("{} (InternalsVisibleTo)", friend.assembly),
+                    }
+                }
+            }
+            Format::Json => {
+                let report = Report {
+                    project: relative_path(&current_dir, &project_path),
+                    dependents: dependents
+                        .into_iter()
+                        .map(|dependent| Dependent {
+                            path: relative_path(&current_dir, &dependent.path),
+                            depth: dependent.depth,
+                            via: dependent
+                                .via
+                                .iter()
+                                .map(|path| relative_path(&current_dir, path))
+                                .collect(),
+                        })
+                        .collect(),
+                    internals_visible_to: friends,
+                };
+                serde_json::to_writer_pretty(std::io::stdout(), &report).unwrap();
+                println!();
+            }
+        }
+    }
+}
+
+/// Returns the projects that reference the project directly or indirectly, ordered by their
+/// depth and path. Dependents further away than the maximum depth are omitted. A path may appear
+/// more than once, for example once with its project references and once with its imports.
+fn dependents<'a>(
+    project_path: &Path,
+    projects: impl Iterator<Item = (&'a Path, &'a [PathBuf])>,
+    max_depth: Option<usize>,
+) -> Vec<Dependent> {
+    let mut referenced_by = BTreeMap::<&Path, Vec<&Path>>::new();
+    for (path, references) in projects {
+        for reference in references.iter() {
+            referenced_by
+                .entry(reference.as_path())
+                .or_default()
+                .push(path);
+        }
+    }
+
+    let mut found = BTreeMap::<&Path, (usize, Vec<&Path>)>::new();
+    let mut queue = VecDeque::new();
+    queue.push_back((project_path, 0));
+
+    while let Some((path, depth)) = queue.pop_front() {
+        if max_depth.map_or(false, |max_depth| depth >= max_depth) {
+            continue;
+        }
+        for &dependent in referenced_by.get(path).into_iter().flatten() {
+            if dependent == project_path {
+                continue;
+            }
+            match found.get_mut(dependent) {
+                Some((dependent_depth, via)) => {
+                    if *dependent_depth == depth + 1 {
+                        via.push(path);
+                    }
+                }
+                None => {
+                    found.insert(dependent, (depth + 1, vec![path]));
+                    queue.push_back((dependent, depth + 1));
+                }
+            }
+        }
+    }
+
+    let mut dependents = found
+        .into_iter()
+        .map(|(path, (depth, via))| Dependent {
+            path: path.to_owned(),
+            depth,
+            via: via.into_iter().map(Path::to_owned).collect(),
+        })
+        .collect::<Vec<_>>();
+    dependents.sort_by(|a, b| (a.depth, &a.path).cmp(&(b.depth, &b.path)));
+    dependents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependents_respects_the_maximum_depth() {
+        let references = [
+            (PathBuf::from("/A.csproj"), vec![]),
+            (PathBuf::from("/B.csproj"), vec![PathBuf::from("/A.csproj")]),
+            (
+                PathBuf::from("/C.csproj"),
+                vec![PathBuf::from("/A.csproj"), PathBuf::from("/B.csproj")],
+            ),
+            (PathBuf::from("/D.csproj"), vec![PathBuf::from("/C.csproj")]),
+        ];
+        let projects = || {
+            references
+                .iter()
+                .map(|(path, references)| (path.as_path(), references.as_slice()))
+        };
+
+        let all = dependents(Path::new("/A.csproj"), projects(), None);
+        assert_eq!(
+            all.iter()
+                .map(|dependent| (dependent.path.to_str().unwrap(), dependent.depth))
+                .collect::<Vec<_>>(),
+            vec![("/B.csproj", 1), ("/C.csproj", 1), ("/D.csproj", 2)]
+        );
+
+        let direct = dependents(Path::new("/A.csproj"), projects(), Some(1));
+        assert_eq!(direct.len(), 2);
+    }
+
+    #[test]
+    fn dependents_of_an_imported_file_include_the_importers() {
+        let references = [
+            (PathBuf::from("/src/A.csproj"), vec![]),
+            (
+                PathBuf::from("/B.csproj"),
+                vec![PathBuf::from("/src/A.csproj")],
+            ),
+        ];
+        let imports = [(
+            PathBuf::from("/src/A.csproj"),
+            vec![PathBuf::from("/src/Directory.Build.props")],
+        )];
+
+        let all = dependents(
+            Path::new("/src/Directory.Build.props"),
+            references
+                .iter()
+                .chain(imports.iter())
+                .map(|(path, references)| (path.as_path(), references.as_slice())),
+            None,
+        );
+        assert_eq!(
+            all.iter()
+                .map(|dependent| (dependent.path.to_str().unwrap(), dependent.depth))
+                .collect::<Vec<_>>(),
+            vec![("/src/A.csproj", 1), ("/B.csproj", 2)]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::{list, output, path_extensions::relative_path};
+
+const ARG_FROM: &'static str = "from";
+const ARG_PRINT_PATH: &'static str = "print-path";
+const ARG_TO: &'static str = "to";
+const CMD_REACHES: &'static str = "reaches";
+
+#[derive(Debug)]
+pub struct ReachesCommand {
+    from: PathBuf,
+    to: PathBuf,
+    print_path: bool,
+}
+
+impl ReachesCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_REACHES)
+            .about(
+                "Exit with 0 if a project transitively references another project and 1 otherwise",
+            )
+            .arg(
+                Arg::with_name(ARG_FROM)
+                    .value_name("FROM_PROJECT_PATH")
+                    .help("The referencing project")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_TO)
+                    .value_name("TO_PROJECT_PATH")
+                    .help("The referenced project")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_PRINT_PATH)
+                    .long("print-path")
+                    .takes_value(false)
+                    .help("Print the shortest chain of references from one project to the other"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_REACHES)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            from: PathBuf::from(matches.value_of_os(ARG_FROM).unwrap()),
+            to: PathBuf::from(matches.value_of_os(ARG_TO).unwrap()),
+            print_path: matches.is_present(ARG_PRINT_PATH),
+        }
+    }
+
+    pub fn execute(&self) {
+        let canonicalize = |path: &Path| {
+            std::fs::canonicalize(path)
+                .unwrap_or_else(|e| panic!("Failed to find {}: {}", path.display(), e))
+        };
+        let from = canonicalize(&self.from);
+        let to = canonicalize(&self.to);
+
+        // Listing with the project as search path and following outgoing references yields
+        // exactly the project and everything it depends on.
+        let projects = list::list(list::Options {
+            search_path: &from,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: true,
+        });
+
+        let path = shortest_path(
+            &from,
+            &to,
+            projects.iter().map(|project| {
+                (
+                    project.path.as_path(),
+                    project.project_references.as_slice(),
+                )
+            }),
+        );
+
+        match path {
+            Some(path) => {
+                let current_dir = std::env::current_dir().unwrap();
+                let path = path
+                    .iter()
+                    .map(|project_path| relative_path(&current_dir, project_path));
+                if output::is_json() {
+                    output::print_json(&path.collect::<Vec<_>>());
+                } else if self.print_path {
+                    for project_path in path {
+                        println!("{}", project_path.display());
+                    }
+                }
+            }
+            None => std::process::exit(crate::error::EXIT_VALIDATION_FAILED),
+        }
+    }
+}
+
+/// Returns the shortest chain of references from one project to the other, starting with `from`
+/// and ending with `to`, or None if `from` does not depend on `to`.
+fn shortest_path<'a>(
+    from: &Path,
+    to: &Path,
+    projects: impl Iterator<Item = (&'a Path, &'a [PathBuf])>,
+) -> Option<Vec<PathBuf>> {
+    let references = projects.collect::<BTreeMap<_, _>>();
+
+    let mut previous = BTreeMap::<&Path, &Path>::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+
+    while let Some(path) = queue.pop_front() {
+        for reference in references.get(path).copied().unwrap_or_default() {
+            let reference = reference.as_path();
+            if reference == from || previous.contains_key(reference) {
+                continue;
+            }
+            previous.insert(reference, path);
+
+            if reference == to {
+                let mut chain = vec![to.to_owned()];
+                let mut current = to;
+                while let Some(&before) = previous.get(current) {
+                    chain.push(before.to_owned());
+                    current = before;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            queue.push_back(reference);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortest_path_follows_transitive_references() {
+        let references = [
+            (PathBuf::from("/A.csproj"), vec![PathBuf::from("/B.csproj")]),
+            (
+                PathBuf::from("/B.csproj"),
+                vec![PathBuf::from("/C.csproj"), PathBuf::from("/D.csproj")],
+            ),
+            (PathBuf::from("/C.csproj"), vec![PathBuf::from("/D.csproj")]),
+            (PathBuf::from("/D.csproj"), vec![]),
+        ];
+        let projects = || {
+            references
+                .iter()
+                .map(|(path, references)| (path.as_path(), references.as_slice()))
+        };
+
+        assert_eq!(
+            shortest_path(Path::new("/A.csproj"), Path::new("/D.csproj"), projects()),
+            Some(vec![
+                PathBuf::from("/A.csproj"),
+                PathBuf::from("/B.csproj"),
+                PathBuf::from("/D.csproj"),
+            ])
+        );
+        assert_eq!(
+            shortest_path(Path::new("/D.csproj"), Path::new("/A.csproj"), projects()),
+            None
+        );
+    }
+}
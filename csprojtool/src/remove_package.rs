@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use crate::output::status;
+use crate::{
+    cli, list, path_extensions::relative_path, plan::Effects, xml_extensions::remove_items,
+};
+
+const ARG_PACKAGE: &'static str = "package";
+const CMD_REMOVE_PACKAGE: &'static str = "remove-package";
+
+#[derive(Debug)]
+pub struct RemovePackageCommand {
+    package: String,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    follow_project_references: bool,
+    plan_json: Option<PathBuf>,
+}
+
+impl RemovePackageCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_REMOVE_PACKAGE)
+            .about("Remove a package reference from projects")
+            .arg(
+                Arg::with_name(ARG_PACKAGE)
+                    .value_name("PACKAGE")
+                    .help("The id of the package")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_REMOVE_PACKAGE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            package: matches.value_of(ARG_PACKAGE).unwrap().to_owned(),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            follow_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: self.follow_project_references,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_REMOVE_PACKAGE, self.plan_json.as_deref());
+
+        // The PackageVersion in Directory.Packages.props is kept because projects outside of the
+        // selection may still use it.
+        let mut removed_count = 0;
+        for project in projects.iter().filter(|project| {
+            project
+                .package_references
+                .iter()
+                .any(|reference| reference.name.eq_ignore_ascii_case(&self.package))
+        }) {
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    if remove_items(&mut root, "PackageReference", &self.package) {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+            status!(
+                "Removed {} from {}",
+                self.package,
+                relative_path(&current_dir, &project.path).display()
+            );
+            removed_count += 1;
+        }
+
+        if removed_count == 0 {
+            status!("No project references {}", self.package);
+        }
+
+        effects.finish();
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use crate::output::status;
+use crate::{
+    cli, list, path_extensions::relative_path, plan::Effects, xml_extensions::remove_property,
+};
+
+const ARG_NAME: &'static str = "name";
+const ARG_VALUE: &'static str = "value";
+const CMD_REMOVE_PROPERTY: &'static str = "rm-prop";
+
+#[derive(Debug)]
+pub struct RemovePropertyCommand {
+    name: String,
+    value: Option<String>,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+impl RemovePropertyCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_REMOVE_PROPERTY)
+            .about("Remove a property from projects, dropping PropertyGroups that become empty")
+            .arg(
+                Arg::with_name(ARG_NAME)
+                    .value_name("NAME")
+                    .help("The name of the property")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_VALUE)
+                    .long("value")
+                    .value_name("VALUE")
+                    .help("Only remove definitions with this value")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_REMOVE_PROPERTY)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            name: matches.value_of(ARG_NAME).unwrap().to_owned(),
+            value: matches.value_of(ARG_VALUE).map(str::to_owned),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_REMOVE_PROPERTY, self.plan_json.as_deref());
+
+        let mut removed_count = 0;
+        for project in projects.iter() {
+            let mut removed = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    removed = remove_property(&mut root, &self.name, self.value.as_deref());
+                    if removed {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if removed {
+                status!(
+                    "Removed {} from {}",
+                    self.name,
+                    relative_path(&current_dir, &project.path).display()
+                );
+                removed_count += 1;
+            }
+        }
+
+        if removed_count == 0 {
+            status!("No project sets {}", self.name);
+        }
+
+        effects.finish();
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    check,
+    csproj::Project,
+    list,
+    path_extensions::{relative_path, PathExt},
+    stats::COLORS,
+};
+
+const ARG_DOT: &'static str = "dot";
+const ARG_ROOTS_FILE: &'static str = "roots-file";
+const CMD_REPORT: &'static str = "report";
+
+#[derive(Debug)]
+pub struct ReportCommand {
+    roots_file: PathBuf,
+    dot_path: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Repository {
+    name: String,
+    root: PathBuf,
+    projects: Vec<Project>,
+}
+
+impl ReportCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_REPORT)
+            .about("Report on the projects of several repositories together")
+            .arg(
+                Arg::with_name(ARG_ROOTS_FILE)
+                    .long("roots-file")
+                    .value_name("ROOTS_PATH")
+                    .help("A file with the path to a repository on each line, relative paths are relative to the file")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_DOT)
+                    .long("dot")
+                    .value_name("DOT_PATH")
+                    .help("Also write the project reference graph with a cluster per repository")
+                    .takes_value(true),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_REPORT)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            roots_file: PathBuf::from(matches.value_of_os(ARG_ROOTS_FILE).unwrap()),
+            dot_path: matches.value_of_os(ARG_DOT).map(PathBuf::from),
+        }
+    }
+
+    pub fn execute(&self) {
+        let roots_file = self.roots_file.simplified_absolute().unwrap();
+        let contents = std::fs::read_to_string(&roots_file)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", roots_file.display(), e));
+        let repositories = parse_roots(&contents, roots_file.parent().unwrap())
+            .into_iter()
+            .map(|root| {
+                let root = std::fs::canonicalize(&root)
+                    .unwrap_or_else(|e| panic!("Failed to find {}: {}", root.display(), e));
+                Repository {
+                    name: root.file_name().map_or_else(
+                        || root.display().to_string(),
+                        |name| name.to_string_lossy().into_owned(),
+                    ),
+                    projects: list::list(list::Options {
+                        search_path: &root,
+                        follow_incoming_project_references: false,
+                        follow_outgoing_project_references: false,
+                    }),
+                    root,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        print_repositories(&repositories);
+        print_shared_names(&repositories);
+        print_package_versions(&repositories);
+        print_cross_references(&repositories);
+
+        if let Some(dot_path) = &self.dot_path {
+            let mut file = std::io::BufWriter::new(
+                std::fs::File::create(dot_path)
+                    .unwrap_or_else(|e| panic!("Failed to create {}: {}", dot_path.display(), e)),
+            );
+            write_dot(&mut file, &repositories).unwrap();
+        }
+    }
+}
+
+/// Reads the repository paths, one per line. Empty lines and lines starting with `#` are
+/// skipped.
+fn parse_roots(contents: &str, base_dir: &Path) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base_dir.join(line).simplify())
+        .collect()
+}
+
+fn print_repositories(repositories: &[Repository]) {
+    println!("Repositories:");
+    for repository in repositories.iter() {
+        let target_frameworks = repository
+            .projects
+            .iter()
+            .flat_map(|project| project.target_frameworks.iter())
+            .collect::<BTreeSet<_>>();
+        println!(
+            "{:>6} projects  {:>3} target frameworks  {:>4} findings  {}",
+            repository.projects.len(),
+            target_frameworks.len(),
+            check::check_all(&repository.projects).len(),
+            repository.name
+        );
+    }
+    println!();
+}
+
+/// Projects with the same name in different repositories usually are copies that drifted apart.
+fn print_shared_names(repositories: &[Repository]) {
+    let mut names = BTreeMap::<String, Vec<(&Repository, &Path)>>::new();
+    for repository in repositories.iter() {
+        for project in repository.projects.iter() {
+            let name = project.path.file_stem().unwrap().to_string_lossy();
+            names
+                .entry(name.to_lowercase())
+                .or_default()
+                .push((repository, &project.path));
+        }
+    }
+    names.retain(|_, projects| {
+        projects
+            .iter()
+            .map(|(repository, _)| &repository.root)
+            .collect::<BTreeSet<_>>()
+            .len()
+            > 1
+    });
+    if names.is_empty() {
+        return;
+    }
+
+    println!("Project names in more than one repository:");
+    for projects in names.values() {
+        for (repository, path) in projects.iter() {
+            println!(
+                "  {}: {}",
+                repository.name,
+                relative_path(&repository.root, path).display()
+            );
+        }
+        println!();
+    }
+}
+
+fn print_package_versions(repositories: &[Repository]) {
+    let differing = package_versions(repositories);
+    if differing.is_empty() {
+        return;
+    }
+
+    println!("Packages referenced at different versions across repositories:");
+    for (package, versions) in differing.iter() {
+        println!("  {}", package);
+        for (version, names) in versions.iter() {
+            println!(
+                "    {}: {}",
+                version,
+                names.iter().copied().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    println!();
+}
+
+/// Returns the packages with more than one version across the repositories, with the names of
+/// the repositories that use each version. Differences within a repository are left to
+/// `check-packages`.
+fn package_versions(repositories: &[Repository]) -> BTreeMap<&str, BTreeMap<&str, BTreeSet<&str>>> {
+    let mut packages = BTreeMap::<&str, BTreeMap<&str, BTreeSet<&str>>>::new();
+    for repository in repositories.iter() {
+        for package in repository
+            .projects
+            .iter()
+            .flat_map(|project| project.package_references.iter())
+        {
+            if let Some(version) = package.version.as_deref() {
+                packages
+                    .entry(&package.name)
+                    .or_default()
+                    .entry(version)
+                    .or_default()
+                    .insert(&repository.name);
+            }
+        }
+    }
+    packages.retain(|_, versions| {
+        versions.len() > 1 && versions.values().flatten().collect::<BTreeSet<_>>().len() > 1
+    });
+    packages
+}
+
+fn print_cross_references(repositories: &[Repository]) {
+    let references = cross_references(repositories);
+    if references.is_empty() {
+        return;
+    }
+
+    println!("Project references between repositories:");
+    for (from, to, project, reference) in references {
+        println!(
+            "  {}: {} -> {}: {}",
+            from.name,
+            relative_path(&from.root, &project.path).display(),
+            to.name,
+            relative_path(&to.root, reference).display()
+        );
+    }
+    println!();
+}
+
+type CrossReference<'a> = (&'a Repository, &'a Repository, &'a Project, &'a PathBuf);
+
+fn cross_references(repositories: &[Repository]) -> Vec<CrossReference<'_>> {
+    let repository_of = |path: &Path| {
+        repositories
+            .iter()
+            .find(|repository| path.starts_with(&repository.root))
+    };
+
+    repositories
+        .iter()
+        .flat_map(|from| {
+            from.projects.iter().flat_map(move |project| {
+                project
+                    .project_references
+                    .iter()
+                    .filter_map(move |reference| match repository_of(reference) {
+                        Some(to) if to.root != from.root => Some((from, to, project, reference)),
+                        _ => None,
+                    })
+            })
+        })
+        .collect()
+}
+
+fn write_dot<W: Write>(writer: &mut W, repositories: &[Repository]) -> std::io::Result<()> {
+    let nodes = repositories
+        .iter()
+        .flat_map(|repository| repository.projects.iter())
+        .enumerate()
+        .map(|(index, project)| (&project.path, index))
+        .collect::<BTreeMap<_, _>>();
+
+    writeln!(writer, "digraph {{")?;
+    writeln!(writer, "  rankdir = \"LR\";")?;
+
+    for (cluster, repository) in repositories.iter().enumerate() {
+        let color = COLORS[cluster % COLORS.len()];
+        writeln!(writer, "  subgraph cluster_{} {{", cluster)?;
+        writeln!(
+            writer,
+            "    label = \"{}\";",
+            repository.name.replace('"', "\\\"")
+        )?;
+        writeln!(writer, "    color = \"{}\";", color)?;
+        for project in repository.projects.iter() {
+            writeln!(
+                writer,
+                "    n{} [label = \"{}\", color = \"{}\"];",
+                nodes[&project.path],
+                project.path.file_stem().unwrap().to_string_lossy(),
+                color
+            )?;
+        }
+        writeln!(writer, "  }}")?;
+    }
+
+    for project in repositories
+        .iter()
+        .flat_map(|repository| repository.projects.iter())
+    {
+        for reference in project.project_references.iter() {
+            if let Some(to) = nodes.get(reference) {
+                writeln!(writer, "  n{} -> n{};", nodes[&project.path], to)?;
+            }
+        }
+    }
+
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csproj::PackageReference;
+
+    fn repository(name: &str, packages: &[(&str, &str)]) -> Repository {
+        Repository {
+            name: name.to_owned(),
+            root: PathBuf::from("/").join(name),
+            projects: vec![Project {
+                package_references: packages
+                    .iter()
+                    .map(|(name, version)| PackageReference {
+                        name: String::from(*name),
+                        version: Some(String::from(*version)),
+                    })
+                    .collect(),
+                ..Project::new(PathBuf::from("/").join(name).join("A.csproj"))
+            }],
+        }
+    }
+
+    #[test]
+    fn parse_roots_skips_comments_and_resolves_relative_paths() {
+        let contents = "# platform\n../api\n\n/src/web\n";
+        assert_eq!(
+            parse_roots(contents, Path::new("/src/tools")),
+            vec![PathBuf::from("/src/api"), PathBuf::from("/src/web")]
+        );
+    }
+
+    #[test]
+    fn package_versions_only_lists_differences_across_repositories() {
+        let repositories = vec![
+            repository("api", &[("Serilog", "2.10.0"), ("Polly", "7.2.2")]),
+            repository("web", &[("Serilog", "2.12.0"), ("Polly", "7.2.2")]),
+        ];
+
+        let differing = package_versions(&repositories);
+        assert_eq!(
+            differing.keys().copied().collect::<Vec<_>>(),
+            vec!["Serilog"]
+        );
+        assert_eq!(
+            differing["Serilog"]["2.10.0"]
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec!["api"]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::output::status;
+use crate::{
+    cli, list,
+    path_extensions::{relative_path, PathExt},
+    plan::Effects,
+    tfms::is_compatible,
+    xml_extensions::{get_target_frameworks, set_target_frameworks},
+};
+
+const ARG_FROM: &'static str = "from";
+const ARG_TO: &'static str = "to";
+const CMD_RETARGET: &'static str = "retarget";
+
+#[derive(Debug)]
+pub struct RetargetCommand {
+    from: String,
+    to: String,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+impl RetargetCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_RETARGET)
+            .about("Replace a target framework in the TargetFramework(s) of projects")
+            .arg(
+                Arg::with_name(ARG_FROM)
+                    .long("from")
+                    .value_name("TFM")
+                    .help("The target framework to replace, like net6.0")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_TO)
+                    .long("to")
+                    .value_name("TFM")
+                    .help("The target framework to replace it with, like net8.0")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob().alias("filter"))
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_RETARGET)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            from: matches.value_of(ARG_FROM).unwrap().to_owned(),
+            to: matches.value_of(ARG_TO).unwrap().to_owned(),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        // Dependents and dependencies outside of the selection are included to report the
+        // references that the new target framework breaks.
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: true,
+            follow_outgoing_project_references: true,
+        });
+
+        let search_path = self.search_path.simplified_absolute().unwrap();
+        let mut selected = projects
+            .iter()
+            .filter(|project| project.path.starts_with(&search_path))
+            .cloned()
+            .collect::<Vec<_>>();
+        list::retain_glob_matches(&mut selected, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_RETARGET, self.plan_json.as_deref());
+        let mut retargeted = BTreeSet::new();
+
+        for project in selected.iter() {
+            if !project
+                .target_frameworks
+                .iter()
+                .any(|target_framework| target_framework.eq_ignore_ascii_case(&self.from))
+            {
+                continue;
+            }
+
+            let mut edited = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    let target_frameworks = replace_target_framework(
+                        &get_target_frameworks(&root)?,
+                        &self.from,
+                        &self.to,
+                    )?;
+                    edited = set_target_frameworks(&mut root, &target_frameworks);
+
+                    if edited {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if edited {
+                status!(
+                    "Retargeted {} from {} to {}",
+                    relative_path(&current_dir, &project.path).display(),
+                    self.from,
+                    self.to
+                );
+                retargeted.insert(project.path.clone());
+            }
+        }
+
+        effects.finish();
+
+        let target_frameworks = projects
+            .iter()
+            .map(|project| {
+                let mut target_frameworks = project.target_frameworks.clone();
+                if retargeted.contains(&project.path) {
+                    for target_framework in target_frameworks.iter_mut() {
+                        if target_framework.eq_ignore_ascii_case(&self.from) {
+                            *target_framework = self.to.clone();
+                        }
+                    }
+                }
+                (&project.path, target_frameworks)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        for project in projects.iter() {
+            for dependency_path in project.project_references.iter() {
+                if !retargeted.contains(&project.path) && !retargeted.contains(dependency_path) {
+                    continue;
+                }
+                let dependency_frameworks = match target_frameworks.get(dependency_path) {
+                    Some(target_frameworks) if !target_frameworks.is_empty() => target_frameworks,
+                    _ => continue,
+                };
+
+                for target_framework in target_frameworks[&project.path].iter() {
+                    if !dependency_frameworks
+                        .iter()
+                        .any(|dependency| is_compatible(dependency, target_framework))
+                    {
+                        status!(
+                            "{} ({}) references {} which targets {}",
+                            relative_path(&current_dir, &project.path).display(),
+                            target_framework,
+                            relative_path(&current_dir, dependency_path).display(),
+                            dependency_frameworks.join(";")
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Replaces the target framework in the target frameworks of a project, returning None if it does
+/// not occur. The other frameworks are kept and the new framework is not listed twice.
+fn replace_target_framework(
+    target_frameworks: &[String],
+    from: &str,
+    to: &str,
+) -> Option<Vec<String>> {
+    if !target_frameworks
+        .iter()
+        .any(|target_framework| target_framework.eq_ignore_ascii_case(from))
+    {
+        return None;
+    }
+
+    let mut replaced = Vec::<String>::with_capacity(target_frameworks.len());
+    for target_framework in target_frameworks.iter() {
+        let target_framework = if target_framework.eq_ignore_ascii_case(from) {
+            to
+        } else {
+            target_framework
+        };
+        if !replaced
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(target_framework))
+        {
+            replaced.push(target_framework.to_owned());
+        }
+    }
+    Some(replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target_frameworks(value: &str) -> Vec<String> {
+        value.split(';').map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn replace_target_framework_works() {
+        assert_eq!(
+            replace_target_framework(&target_frameworks("NET6.0"), "net6.0", "net8.0"),
+            Some(target_frameworks("net8.0"))
+        );
+        assert_eq!(
+            replace_target_framework(&target_frameworks("net48;net6.0"), "net6.0", "net8.0"),
+            Some(target_frameworks("net48;net8.0"))
+        );
+        assert_eq!(
+            replace_target_framework(&target_frameworks("net6.0;net8.0"), "net6.0", "net8.0"),
+            Some(target_frameworks("net8.0"))
+        );
+        assert_eq!(
+            replace_target_framework(&target_frameworks("net7.0"), "net6.0", "net8.0"),
+            None
+        );
+    }
+}
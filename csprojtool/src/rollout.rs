@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::output::{self, status};
+use crate::{
+    cli,
+    csproj::{implicit_imports, Project},
+    evaluation::Evaluator,
+    list,
+    path_extensions::relative_path,
+    plan::Effects,
+    xml_extensions::{remove_property, set_property},
+};
+
+const ARG_STATUS: &'static str = "status";
+const CMD_ROLLOUT: &'static str = "rollout";
+const CMD_NULLABLE: &'static str = "nullable";
+
+const NULLABLE: &'static str = "Nullable";
+
+#[derive(Debug)]
+pub enum RolloutCommand {
+    Nullable(NullableCommand),
+}
+
+#[derive(Debug)]
+pub struct NullableCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    status_only: bool,
+    plan_json: Option<PathBuf>,
+}
+
+/// How far a project is with nullable reference types, by the value of its Nullable property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NullableState {
+    Enabled,
+    /// `warnings` or `annotations`, the compiler only does half of the work.
+    WarningsOnly,
+    Disabled,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Progress {
+    enabled: usize,
+    warnings_only: usize,
+    disabled: usize,
+}
+
+impl RolloutCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::{Arg, SubCommand};
+
+        SubCommand::with_name(CMD_ROLLOUT)
+            .about("Enable a language feature project by project and track the progress")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(CMD_NULLABLE)
+                    .about("Set Nullable to enable in projects that do not inherit it and summarize how many projects have it enabled")
+                    .arg(cli::arg_search())
+                    .arg(cli::arg_glob())
+                    .arg(
+                        Arg::with_name(ARG_STATUS)
+                            .long("status")
+                            .help("Only print the summary without changing projects")
+                            .takes_value(false),
+                    )
+                    .arg(cli::arg_plan_json()),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_ROLLOUT)?;
+
+        match matches.subcommand() {
+            (CMD_NULLABLE, Some(matches)) => Some(Self::Nullable(NullableCommand {
+                search_path: cli::get_search_path(matches),
+                glob_matcher: cli::get_glob_matcher(matches),
+                status_only: matches.is_present(ARG_STATUS),
+                plan_json: cli::get_plan_json(matches),
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn execute(&self) {
+        match self {
+            Self::Nullable(command) => command.execute(),
+        }
+    }
+}
+
+impl NullableCommand {
+    fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        let mut selected = projects.clone();
+        list::retain_glob_matches(&mut selected, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut enabled = HashSet::new();
+        if !self.status_only {
+            let mut effects = Effects::new(CMD_NULLABLE, self.plan_json.as_deref());
+            for project in selected.iter() {
+                let rel_path = relative_path(&current_dir, &project.path);
+                let values = NullableValues::of(project);
+                if nullable_state(values.effective()) == NullableState::Enabled {
+                    continue;
+                }
+                if let Some(value) = values.targets.as_deref() {
+                    warn!(
+                        "Skipping {}, the targets it imports set Nullable to {}",
+                        rel_path.display(),
+                        value
+                    );
+                    continue;
+                }
+                let inherited = nullable_state(values.props.as_deref()) == NullableState::Enabled;
+
+                // A project that opts out of what its imports enable only has to stop opting out.
+                let mut changed = false;
+                effects
+                    .transform_xml_file(&project.path, |mut root| {
+                        changed = if inherited {
+                            remove_property(&mut root, NULLABLE, None)
+                        } else {
+                            set_property(&mut root, NULLABLE, "enable")
+                        };
+                        if changed {
+                            Some(root)
+                        } else {
+                            None
+                        }
+                    })
+                    .unwrap();
+
+                if changed {
+                    if inherited {
+                        status!(
+                            "Removed Nullable from {}, its imports enable it",
+                            rel_path.display()
+                        );
+                    } else {
+                        status!("Enabled Nullable in {}", rel_path.display());
+                    }
+                    enabled.insert(project.path.clone());
+                }
+            }
+            effects.finish();
+        }
+
+        let progress = progress(projects.iter().map(|project| {
+            if enabled.contains(&project.path) {
+                NullableState::Enabled
+            } else {
+                nullable_state(NullableValues::of(project).effective())
+            }
+        }));
+
+        if output::is_json() {
+            output::print_json(&progress);
+        } else {
+            let total = progress.enabled + progress.warnings_only + progress.disabled;
+            println!(
+                "{} enabled, {} warnings only, {} disabled, {}% of {} projects enabled",
+                progress.enabled,
+                progress.warnings_only,
+                progress.disabled,
+                (progress.enabled * 100).checked_div(total).unwrap_or(0),
+                total
+            );
+        }
+    }
+}
+
+fn nullable_state(value: Option<&str>) -> NullableState {
+    match value.map(|value| value.trim().to_lowercase()).as_deref() {
+        Some("enable") => NullableState::Enabled,
+        Some("warnings") | Some("annotations") => NullableState::WarningsOnly,
+        _ => NullableState::Disabled,
+    }
+}
+
+fn progress(states: impl Iterator<Item = NullableState>) -> Progress {
+    let mut progress = Progress::default();
+    for state in states {
+        match state {
+            NullableState::Enabled => progress.enabled += 1,
+            NullableState::WarningsOnly => progress.warnings_only += 1,
+            NullableState::Disabled => progress.disabled += 1,
+        }
+    }
+    progress
+}
+
+/// Where a project gets its Nullable from, MSBuild evaluates the props before the project and
+/// the targets after it, so the last one that sets it wins.
+#[derive(Debug, Default)]
+struct NullableValues {
+    /// From the files imported before the project body, like Directory.Build.props. The imports
+    /// of the project itself are counted here too.
+    props: Option<String>,
+    own: Option<String>,
+    /// From the files imported after the project body, Directory.Build.targets and what it
+    /// imports.
+    targets: Option<String>,
+}
+
+impl NullableValues {
+    fn of(project: &Project) -> Self {
+        let (_, targets_path) = implicit_imports(&project.path);
+        let split = targets_path
+            .and_then(|targets_path| {
+                project
+                    .imports
+                    .iter()
+                    .position(|path| path == &targets_path)
+            })
+            .unwrap_or(project.imports.len());
+        let (props, targets) = project.imports.split_at(split);
+        let last_value = |paths: &[PathBuf]| paths.iter().rev().find_map(|path| own_value(path));
+
+        Self {
+            props: last_value(props),
+            own: own_value(&project.path),
+            targets: last_value(targets),
+        }
+    }
+
+    fn effective(&self) -> Option<&str> {
+        self.targets
+            .as_deref()
+            .or(self.own.as_deref())
+            .or(self.props.as_deref())
+    }
+}
+
+/// The value of Nullable that the file defines itself.
+fn own_value(path: &Path) -> Option<String> {
+    Evaluator::for_file(path)?
+        .property(NULLABLE)
+        .map(|value| value.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_counts_the_nullable_states() {
+        assert_eq!(
+            progress(
+                [
+                    Some("enable"),
+                    Some(" Enable "),
+                    Some("warnings"),
+                    Some("annotations"),
+                    Some("disable"),
+                    None,
+                ]
+                .iter()
+                .map(|value| nullable_state(*value))
+            ),
+            Progress {
+                enabled: 2,
+                warnings_only: 2,
+                disabled: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn targets_override_the_project_which_overrides_the_props() {
+        let values =
+            |props: Option<&str>, own: Option<&str>, targets: Option<&str>| NullableValues {
+                props: props.map(str::to_owned),
+                own: own.map(str::to_owned),
+                targets: targets.map(str::to_owned),
+            };
+
+        assert_eq!(
+            values(Some("enable"), Some("disable"), None).effective(),
+            Some("disable")
+        );
+        assert_eq!(
+            values(Some("enable"), Some("disable"), Some("warnings")).effective(),
+            Some("warnings")
+        );
+        assert_eq!(
+            values(Some("enable"), None, None).effective(),
+            Some("enable")
+        );
+        assert_eq!(values(None, None, None).effective(), None);
+    }
+}
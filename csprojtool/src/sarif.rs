@@ -0,0 +1,229 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{check::Finding, output, path_extensions::relative_path};
+
+const SCHEMA: &'static str = "https://json.schemastore.org/sarif-2.1.0.json";
+const VERSION: &'static str = "2.1.0";
+/// The base of the relative artifact URIs, code scanning resolves it to the repository root.
+const SRCROOT: &'static str = "%SRCROOT%";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// A problem found by a command, reported as a SARIF result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub rule: &'static str,
+    pub level: Level,
+    pub message: String,
+    pub locations: Vec<Location>,
+}
+
+/// A file and the line in it, starting at 1, where a problem is. Without a line the whole file
+/// is reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: Option<u32>,
+}
+
+impl From<&Finding> for Issue {
+    fn from(finding: &Finding) -> Self {
+        Self {
+            rule: finding.rule,
+            level: Level::Error,
+            message: finding.message.clone(),
+            locations: finding
+                .paths
+                .iter()
+                .map(|path| Location {
+                    path: path.clone(),
+                    line: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Log<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Run<'a> {
+    tool: Tool<'a>,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Tool<'a> {
+    driver: Driver<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct Driver<'a> {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<Rule<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Rule<'a> {
+    id: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'a str,
+    level: Level,
+    message: Message<'a>,
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Debug, Serialize)]
+struct Message<'a> {
+    text: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<Region>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    uri_base_id: &'static str,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u32,
+}
+
+/// Writes the issues to stdout as a SARIF log, with paths relative to the repository root.
+pub fn print(issues: &[Issue], root: &Path) {
+    output::print_json(&log(issues, root));
+}
+
+fn log<'a>(issues: &'a [Issue], root: &Path) -> Log<'a> {
+    let rules = issues
+        .iter()
+        .map(|issue| issue.rule)
+        .collect::<BTreeSet<_>>();
+    Log {
+        schema: SCHEMA,
+        version: VERSION,
+        runs: vec![Run {
+            tool: Tool {
+                driver: Driver {
+                    name: "csprojtool",
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules: rules.into_iter().map(|id| Rule { id }).collect(),
+                },
+            },
+            results: issues
+                .iter()
+                .map(|issue| SarifResult {
+                    rule_id: issue.rule,
+                    level: issue.level,
+                    message: Message {
+                        text: &issue.message,
+                    },
+                    locations: issue
+                        .locations
+                        .iter()
+                        .map(|location| sarif_location(location, root))
+                        .collect(),
+                })
+                .collect(),
+        }],
+    }
+}
+
+fn sarif_location(location: &Location, root: &Path) -> SarifLocation {
+    SarifLocation {
+        physical_location: PhysicalLocation {
+            artifact_location: ArtifactLocation {
+                uri: uri(&relative_path(root, &location.path)),
+                uri_base_id: SRCROOT,
+            },
+            region: location.line.map(|start_line| Region { start_line }),
+        },
+    }
+}
+
+/// Turns a relative path into a relative URI reference, which uses forward slashes and has to
+/// escape the few characters that can appear in file names but not in URIs.
+fn uri(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('#', "%23")
+        .replace('?', "%3F")
+}
+
+/// Returns the line, starting at 1, of the first line of the contents that contains the text.
+pub fn line_containing(contents: &str, text: &str) -> Option<u32> {
+    contents
+        .lines()
+        .position(|line| line.contains(text))
+        .map(|index| index as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations_are_relative_uris_with_regions() {
+        let location = Location {
+            path: PathBuf::from("/repo/src/My App/My App.csproj"),
+            line: Some(7),
+        };
+        assert_eq!(
+            sarif_location(&location, Path::new("/repo")),
+            SarifLocation {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation {
+                        uri: String::from("src/My%20App/My%20App.csproj"),
+                        uri_base_id: SRCROOT,
+                    },
+                    region: Some(Region { start_line: 7 }),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn line_containing_counts_from_one() {
+        let contents =
+            "<Project>\n  <ItemGroup>\n    <ProjectReference Include=\"..\\B\\B.csproj\" />\n";
+        assert_eq!(line_containing(contents, "B.csproj"), Some(3));
+        assert_eq!(line_containing(contents, "C.csproj"), None);
+    }
+}
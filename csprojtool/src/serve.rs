@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{check, check::Finding, cli, csproj::Project, list};
+
+const CMD_SERVE: &'static str = "serve";
+
+/// A request read from stdin, one json object per line, for example
+/// `{"method": "check", "id": 1}` or `{"method": "cancel", "id": 1}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+enum Request {
+    List { id: u64 },
+    Check { id: u64 },
+    Cancel { id: u64 },
+    Shutdown,
+}
+
+/// An event written to stdout, one json object per line. Every event about a query carries the
+/// id of its request.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum Event<'a> {
+    Started {
+        id: u64,
+    },
+    /// Project files that were added, removed or changed since the previous query.
+    Invalidated {
+        id: u64,
+        paths: Vec<&'a Path>,
+    },
+    ProjectParsed {
+        id: u64,
+        path: &'a Path,
+    },
+    GraphRebuilt {
+        id: u64,
+        project_count: usize,
+        reference_count: usize,
+    },
+    Projects {
+        id: u64,
+        paths: Vec<&'a Path>,
+    },
+    CheckResults {
+        id: u64,
+        findings: &'a [Finding],
+    },
+    Cancelled {
+        id: u64,
+    },
+    Finished {
+        id: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Query {
+    List,
+    Check,
+}
+
+#[derive(Debug)]
+struct Job {
+    id: u64,
+    query: Query,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// The modification times of the project files as of the previous query.
+pub type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+#[derive(Debug)]
+pub struct ServeCommand {
+    search_path: PathBuf,
+}
+
+impl ServeCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_SERVE)
+            .about("Answer list and check requests read from stdin and write progress events to stdout, for IDE integrations")
+            .arg(cli::arg_search())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_SERVE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+        }
+    }
+
+    /// Reads the requests on this thread and runs the queries one at a time on a worker, so that
+    /// a cancel or a newer query is seen while a query runs. A new query supersedes the queries
+    /// that have not finished yet.
+    pub fn execute(&self) {
+        let (job_sender, job_receiver) = crossbeam_channel::unbounded::<Job>();
+
+        let search_path = self.search_path.clone();
+        let worker = std::thread::spawn(move || {
+            let mut snapshot = None;
+            for job in job_receiver.iter() {
+                run(&search_path, &job, &mut snapshot);
+            }
+        });
+
+        let mut pending = Vec::<(u64, Arc<AtomicBool>)>::new();
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = line.unwrap();
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Request>(&line) {
+                Ok(Request::List { id }) => submit(&job_sender, &mut pending, id, Query::List),
+                Ok(Request::Check { id }) => submit(&job_sender, &mut pending, id, Query::Check),
+                Ok(Request::Cancel { id }) => {
+                    for (_, cancelled) in pending.iter().filter(|(pending_id, _)| *pending_id == id)
+                    {
+                        cancelled.store(true, Ordering::Relaxed);
+                    }
+                }
+                Ok(Request::Shutdown) => break,
+                Err(e) => emit(&Event::Error {
+                    message: format!("Failed to parse request {}: {}", line, e),
+                }),
+            }
+        }
+
+        drop(job_sender);
+        worker.join().unwrap();
+    }
+}
+
+/// Cancels the queries that were not finished yet and queues the new one.
+fn submit(
+    job_sender: &crossbeam_channel::Sender<Job>,
+    pending: &mut Vec<(u64, Arc<AtomicBool>)>,
+    id: u64,
+    query: Query,
+) {
+    for (_, cancelled) in pending.drain(..) {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    let cancelled = Arc::new(AtomicBool::new(false));
+    pending.push((id, cancelled.clone()));
+    job_sender
+        .send(Job {
+            id,
+            query,
+            cancelled,
+        })
+        .unwrap();
+}
+
+fn run(search_path: &Path, job: &Job, snapshot: &mut Option<Snapshot>) {
+    let id = job.id;
+    if job.is_cancelled() {
+        emit(&Event::Cancelled { id });
+        return;
+    }
+    emit(&Event::Started { id });
+
+    let projects = list::list(list::Options {
+        search_path,
+        follow_incoming_project_references: false,
+        follow_outgoing_project_references: false,
+    });
+
+    let current = take_snapshot(&projects);
+    if let Some(previous) = snapshot.as_ref() {
+        let paths = invalidated(previous, &current);
+        if !paths.is_empty() {
+            emit(&Event::Invalidated { id, paths });
+        }
+    }
+    *snapshot = Some(current);
+
+    for project in projects.iter() {
+        if job.is_cancelled() {
+            emit(&Event::Cancelled { id });
+            return;
+        }
+        emit(&Event::ProjectParsed {
+            id,
+            path: &project.path,
+        });
+    }
+    emit(&Event::GraphRebuilt {
+        id,
+        project_count: projects.len(),
+        reference_count: projects
+            .iter()
+            .map(|project| project.project_references.len())
+            .sum(),
+    });
+
+    match job.query {
+        Query::List => emit(&Event::Projects {
+            id,
+            paths: projects
+                .iter()
+                .map(|project| project.path.as_path())
+                .collect(),
+        }),
+        Query::Check => match check::check_all_until(&projects, &|| job.is_cancelled()) {
+            Some(findings) => emit(&Event::CheckResults {
+                id,
+                findings: &findings,
+            }),
+            None => {
+                emit(&Event::Cancelled { id });
+                return;
+            }
+        },
+    }
+    emit(&Event::Finished { id });
+}
+
+fn take_snapshot(projects: &[Project]) -> Snapshot {
+    projects
+        .iter()
+        .map(|project| {
+            let modified = std::fs::metadata(&project.path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            (project.path.clone(), modified)
+        })
+        .collect()
+}
+
+/// Returns the paths that were added, removed or modified between the snapshots.
+pub fn invalidated<'a>(previous: &'a Snapshot, current: &'a Snapshot) -> Vec<&'a Path> {
+    let mut paths = current
+        .iter()
+        .filter(|(path, modified)| previous.get(*path) != Some(modified))
+        .map(|(path, _)| path.as_path())
+        .chain(
+            previous
+                .keys()
+                .filter(|path| !current.contains_key(*path))
+                .map(PathBuf::as_path),
+        )
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+fn emit(event: &Event) {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    writeln!(stdout, "{}", serde_json::to_string(event).unwrap()).unwrap();
+    stdout.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn invalidated_lists_added_removed_and_modified_projects() {
+        let time = |seconds: u64| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        let previous = vec![
+            (PathBuf::from("/src/A/A.csproj"), time(1)),
+            (PathBuf::from("/src/B/B.csproj"), time(1)),
+            (PathBuf::from("/src/C/C.csproj"), time(1)),
+        ]
+        .into_iter()
+        .collect::<Snapshot>();
+        let current = vec![
+            (PathBuf::from("/src/A/A.csproj"), time(1)),
+            (PathBuf::from("/src/B/B.csproj"), time(2)),
+            (PathBuf::from("/src/D/D.csproj"), time(1)),
+        ]
+        .into_iter()
+        .collect::<Snapshot>();
+
+        assert_eq!(
+            invalidated(&previous, &current),
+            vec![
+                Path::new("/src/B/B.csproj"),
+                Path::new("/src/C/C.csproj"),
+                Path::new("/src/D/D.csproj"),
+            ]
+        );
+    }
+}
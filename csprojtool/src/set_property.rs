@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+use crate::output::status;
+use crate::{
+    cli, list, path_extensions::relative_path, plan::Effects, xml_extensions::set_property,
+};
+
+const ARG_ASSIGNMENT: &'static str = "assignment";
+const CMD_SET_PROPERTY: &'static str = "set-prop";
+
+#[derive(Debug)]
+pub struct SetPropertyCommand {
+    name: String,
+    value: String,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+impl SetPropertyCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_SET_PROPERTY)
+            .about("Set a property in the first unconditional PropertyGroup of projects")
+            .arg(
+                Arg::with_name(ARG_ASSIGNMENT)
+                    .value_name("NAME=VALUE")
+                    .help("The property and its value, like Nullable=enable")
+                    .required(true)
+                    .takes_value(true)
+                    .validator(|value| parse_assignment(&value).map(|_| ())),
+            )
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_SET_PROPERTY)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        let (name, value) = parse_assignment(matches.value_of(ARG_ASSIGNMENT).unwrap()).unwrap();
+        Self {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_SET_PROPERTY, self.plan_json.as_deref());
+
+        let mut changed_count = 0;
+        for project in projects.iter() {
+            let mut changed = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    changed = set_property(&mut root, &self.name, &self.value);
+                    if changed {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if changed {
+                status!(
+                    "Set {}={} in {}",
+                    self.name,
+                    self.value,
+                    relative_path(&current_dir, &project.path).display()
+                );
+                changed_count += 1;
+            }
+        }
+
+        if changed_count == 0 {
+            status!(
+                "All {} projects already set {}={}",
+                projects.len(),
+                self.name,
+                self.value
+            );
+        }
+
+        effects.finish();
+    }
+}
+
+/// Splits `Name=Value` at the first `=`, the value may be empty.
+pub fn parse_assignment(assignment: &str) -> Result<(&str, &str), String> {
+    let (name, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE instead of {}", assignment))?;
+    let name = name.trim();
+    let valid = name
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if !valid {
+        return Err(format!("{} is not a valid property name", name));
+    }
+    Ok((name, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_assignment_splits_at_the_first_equals_sign() {
+        assert_eq!(
+            parse_assignment("Nullable=enable"),
+            Ok(("Nullable", "enable"))
+        );
+        assert_eq!(
+            parse_assignment("DefineConstants=TRACE;A=1"),
+            Ok(("DefineConstants", "TRACE;A=1"))
+        );
+        assert_eq!(parse_assignment("NoWarn="), Ok(("NoWarn", "")));
+        assert!(parse_assignment("Nullable").is_err());
+        assert!(parse_assignment("$(Nullable)=enable").is_err());
+    }
+}
@@ -1,45 +1,549 @@
+mod document;
 mod file;
+mod merge;
+mod solution;
 mod types;
 
 use log::debug;
 use log::warn;
 
+use crate::config::{Config, FolderMapping, PlatformOverride};
 use crate::csproj::*;
+use crate::error::{Error, Result};
+use crate::list::TestFilter;
+use crate::output::status;
 use crate::path_extensions::*;
-use std::path::Path;
+use crate::plan::Effects;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub use document::Document;
+pub use merge::merge;
+pub use solution::{ProjectConfiguration, Solution, SolutionProject};
+use types::{
+    normalize_guids, project_platform, replace_guids, solution_platform_name, ConfigurationMatrix,
+};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Options<'a> {
     pub sln_path: &'a Path,
-    pub search_path: &'a Path,
+    /// The projects of all search paths are added.
+    pub search_paths: &'a [PathBuf],
     pub follow_incoming_project_references: bool,
     pub follow_outgoing_project_references: bool,
+    /// Whether to add test projects, other projects or both.
+    pub tests: TestFilter,
+    /// Globs relative to the current directory of projects to leave out, in addition to the
+    /// `[sln] exclude` globs of the configuration.
+    pub exclude: &'a [String],
+    /// Update the existing solution instead of generating it from scratch.
+    pub update: bool,
+    /// The solution configurations, the configuration file or the defaults are used if empty.
+    pub configurations: &'a [String],
+    /// The solution platforms, the configuration file or the defaults are used if empty.
+    pub platforms: &'a [String],
+    pub plan_json: Option<&'a Path>,
 }
 
-pub fn sln(options: Options) {
+pub fn sln(options: Options) -> Result<()> {
     debug!("Generating solution with options {:?}", &options);
 
     let Options {
         sln_path,
-        search_path,
+        search_paths,
         follow_incoming_project_references,
         follow_outgoing_project_references,
+        tests,
+        exclude,
+        update,
+        configurations,
+        platforms,
+        plan_json,
     } = options;
 
-    let projects = crate::list::list(crate::list::Options {
-        search_path,
+    let mut projects = crate::list::list_each(
+        search_paths,
         follow_incoming_project_references,
         follow_outgoing_project_references,
+    );
+    projects.retain(|project| tests.matches(project));
+
+    let config = Config::discover(sln_path.simplified_absolute().unwrap().parent().unwrap());
+    let exclusions = Exclusions::new(&config, exclude)?;
+    projects.retain(|project| {
+        let excluded = exclusions.excludes(project);
+        if excluded {
+            debug!(
+                "Leaving out {} excluded from solutions",
+                project.path.display()
+            );
+        }
+        !excluded
     });
+    let folder_mappings = FolderMappings::new(&config)?;
+    let project_platforms = ProjectPlatforms::new(&config)?;
+
+    let matrix = configuration_matrix(&config, configurations, platforms);
+
+    let mut effects = Effects::new(crate::cli::CMD_SLN, plan_json);
+
+    let contents = if update && effects.is_file(sln_path) {
+        let contents = read_solution(&effects, sln_path)?;
+        let mut document = document::Document::parse(&contents);
+        update_solution(
+            &mut document,
+            sln_path,
+            &projects,
+            &folder_mappings,
+            &project_platforms,
+            &matrix,
+        );
+        document.write()
+    } else {
+        let sln = create_solution(
+            &sln_path,
+            projects.into_iter(),
+            &folder_mappings,
+            &project_platforms,
+            matrix,
+        )?;
+        let mut contents = Vec::new();
+        sln.write(&mut contents)?;
+        String::from_utf8(contents)
+            .map_err(|e| Error::internal(format!("The solution is not valid UTF-8: {}", e)))?
+    };
+
+    // Existing entries are rewritten too, so that hand edits and other tools do not leave a mix
+    // of styles behind.
+    let contents = normalize_guids(&contents, config.sln.guids);
+
+    effects.write_file(sln_path, contents, Vec::new())?;
+    effects.finish();
+    Ok(())
+}
+
+/// Adds projects to an existing solution the way `sln --update` does, projects that are already
+/// in the solution are skipped.
+pub fn add_to_solution(
+    effects: &mut Effects,
+    sln_path: &Path,
+    projects: &[&Project],
+) -> Result<()> {
+    let config = Config::discover(sln_path.simplified_absolute().unwrap().parent().unwrap());
+    let matrix = configuration_matrix(&config, &[], &[]);
+
+    let contents = read_solution(effects, sln_path)?;
+    let mut document = document::Document::parse(&contents);
+    add_projects(
+        &mut document,
+        sln_path,
+        projects,
+        &FolderMappings::new(&config)?,
+        &ProjectPlatforms::new(&config)?,
+        &matrix,
+    );
+    let contents = normalize_guids(&document.write(), config.sln.guids);
+
+    effects.write_file(sln_path, contents, Vec::new())?;
+    Ok(())
+}
+
+/// Replaces project GUIDs in the entries, configurations and nesting of an existing solution.
+pub fn replace_project_guids(
+    effects: &mut Effects,
+    sln_path: &Path,
+    replacements: &HashMap<Uuid, Uuid>,
+) -> Result<()> {
+    let config = Config::discover(sln_path.simplified_absolute().unwrap().parent().unwrap());
+    let contents = read_solution(effects, sln_path)?;
+    let contents = normalize_guids(&replace_guids(&contents, replacements), config.sln.guids);
+
+    effects.write_file(sln_path, contents, Vec::new())?;
+    Ok(())
+}
+
+fn read_solution(effects: &Effects, sln_path: &Path) -> Result<String> {
+    effects
+        .read_to_string(sln_path)
+        .map_err(|e| Error::user(format!("Failed to read {}: {}", sln_path.display(), e)))
+}
+
+/// The solution configurations and platforms from the command line, the configuration file or
+/// the defaults, in that order.
+fn configuration_matrix(
+    config: &Config,
+    configurations: &[String],
+    platforms: &[String],
+) -> ConfigurationMatrix {
+    let mut matrix = ConfigurationMatrix::default();
+    for (values, src, dst) in [
+        (
+            configurations,
+            &config.sln.configurations,
+            &mut matrix.configurations,
+        ),
+        (platforms, &config.sln.platforms, &mut matrix.platforms),
+    ] {
+        if !values.is_empty() {
+            *dst = values.to_vec();
+        } else if !src.is_empty() {
+            *dst = src.clone();
+        }
+    }
+    // Solutions write AnyCPU with a space.
+    for platform in matrix.platforms.iter_mut() {
+        *platform = solution_platform_name(platform).to_owned();
+    }
+    matrix
+}
+
+/// Adds the projects that are missing from the solution and removes the projects whose file no
+/// longer exists. Solution folders, solution items and configurations are left as they are.
+/// Added projects are nested in the existing solution folders that match their mapped folder or
+/// directory.
+fn update_solution(
+    document: &mut document::Document,
+    sln_path: &Path,
+    projects: &[Project],
+    folder_mappings: &FolderMappings,
+    project_platforms: &ProjectPlatforms,
+    matrix: &ConfigurationMatrix,
+) {
+    let sln_path = sln_path.simplified_absolute().unwrap();
+    let sln_dir = sln_path.parent().unwrap();
+
+    let project_path = |entry: &document::ProjectEntry| {
+        let path = sln_dir.join(from_msbuild_path(&entry.path)).simplify();
+        std::fs::canonicalize(&path).unwrap_or(path)
+    };
+
+    let removed = document
+        .projects()
+        .filter(|entry| {
+            !entry.is_folder() && entry.path.ends_with("proj") && !project_path(entry).exists()
+        })
+        .map(|entry| (entry.guid, entry.path.clone()))
+        .collect::<Vec<_>>();
+    for (guid, path) in removed {
+        status!("Removed {} from the solution", path);
+        document.remove_project(guid);
+    }
+
+    add_projects(
+        document,
+        &sln_path,
+        &projects.iter().collect::<Vec<_>>(),
+        folder_mappings,
+        project_platforms,
+        matrix,
+    );
+}
+
+/// Adds the projects that are not in the solution yet. Added projects are nested in the existing
+/// solution folders that match their mapped folder or directory.
+fn add_projects(
+    document: &mut document::Document,
+    sln_path: &Path,
+    projects: &[&Project],
+    folder_mappings: &FolderMappings,
+    project_platforms: &ProjectPlatforms,
+    matrix: &ConfigurationMatrix,
+) {
+    let sln_path = sln_path.simplified_absolute().unwrap();
+    let sln_dir = sln_path.parent().unwrap();
+    let current_dir = std::env::current_dir().unwrap();
+
+    let solution = Solution::from_document(&sln_path, document);
+    let existing = solution
+        .projects
+        .iter()
+        .map(|project| {
+            std::fs::canonicalize(&project.path).unwrap_or_else(|_| project.path.clone())
+        })
+        .collect::<HashSet<_>>();
 
-    let sln = create_solution(&sln_path, projects.into_iter());
+    let solution_configurations = if solution.configurations.is_empty() {
+        matrix
+            .solution_configurations()
+            .map(|(conf, platform)| format!("{}|{}", conf, platform))
+            .collect()
+    } else {
+        solution.configurations.clone()
+    };
 
-    let file = std::fs::File::create(&sln_path).unwrap();
-    let mut writer = std::io::BufWriter::new(file);
-    sln.write(&mut writer).unwrap();
+    for project in projects
+        .iter()
+        .copied()
+        .filter(|project| !existing.contains(&project.path))
+    {
+        let rel_path = relative_path(sln_dir, &project.path);
+        let msbuild_path = to_msbuild_path(&rel_path);
+        let guid = project
+            .project_guid
+            .unwrap_or_else(|| file::guid_from_hash(&msbuild_path));
+
+        let type_guid = file::project_type_uuid(&msbuild_path);
+        document.add_project(document::ProjectEntry {
+            type_guid,
+            name: project
+                .path
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned(),
+            path: msbuild_path,
+            guid,
+            body: Vec::new(),
+            header: None,
+        });
+
+        // Shared projects are not built by themselves.
+        let platforms = project_platforms.platforms(project);
+        let configurations = document.section_mut("ProjectConfigurationPlatforms", "postSolution");
+        for solution_configuration in solution_configurations
+            .iter()
+            .filter(|_| type_guid != file::SHARED_PROJECT_UUID)
+        {
+            let mut parts = solution_configuration.splitn(2, '|');
+            let conf = parts.next().unwrap();
+            let platform = project_platform(&platforms, parts.next().unwrap_or("Any CPU"));
+            for suffix in ["ActiveCfg", "Build.0"] {
+                configurations.entries.push(format!(
+                    "{{{:X}}}.{}.{} = {}|{}",
+                    guid, solution_configuration, suffix, conf, platform
+                ));
+            }
+        }
+
+        let folder_names = folder_mappings.folders(project).unwrap_or_else(|| {
+            rel_path
+                .parent()
+                .unwrap()
+                .components()
+                .take_while(|component| matches!(component, std::path::Component::Normal(_)))
+                .map(|component| component.as_os_str().to_str().unwrap().to_owned())
+                .collect()
+        });
+        let mut parent = None;
+        for name in folder_names.iter() {
+            match solution
+                .folders
+                .iter()
+                .find(|folder| folder.parent == parent && folder.name.eq_ignore_ascii_case(name))
+            {
+                Some(folder) => parent = Some(folder.guid),
+                None => break,
+            }
+        }
+        if let Some(parent) = parent {
+            document
+                .section_mut("NestedProjects", "preSolution")
+                .entries
+                .push(format!("{{{:X}}} = {{{:X}}}", guid, parent));
+        }
+
+        status!(
+            "Added {} to the solution",
+            relative_path(&current_dir, &project.path).display()
+        );
+    }
 }
 
-fn create_solution(sln_path: &Path, projects: impl Iterator<Item = Project>) -> file::SolutionFile {
+/// Reads the paths of the projects in a solution file, solution folders are skipped.
+pub fn read_project_paths(sln_path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    Ok(Solution::read(sln_path)?
+        .projects
+        .into_iter()
+        .map(|project| project.path)
+        .collect())
+}
+
+/// The `[[sln.folders]]` mappings of the configuration with their globs compiled.
+struct FolderMappings<'a> {
+    config_dir: &'a Path,
+    mappings: Vec<(Option<globset::GlobMatcher>, &'a FolderMapping)>,
+}
+
+impl<'a> FolderMappings<'a> {
+    fn new(config: &'a Config) -> Result<Self> {
+        Ok(Self {
+            config_dir: &config.dir,
+            mappings: config
+                .sln
+                .folders
+                .iter()
+                .map(|mapping| {
+                    Ok((
+                        mapping.glob.as_deref().map(compile_glob).transpose()?,
+                        mapping,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Returns the solution folders of the first mapping that matches the project, from the
+    /// outermost to the innermost folder.
+    fn folders(&self, project: &Project) -> Option<Vec<String>> {
+        let rel_path = config_relative_path(self.config_dir, project);
+
+        self.mappings
+            .iter()
+            .find(|(matcher, mapping)| {
+                matcher
+                    .as_ref()
+                    .map_or(true, |matcher| matcher.is_match(&rel_path))
+                    && (!mapping.test_projects || project.is_test_project())
+            })
+            .map(|(_, mapping)| {
+                mapping
+                    .folder
+                    .split('/')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+    }
+}
+
+/// The `[sln] exclude` globs of the configuration and the `--exclude` globs, compiled.
+struct Exclusions<'a> {
+    config_dir: &'a Path,
+    config_matcher: globset::GlobSet,
+    current_dir: PathBuf,
+    matcher: globset::GlobSet,
+}
+
+impl<'a> Exclusions<'a> {
+    fn new(config: &'a Config, exclude: &[String]) -> Result<Self> {
+        let glob_set = |globs: &[String], origin: &str| {
+            let invalid = |glob: &str, e: globset::Error| {
+                Error::user(format!("Invalid glob {} in {}: {}", glob, origin, e))
+            };
+            let mut builder = globset::GlobSetBuilder::new();
+            for glob in globs.iter() {
+                builder.add(globset::Glob::new(glob).map_err(|e| invalid(glob, e))?);
+            }
+            builder.build().map_err(|e| invalid(&globs.join(", "), e))
+        };
+        Ok(Self {
+            config_dir: &config.dir,
+            config_matcher: glob_set(&config.sln.exclude, "the configuration")?,
+            current_dir: std::fs::canonicalize(std::env::current_dir()?)?,
+            matcher: glob_set(exclude, "--exclude")?,
+        })
+    }
+
+    fn excludes(&self, project: &Project) -> bool {
+        self.config_matcher
+            .is_match(config_relative_path(self.config_dir, project))
+            || self.matcher.is_match(
+                relative_path(&self.current_dir, &project.path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            )
+    }
+}
+
+/// The `[[sln.project-platforms]]` overrides of the configuration with their globs compiled.
+struct ProjectPlatforms<'a> {
+    config_dir: &'a Path,
+    overrides: Vec<(globset::GlobMatcher, &'a PlatformOverride)>,
+}
+
+impl<'a> ProjectPlatforms<'a> {
+    fn new(config: &'a Config) -> Result<Self> {
+        Ok(Self {
+            config_dir: &config.dir,
+            overrides: config
+                .sln
+                .project_platforms
+                .iter()
+                .map(|platform_override| {
+                    Ok((compile_glob(&platform_override.glob)?, platform_override))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Returns the platforms the project can build, empty for Any CPU. The platforms are taken
+    /// from the first matching override, the Platforms property, or the platforms of the
+    /// `'$(Configuration)|$(Platform)'` conditions of legacy projects.
+    fn platforms(&self, project: &Project) -> Vec<String> {
+        lazy_static::lazy_static! {
+            static ref CONDITION_RE: regex::Regex = regex::Regex::new(
+                r"'\$\(Configuration\)\|\$\(Platform\)'\s*==\s*'[^|']*\|([^']+)'"
+            )
+            .unwrap();
+        }
+
+        let rel_path = config_relative_path(self.config_dir, project);
+        if let Some((_, platform_override)) = self
+            .overrides
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(&rel_path))
+        {
+            return platform_override.platforms.clone();
+        }
+
+        if let Some(property) = project
+            .properties
+            .iter()
+            .rev()
+            .find(|property| property.name == "Platforms" && property.condition.is_none())
+        {
+            return property
+                .value
+                .split(';')
+                .map(str::trim)
+                .filter(|platform| !platform.is_empty() && !platform.contains("$("))
+                .map(str::to_owned)
+                .collect();
+        }
+
+        let mut platforms = Vec::<String>::new();
+        for captures in project
+            .properties
+            .iter()
+            .filter_map(|property| CONDITION_RE.captures(property.condition.as_deref()?))
+        {
+            let platform = captures[1].trim();
+            if !platforms
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(platform))
+            {
+                platforms.push(platform.to_owned());
+            }
+        }
+        platforms
+    }
+}
+
+fn compile_glob(glob: &str) -> Result<globset::GlobMatcher> {
+    globset::Glob::new(glob)
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| Error::user(format!("Invalid glob {} in the configuration: {}", glob, e)))
+}
+
+/// Returns the path of the project relative to the configuration file with forward slashes,
+/// which is what the globs in the configuration match.
+fn config_relative_path(config_dir: &Path, project: &Project) -> String {
+    relative_path(config_dir, &project.path)
+        .to_str()
+        .unwrap()
+        .replace('\\', "/")
+}
+
+fn create_solution(
+    sln_path: &Path,
+    projects: impl Iterator<Item = Project>,
+    folder_mappings: &FolderMappings,
+    project_platforms: &ProjectPlatforms,
+    matrix: ConfigurationMatrix,
+) -> Result<file::SolutionFile> {
     let mut root = file::Directory::default();
     let sln_path = sln_path.simplified_absolute().unwrap().simplify();
     let sln_dir = sln_path.parent().unwrap();
@@ -54,37 +558,71 @@ fn create_solution(sln_path: &Path, projects: impl Iterator<Item = Project>) ->
             rel_project_path.display()
         );
 
-        let mut components = rel_project_path.components().peekable();
+        // Mapped projects are placed directly in their solution folder, other projects mirror
+        // their directory.
+        let components = match folder_mappings.folders(&project) {
+            Some(mut folders) => {
+                folders.push(
+                    project
+                        .path
+                        .file_name()
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                );
+                folders
+            }
+            None => rel_project_path
+                .components()
+                .map(|comp| match comp {
+                    std::path::Component::Normal(val) => Ok(val.to_str().unwrap().to_owned()),
+                    _ => Err(Error::user(format!(
+                        "Can not add {} because it is outside of the solution directory {}",
+                        project.path.display(),
+                        sln_dir.display()
+                    ))),
+                })
+                .collect::<Result<_>>()?,
+        };
+
+        let mut components = components.into_iter().peekable();
 
         let mut dir = &mut root;
         while let Some(comp) = components.next() {
-            let comp = match comp {
-                std::path::Component::ParentDir => {
-                    panic!("Can not reference projects outside of solution directory!")
-                }
-                std::path::Component::Normal(val) => val.to_str().unwrap().to_owned(),
-                _ => panic!("Unexpected path component!"),
-            };
-
             if components.peek().is_some() {
                 dir = match dir
                     .nodes
-                    .entry(comp)
+                    .entry(comp.clone())
                     .or_insert_with(|| file::Node::Directory(file::Directory::default()))
                 {
                     file::Node::Directory(dir) => dir,
-                    file::Node::Project(_) => panic!("Project path used as directory!"),
+                    file::Node::Project(_) => {
+                        return Err(Error::user(format!(
+                            "Can not add {} because its solution folder {} is also a project",
+                            project.path.display(),
+                            comp
+                        )))
+                    }
                 };
             } else {
+                if dir.nodes.contains_key(&comp) {
+                    return Err(Error::user(format!(
+                        "Multiple projects named {} in the same solution folder",
+                        comp
+                    )));
+                }
                 dir.nodes.insert(
                     comp,
                     file::Node::Project(file::Project {
+                        path: to_msbuild_path(&rel_project_path),
                         guid: project.project_guid,
+                        platforms: project_platforms.platforms(&project),
                     }),
                 );
             }
         }
     }
 
-    file::SolutionFile::new(root)
+    Ok(file::SolutionFile::new(root, matrix))
 }
@@ -0,0 +1,337 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref PROJECT_RE: Regex = Regex::new(
+        r#"^Project\("\{([^}]+)\}"\)\s*=\s*"([^"]*)",\s*"([^"]*)",\s*"\{([^}]+)\}"\s*$"#
+    )
+    .unwrap();
+    static ref SECTION_RE: Regex =
+        Regex::new(r#"^GlobalSection\(([^)]+)\)\s*=\s*(\w+)\s*$"#).unwrap();
+}
+
+/// A solution file that can be edited without losing anything it does not understand. Lines
+/// are kept as written, only the project headers and global sections are interpreted.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub items: Vec<Item>,
+    pub line_ending: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Line(String),
+    Project(ProjectEntry),
+    Global(Vec<GlobalItem>),
+}
+
+/// A `Project(...) = ...` block, the lines up to the EndProject are kept as written.
+#[derive(Debug, Clone)]
+pub struct ProjectEntry {
+    pub type_guid: Uuid,
+    pub name: String,
+    pub path: String,
+    pub guid: Uuid,
+    pub body: Vec<String>,
+    /// The header as written, so that unchanged projects keep the casing of their GUIDs. None
+    /// for added projects.
+    pub header: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum GlobalItem {
+    Line(String),
+    Section(Section),
+}
+
+/// A `GlobalSection(<name>) = <position>` block with one entry per line.
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub position: String,
+    pub entries: Vec<String>,
+}
+
+impl Section {
+    /// Returns the entries as key value pairs split on the first ` = `.
+    pub fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().filter_map(|entry| {
+            let mut parts = entry.trim().splitn(2, '=');
+            Some((parts.next()?.trim(), parts.next()?.trim()))
+        })
+    }
+}
+
+impl ProjectEntry {
+    pub fn is_folder(&self) -> bool {
+        self.type_guid == super::file::FOLDER_UUID
+    }
+}
+
+fn parse_guid(value: &str) -> Option<Uuid> {
+    Uuid::parse_str(value).ok()
+}
+
+impl Document {
+    pub fn parse(contents: &str) -> Self {
+        let line_ending = if contents.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let mut items = Vec::new();
+        let mut lines = contents.lines();
+
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim();
+
+            if let Some(captures) = PROJECT_RE.captures(trimmed) {
+                if let (Some(type_guid), Some(guid)) =
+                    (parse_guid(&captures[1]), parse_guid(&captures[4]))
+                {
+                    let mut body = Vec::new();
+                    for line in lines.by_ref() {
+                        if line.trim() == "EndProject" {
+                            break;
+                        }
+                        body.push(line.to_owned());
+                    }
+                    items.push(Item::Project(ProjectEntry {
+                        type_guid,
+                        name: captures[2].to_owned(),
+                        path: captures[3].to_owned(),
+                        guid,
+                        body,
+                        header: Some(line.to_owned()),
+                    }));
+                    continue;
+                }
+            }
+
+            if trimmed == "Global" {
+                let mut global = Vec::new();
+                while let Some(line) = lines.next() {
+                    let trimmed = line.trim();
+                    if trimmed == "EndGlobal" {
+                        break;
+                    }
+                    match SECTION_RE.captures(trimmed) {
+                        Some(captures) => {
+                            let mut entries = Vec::new();
+                            for line in lines.by_ref() {
+                                if line.trim() == "EndGlobalSection" {
+                                    break;
+                                }
+                                entries.push(line.trim().to_owned());
+                            }
+                            global.push(GlobalItem::Section(Section {
+                                name: captures[1].to_owned(),
+                                position: captures[2].to_owned(),
+                                entries,
+                            }));
+                        }
+                        None => global.push(GlobalItem::Line(line.to_owned())),
+                    }
+                }
+                items.push(Item::Global(global));
+                continue;
+            }
+
+            items.push(Item::Line(line.to_owned()));
+        }
+
+        Self { items, line_ending }
+    }
+
+    pub fn write(&self) -> String {
+        let mut out = String::new();
+        let mut push = |line: &str| {
+            out.push_str(line);
+            out.push_str(self.line_ending);
+        };
+
+        for item in self.items.iter() {
+            match item {
+                Item::Line(line) => push(line),
+                Item::Project(project) => {
+                    match &project.header {
+                        Some(header) => push(header),
+                        None => push(&format!(
+                            "Project(\"{{{:X}}}\") = \"{}\", \"{}\", \"{{{:X}}}\"",
+                            project.type_guid, project.name, project.path, project.guid
+                        )),
+                    }
+                    for line in project.body.iter() {
+                        push(line);
+                    }
+                    push("EndProject");
+                }
+                Item::Global(global) => {
+                    push("Global");
+                    for item in global.iter() {
+                        match item {
+                            GlobalItem::Line(line) => push(line),
+                            GlobalItem::Section(section) => {
+                                push(&format!(
+                                    "\tGlobalSection({}) = {}",
+                                    section.name, section.position
+                                ));
+                                for entry in section.entries.iter() {
+                                    push(&format!("\t\t{}", entry));
+                                }
+                                push("\tEndGlobalSection");
+                            }
+                        }
+                    }
+                    push("EndGlobal");
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn projects(&self) -> impl Iterator<Item = &ProjectEntry> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Project(project) => Some(project),
+            _ => None,
+        })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Global(global) => Some(global),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|item| match item {
+                GlobalItem::Section(section) if section.name == name => Some(section),
+                _ => None,
+            })
+    }
+
+    /// Returns the global section, adding it with the given position if it does not exist.
+    pub fn section_mut(&mut self, name: &str, position: &str) -> &mut Section {
+        if !self
+            .items
+            .iter()
+            .any(|item| matches!(item, Item::Global(_)))
+        {
+            self.items.push(Item::Global(Vec::new()));
+        }
+        let global = self
+            .items
+            .iter_mut()
+            .find_map(|item| match item {
+                Item::Global(global) => Some(global),
+                _ => None,
+            })
+            .unwrap();
+
+        let index = match global
+            .iter()
+            .position(|item| matches!(item, GlobalItem::Section(section) if section.name == name))
+        {
+            Some(index) => index,
+            None => {
+                global.push(GlobalItem::Section(Section {
+                    name: name.to_owned(),
+                    position: position.to_owned(),
+                    entries: Vec::new(),
+                }));
+                global.len() - 1
+            }
+        };
+
+        match &mut global[index] {
+            GlobalItem::Section(section) => section,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Adds the project after the last project, or before the Global block.
+    pub fn add_project(&mut self, project: ProjectEntry) {
+        let index = self
+            .items
+            .iter()
+            .rposition(|item| matches!(item, Item::Project(_)))
+            .map(|index| index + 1)
+            .or_else(|| {
+                self.items
+                    .iter()
+                    .position(|item| matches!(item, Item::Global(_)))
+            })
+            .unwrap_or(self.items.len());
+        self.items.insert(index, Item::Project(project));
+    }
+
+    /// Removes the project along with its configurations and nesting.
+    pub fn remove_project(&mut self, guid: Uuid) {
+        self.items
+            .retain(|item| !matches!(item, Item::Project(project) if project.guid == guid));
+
+        let prefix = format!("{{{:X}}}", guid);
+        for item in self.items.iter_mut() {
+            if let Item::Global(global) = item {
+                for item in global.iter_mut() {
+                    if let GlobalItem::Section(section) = item {
+                        section.entries.retain(|entry| {
+                            !entry
+                                .get(..prefix.len())
+                                .map_or(false, |start| start.eq_ignore_ascii_case(&prefix))
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLN: &'static str = "\r\nMicrosoft Visual Studio Solution File, Format Version 12.00\r\n\
+Project(\"{2150E333-8FDC-42A3-9474-1A3956D46DE8}\") = \"src\", \"src\", \"{11111111-1111-1111-1111-111111111111}\"\r\n\
+EndProject\r\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"src\\A\\A.csproj\", \"{22222222-2222-2222-2222-222222222222}\"\r\n\
+\tProjectSection(ProjectDependencies) = postProject\r\n\
+\tEndProjectSection\r\n\
+EndProject\r\n\
+Global\r\n\
+\tGlobalSection(ProjectConfigurationPlatforms) = postSolution\r\n\
+\t\t{22222222-2222-2222-2222-222222222222}.Debug|Any CPU.ActiveCfg = Debug|Any CPU\r\n\
+\tEndGlobalSection\r\n\
+\tGlobalSection(NestedProjects) = preSolution\r\n\
+\t\t{22222222-2222-2222-2222-222222222222} = {11111111-1111-1111-1111-111111111111}\r\n\
+\tEndGlobalSection\r\n\
+EndGlobal\r\n";
+
+    #[test]
+    fn document_round_trips() {
+        assert_eq!(Document::parse(SLN).write(), SLN);
+    }
+
+    #[test]
+    fn remove_project_removes_configurations_and_nesting() {
+        let mut document = Document::parse(SLN);
+        let guid = Uuid::parse_str("22222222-2222-2222-2222-222222222222").unwrap();
+        document.remove_project(guid);
+
+        assert_eq!(document.projects().count(), 1);
+        assert!(document
+            .section("ProjectConfigurationPlatforms")
+            .unwrap()
+            .entries
+            .is_empty());
+        assert!(document
+            .section("NestedProjects")
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+}
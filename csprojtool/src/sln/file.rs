@@ -12,8 +12,16 @@ VisualStudioVersion = 16.0.30114.105
 MinimumVisualStudioVersion = 10.0.40219.1
 "###;
 
-const FOLDER_UUID: Uuid = Uuid::from_bytes(0x2150E3338FDC42A394741A3956D46DE8u128.to_be_bytes());
-const PROJECT_UUID: Uuid = Uuid::from_bytes(0xFAE04EC0301F11D3BF4B00C04F79EFBCu128.to_be_bytes());
+pub const FOLDER_UUID: Uuid =
+    Uuid::from_bytes(0x2150E3338FDC42A394741A3956D46DE8u128.to_be_bytes());
+pub const PROJECT_UUID: Uuid =
+    Uuid::from_bytes(0xFAE04EC0301F11D3BF4B00C04F79EFBCu128.to_be_bytes());
+pub const FSHARP_PROJECT_UUID: Uuid =
+    Uuid::from_bytes(0xF2A71F9B5D33465AA702920D77279786u128.to_be_bytes());
+pub const VB_PROJECT_UUID: Uuid =
+    Uuid::from_bytes(0xF184B08FC81C45F6A57F5ABD9991F28Fu128.to_be_bytes());
+pub const SHARED_PROJECT_UUID: Uuid =
+    Uuid::from_bytes(0xD954291E2A0B460D934EDC6B0785DB48u128.to_be_bytes());
 
 #[derive(Debug, Clone)]
 pub enum Node {
@@ -24,12 +32,14 @@ pub enum Node {
 #[derive(Debug, Clone)]
 pub struct SolutionFile {
     pub root: InnerRootDirectory,
+    pub matrix: ConfigurationMatrix,
 }
 
 impl SolutionFile {
-    pub fn new(root: Directory) -> Self {
+    pub fn new(root: Directory, matrix: ConfigurationMatrix) -> Self {
         Self {
             root: InnerRootDirectory::new(root),
+            matrix,
         }
     }
 
@@ -41,7 +51,8 @@ impl SolutionFile {
         write!(writer, "Global\n")?;
         self.write_global_section_solution_configuration_platforms(writer)?;
         self.write_global_section_solution_properties(writer)?;
-        self.root.write_project_configurations(writer)?;
+        self.root
+            .write_project_configurations(writer, &self.matrix)?;
         self.root.write_nested_projects(writer)?;
         write!(writer, "EndGlobal\n")?;
 
@@ -57,10 +68,8 @@ impl SolutionFile {
             "\tGlobalSection(SolutionConfigurationPlatforms) = preSolution\n"
         )?;
 
-        for conf in CONFIGURATIONS {
-            for arch in PROCESSOR_ARCHITECTURES {
-                write!(writer, "\t\t{0}|{1} = {0}|{1}\n", conf, arch)?;
-            }
+        for (conf, platform) in self.matrix.solution_configurations() {
+            write!(writer, "\t\t{0}|{1} = {0}|{1}\n", conf, platform)?;
         }
 
         write!(writer, "\tEndGlobalSection\n")?;
@@ -84,7 +93,11 @@ impl SolutionFile {
 
 #[derive(Debug, Clone)]
 pub struct Project {
+    /// The path relative to the solution directory.
+    pub path: String,
     pub guid: Option<Uuid>,
+    /// The platforms the project can build, empty for Any CPU.
+    pub platforms: Vec<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -102,7 +115,7 @@ impl InnerNode {
     pub fn new(path: &str, name: String, node: Node) -> Self {
         match node {
             Node::Directory(dir) => Self::Directory(InnerDirectory::new(path, name, dir)),
-            Node::Project(proj) => Self::Project(InnerProject::new(path, name, proj)),
+            Node::Project(proj) => Self::Project(InnerProject::new(name, proj)),
         }
     }
 }
@@ -134,7 +147,11 @@ impl InnerRootDirectory {
         Ok(())
     }
 
-    fn write_project_configurations<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+    fn write_project_configurations<W: Write>(
+        &self,
+        writer: &mut W,
+        matrix: &ConfigurationMatrix,
+    ) -> std::io::Result<()> {
         write!(
             writer,
             "\tGlobalSection(ProjectConfigurationPlatforms) = postSolution\n"
@@ -142,8 +159,8 @@ impl InnerRootDirectory {
 
         for node in self.nodes.iter() {
             match node {
-                InnerNode::Directory(dir) => dir.write_project_configurations(writer)?,
-                InnerNode::Project(proj) => proj.write_project_configuration(writer)?,
+                InnerNode::Directory(dir) => dir.write_project_configurations(writer, matrix)?,
+                InnerNode::Project(proj) => proj.write_project_configuration(writer, matrix)?,
             }
         }
 
@@ -229,11 +246,15 @@ impl InnerDirectory {
         Ok(())
     }
 
-    fn write_project_configurations<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+    fn write_project_configurations<W: Write>(
+        &self,
+        writer: &mut W,
+        matrix: &ConfigurationMatrix,
+    ) -> std::io::Result<()> {
         for node in self.nodes.iter() {
             match node {
-                InnerNode::Directory(dir) => dir.write_project_configurations(writer)?,
-                InnerNode::Project(proj) => proj.write_project_configuration(writer)?,
+                InnerNode::Directory(dir) => dir.write_project_configurations(writer, matrix)?,
+                InnerNode::Project(proj) => proj.write_project_configuration(writer, matrix)?,
             }
         }
 
@@ -276,37 +297,58 @@ pub struct InnerProject {
     pub path: String,
     pub guid: Uuid,
     pub name: String,
+    pub platforms: Vec<String>,
 }
 
 impl InnerProject {
-    pub fn new(path: &str, name: String, proj: Project) -> Self {
-        let path = join_str_path(path, &name);
-        let name = name.strip_suffix(".csproj").unwrap().to_owned();
+    pub fn new(name: String, proj: Project) -> Self {
+        let path = proj.path;
+        let name = std::path::Path::new(&name)
+            .file_stem()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
         let guid = proj.guid.unwrap_or_else(|| guid_from_hash(&path));
-        Self { path, name, guid }
+        Self {
+            path,
+            name,
+            guid,
+            platforms: proj.platforms,
+        }
     }
 
     fn write_project<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         write!(
             writer,
             "Project(\"{{{0:X}}}\") = \"{1}\", \"{2}\", \"{{{3:X}}}\"\nEndProject\n",
-            PROJECT_UUID, self.name, self.path, self.guid
+            project_type_uuid(&self.path),
+            self.name,
+            self.path,
+            self.guid
         )
     }
 
-    pub fn write_project_configuration<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
-        for conf in CONFIGURATIONS {
-            for arch in PROCESSOR_ARCHITECTURES {
-                for suffix in ["ActiveCfg", "Build.0"] {
-                    write!(
-                        writer,
-                        "\t\t{{{guid:X}}}.{conf}|{arch}.{suffix} = {conf}|Any CPU\n",
-                        guid = self.guid,
-                        conf = conf,
-                        arch = arch,
-                        suffix = suffix,
-                    )?;
-                }
+    pub fn write_project_configuration<W: Write>(
+        &self,
+        writer: &mut W,
+        matrix: &ConfigurationMatrix,
+    ) -> std::io::Result<()> {
+        // Shared projects are not built by themselves.
+        if project_type_uuid(&self.path) == SHARED_PROJECT_UUID {
+            return Ok(());
+        }
+        for (conf, platform) in matrix.solution_configurations() {
+            for suffix in ["ActiveCfg", "Build.0"] {
+                write!(
+                    writer,
+                    "\t\t{{{guid:X}}}.{conf}|{platform}.{suffix} = {conf}|{project_platform}\n",
+                    guid = self.guid,
+                    conf = conf,
+                    platform = platform,
+                    suffix = suffix,
+                    project_platform = project_platform(&self.platforms, platform),
+                )?;
             }
         }
 
@@ -314,6 +356,20 @@ impl InnerProject {
     }
 }
 
+/// Returns the project type GUID by the extension of the project path, C# for unknown extensions.
+pub fn project_type_uuid(path: &str) -> Uuid {
+    let path = path.to_lowercase();
+    if path.ends_with(".fsproj") {
+        FSHARP_PROJECT_UUID
+    } else if path.ends_with(".vbproj") {
+        VB_PROJECT_UUID
+    } else if path.ends_with(".shproj") {
+        SHARED_PROJECT_UUID
+    } else {
+        PROJECT_UUID
+    }
+}
+
 pub fn guid_from_hash<H: Hash>(value: H) -> Uuid {
     let mut hasher = siphasher::sip128::SipHasher::new();
     value.hash(&mut hasher);
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use super::document::{Document, GlobalItem, Item, ProjectEntry};
+use super::file::{guid_from_hash, FOLDER_UUID};
+use super::solution::{solution_items, Solution};
+use super::types::normalize_guids;
+use crate::config::Config;
+use crate::path_extensions::{from_msbuild_path, relative_path, to_msbuild_path, PathExt};
+
+/// The result of merging solutions.
+#[derive(Debug)]
+pub struct Merged {
+    pub contents: String,
+    pub project_count: usize,
+    /// GUIDs that were used by different projects, or by the same project with different GUIDs.
+    pub conflicts: Vec<String>,
+}
+
+/// The merged solution being built up.
+struct Merger<'a> {
+    out_dir: &'a Path,
+    document: Document,
+    /// The GUIDs of projects by their lowercase absolute path, with the solution they came from.
+    project_guids: HashMap<String, (Uuid, String)>,
+    /// The GUIDs of all projects and folders.
+    guids: HashSet<Uuid>,
+    conflicts: Vec<String>,
+}
+
+/// Unions the projects of the solutions into one solution at the output path. Projects are
+/// de-duplicated by the path they resolve to, folders with the same name and parent are merged
+/// and the solution configurations are combined. The first solution provides the header and the
+/// global sections that are not about projects.
+pub fn merge(out_path: &Path, solutions: &[(PathBuf, String)]) -> Merged {
+    let out_dir = out_path.parent().unwrap();
+
+    let mut document = Document::parse(&solutions[0].1);
+    document
+        .items
+        .retain(|item| !matches!(item, Item::Project(_)));
+    for item in document.items.iter_mut() {
+        if let Item::Global(global) = item {
+            for item in global.iter_mut() {
+                if let GlobalItem::Section(section) = item {
+                    match section.name.as_str() {
+                        "ProjectConfigurationPlatforms" | "NestedProjects" => {
+                            section.entries.clear()
+                        }
+                        "ExtensibilityGlobals" => {
+                            for entry in section.entries.iter_mut() {
+                                if entry.starts_with("SolutionGuid") {
+                                    *entry = format!(
+                                        "SolutionGuid = {{{:X}}}",
+                                        guid_from_hash(to_msbuild_path(out_path))
+                                    );
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    let mut merger = Merger {
+        out_dir,
+        document,
+        project_guids: HashMap::new(),
+        guids: HashSet::new(),
+        conflicts: Vec::new(),
+    };
+    for (sln_path, contents) in solutions.iter() {
+        merger.add(sln_path, &Document::parse(contents));
+    }
+
+    let config = Config::discover(out_dir);
+    Merged {
+        contents: normalize_guids(&merger.document.write(), config.sln.guids),
+        project_count: merger.project_guids.len(),
+        conflicts: merger.conflicts,
+    }
+}
+
+impl<'a> Merger<'a> {
+    fn add(&mut self, sln_path: &Path, document: &Document) {
+        let sln_dir = sln_path.parent().unwrap();
+        let solution = Solution::from_document(sln_path, document);
+        let sln_name = sln_path.file_name().unwrap().to_string_lossy();
+
+        let configurations = self
+            .document
+            .section_mut("SolutionConfigurationPlatforms", "preSolution");
+        for configuration in solution.configurations.iter() {
+            let entry = format!("{0} = {0}", configuration);
+            if !configurations.entries.contains(&entry) {
+                configurations.entries.push(entry);
+            }
+        }
+
+        // The GUIDs of the folders of this solution in the merged solution.
+        let mut folders = HashMap::<Uuid, Uuid>::new();
+        let mut pending = solution.folders.iter().collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let count = pending.len();
+            pending.retain(|folder| {
+                let parent = match folder.parent {
+                    Some(parent) => match folders.get(&parent) {
+                        Some(parent) => Some(*parent),
+                        // Nested in a folder that has not been merged yet.
+                        None if solution.folder(parent).is_some() => return true,
+                        None => None,
+                    },
+                    None => None,
+                };
+                let entry = document
+                    .projects()
+                    .find(|entry| entry.guid == folder.guid)
+                    .unwrap();
+                let guid = self.add_folder(entry, parent, sln_dir);
+                folders.insert(folder.guid, guid);
+                false
+            });
+            if pending.len() == count {
+                // The rest is nested in a cycle, add them at the root.
+                for folder in pending.drain(..) {
+                    let entry = document
+                        .projects()
+                        .find(|entry| entry.guid == folder.guid)
+                        .unwrap();
+                    let guid = self.add_folder(entry, None, sln_dir);
+                    folders.insert(folder.guid, guid);
+                }
+            }
+        }
+
+        let project_configurations = document
+            .section("ProjectConfigurationPlatforms")
+            .map(|section| section.entries.clone())
+            .unwrap_or_default();
+
+        for (project, entry) in solution
+            .projects
+            .iter()
+            .zip(document.projects().filter(|entry| !entry.is_folder()))
+        {
+            let key = project.path.to_string_lossy().to_lowercase();
+            let msbuild_path = to_msbuild_path(&relative_path(self.out_dir, &project.path));
+
+            if let Some((guid, first_sln)) = self.project_guids.get(&key) {
+                if *guid != project.guid {
+                    self.conflicts.push(format!(
+                        "{} has GUID {{{:X}}} in {} and {{{:X}}} in {}, keeping the first",
+                        msbuild_path, guid, first_sln, project.guid, sln_name
+                    ));
+                }
+                continue;
+            }
+
+            let guid = if self.guids.contains(&project.guid) {
+                let guid = guid_from_hash(&msbuild_path);
+                self.conflicts.push(format!(
+                    "{} in {} has GUID {{{:X}}} which is already used, it was given {{{:X}}}",
+                    msbuild_path, sln_name, project.guid, guid
+                ));
+                guid
+            } else {
+                project.guid
+            };
+            self.guids.insert(guid);
+            self.project_guids.insert(key, (guid, sln_name.to_string()));
+
+            let unchanged = guid == project.guid && msbuild_path == entry.path;
+            self.document.add_project(ProjectEntry {
+                type_guid: entry.type_guid,
+                name: entry.name.clone(),
+                path: msbuild_path,
+                guid,
+                body: entry.body.clone(),
+                header: if unchanged {
+                    entry.header.clone()
+                } else {
+                    None
+                },
+            });
+
+            let prefix = format!("{{{:X}}}", project.guid);
+            let configurations = self
+                .document
+                .section_mut("ProjectConfigurationPlatforms", "postSolution");
+            for configuration in project_configurations.iter() {
+                if let Some(rest) = configuration
+                    .get(..prefix.len())
+                    .filter(|start| start.eq_ignore_ascii_case(&prefix))
+                    .map(|_| &configuration[prefix.len()..])
+                {
+                    configurations
+                        .entries
+                        .push(format!("{{{:X}}}{}", guid, rest));
+                }
+            }
+
+            if let Some(parent) = project.parent.and_then(|parent| folders.get(&parent)) {
+                self.document
+                    .section_mut("NestedProjects", "preSolution")
+                    .entries
+                    .push(format!("{{{:X}}} = {{{:X}}}", guid, parent));
+            }
+        }
+    }
+
+    /// Adds the folder to the merged solution or merges its items into the folder with the same
+    /// name under the same parent. Returns the GUID of the folder in the merged solution.
+    fn add_folder(&mut self, entry: &ProjectEntry, parent: Option<Uuid>, sln_dir: &Path) -> Uuid {
+        let items = solution_items(&entry.body)
+            .into_iter()
+            .map(|item| self.rebase(sln_dir, &item))
+            .collect::<Vec<_>>();
+
+        let nested = self
+            .document
+            .section("NestedProjects")
+            .map(|section| {
+                section
+                    .pairs()
+                    .map(|(child, parent)| (child.to_uppercase(), parent.to_uppercase()))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+        let parent_of = |guid: Uuid| nested.get(&format!("{{{:X}}}", guid)).cloned();
+        let parent_key = parent.map(|parent| format!("{{{:X}}}", parent));
+
+        let existing = self.document.items.iter_mut().find_map(|item| match item {
+            Item::Project(folder)
+                if folder.is_folder()
+                    && folder.name.eq_ignore_ascii_case(&entry.name)
+                    && parent_of(folder.guid) == parent_key =>
+            {
+                Some(folder)
+            }
+            _ => None,
+        });
+
+        if let Some(folder) = existing {
+            let existing_items = solution_items(&folder.body)
+                .into_iter()
+                .map(|item| item.to_lowercase())
+                .collect::<HashSet<_>>();
+            let missing = items
+                .into_iter()
+                .filter(|item| !existing_items.contains(&item.to_lowercase()))
+                .collect::<Vec<_>>();
+            add_section_items(&mut folder.body, &missing);
+            return folder.guid;
+        }
+
+        let guid = if self.guids.contains(&entry.guid) {
+            guid_from_hash((&parent_key, &entry.name))
+        } else {
+            entry.guid
+        };
+        self.guids.insert(guid);
+
+        let mut body = Vec::new();
+        add_section_items(&mut body, &items);
+        self.document.add_project(ProjectEntry {
+            type_guid: FOLDER_UUID,
+            name: entry.name.clone(),
+            path: entry.name.clone(),
+            guid,
+            body,
+            header: None,
+        });
+        if let Some(parent) = parent {
+            self.document
+                .section_mut("NestedProjects", "preSolution")
+                .entries
+                .push(format!("{{{:X}}} = {{{:X}}}", guid, parent));
+        }
+        guid
+    }
+
+    fn rebase(&self, sln_dir: &Path, path: &str) -> String {
+        let abs_path = sln_dir.join(from_msbuild_path(path)).simplify();
+        to_msbuild_path(&relative_path(self.out_dir, &abs_path))
+    }
+}
+
+fn add_section_items(body: &mut Vec<String>, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    let lines = items
+        .iter()
+        .map(|item| format!("\t\t{0} = {0}", item))
+        .collect::<Vec<_>>();
+
+    let start = body
+        .iter()
+        .position(|line| line.trim().starts_with("ProjectSection(SolutionItems)"));
+    match start.and_then(|start| {
+        body[start..]
+            .iter()
+            .position(|line| line.trim() == "EndProjectSection")
+            .map(|end| start + end)
+    }) {
+        Some(end) => {
+            body.splice(end..end, lines);
+        }
+        None => {
+            body.push(String::from("\tProjectSection(SolutionItems) = preProject"));
+            body.extend(lines);
+            body.push(String::from("\tEndProjectSection"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FOLDER: &str = "2150E333-8FDC-42A3-9474-1A3956D46DE8";
+    const CSPROJ: &str = "FAE04EC0-301F-11D3-BF4B-00C04F79EFBC";
+
+    fn sln(entries: &[(&str, &str, &str, &str)], nested: &[(&str, &str)]) -> String {
+        let mut out =
+            String::from("\nMicrosoft Visual Studio Solution File, Format Version 12.00\n");
+        for (type_guid, name, path, guid) in entries.iter() {
+            out.push_str(&format!(
+                "Project(\"{{{}}}\") = \"{}\", \"{}\", \"{{{}}}\"\nEndProject\n",
+                type_guid, name, path, guid
+            ));
+        }
+        out.push_str("Global\n\tGlobalSection(SolutionConfigurationPlatforms) = preSolution\n\t\tDebug|Any CPU = Debug|Any CPU\n\tEndGlobalSection\n\tGlobalSection(NestedProjects) = preSolution\n");
+        for (child, parent) in nested.iter() {
+            out.push_str(&format!("\t\t{{{}}} = {{{}}}\n", child, parent));
+        }
+        out.push_str("\tEndGlobalSection\nEndGlobal\n");
+        out
+    }
+
+    #[test]
+    fn merge_deduplicates_projects_and_folders() {
+        let a = sln(
+            &[
+                (FOLDER, "src", "src", "00000000-0000-0000-0000-000000000001"),
+                (
+                    CSPROJ,
+                    "A",
+                    "src\\A\\A.csproj",
+                    "00000000-0000-0000-0000-00000000000A",
+                ),
+            ],
+            &[(
+                "00000000-0000-0000-0000-00000000000A",
+                "00000000-0000-0000-0000-000000000001",
+            )],
+        );
+        // Lives in a subdirectory, references A through a different relative path and uses the
+        // GUID of A for another project.
+        let b = sln(
+            &[
+                (FOLDER, "src", "src", "00000000-0000-0000-0000-000000000002"),
+                (
+                    CSPROJ,
+                    "A",
+                    "..\\src\\A\\A.csproj",
+                    "00000000-0000-0000-0000-00000000000A",
+                ),
+                (
+                    CSPROJ,
+                    "B",
+                    "..\\src\\B\\B.csproj",
+                    "00000000-0000-0000-0000-00000000000A",
+                ),
+            ],
+            &[(
+                "00000000-0000-0000-0000-00000000000A",
+                "00000000-0000-0000-0000-000000000002",
+            )],
+        );
+
+        let merged = merge(
+            Path::new("/repo/All.sln"),
+            &[
+                (PathBuf::from("/repo/A.sln"), a),
+                (PathBuf::from("/repo/b/B.sln"), b),
+            ],
+        );
+
+        let solution = Solution::parse(Path::new("/repo/All.sln"), &merged.contents);
+        assert_eq!(solution.folders.len(), 1);
+        assert_eq!(
+            solution
+                .projects
+                .iter()
+                .map(|project| project.rel_path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["src\\A\\A.csproj", "src\\B\\B.csproj"]
+        );
+        assert_eq!(solution.projects[0].parent, Some(solution.folders[0].guid));
+        assert_ne!(solution.projects[0].guid, solution.projects[1].guid);
+        assert_eq!(solution.configurations, vec!["Debug|Any CPU"]);
+        assert_eq!(merged.project_count, 2);
+        assert_eq!(merged.conflicts.len(), 1);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use super::document::Document;
+use crate::path_extensions::{from_msbuild_path, PathExt};
+
+/// A solution file read into its projects, folders, nesting and configurations. Unlike the
+/// document it does not keep the formatting, use it to inspect a solution rather than to edit it.
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub path: PathBuf,
+    pub projects: Vec<SolutionProject>,
+    pub folders: Vec<SolutionFolder>,
+    /// The solution configurations, like `Debug|Any CPU`.
+    pub configurations: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SolutionProject {
+    pub name: String,
+    /// The path as written in the solution, relative to the solution with backslashes.
+    pub rel_path: String,
+    /// The absolute path.
+    pub path: PathBuf,
+    pub guid: Uuid,
+    pub type_guid: Uuid,
+    /// The folder the project is nested in.
+    pub parent: Option<Uuid>,
+    /// The project configuration for each solution configuration.
+    pub configurations: BTreeMap<String, ProjectConfiguration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectConfiguration {
+    /// The project configuration and platform, like `Debug|x86`.
+    pub active: Option<String>,
+    pub build: bool,
+    pub deploy: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SolutionFolder {
+    pub name: String,
+    pub guid: Uuid,
+    /// The folder the folder is nested in.
+    pub parent: Option<Uuid>,
+    /// The solution items, relative to the solution with backslashes.
+    pub items: Vec<String>,
+}
+
+impl Solution {
+    pub fn read(sln_path: &Path) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(sln_path)?;
+        Ok(Self::parse(sln_path, &contents))
+    }
+
+    pub fn parse(sln_path: &Path, contents: &str) -> Self {
+        Self::from_document(sln_path, &Document::parse(contents))
+    }
+
+    pub fn from_document(sln_path: &Path, document: &Document) -> Self {
+        let sln_dir = sln_path.parent().unwrap_or_else(|| Path::new(""));
+
+        let parents = document
+            .section("NestedProjects")
+            .map(|section| {
+                section
+                    .pairs()
+                    .filter_map(|(child, parent)| {
+                        Some((parse_braced_guid(child)?, parse_braced_guid(parent)?))
+                    })
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let mut configurations = BTreeMap::<Uuid, BTreeMap<String, ProjectConfiguration>>::new();
+        if let Some(section) = document.section("ProjectConfigurationPlatforms") {
+            for (key, value) in section.pairs() {
+                let (guid, solution_configuration, kind) = match parse_configuration_key(key) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                let configuration = configurations
+                    .entry(guid)
+                    .or_default()
+                    .entry(solution_configuration.to_owned())
+                    .or_default();
+                match kind {
+                    "ActiveCfg" => configuration.active = Some(value.to_owned()),
+                    "Build.0" => configuration.build = true,
+                    "Deploy.0" => configuration.deploy = true,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut projects = Vec::new();
+        let mut folders = Vec::new();
+        for entry in document.projects() {
+            if entry.is_folder() {
+                folders.push(SolutionFolder {
+                    name: entry.name.clone(),
+                    guid: entry.guid,
+                    parent: parents.get(&entry.guid).copied(),
+                    items: solution_items(&entry.body),
+                });
+            } else {
+                projects.push(SolutionProject {
+                    name: entry.name.clone(),
+                    rel_path: entry.path.clone(),
+                    path: sln_dir.join(from_msbuild_path(&entry.path)).simplify(),
+                    guid: entry.guid,
+                    type_guid: entry.type_guid,
+                    parent: parents.get(&entry.guid).copied(),
+                    configurations: configurations.remove(&entry.guid).unwrap_or_default(),
+                });
+            }
+        }
+
+        Self {
+            path: sln_path.to_owned(),
+            projects,
+            folders,
+            configurations: document
+                .section("SolutionConfigurationPlatforms")
+                .map(|section| section.pairs().map(|(key, _)| key.to_owned()).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn folder(&self, guid: Uuid) -> Option<&SolutionFolder> {
+        self.folders.iter().find(|folder| folder.guid == guid)
+    }
+
+    /// Returns the names of the folders the folder or project with the GUID is nested in, from
+    /// the outermost to the innermost folder.
+    pub fn folder_path(&self, parent: Option<Uuid>) -> Vec<&str> {
+        let mut names = Vec::new();
+        let mut parent = parent;
+        while let Some(folder) = parent.and_then(|guid| self.folder(guid)) {
+            // Guard against nesting cycles in hand edited solutions.
+            if names.len() > self.folders.len() {
+                break;
+            }
+            names.push(folder.name.as_str());
+            parent = folder.parent;
+        }
+        names.reverse();
+        names
+    }
+}
+
+fn parse_braced_guid(value: &str) -> Option<Uuid> {
+    Uuid::parse_str(value.trim().trim_start_matches('{').trim_end_matches('}')).ok()
+}
+
+/// Splits `{GUID}.Debug|Any CPU.Build.0` into the GUID, the solution configuration and the kind
+/// of entry.
+fn parse_configuration_key(key: &str) -> Option<(Uuid, &str, &str)> {
+    let end = key.find('}')?;
+    let guid = parse_braced_guid(&key[..=end])?;
+    let rest = key[end + 1..].strip_prefix('.')?;
+    ["ActiveCfg", "Build.0", "Deploy.0"]
+        .iter()
+        .find_map(|kind| {
+            rest.strip_suffix(kind)
+                .and_then(|rest| rest.strip_suffix('.'))
+                .map(|solution_configuration| (guid, solution_configuration, *kind))
+        })
+}
+
+/// Returns the solution items of a folder, as written.
+pub(super) fn solution_items(body: &[String]) -> Vec<String> {
+    let mut in_items = false;
+    let mut items = Vec::new();
+    for line in body.iter().map(|line| line.trim()) {
+        if line.starts_with("ProjectSection(SolutionItems)") {
+            in_items = true;
+        } else if line == "EndProjectSection" {
+            in_items = false;
+        } else if in_items {
+            if let Some((item, _)) = line.split_once('=') {
+                items.push(item.trim().to_owned());
+            }
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLN: &'static str = "\n\
+Microsoft Visual Studio Solution File, Format Version 12.00\n\
+Project(\"{2150E333-8FDC-42A3-9474-1A3956D46DE8}\") = \"src\", \"src\", \"{11111111-1111-1111-1111-111111111111}\"\n\
+\tProjectSection(SolutionItems) = preProject\n\
+\t\tsrc\\Directory.Build.props = src\\Directory.Build.props\n\
+\tEndProjectSection\n\
+EndProject\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"src\\A\\A.csproj\", \"{22222222-2222-2222-2222-222222222222}\"\n\
+EndProject\n\
+Global\n\
+\tGlobalSection(SolutionConfigurationPlatforms) = preSolution\n\
+\t\tDebug|Any CPU = Debug|Any CPU\n\
+\t\tDebug|x64 = Debug|x64\n\
+\tEndGlobalSection\n\
+\tGlobalSection(ProjectConfigurationPlatforms) = postSolution\n\
+\t\t{22222222-2222-2222-2222-222222222222}.Debug|Any CPU.ActiveCfg = Debug|Any CPU\n\
+\t\t{22222222-2222-2222-2222-222222222222}.Debug|Any CPU.Build.0 = Debug|Any CPU\n\
+\t\t{22222222-2222-2222-2222-222222222222}.Debug|x64.ActiveCfg = Debug|x86\n\
+\tEndGlobalSection\n\
+\tGlobalSection(NestedProjects) = preSolution\n\
+\t\t{22222222-2222-2222-2222-222222222222} = {11111111-1111-1111-1111-111111111111}\n\
+\tEndGlobalSection\n\
+EndGlobal\n";
+
+    #[test]
+    fn solution_reads_projects_folders_and_configurations() {
+        let solution = Solution::from_document(Path::new("/repo/All.sln"), &Document::parse(SLN));
+
+        assert_eq!(solution.configurations, vec!["Debug|Any CPU", "Debug|x64"]);
+
+        let folder = &solution.folders[0];
+        assert_eq!(folder.name, "src");
+        assert_eq!(folder.items, vec!["src\\Directory.Build.props"]);
+
+        let project = &solution.projects[0];
+        assert_eq!(project.path, Path::new("/repo/src/A/A.csproj"));
+        assert_eq!(project.parent, Some(folder.guid));
+        assert_eq!(solution.folder_path(project.parent), vec!["src"]);
+        assert_eq!(
+            project.configurations["Debug|Any CPU"],
+            ProjectConfiguration {
+                active: Some(String::from("Debug|Any CPU")),
+                build: true,
+                deploy: false,
+            }
+        );
+        assert_eq!(
+            project.configurations["Debug|x64"].active.as_deref(),
+            Some("Debug|x86")
+        );
+        assert!(!project.configurations["Debug|x64"].build);
+    }
+}
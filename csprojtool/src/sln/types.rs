@@ -1,3 +1,16 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::config::{GuidCase, GuidStyle};
+
+lazy_static::lazy_static! {
+    static ref GUID_RE: regex::Regex = regex::Regex::new(
+        r"\{?([0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12})\}?"
+    )
+    .unwrap();
+}
+
 pub enum Configuration {
     Debug,
     Release,
@@ -35,3 +48,149 @@ pub const PROCESSOR_ARCHITECTURES: [ProcessorArchitecture; 3] = [
     ProcessorArchitecture::X64,
     ProcessorArchitecture::X86,
 ];
+
+/// The configurations and platforms of a solution, every combination is a solution
+/// configuration.
+#[derive(Debug, Clone)]
+pub struct ConfigurationMatrix {
+    pub configurations: Vec<String>,
+    pub platforms: Vec<String>,
+}
+
+impl Default for ConfigurationMatrix {
+    fn default() -> Self {
+        Self {
+            configurations: CONFIGURATIONS.iter().map(ToString::to_string).collect(),
+            platforms: PROCESSOR_ARCHITECTURES
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl ConfigurationMatrix {
+    /// Returns the configuration and platform of every solution configuration.
+    pub fn solution_configurations(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.configurations.iter().flat_map(move |conf| {
+            self.platforms
+                .iter()
+                .map(move |platform| (conf.as_str(), platform.as_str()))
+        })
+    }
+}
+
+/// Solutions write AnyCPU with a space while projects write it without.
+pub fn solution_platform_name(platform: &str) -> &str {
+    if platform.eq_ignore_ascii_case("AnyCPU") {
+        "Any CPU"
+    } else {
+        platform
+    }
+}
+
+/// Returns the project platform that is built for the solution platform, which is the solution
+/// platform itself if the project supports it and the first platform of the project otherwise.
+/// Projects without platforms build Any CPU.
+pub fn project_platform<'a>(
+    project_platforms: &'a [String],
+    solution_platform: &'a str,
+) -> &'a str {
+    if project_platforms.is_empty() {
+        return "Any CPU";
+    }
+    project_platforms
+        .iter()
+        .map(|platform| solution_platform_name(platform))
+        .find(|platform| platform.eq_ignore_ascii_case(solution_platform))
+        .unwrap_or_else(|| solution_platform_name(&project_platforms[0]))
+}
+
+/// Rewrites every GUID in the solution in the style, so that solutions written by other tools or
+/// by hand end up formatted the same as generated ones.
+pub fn normalize_guids(contents: &str, style: GuidStyle) -> String {
+    GUID_RE
+        .replace_all(contents, |captures: &regex::Captures| {
+            let guid = match style.case {
+                GuidCase::Upper => captures[1].to_uppercase(),
+                GuidCase::Lower => captures[1].to_lowercase(),
+            };
+            if style.braces {
+                format!("{{{}}}", guid)
+            } else {
+                guid
+            }
+        })
+        .into_owned()
+}
+
+/// Replaces the GUIDs wherever they appear in the solution, in the project entries as well as in
+/// the configuration and nesting sections. The replacements are written upper case with braces.
+pub fn replace_guids(contents: &str, replacements: &HashMap<Uuid, Uuid>) -> String {
+    GUID_RE
+        .replace_all(
+            contents,
+            |captures: &regex::Captures| match Uuid::parse_str(&captures[1])
+                .ok()
+                .and_then(|guid| replacements.get(&guid))
+            {
+                Some(replacement) => format!("{{{:X}}}", replacement),
+                None => captures[0].to_owned(),
+            },
+        )
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_platform_falls_back_to_the_first_project_platform() {
+        let any = Vec::new();
+        let x86 = vec![String::from("x86")];
+        let both = vec![String::from("AnyCPU"), String::from("x64")];
+
+        assert_eq!(project_platform(&any, "x64"), "Any CPU");
+        assert_eq!(project_platform(&x86, "Any CPU"), "x86");
+        assert_eq!(project_platform(&both, "x64"), "x64");
+        assert_eq!(project_platform(&both, "Any CPU"), "Any CPU");
+        assert_eq!(project_platform(&both, "x86"), "Any CPU");
+    }
+
+    #[test]
+    fn normalize_guids_rewrites_casing_and_braces() {
+        let contents = "Project(\"{fae04ec0-301f-11d3-bf4b-00c04f79efbc}\") = \"A\", \"A.csproj\", \"{AB12CD34-0000-0000-0000-0000000000EF}\"\n\
+\t\t{ab12cd34-0000-0000-0000-0000000000ef}.Debug|Any CPU.ActiveCfg = Debug|Any CPU\n";
+
+        assert_eq!(
+            normalize_guids(contents, GuidStyle::default()),
+            "Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"A.csproj\", \"{AB12CD34-0000-0000-0000-0000000000EF}\"\n\
+\t\t{AB12CD34-0000-0000-0000-0000000000EF}.Debug|Any CPU.ActiveCfg = Debug|Any CPU\n"
+        );
+        assert_eq!(
+            normalize_guids(
+                "SolutionGuid = {AB12CD34-0000-0000-0000-0000000000EF}",
+                GuidStyle {
+                    case: GuidCase::Lower,
+                    braces: false,
+                }
+            ),
+            "SolutionGuid = ab12cd34-0000-0000-0000-0000000000ef"
+        );
+    }
+
+    #[test]
+    fn replace_guids_rewrites_every_occurrence() {
+        let old = Uuid::parse_str("ab12cd34-0000-0000-0000-0000000000ef").unwrap();
+        let new = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let contents = "Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"A.csproj\", \"{AB12CD34-0000-0000-0000-0000000000EF}\"\n\
+\t\t{ab12cd34-0000-0000-0000-0000000000ef}.Debug|Any CPU.ActiveCfg = Debug|Any CPU\n";
+
+        assert_eq!(
+            replace_guids(contents, &std::iter::once((old, new)).collect()),
+            "Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"A.csproj\", \"{00000000-0000-0000-0000-000000000001}\"\n\
+\t\t{00000000-0000-0000-0000-000000000001}.Debug|Any CPU.ActiveCfg = Debug|Any CPU\n"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::{
+    path_extensions::{relative_path, PathExt},
+    sln::{ProjectConfiguration, Solution, SolutionProject},
+};
+
+const ARG_OLD: &'static str = "old";
+const ARG_NEW: &'static str = "new";
+const CMD_SLN_DIFF: &'static str = "sln-diff";
+
+#[derive(Debug)]
+pub struct SlnDiffCommand {
+    old_path: PathBuf,
+    new_path: PathBuf,
+}
+
+/// A structural difference between two solutions. Projects are identified by their path, or by
+/// their GUID when the path changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Difference {
+    ConfigurationAdded(String),
+    ConfigurationRemoved(String),
+    ProjectAdded(PathBuf),
+    ProjectRemoved(PathBuf),
+    PathChanged {
+        old: PathBuf,
+        new: PathBuf,
+    },
+    GuidChanged {
+        path: PathBuf,
+        old: Uuid,
+        new: Uuid,
+    },
+    FolderChanged {
+        path: PathBuf,
+        old: String,
+        new: String,
+    },
+    ProjectConfigurationChanged {
+        path: PathBuf,
+        configuration: String,
+        old: Option<ProjectConfiguration>,
+        new: Option<ProjectConfiguration>,
+    },
+}
+
+impl SlnDiffCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_SLN_DIFF)
+            .about("Compare the projects, folders and configurations of two solutions")
+            .arg(
+                Arg::with_name(ARG_OLD)
+                    .value_name("OLD_SLN_PATH")
+                    .help("The solution to compare with")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::with_name(ARG_NEW)
+                    .value_name("NEW_SLN_PATH")
+                    .help("The solution to compare")
+                    .required(true)
+                    .takes_value(true)
+                    .index(2),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_SLN_DIFF)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            old_path: PathBuf::from(matches.value_of_os(ARG_OLD).unwrap()),
+            new_path: PathBuf::from(matches.value_of_os(ARG_NEW).unwrap()),
+        }
+    }
+
+    pub fn execute(&self) {
+        let read = |path: &Path| {
+            let path = path.simplified_absolute().unwrap();
+            Solution::read(&path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e))
+        };
+        let old = read(&self.old_path);
+        let new = read(&self.new_path);
+
+        let differences = diff(&old, &new);
+        if differences.is_empty() {
+            println!("The solutions contain the same projects and configurations");
+            return;
+        }
+
+        let current_dir = std::env::current_dir().unwrap();
+        let display = |path: &Path| relative_path(&current_dir, path).display().to_string();
+
+        for difference in differences.iter() {
+            match difference {
+                Difference::ConfigurationAdded(configuration) => {
+                    println!("+ configuration {}", configuration)
+                }
+                Difference::ConfigurationRemoved(configuration) => {
+                    println!("- configuration {}", configuration)
+                }
+                Difference::ProjectAdded(path) => println!("+ {}", display(path)),
+                Difference::ProjectRemoved(path) => println!("- {}", display(path)),
+                Difference::PathChanged { old, new } => {
+                    println!("~ {} moved to {}", display(old), display(new))
+                }
+                Difference::GuidChanged { path, old, new } => println!(
+                    "~ {} GUID {{{:X}}} changed to {{{:X}}}",
+                    display(path),
+                    old,
+                    new
+                ),
+                Difference::FolderChanged { path, old, new } => println!(
+                    "~ {} folder {} changed to {}",
+                    display(path),
+                    folder_display(old),
+                    folder_display(new)
+                ),
+                Difference::ProjectConfigurationChanged {
+                    path,
+                    configuration,
+                    old,
+                    new,
+                } => println!(
+                    "~ {} {}: {} changed to {}",
+                    display(path),
+                    configuration,
+                    configuration_display(old.as_ref()),
+                    configuration_display(new.as_ref())
+                ),
+            }
+        }
+    }
+}
+
+fn folder_display(folder: &str) -> &str {
+    if folder.is_empty() {
+        "(root)"
+    } else {
+        folder
+    }
+}
+
+fn configuration_display(configuration: Option<&ProjectConfiguration>) -> String {
+    match configuration {
+        None => String::from("(none)"),
+        Some(configuration) => {
+            let mut out = configuration
+                .active
+                .clone()
+                .unwrap_or_else(|| String::from("(no active configuration)"));
+            if !configuration.build {
+                out.push_str(" not built");
+            }
+            if configuration.deploy {
+                out.push_str(" deployed");
+            }
+            out
+        }
+    }
+}
+
+fn diff(old: &Solution, new: &Solution) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    for configuration in new.configurations.iter() {
+        if !old.configurations.contains(configuration) {
+            differences.push(Difference::ConfigurationAdded(configuration.clone()));
+        }
+    }
+    for configuration in old.configurations.iter() {
+        if !new.configurations.contains(configuration) {
+            differences.push(Difference::ConfigurationRemoved(configuration.clone()));
+        }
+    }
+
+    // Pair the projects by path first, so that a changed GUID is reported as such, and then the
+    // remaining projects by GUID to find the ones that moved.
+    let mut pairs = Vec::new();
+    let mut unmatched_old = old.projects.iter().collect::<Vec<_>>();
+    let mut unmatched_new = Vec::new();
+    for new_project in new.projects.iter() {
+        match unmatched_old
+            .iter()
+            .position(|old_project| same_path(&old_project.path, &new_project.path))
+        {
+            Some(index) => pairs.push((unmatched_old.remove(index), new_project)),
+            None => unmatched_new.push(new_project),
+        }
+    }
+    unmatched_new.retain(|new_project| {
+        match unmatched_old
+            .iter()
+            .position(|old_project| old_project.guid == new_project.guid)
+        {
+            Some(index) => {
+                pairs.push((unmatched_old.remove(index), new_project));
+                false
+            }
+            None => true,
+        }
+    });
+
+    for project in unmatched_new {
+        differences.push(Difference::ProjectAdded(project.path.clone()));
+    }
+    for project in unmatched_old {
+        differences.push(Difference::ProjectRemoved(project.path.clone()));
+    }
+
+    pairs.sort_by(|a, b| a.1.path.cmp(&b.1.path));
+    for (old_project, new_project) in pairs {
+        differences.extend(project_differences(old, old_project, new, new_project));
+    }
+
+    differences
+}
+
+fn project_differences(
+    old: &Solution,
+    old_project: &SolutionProject,
+    new: &Solution,
+    new_project: &SolutionProject,
+) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let path = new_project.path.clone();
+
+    if !same_path(&old_project.path, &new_project.path) {
+        differences.push(Difference::PathChanged {
+            old: old_project.path.clone(),
+            new: new_project.path.clone(),
+        });
+    }
+
+    if old_project.guid != new_project.guid {
+        differences.push(Difference::GuidChanged {
+            path: path.clone(),
+            old: old_project.guid,
+            new: new_project.guid,
+        });
+    }
+
+    let old_folder = old.folder_path(old_project.parent).join("/");
+    let new_folder = new.folder_path(new_project.parent).join("/");
+    if !old_folder.eq_ignore_ascii_case(&new_folder) {
+        differences.push(Difference::FolderChanged {
+            path: path.clone(),
+            old: old_folder,
+            new: new_folder,
+        });
+    }
+
+    let configurations = old_project
+        .configurations
+        .keys()
+        .chain(new_project.configurations.keys())
+        .collect::<BTreeSet<_>>();
+    for configuration in configurations {
+        let old_configuration = old_project.configurations.get(configuration);
+        let new_configuration = new_project.configurations.get(configuration);
+        if old_configuration != new_configuration {
+            differences.push(Difference::ProjectConfigurationChanged {
+                path: path.clone(),
+                configuration: configuration.clone(),
+                old: old_configuration.cloned(),
+                new: new_configuration.cloned(),
+            });
+        }
+    }
+
+    differences
+}
+
+/// Compares paths case insensitively, because solutions are mostly edited on Windows.
+fn same_path(a: &Path, b: &Path) -> bool {
+    a.to_string_lossy()
+        .eq_ignore_ascii_case(&b.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solution(projects: &[(&str, &str)], nested: &[(&str, &str)]) -> Solution {
+        let mut contents = String::from("\nMicrosoft Visual Studio Solution File, Format Version 12.00\nProject(\"{2150E333-8FDC-42A3-9474-1A3956D46DE8}\") = \"src\", \"src\", \"{00000000-0000-0000-0000-000000000001}\"\nEndProject\n");
+        for (path, guid) in projects.iter() {
+            let name = Path::new(&path.replace('\\', "/"))
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned();
+            contents.push_str(&format!("Project(\"{{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}}\") = \"{}\", \"{}\", \"{{{}}}\"\nEndProject\n", name, path, guid));
+        }
+        contents.push_str("Global\n\tGlobalSection(NestedProjects) = preSolution\n");
+        for (child, parent) in nested.iter() {
+            contents.push_str(&format!("\t\t{{{}}} = {{{}}}\n", child, parent));
+        }
+        contents.push_str("\tEndGlobalSection\nEndGlobal\n");
+        Solution::parse(Path::new("/repo/All.sln"), &contents)
+    }
+
+    const A: &str = "00000000-0000-0000-0000-00000000000A";
+    const B: &str = "00000000-0000-0000-0000-00000000000B";
+    const C: &str = "00000000-0000-0000-0000-00000000000C";
+    const SRC: &str = "00000000-0000-0000-0000-000000000001";
+
+    #[test]
+    fn diff_reports_projects_by_path_then_guid() {
+        let old = solution(
+            &[("src\\A\\A.csproj", A), ("src\\B\\B.csproj", B)],
+            &[(A, SRC)],
+        );
+        let new = solution(
+            &[
+                ("src\\A\\A.csproj", C),
+                ("lib\\B\\B.csproj", B),
+                ("src\\D\\D.csproj", A),
+            ],
+            &[],
+        );
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Difference::ProjectAdded(PathBuf::from("/repo/src/D/D.csproj")),
+                Difference::PathChanged {
+                    old: PathBuf::from("/repo/src/B/B.csproj"),
+                    new: PathBuf::from("/repo/lib/B/B.csproj"),
+                },
+                Difference::GuidChanged {
+                    path: PathBuf::from("/repo/src/A/A.csproj"),
+                    old: Uuid::parse_str(A).unwrap(),
+                    new: Uuid::parse_str(C).unwrap(),
+                },
+                Difference::FolderChanged {
+                    path: PathBuf::from("/repo/src/A/A.csproj"),
+                    old: String::from("src"),
+                    new: String::new(),
+                },
+            ]
+        );
+    }
+}
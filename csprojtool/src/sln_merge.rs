@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use crate::output::status;
+use crate::{
+    cli,
+    path_extensions::{relative_path, PathExt},
+    plan::Effects,
+    sln,
+};
+
+const ARG_INPUT: &'static str = "input";
+const ARG_OUTPUT: &'static str = "output";
+const CMD_SLN_MERGE: &'static str = "sln-merge";
+
+#[derive(Debug)]
+pub struct SlnMergeCommand {
+    output_path: PathBuf,
+    input_paths: Vec<PathBuf>,
+    plan_json: Option<PathBuf>,
+}
+
+impl SlnMergeCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_SLN_MERGE)
+            .about("Write a solution with the projects of several solutions")
+            .arg(
+                Arg::with_name(ARG_OUTPUT)
+                    .value_name("OUT_SLN_PATH")
+                    .help("The solution to write")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::with_name(ARG_INPUT)
+                    .value_name("SLN_PATH")
+                    .help("The solutions to merge, the first provides the solution properties")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)
+                    .index(2),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_SLN_MERGE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            output_path: PathBuf::from(matches.value_of_os(ARG_OUTPUT).unwrap()),
+            input_paths: matches
+                .values_of_os(ARG_INPUT)
+                .unwrap()
+                .map(PathBuf::from)
+                .collect(),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let output_path = self.output_path.simplified_absolute().unwrap();
+        let solutions = self
+            .input_paths
+            .iter()
+            .map(|path| {
+                let path = path.simplified_absolute().unwrap();
+                let contents = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+                (path, contents)
+            })
+            .collect::<Vec<_>>();
+
+        let merged = sln::merge(&output_path, &solutions);
+        let mut effects = Effects::new(CMD_SLN_MERGE, self.plan_json.as_deref());
+        effects
+            .write_file(&output_path, merged.contents, Vec::new())
+            .unwrap();
+        effects.finish();
+
+        let current_dir = std::env::current_dir().unwrap();
+        status!(
+            "Wrote {} with {} projects from {} solutions",
+            relative_path(&current_dir, &output_path).display(),
+            merged.project_count,
+            solutions.len()
+        );
+        for conflict in merged.conflicts.iter() {
+            status!("GUID conflict: {}", conflict);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::output::status;
+use crate::{
+    cli, list,
+    path_extensions::{from_msbuild_path, relative_path, to_msbuild_path, PathExt},
+    plan::Effects,
+    sln,
+};
+
+const ARG_OUTPUT: &'static str = "output";
+const ARG_ROOT: &'static str = "root";
+const ARG_SLN: &'static str = "sln";
+const CMD_SLNF: &'static str = "slnf";
+
+#[derive(Debug)]
+pub struct SlnfCommand {
+    sln_path: PathBuf,
+    roots: Vec<PathBuf>,
+    output_path: Option<PathBuf>,
+    plan_json: Option<PathBuf>,
+}
+
+/// The contents of a solution filter, see
+/// https://learn.microsoft.com/visualstudio/ide/filtered-solutions.
+#[derive(Debug, Serialize, Deserialize)]
+struct SolutionFilter {
+    solution: FilteredSolution,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FilteredSolution {
+    /// The solution relative to the filter.
+    path: String,
+    /// The projects relative to the solution.
+    projects: Vec<String>,
+}
+
+impl SlnfCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_SLNF)
+            .about("Write a solution filter with the root projects and everything they reference")
+            .arg(
+                Arg::with_name(ARG_SLN)
+                    .long("sln")
+                    .value_name("SLN_PATH")
+                    .help("The solution to filter")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name(ARG_ROOT)
+                    .long("root")
+                    .value_name("PATH")
+                    .help("A project or a directory of projects to include, can be passed multiple times")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name(ARG_OUTPUT)
+                    .long("output")
+                    .short("o")
+                    .value_name("SLNF_PATH")
+                    .help("The filter to write, defaults to the name of the first root next to the solution")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_SLNF).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            sln_path: PathBuf::from(matches.value_of_os(ARG_SLN).unwrap()),
+            roots: matches
+                .values_of_os(ARG_ROOT)
+                .unwrap()
+                .map(PathBuf::from)
+                .collect(),
+            output_path: matches.value_of_os(ARG_OUTPUT).map(PathBuf::from),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let sln_path = self.sln_path.simplified_absolute().unwrap();
+        let sln_dir = sln_path.parent().unwrap();
+        let current_dir = std::env::current_dir().unwrap();
+
+        let canonical =
+            |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+
+        let sln_projects = sln::read_project_paths(&sln_path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", sln_path.display(), e))
+            .into_iter()
+            .map(|path| (canonical(&path), path))
+            .collect::<Vec<_>>();
+
+        let mut closure = BTreeSet::new();
+        for root in self.roots.iter() {
+            closure.extend(
+                list::list(list::Options {
+                    search_path: root,
+                    follow_incoming_project_references: false,
+                    follow_outgoing_project_references: true,
+                })
+                .into_iter()
+                .map(|project| project.path),
+            );
+        }
+
+        for path in closure
+            .iter()
+            .filter(|path| !sln_projects.iter().any(|(canonical, _)| canonical == *path))
+        {
+            warn!(
+                "{} is referenced but not part of {}",
+                relative_path(&current_dir, path).display(),
+                relative_path(&current_dir, &sln_path).display()
+            );
+        }
+
+        let projects = sln_projects
+            .iter()
+            .filter(|(canonical, _)| closure.contains(canonical))
+            .map(|(_, path)| to_msbuild_path(&relative_path(sln_dir, path)))
+            .collect::<Vec<_>>();
+
+        let output_path = match &self.output_path {
+            Some(output_path) => output_path.simplified_absolute().unwrap(),
+            None => {
+                let root = self.roots[0].simplified_absolute().unwrap();
+                sln_dir.join(format!(
+                    "{}.slnf",
+                    root.file_stem().unwrap().to_str().unwrap()
+                ))
+            }
+        };
+
+        let filter = SolutionFilter {
+            solution: FilteredSolution {
+                path: to_msbuild_path(&relative_path(output_path.parent().unwrap(), &sln_path)),
+                projects,
+            },
+        };
+
+        let mut effects = Effects::new(CMD_SLNF, self.plan_json.as_deref());
+        effects
+            .write_file(
+                &output_path,
+                serde_json::to_string_pretty(&filter).unwrap() + "\n",
+                Vec::new(),
+            )
+            .unwrap();
+        effects.finish();
+
+        status!(
+            "Wrote {} with {} projects",
+            relative_path(&current_dir, &output_path).display(),
+            filter.solution.projects.len()
+        );
+    }
+}
+
+/// Reads a solution filter into the absolute paths of the solution it filters and of the
+/// projects it includes.
+pub fn read_filter(slnf_path: &Path) -> std::io::Result<(PathBuf, Vec<PathBuf>)> {
+    let contents = crate::encoding::read_to_string(slnf_path)?;
+    let filter = serde_json::from_str::<SolutionFilter>(&contents)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let sln_path = slnf_path
+        .parent()
+        .unwrap()
+        .join(from_msbuild_path(&filter.solution.path))
+        .simplify();
+    let sln_dir = sln_path.parent().unwrap();
+    let projects = filter
+        .solution
+        .projects
+        .iter()
+        .map(|path| sln_dir.join(from_msbuild_path(path)).simplify())
+        .collect();
+    Ok((sln_path, projects))
+}
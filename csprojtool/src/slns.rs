@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    cli, git, output,
+    path_extensions::{relative_path, PathExt},
+    sln::Solution,
+    utils::find_sln_files,
+};
+
+const ARG_FORMAT: &'static str = "format";
+const CMD_SLNS: &'static str = "slns";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct SlnsCommand {
+    search_path: PathBuf,
+    format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    path: PathBuf,
+    projects: usize,
+    /// The number of projects in the solution that do not exist.
+    missing_projects: usize,
+    /// The date of the last commit that changed the solution, absent when it was never committed.
+    last_commit: Option<String>,
+}
+
+impl SlnsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_SLNS)
+            .about("List the solutions with their number of projects, missing projects and last commit")
+            .arg(cli::arg_search())
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_SLNS).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) {
+        let search_path = self.search_path.simplified_absolute().unwrap();
+        let current_dir = std::env::current_dir().unwrap();
+
+        let summaries = find_sln_files(&search_path)
+            .into_iter()
+            .filter_map(|sln_path| match Solution::read(&sln_path) {
+                Ok(solution) => Some(Summary {
+                    path: relative_path(&current_dir, &sln_path),
+                    projects: solution.projects.len(),
+                    missing_projects: solution
+                        .projects
+                        .iter()
+                        .filter(|project| !project.path.exists())
+                        .count(),
+                    last_commit: git::last_commit_date(&sln_path),
+                }),
+                Err(e) => {
+                    warn!("Failed to read {}: {}", sln_path.display(), e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                println!(
+                    "{:>8}  {:>7}  {:<11}  solution",
+                    "projects", "missing", "last commit"
+                );
+                for summary in summaries.iter() {
+                    println!(
+                        "{:>8}  {:>7}  {:<11}  {}",
+                        summary.projects,
+                        summary.missing_projects,
+                        summary.last_commit.as_deref().unwrap_or("-"),
+                        summary.path.display()
+                    );
+                }
+            }
+            Format::Json => {
+                serde_json::to_writer_pretty(std::io::stdout(), &summaries).unwrap();
+                println!();
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use crate::output::status;
+use crate::{cli, fmt::sort_items, list, path_extensions::relative_path, plan::Effects};
+
+const CMD_SORT: &'static str = "sort";
+
+/// The items that `sort` orders by Include.
+const SORTED_ITEMS: &[&str] = &["ProjectReference", "PackageReference", "Compile"];
+
+#[derive(Debug)]
+pub struct SortCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+impl SortCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_SORT)
+            .about("Order ProjectReference, PackageReference and Compile items by Include within their ItemGroups")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_SORT).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_SORT, self.plan_json.as_deref());
+
+        let mut sorted_count = 0;
+        for project in projects.iter() {
+            let mut sorted = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    sorted = sort_items(&mut root, SORTED_ITEMS);
+                    if sorted {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if sorted {
+                status!(
+                    "Sorted {}",
+                    relative_path(&current_dir, &project.path).display()
+                );
+                sorted_count += 1;
+            }
+        }
+
+        if sorted_count == 0 {
+            status!("All {} projects are sorted", projects.len());
+        }
+
+        effects.finish();
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::{self, status};
+use crate::{
+    chains::longest_chains, check, cli, csproj::Project, list, path_extensions::relative_path,
+    tfms, utils::find_sln_files,
+};
+
+const ARG_APPEND_HISTORY: &'static str = "append-history";
+const ARG_HISTORY: &'static str = "history";
+const ARG_OUTPUT: &'static str = "output";
+const CMD_STATS: &'static str = "stats";
+const CMD_TREND: &'static str = "trend";
+
+const CHART_WIDTH: usize = 800;
+const CHART_HEIGHT: usize = 240;
+const CHART_MARGIN: usize = 40;
+
+/// The colors of the series in a chart, reused when a chart has more series.
+pub const COLORS: &[&str] = &[
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f", "#bab0ac",
+];
+
+#[derive(Debug)]
+pub enum StatsCommand {
+    Summary(SummaryCommand),
+    Trend(TrendCommand),
+}
+
+#[derive(Debug)]
+pub struct SummaryCommand {
+    search_path: PathBuf,
+    history_path: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct TrendCommand {
+    history_path: PathBuf,
+    output_path: PathBuf,
+}
+
+/// The statistics of one run, stored as a line in the history file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    projects: usize,
+    /// The number of projects per target framework.
+    target_frameworks: BTreeMap<String, usize>,
+    /// The number of findings per check rule.
+    findings: BTreeMap<String, usize>,
+    /// The number of projects per Sdk attribute, `(legacy)` for projects without one.
+    #[serde(default)]
+    sdks: BTreeMap<String, usize>,
+    /// The number of projects per type, `exe`, `test` or `library`.
+    #[serde(default)]
+    project_types: BTreeMap<String, usize>,
+    #[serde(default)]
+    project_references: ReferenceCounts,
+    #[serde(default)]
+    package_references: ReferenceCounts,
+    /// The number of distinct packages that the projects reference.
+    #[serde(default)]
+    packages: usize,
+    /// The number of projects in the longest chain of project references.
+    #[serde(default)]
+    deepest_chain: usize,
+    #[serde(default)]
+    solutions: usize,
+}
+
+/// The references per project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ReferenceCounts {
+    total: usize,
+    average: f64,
+    max: usize,
+}
+
+impl StatsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::{Arg, SubCommand};
+
+        SubCommand::with_name(CMD_STATS)
+            .about("Print statistics about the projects")
+            .arg(cli::arg_search())
+            .arg(
+                Arg::with_name(ARG_APPEND_HISTORY)
+                    .long("append-history")
+                    .value_name("NDJSON_PATH")
+                    .help("Append the statistics with a timestamp to the history file")
+                    .takes_value(true),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_TREND)
+                    .about("Chart the statistics in a history file as an HTML report")
+                    .arg(
+                        Arg::with_name(ARG_HISTORY)
+                            .value_name("NDJSON_PATH")
+                            .help("The history file written by --append-history")
+                            .required(true)
+                            .takes_value(true)
+                            .index(1),
+                    )
+                    .arg(
+                        Arg::with_name(ARG_OUTPUT)
+                            .long("output")
+                            .short("o")
+                            .value_name("HTML_PATH")
+                            .help("The report to write")
+                            .default_value("stats.html")
+                            .takes_value(true),
+                    ),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_STATS)?;
+
+        Some(match matches.subcommand() {
+            (CMD_TREND, Some(matches)) => Self::Trend(TrendCommand {
+                history_path: PathBuf::from(matches.value_of_os(ARG_HISTORY).unwrap()),
+                output_path: PathBuf::from(matches.value_of_os(ARG_OUTPUT).unwrap()),
+            }),
+            _ => Self::Summary(SummaryCommand {
+                search_path: cli::get_search_path(matches),
+                history_path: matches.value_of_os(ARG_APPEND_HISTORY).map(PathBuf::from),
+            }),
+        })
+    }
+
+    pub fn execute(&self) {
+        match self {
+            Self::Summary(command) => command.execute(),
+            Self::Trend(command) => command.execute(),
+        }
+    }
+}
+
+impl SummaryCommand {
+    fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+
+        let mut findings = BTreeMap::<String, usize>::new();
+        for finding in check::check_all(&projects) {
+            *findings.entry(finding.rule.to_owned()).or_default() += 1;
+        }
+
+        let record = Record {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            projects: projects.len(),
+            target_frameworks: tfms::group_by_target_framework(&projects)
+                .into_iter()
+                .map(|(target_framework, paths)| (target_framework, paths.len()))
+                .collect(),
+            findings,
+            sdks: count_by(&projects, |project| {
+                project
+                    .sdk
+                    .clone()
+                    .unwrap_or_else(|| String::from("(legacy)"))
+            }),
+            project_types: count_by(&projects, |project| {
+                String::from(if project.is_test_project() {
+                    "test"
+                } else if project.is_exe {
+                    "exe"
+                } else {
+                    "library"
+                })
+            }),
+            project_references: reference_counts(
+                projects
+                    .iter()
+                    .map(|project| project.project_references.len()),
+            ),
+            package_references: reference_counts(
+                projects
+                    .iter()
+                    .map(|project| project.package_references.len()),
+            ),
+            packages: projects
+                .iter()
+                .flat_map(|project| project.package_references.iter())
+                .map(|package| package.name.to_lowercase())
+                .collect::<BTreeSet<_>>()
+                .len(),
+            deepest_chain: deepest_chain(projects.iter().map(|project| {
+                (
+                    project.path.as_path(),
+                    project.project_references.as_slice(),
+                )
+            })),
+            solutions: if self.search_path.is_dir() {
+                find_sln_files(&self.search_path).len()
+            } else {
+                0
+            },
+        };
+
+        if output::is_json() {
+            output::print_json(&record);
+        } else {
+            print_summary(&record);
+        }
+
+        if let Some(history_path) = &self.history_path {
+            append_record(history_path, &record);
+            let current_dir = std::env::current_dir().unwrap();
+            status!("");
+            status!(
+                "Appended the statistics to {}",
+                relative_path(&current_dir, history_path).display()
+            );
+        }
+    }
+}
+
+fn print_summary(record: &Record) {
+    println!("{:>6}  projects", record.projects);
+    println!("{:>6}  solutions", record.solutions);
+    println!("{:>6}  distinct packages", record.packages);
+    println!(
+        "{:>6}  projects in the deepest chain of project references",
+        record.deepest_chain
+    );
+    println!();
+    println!("SDKs:");
+    for (sdk, count) in record.sdks.iter() {
+        println!("{:>6}  {}", count, sdk);
+    }
+    println!();
+    println!("Project types:");
+    for (project_type, count) in record.project_types.iter() {
+        println!("{:>6}  {}", count, project_type);
+    }
+    println!();
+    println!("References per project:");
+    for (name, counts) in [
+        ("project references", &record.project_references),
+        ("package references", &record.package_references),
+    ]
+    .iter()
+    {
+        println!(
+            "{:>6}  {}, {:.1} on average and at most {}",
+            counts.total, name, counts.average, counts.max
+        );
+    }
+    println!();
+    println!("Target frameworks:");
+    for (target_framework, count) in record.target_frameworks.iter() {
+        println!("{:>6}  {}", count, target_framework);
+    }
+    println!();
+    println!("Check findings:");
+    for (rule, count) in record.findings.iter() {
+        println!("{:>6}  {}", count, rule);
+    }
+}
+
+impl TrendCommand {
+    fn execute(&self) {
+        let records = read_history(&self.history_path);
+        if records.is_empty() {
+            panic!("{} contains no statistics", self.history_path.display());
+        }
+
+        std::fs::write(&self.output_path, render_trend(&records))
+            .unwrap_or_else(|e| panic!("Failed to write {}: {}", self.output_path.display(), e));
+
+        let current_dir = std::env::current_dir().unwrap();
+        status!(
+            "Wrote {} with {} records",
+            relative_path(&current_dir, &self.output_path).display(),
+            records.len()
+        );
+    }
+}
+
+fn count_by<F>(projects: &[Project], key: F) -> BTreeMap<String, usize>
+where
+    F: Fn(&Project) -> String,
+{
+    let mut counts = BTreeMap::new();
+    for project in projects.iter() {
+        *counts.entry(key(project)).or_default() += 1;
+    }
+    counts
+}
+
+fn reference_counts(counts: impl Iterator<Item = usize>) -> ReferenceCounts {
+    let counts = counts.collect::<Vec<_>>();
+    let total = counts.iter().sum::<usize>();
+    ReferenceCounts {
+        total,
+        average: if counts.is_empty() {
+            0.0
+        } else {
+            total as f64 / counts.len() as f64
+        },
+        max: counts.iter().copied().max().unwrap_or_default(),
+    }
+}
+
+/// Returns the number of projects in the longest chain of references.
+fn deepest_chain<'a>(projects: impl Iterator<Item = (&'a Path, &'a [PathBuf])>) -> usize {
+    longest_chains(projects).first().map_or(0, Vec::len)
+}
+
+fn append_record(history_path: &Path, record: &Record) {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path)
+        .unwrap_or_else(|e| panic!("Failed to open {}: {}", history_path.display(), e));
+    writeln!(file, "{}", serde_json::to_string(record).unwrap())
+        .unwrap_or_else(|e| panic!("Failed to write {}: {}", history_path.display(), e));
+}
+
+fn read_history(history_path: &Path) -> Vec<Record> {
+    let contents = std::fs::read_to_string(history_path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", history_path.display(), e));
+
+    let mut records = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str::<Record>(line).unwrap_or_else(|e| {
+                panic!(
+                    "Failed to parse {}:{}: {}",
+                    history_path.display(),
+                    index + 1,
+                    e
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    records.sort_by_key(|record| record.timestamp);
+    records
+}
+
+/// Renders an HTML page with a line chart of the project count, the target framework
+/// distribution and the check findings over time.
+fn render_trend(records: &[Record]) -> String {
+    let dates = records
+        .iter()
+        .map(|record| format_date(record.timestamp))
+        .collect::<Vec<_>>();
+
+    let projects = vec![(
+        String::from("projects"),
+        records.iter().map(|record| record.projects).collect(),
+    )];
+    let target_frameworks = series(records, |record| &record.target_frameworks);
+    let findings = series(records, |record| &record.findings);
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<title>csprojtool stats</title>\n<style>\n");
+    out.push_str("body { font-family: sans-serif; margin: 2em; }\n");
+    out.push_str(".legend { list-style: none; padding: 0; }\n");
+    out.push_str(".legend li { display: inline-block; margin-right: 1.5em; }\n");
+    out.push_str(".swatch { display: inline-block; width: 1em; height: 1em; margin-right: 0.3em; vertical-align: middle; }\n");
+    out.push_str("</style>\n</head>\n<body>\n<h1>csprojtool stats</h1>\n");
+    out.push_str(&render_chart("Projects", &dates, &projects));
+    out.push_str(&render_chart(
+        "Target frameworks",
+        &dates,
+        &target_frameworks,
+    ));
+    out.push_str(&render_chart("Check findings", &dates, &findings));
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Turns the counts of the records into a series per key, records without the key count zero.
+fn series<F>(records: &[Record], counts: F) -> Vec<(String, Vec<usize>)>
+where
+    F: Fn(&Record) -> &BTreeMap<String, usize>,
+{
+    let keys = records
+        .iter()
+        .flat_map(|record| counts(record).keys())
+        .collect::<BTreeSet<_>>();
+
+    keys.into_iter()
+        .map(|key| {
+            (
+                key.clone(),
+                records
+                    .iter()
+                    .map(|record| counts(record).get(key).copied().unwrap_or_default())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+fn render_chart(title: &str, dates: &[String], series: &[(String, Vec<usize>)]) -> String {
+    let mut out = format!("<h2>{}</h2>\n", escape(title));
+    if series.is_empty() {
+        out.push_str("<p>None recorded.</p>\n");
+        return out;
+    }
+
+    let max = series
+        .iter()
+        .flat_map(|(_, values)| values.iter().copied())
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let plot_width = CHART_WIDTH - 2 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2 * CHART_MARGIN;
+    let x = |index: usize| {
+        CHART_MARGIN
+            + if dates.len() > 1 {
+                index * plot_width / (dates.len() - 1)
+            } else {
+                plot_width / 2
+            }
+    };
+    let y = |value: usize| CHART_MARGIN + plot_height - value * plot_height / max;
+
+    out.push_str(&format!(
+        "<svg width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        CHART_WIDTH, CHART_HEIGHT
+    ));
+    out.push_str(&format!(
+        "<polyline points=\"{0},{1} {0},{2} {3},{2}\" fill=\"none\" stroke=\"#888\"/>\n",
+        CHART_MARGIN,
+        CHART_MARGIN,
+        CHART_MARGIN + plot_height,
+        CHART_MARGIN + plot_width
+    ));
+    out.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" font-size=\"12\">{}</text>\n",
+        CHART_MARGIN - 4,
+        CHART_MARGIN + 4,
+        max
+    ));
+    out.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" font-size=\"12\">0</text>\n",
+        CHART_MARGIN - 4,
+        CHART_MARGIN + plot_height + 4
+    ));
+    if let (Some(first), Some(last)) = (dates.first(), dates.last()) {
+        let label_y = CHART_MARGIN + plot_height + 20;
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\">{}</text>\n",
+            CHART_MARGIN, label_y, first
+        ));
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" font-size=\"12\">{}</text>\n",
+            CHART_MARGIN + plot_width,
+            label_y,
+            last
+        ));
+    }
+
+    for (index, (name, values)) in series.iter().enumerate() {
+        let color = COLORS[index % COLORS.len()];
+        let points = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| format!("{},{}", x(index), y(*value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        out.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"><title>{}</title></polyline>\n",
+            points,
+            color,
+            escape(name)
+        ));
+    }
+    out.push_str("</svg>\n<ul class=\"legend\">\n");
+
+    for (index, (name, values)) in series.iter().enumerate() {
+        out.push_str(&format!(
+            "<li><span class=\"swatch\" style=\"background: {}\"></span>{} ({})</li>\n",
+            COLORS[index % COLORS.len()],
+            escape(name),
+            values.last().copied().unwrap_or_default()
+        ));
+    }
+    out.push_str("</ul>\n");
+
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats seconds since the Unix epoch as a UTC date, see
+/// https://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn format_date(timestamp: u64) -> String {
+    let z = timestamp / 86400 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: u64, target_frameworks: &[(&str, usize)]) -> Record {
+        Record {
+            timestamp,
+            projects: target_frameworks.iter().map(|(_, count)| count).sum(),
+            target_frameworks: target_frameworks
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect(),
+            findings: BTreeMap::new(),
+            sdks: BTreeMap::new(),
+            project_types: BTreeMap::new(),
+            project_references: ReferenceCounts::default(),
+            package_references: ReferenceCounts::default(),
+            packages: 0,
+            deepest_chain: 0,
+            solutions: 0,
+        }
+    }
+
+    #[test]
+    fn deepest_chain_counts_the_projects_and_survives_cycles() {
+        let references = [
+            (PathBuf::from("/A.csproj"), vec![]),
+            (PathBuf::from("/B.csproj"), vec![PathBuf::from("/A.csproj")]),
+            (
+                PathBuf::from("/C.csproj"),
+                vec![PathBuf::from("/B.csproj"), PathBuf::from("/Missing.csproj")],
+            ),
+            (PathBuf::from("/D.csproj"), vec![PathBuf::from("/E.csproj")]),
+            (PathBuf::from("/E.csproj"), vec![PathBuf::from("/D.csproj")]),
+        ];
+
+        assert_eq!(
+            deepest_chain(
+                references
+                    .iter()
+                    .map(|(path, references)| (path.as_path(), references.as_slice()))
+            ),
+            3
+        );
+    }
+
+    #[test]
+    fn format_date_converts_unix_timestamps() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951782400), "2000-02-29");
+        assert_eq!(format_date(1700000000), "2023-11-14");
+    }
+
+    #[test]
+    fn series_fills_missing_keys_with_zero() {
+        let records = vec![
+            record(0, &[("net48", 3)]),
+            record(86400, &[("net48", 2), ("net8.0", 1)]),
+        ];
+
+        assert_eq!(
+            series(&records, |record| &record.target_frameworks),
+            vec![
+                (String::from("net48"), vec![3, 2]),
+                (String::from("net8.0"), vec![0, 1]),
+            ]
+        );
+    }
+}
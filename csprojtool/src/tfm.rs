@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use log::warn;
+
+use crate::output::status;
+use crate::{
+    cli, list,
+    path_extensions::relative_path,
+    plan::Effects,
+    xml_extensions::{get_target_frameworks, set_target_frameworks},
+};
+
+const ARG_TARGET_FRAMEWORK: &'static str = "target-framework";
+const CMD_TFM: &'static str = "tfm";
+const CMD_ADD: &'static str = "add";
+const CMD_REMOVE: &'static str = "remove";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Add,
+    Remove,
+}
+
+#[derive(Debug)]
+pub struct TfmCommand {
+    action: Action,
+    target_framework: String,
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    plan_json: Option<PathBuf>,
+}
+
+impl TfmCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::{Arg, SubCommand};
+
+        let edit = |name: &'static str, about: &'static str| {
+            SubCommand::with_name(name)
+                .about(about)
+                .arg(
+                    Arg::with_name(ARG_TARGET_FRAMEWORK)
+                        .value_name("TFM")
+                        .help("The target framework, like net8.0")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(cli::arg_search())
+                .arg(cli::arg_glob())
+                .arg(cli::arg_plan_json())
+        };
+
+        SubCommand::with_name(CMD_TFM)
+            .about("Edit the target frameworks of projects")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(edit(CMD_ADD, "Add a target framework to projects"))
+            .subcommand(edit(CMD_REMOVE, "Remove a target framework from projects"))
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        let matches = matches.subcommand_matches(CMD_TFM)?;
+
+        let (action, matches) = match matches.subcommand() {
+            (CMD_ADD, Some(matches)) => (Action::Add, matches),
+            (CMD_REMOVE, Some(matches)) => (Action::Remove, matches),
+            _ => return None,
+        };
+
+        Some(Self {
+            action,
+            target_framework: matches.value_of(ARG_TARGET_FRAMEWORK).unwrap().to_owned(),
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            plan_json: cli::get_plan_json(matches),
+        })
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut effects = Effects::new(CMD_TFM, self.plan_json.as_deref());
+
+        for project in projects.iter() {
+            let rel_path = relative_path(&current_dir, &project.path);
+
+            let mut edited = false;
+            effects
+                .transform_xml_file(&project.path, |mut root| {
+                    let mut target_frameworks = match get_target_frameworks(&root) {
+                        Some(target_frameworks) => target_frameworks,
+                        None => {
+                            warn!(
+                                "Skipping {} which does not set TargetFramework(s)",
+                                rel_path.display()
+                            );
+                            return None;
+                        }
+                    };
+                    if target_frameworks.iter().any(|value| value.contains("$(")) {
+                        warn!(
+                            "Skipping {} whose target frameworks use properties",
+                            rel_path.display()
+                        );
+                        return None;
+                    }
+
+                    let index = target_frameworks
+                        .iter()
+                        .position(|value| value.eq_ignore_ascii_case(&self.target_framework));
+                    match (self.action, index) {
+                        (Action::Add, None) => {
+                            target_frameworks.push(self.target_framework.clone())
+                        }
+                        (Action::Remove, Some(_)) if target_frameworks.len() == 1 => {
+                            warn!(
+                                "Not removing the only target framework of {}",
+                                rel_path.display()
+                            );
+                            return None;
+                        }
+                        (Action::Remove, Some(index)) => {
+                            target_frameworks.remove(index);
+                        }
+                        _ => return None,
+                    }
+
+                    edited = set_target_frameworks(&mut root, &target_frameworks);
+                    if edited {
+                        Some(root)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+
+            if edited {
+                match self.action {
+                    Action::Add => {
+                        status!("Added {} to {}", self.target_framework, rel_path.display())
+                    }
+                    Action::Remove => status!(
+                        "Removed {} from {}",
+                        self.target_framework,
+                        rel_path.display()
+                    ),
+                }
+            }
+        }
+
+        effects.finish();
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    cli, csproj::Project, evaluation::Evaluator, list, output, path_extensions::relative_path,
+};
+
+const CMD_TFMS: &'static str = "tfms";
+
+#[derive(Debug, Serialize)]
+struct Group<'a> {
+    target_framework: &'a str,
+    projects: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct TfmsCommand {
+    search_path: PathBuf,
+    follow_project_references: bool,
+}
+
+impl TfmsCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_TFMS)
+            .about("List the target frameworks of projects, grouped by target framework")
+            .arg(cli::arg_search())
+            .arg(cli::arg_do_not_follow_outgoing_project_references())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_TFMS).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            follow_project_references: !matches
+                .is_present(cli::ARG_DO_NOT_FOLLOW_OUTGOING_PROJECT_REFERENCES),
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: self.follow_project_references,
+        });
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut groups = group_by_target_framework(&projects)
+            .into_iter()
+            .collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+        if output::is_json() {
+            output::print_json(
+                &groups
+                    .iter()
+                    .map(|(target_framework, project_paths)| Group {
+                        target_framework,
+                        projects: project_paths
+                            .iter()
+                            .map(|project_path| relative_path(&current_dir, project_path))
+                            .collect(),
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            return;
+        }
+
+        for (target_framework, project_paths) in groups.iter() {
+            println!("{} ({})", target_framework, project_paths.len());
+            for project_path in project_paths.iter() {
+                println!("  {}", relative_path(&current_dir, project_path).display());
+            }
+        }
+
+        println!();
+        for (target_framework, project_paths) in groups.iter() {
+            println!("{:>6}  {}", project_paths.len(), target_framework);
+        }
+        println!("{:>6}  projects", projects.len());
+    }
+}
+
+/// Groups the projects by each of their target frameworks, projects without a target framework
+/// are grouped under `(none)`.
+pub fn group_by_target_framework(projects: &[Project]) -> BTreeMap<String, Vec<&Path>> {
+    let mut groups = BTreeMap::<String, Vec<&Path>>::new();
+    for project in projects.iter() {
+        let target_frameworks = target_frameworks(project);
+        if target_frameworks.is_empty() {
+            groups
+                .entry(String::from("(none)"))
+                .or_default()
+                .push(&project.path);
+        }
+        for target_framework in target_frameworks {
+            groups
+                .entry(target_framework)
+                .or_default()
+                .push(&project.path);
+        }
+    }
+
+    groups
+}
+
+/// The target frameworks of the project with property references expanded by the properties of
+/// the project and its imports, which also provide the target framework when the project does not
+/// set one. References that cannot be resolved are kept as written.
+fn target_frameworks(project: &Project) -> BTreeSet<String> {
+    let mut evaluator = Evaluator::for_project(&project.path);
+    for property in project
+        .properties
+        .iter()
+        .filter(|property| property.condition.is_none())
+    {
+        evaluator.set_property(&property.name, &property.value);
+    }
+
+    let values = if project.target_frameworks.is_empty() {
+        ["TargetFrameworks", "TargetFramework"]
+            .iter()
+            .filter_map(|name| evaluator.property(name))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    } else {
+        project.target_frameworks.clone()
+    };
+
+    values
+        .iter()
+        .flat_map(|value| value.split(';'))
+        .flat_map(|value| match evaluator.expand(value) {
+            Some(expanded) if evaluator.defines_all(value) => {
+                expanded.split(';').map(str::to_owned).collect()
+            }
+            _ => vec![value.to_owned()],
+        })
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// The framework family and version of a target framework moniker, ignoring any platform suffix
+/// like `-windows`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Tfm {
+    /// .NET Framework, `net48` is version 4.8.0.
+    Framework(u32, u32, u32),
+    /// .NET Standard, `netstandard2.0`.
+    Standard(u32, u32),
+    /// .NET Core and .NET 5 and later, `netcoreapp3.1` and `net8.0`.
+    Core(u32, u32),
+}
+
+fn parse_framework(target_framework: &str) -> Option<Tfm> {
+    let target_framework = target_framework
+        .split('-')
+        .next()
+        .unwrap()
+        .trim()
+        .to_lowercase();
+
+    fn major_minor(version: &str) -> Option<(u32, u32)> {
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+        Some((major, minor))
+    }
+
+    if let Some(version) = target_framework.strip_prefix("netstandard") {
+        let (major, minor) = major_minor(version)?;
+        Some(Tfm::Standard(major, minor))
+    } else if let Some(version) = target_framework.strip_prefix("netcoreapp") {
+        let (major, minor) = major_minor(version)?;
+        Some(Tfm::Core(major, minor))
+    } else if let Some(version) = target_framework.strip_prefix("net") {
+        if version.contains('.') {
+            let (major, minor) = major_minor(version)?;
+            Some(Tfm::Core(major, minor))
+        } else if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) {
+            let digit = |index: usize| {
+                version
+                    .get(index..index + 1)
+                    .map_or(0, |digit| digit.parse().unwrap())
+            };
+            Some(Tfm::Framework(digit(0), digit(1), digit(2)))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+/// The C# language version, as major and minor, that the compiler uses for the target framework
+/// when a project does not set LangVersion. Monikers that are not recognized have no default.
+pub fn default_lang_version(target_framework: &str) -> Option<(u32, u32)> {
+    match parse_framework(target_framework)? {
+        Tfm::Core(major, _) if major >= 5 => Some((major + 4, 0)),
+        Tfm::Core(3, _) | Tfm::Standard(2, 1) => Some((8, 0)),
+        _ => Some((7, 3)),
+    }
+}
+
+/// Returns whether a project targeting `dependent` can reference a project targeting
+/// `dependency`. Monikers that are not recognized are assumed to be compatible.
+pub fn is_compatible(dependency: &str, dependent: &str) -> bool {
+    use Tfm::*;
+
+    match (parse_framework(dependency), parse_framework(dependent)) {
+        (Some(Framework(a, b, c)), Some(Framework(x, y, z))) => (a, b, c) <= (x, y, z),
+        (Some(Core(a, b)), Some(Core(x, y))) => (a, b) <= (x, y),
+        (Some(Standard(a, b)), Some(Standard(x, y))) => (a, b) <= (x, y),
+        (Some(Standard(a, b)), Some(Core(x, _))) => (a, b) <= (2, 0) || x >= 3,
+        (Some(Standard(a, b)), Some(Framework(x, y, z))) => {
+            (a, b) <= (2, 0) && (x, y, z) >= (4, 6, 1)
+        }
+        (Some(_), Some(_)) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csproj::Property;
+
+    #[test]
+    fn is_compatible_works() {
+        assert!(is_compatible("net6.0", "net8.0"));
+        assert!(!is_compatible("net8.0", "net6.0"));
+        assert!(is_compatible("netstandard2.0", "net48"));
+        assert!(!is_compatible("netstandard2.1", "net48"));
+        assert!(is_compatible("net472", "net48"));
+        assert!(!is_compatible("net8.0", "net48"));
+        assert!(is_compatible("net6.0", "net8.0-windows"));
+        assert!(is_compatible("$(DefaultTfm)", "net48"));
+    }
+
+    #[test]
+    fn default_lang_version_follows_the_target_framework() {
+        assert_eq!(default_lang_version("net8.0"), Some((12, 0)));
+        assert_eq!(default_lang_version("net5.0-windows"), Some((9, 0)));
+        assert_eq!(default_lang_version("netcoreapp3.1"), Some((8, 0)));
+        assert_eq!(default_lang_version("netstandard2.1"), Some((8, 0)));
+        assert_eq!(default_lang_version("netstandard2.0"), Some((7, 3)));
+        assert_eq!(default_lang_version("net48"), Some((7, 3)));
+        assert_eq!(default_lang_version("$(DefaultTargetFramework)"), None);
+    }
+
+    #[test]
+    fn target_frameworks_expand_nested_references() {
+        let project = Project {
+            target_frameworks: vec![String::from("$(DefaultTfms);$(Unknown)")],
+            properties: [
+                ("LegacyTfm", "net48"),
+                ("DefaultTfms", "$(LegacyTfm);net8.0"),
+            ]
+            .iter()
+            .map(|&(name, value)| Property {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                condition: None,
+            })
+            .collect(),
+            ..Project::new("/repo/A/A.csproj")
+        };
+
+        assert_eq!(
+            target_frameworks(&project).into_iter().collect::<Vec<_>>(),
+            vec!["$(Unknown)", "net48", "net8.0"]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use log::debug;
+
+use crate::config::Config;
+
+/// Whether network access, like querying package feeds, is disabled.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref PATHS: RwLock<BTreeMap<Tool, PathBuf>> = RwLock::new(BTreeMap::new());
+}
+
+/// The external executables the commands run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tool {
+    Git,
+    Dotnet,
+    Msbuild,
+    /// Sysinternals handle, used to find the process that locks a file.
+    Handle,
+}
+
+impl Tool {
+    fn program(self) -> &'static str {
+        match self {
+            Tool::Git => "git",
+            Tool::Dotnet => "dotnet",
+            Tool::Msbuild => "msbuild",
+            Tool::Handle => "handle",
+        }
+    }
+}
+
+/// Applies the `[tools]` configuration and the options given on the command line, the options
+/// win. Must be called before any tool is run.
+pub fn init(config: &Config, paths: Vec<(Tool, PathBuf)>, offline: bool) {
+    let tools = &config.tools;
+    let configured = [
+        (Tool::Git, &tools.git),
+        (Tool::Dotnet, &tools.dotnet),
+        (Tool::Msbuild, &tools.msbuild),
+        (Tool::Handle, &tools.handle),
+    ];
+
+    let mut resolved = PATHS.write().unwrap();
+    for (tool, path) in configured.iter() {
+        if let Some(path) = path {
+            resolved.insert(*tool, resolve(&config.dir, path));
+        }
+    }
+    resolved.extend(paths);
+    debug!("Using tools {:?}", *resolved);
+
+    OFFLINE.store(offline || tools.offline, Ordering::Relaxed);
+}
+
+/// Bare names are looked up on the path when the tool runs, other relative paths are relative to
+/// the configuration file.
+fn resolve(config_dir: &Path, path: &Path) -> PathBuf {
+    if path.components().count() > 1 {
+        config_dir.join(path)
+    } else {
+        path.to_owned()
+    }
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub fn command(tool: Tool) -> Command {
+    let mut command = match PATHS.read().unwrap().get(&tool) {
+        Some(path) => Command::new(path),
+        None => Command::new(tool.program()),
+    };
+    if tool == Tool::Git && cfg!(windows) {
+        // Git for Windows fails on paths longer than MAX_PATH unless this is set.
+        command.args(["-c", "core.longpaths=true"]);
+    }
+    command
+}
+
+/// Runs MSBuild directly when its path is configured and through `dotnet msbuild` otherwise.
+pub fn msbuild() -> Command {
+    if PATHS.read().unwrap().contains_key(&Tool::Msbuild) {
+        command(Tool::Msbuild)
+    } else {
+        let mut dotnet = command(Tool::Dotnet);
+        dotnet.arg("msbuild");
+        dotnet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_keeps_bare_names_for_the_path() {
+        let config_dir = Path::new("/src/repo");
+        assert_eq!(resolve(config_dir, Path::new("git")), Path::new("git"));
+        assert_eq!(
+            resolve(config_dir, Path::new("tools/dotnet/dotnet")),
+            Path::new("/src/repo/tools/dotnet/dotnet")
+        );
+        assert_eq!(
+            resolve(config_dir, Path::new("/opt/git/bin/git")),
+            Path::new("/opt/git/bin/git")
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+use xmltree::{Element, XMLNode};
+
+use crate::output::status;
+use crate::{
+    cli,
+    csproj::Project,
+    list,
+    path_extensions::{relative_path, to_msbuild_path, PathExt},
+    plan::Effects,
+};
+
+const ARG_OUTPUT: &'static str = "output";
+const CMD_TRAVERSAL: &'static str = "traversal";
+
+/// The SDK of the traversal project, see https://github.com/microsoft/MSBuildSdks.
+pub const TRAVERSAL_SDK: &'static str = "Microsoft.Build.Traversal/4.1.0";
+
+#[derive(Debug)]
+pub struct TraversalCommand {
+    search_path: PathBuf,
+    glob_matcher: globset::GlobMatcher,
+    output_path: PathBuf,
+    plan_json: Option<PathBuf>,
+}
+
+impl TraversalCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_TRAVERSAL)
+            .about("Write a traversal project like dirs.proj that builds the selected projects, an alternative to a solution for CI builds")
+            .arg(cli::arg_search())
+            .arg(cli::arg_glob())
+            .arg(
+                Arg::with_name(ARG_OUTPUT)
+                    .long("output")
+                    .short("o")
+                    .value_name("PROJ_PATH")
+                    .help("The traversal project to write")
+                    .default_value("dirs.proj")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_TRAVERSAL)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            glob_matcher: cli::get_glob_matcher(matches),
+            output_path: PathBuf::from(matches.value_of_os(ARG_OUTPUT).unwrap()),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let mut projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        list::retain_glob_matches(&mut projects, &self.glob_matcher);
+        projects.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let output_path = self.output_path.simplified_absolute().unwrap();
+        let mut effects = Effects::new(CMD_TRAVERSAL, self.plan_json.as_deref());
+        effects
+            .write_xml_file(
+                &output_path,
+                &traversal_project(output_path.parent().unwrap(), &projects),
+            )
+            .unwrap();
+
+        let current_dir = std::env::current_dir().unwrap();
+        status!(
+            "Wrote {} with {} projects",
+            relative_path(&current_dir, &output_path).display(),
+            projects.len()
+        );
+
+        effects.finish();
+    }
+}
+
+/// Returns a traversal project in the directory that references the projects.
+pub fn traversal_project(dir: &Path, projects: &[Project]) -> Element {
+    let mut group = Element::new("ItemGroup");
+    for project in projects.iter() {
+        let mut reference = Element::new("ProjectReference");
+        reference.attributes.insert(
+            "Include".to_owned(),
+            to_msbuild_path(&relative_path(dir, &project.path)),
+        );
+        group.children.push(XMLNode::Element(reference));
+    }
+
+    let mut root = Element::new("Project");
+    root.attributes
+        .insert("Sdk".to_owned(), TRAVERSAL_SDK.to_owned());
+    root.children.push(XMLNode::Element(group));
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_extensions::child_elements;
+
+    #[test]
+    fn traversal_project_references_the_projects_relative_to_the_directory() {
+        let root = traversal_project(
+            Path::new("/repo"),
+            &[
+                Project::new("/repo/src/A/A.csproj"),
+                Project::new("/repo/test/B/B.csproj"),
+            ],
+        );
+
+        assert_eq!(root.attributes.get("Sdk").unwrap(), TRAVERSAL_SDK);
+        let includes = child_elements(&root)
+            .flat_map(child_elements)
+            .map(|reference| reference.attributes.get("Include").unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(includes, vec!["src\\A\\A.csproj", "test\\B\\B.csproj"]);
+    }
+}
@@ -0,0 +1,566 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Stdout;
+use std::path::{Path, PathBuf};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    tty::IsTty,
+};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::{
+    check::{self, Finding},
+    cli,
+    csproj::Project,
+    error::{Error, Result},
+    list,
+    path_extensions::relative_path,
+};
+
+const CMD_UI: &'static str = "ui";
+
+const HELP: &'static str =
+    "↑↓ move  → expand  ← collapse  enter toggle  / search  esc clear search  q quit";
+
+/// The number of rows page up and page down move.
+const PAGE: usize = 10;
+
+#[derive(Debug)]
+pub struct UiCommand {
+    search_path: PathBuf,
+}
+
+/// What a row of the tree shows. The projects that match the search are at the top level, the
+/// references, dependents and findings of a project are below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Project(usize),
+    Reference(usize),
+    Dependent(usize),
+    /// A project reference to a project that was not listed.
+    Missing(PathBuf),
+    Finding(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    depth: usize,
+    node: Node,
+    expanded: bool,
+}
+
+struct Browser<'a> {
+    projects: &'a [Project],
+    names: Vec<Cow<'a, str>>,
+    indices: BTreeMap<&'a Path, usize>,
+    referenced_by: BTreeMap<usize, Vec<usize>>,
+    /// Computed when the first project is expanded, checking a large repository takes a while.
+    findings: Option<Vec<Finding>>,
+    current_dir: PathBuf,
+    search: String,
+    /// Whether keys are typed into the search.
+    searching: bool,
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+impl UiCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_UI)
+            .about("Browse the projects, their references and findings interactively")
+            .arg(cli::arg_search())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.subcommand_matches(CMD_UI).map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+        }
+    }
+
+    /// Parses the projects once and shows them as a tree in the terminal until quit.
+    pub fn execute(&self) -> Result<()> {
+        if !std::io::stdout().is_tty() {
+            return Err(Error::user("ui needs a terminal to draw in"));
+        }
+
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+        let mut browser = Browser::new(&projects);
+
+        // The panic hook exits the process, so the screen would not be dropped.
+        let panic_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            panic_hook(info);
+        }));
+
+        let mut screen = Screen::enter()?;
+        loop {
+            screen.terminal.draw(|frame| browser.render(frame))?;
+            if let Event::Key(key) = event::read()? {
+                // Windows also reports releasing a key.
+                if key.kind == KeyEventKind::Press && !browser.handle_key(key) {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Switches the terminal to raw mode and the alternate screen, and back when dropped.
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Screen {
+    fn enter() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        match execute!(std::io::stdout(), EnterAlternateScreen)
+            .and_then(|_| Terminal::new(CrosstermBackend::new(std::io::stdout())))
+        {
+            Ok(terminal) => Ok(Self { terminal }),
+            Err(e) => {
+                restore_terminal();
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+}
+
+impl Node {
+    fn project(&self) -> Option<usize> {
+        match *self {
+            Node::Project(index) | Node::Reference(index) | Node::Dependent(index) => Some(index),
+            Node::Missing(_) | Node::Finding(_) => None,
+        }
+    }
+}
+
+impl<'a> Browser<'a> {
+    fn new(projects: &'a [Project]) -> Self {
+        let indices = projects
+            .iter()
+            .enumerate()
+            .map(|(index, project)| (project.path.as_path(), index))
+            .collect::<BTreeMap<_, _>>();
+        let mut referenced_by = BTreeMap::<usize, Vec<usize>>::new();
+        for (index, project) in projects.iter().enumerate() {
+            for reference in project.project_references.iter() {
+                if let Some(&reference) = indices.get(reference.as_path()) {
+                    referenced_by.entry(reference).or_default().push(index);
+                }
+            }
+        }
+
+        let mut browser = Self {
+            projects,
+            names: projects
+                .iter()
+                .map(|project| project.path.file_stem().unwrap().to_string_lossy())
+                .collect(),
+            indices,
+            referenced_by,
+            findings: None,
+            current_dir: std::env::current_dir().unwrap(),
+            search: String::new(),
+            searching: false,
+            rows: Vec::new(),
+            selected: 0,
+        };
+        browser.reset_rows();
+        browser
+    }
+
+    /// Lists the projects that match the search, collapsed.
+    fn reset_rows(&mut self) {
+        self.rows = search(&self.names, &self.search)
+            .into_iter()
+            .map(|index| Row {
+                depth: 0,
+                node: Node::Project(index),
+                expanded: false,
+            })
+            .collect();
+        self.selected = 0;
+    }
+
+    /// Handles a key press, returns false to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+
+        if self.searching {
+            match key.code {
+                KeyCode::Char(c) => {
+                    self.search.push(c);
+                    self.reset_rows();
+                }
+                KeyCode::Backspace => {
+                    self.search.pop();
+                    self.reset_rows();
+                }
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.search.clear();
+                    self.reset_rows();
+                }
+                _ => {}
+            }
+            return true;
+        }
+
+        let last = self.rows.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(PAGE),
+            KeyCode::PageDown => self.selected = (self.selected + PAGE).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Right | KeyCode::Char('l') => self.expand(self.selected),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(self.selected),
+            KeyCode::Enter | KeyCode::Char(' ') => match self.rows.get(self.selected) {
+                Some(row) if row.expanded => self.collapse(self.selected),
+                Some(_) => self.expand(self.selected),
+                None => {}
+            },
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Esc if !self.search.is_empty() => {
+                self.search.clear();
+                self.reset_rows();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Shows the references, dependents and findings of the project in the row below it.
+    fn expand(&mut self, row_index: usize) {
+        let (depth, project) = match self.rows.get(row_index) {
+            Some(row) if !row.expanded => match row.node.project() {
+                Some(project) => (row.depth, project),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let children = self
+            .children(project)
+            .into_iter()
+            .map(|node| Row {
+                depth: depth + 1,
+                node,
+                expanded: false,
+            })
+            .collect::<Vec<_>>();
+        self.rows[row_index].expanded = true;
+        self.rows.splice(row_index + 1..row_index + 1, children);
+    }
+
+    /// Hides what is below the row, or selects the row above it if it is not expanded.
+    fn collapse(&mut self, row_index: usize) {
+        let depth = match self.rows.get(row_index) {
+            Some(row) => row.depth,
+            None => return,
+        };
+
+        if self.rows[row_index].expanded {
+            let count = self.rows[row_index + 1..]
+                .iter()
+                .take_while(|row| row.depth > depth)
+                .count();
+            self.rows.drain(row_index + 1..row_index + 1 + count);
+            self.rows[row_index].expanded = false;
+        } else if let Some(parent) = self.rows[..row_index]
+            .iter()
+            .rposition(|row| row.depth < depth)
+        {
+            self.selected = parent;
+        }
+    }
+
+    fn children(&mut self, index: usize) -> Vec<Node> {
+        let projects = self.projects;
+        let project = &projects[index];
+
+        let mut children = project
+            .project_references
+            .iter()
+            .map(|path| match self.indices.get(path.as_path()) {
+                Some(&reference) => Node::Reference(reference),
+                None => Node::Missing(path.clone()),
+            })
+            .collect::<Vec<_>>();
+        children.extend(
+            self.referenced_by
+                .get(&index)
+                .into_iter()
+                .flatten()
+                .map(|&dependent| Node::Dependent(dependent)),
+        );
+        let findings = self
+            .findings
+            .get_or_insert_with(|| check::check_all(projects));
+        children.extend(
+            findings
+                .iter()
+                .enumerate()
+                .filter(|(_, finding)| involves(finding, &project.path))
+                .map(|(finding, _)| Node::Finding(finding)),
+        );
+        children
+    }
+
+    fn label(&self, row: &Row) -> String {
+        let path = |index: usize| {
+            relative_path(&self.current_dir, &self.projects[index].path)
+                .display()
+                .to_string()
+        };
+        let marker = match (row.expanded, row.node.project()) {
+            (true, _) => "▾ ",
+            (false, Some(_)) => "▸ ",
+            (false, None) => "  ",
+        };
+        let text = match &row.node {
+            Node::Project(index) => path(*index),
+            Node::Reference(index) => format!("→ {}", path(*index)),
+            Node::Dependent(index) => format!("← {}", path(*index)),
+            Node::Missing(path) => format!(
+                "→ {} (not found)",
+                relative_path(&self.current_dir, path).display()
+            ),
+            Node::Finding(index) => {
+                let finding = &self.findings.as_ref().unwrap()[*index];
+                format!("[{}] {}", finding.rule, finding.message)
+            }
+        };
+        format!("{}{}{}", "  ".repeat(row.depth), marker, text)
+    }
+
+    /// Describes the selected row.
+    fn details(&self) -> String {
+        let row = match self.rows.get(self.selected) {
+            Some(row) => row,
+            None => return format!("No project name contains {}", self.search),
+        };
+        match (&row.node, row.node.project()) {
+            (_, Some(index)) => {
+                let project = &self.projects[index];
+                format!(
+                    "{}\n{}\n{} project references, {} dependents, {} package references",
+                    relative_path(&self.current_dir, &project.path).display(),
+                    project.target_frameworks.join(";"),
+                    project.project_references.len(),
+                    self.referenced_by.get(&index).map_or(0, Vec::len),
+                    project.package_references.len()
+                )
+            }
+            (Node::Missing(path), _) => format!(
+                "{}\nThe referenced project was not found",
+                relative_path(&self.current_dir, path).display()
+            ),
+            (Node::Finding(index), _) => {
+                let finding = &self.findings.as_ref().unwrap()[*index];
+                let mut details = format!("[{}] {}", finding.rule, finding.message);
+                for path in finding.paths.iter() {
+                    details.push('\n');
+                    details.push_str(&relative_path(&self.current_dir, path).display().to_string());
+                }
+                details
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn render<B: Backend>(&self, frame: &mut Frame<B>) {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),
+                Constraint::Length(5),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let title = format!("{} of {} projects", self.rows_at_top(), self.projects.len());
+        let items = self
+            .rows
+            .iter()
+            .map(|row| ListItem::new(self.label(row)))
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default();
+        state.select(if self.rows.is_empty() {
+            None
+        } else {
+            Some(self.selected)
+        });
+        frame.render_stateful_widget(list, areas[0], &mut state);
+
+        frame.render_widget(
+            Paragraph::new(self.details()).block(Block::default().borders(Borders::ALL)),
+            areas[1],
+        );
+
+        let status = if self.searching {
+            format!("/{}", self.search)
+        } else if !self.search.is_empty() {
+            format!("/{}  {}", self.search, HELP)
+        } else {
+            String::from(HELP)
+        };
+        frame.render_widget(Paragraph::new(status), areas[2]);
+    }
+
+    fn rows_at_top(&self) -> usize {
+        self.rows.iter().filter(|row| row.depth == 0).count()
+    }
+}
+
+fn involves(finding: &Finding, path: &Path) -> bool {
+    finding
+        .paths
+        .iter()
+        .any(|finding_path| finding_path == path)
+}
+
+/// Returns the indices of the names that contain the text, ignoring case, sorted by name.
+fn search<S: AsRef<str>>(names: &[S], text: &str) -> Vec<usize> {
+    let text = text.to_lowercase();
+    let mut indices = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.as_ref().to_lowercase().contains(&text))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    indices.sort_by_key(|&index| names[index].as_ref().to_lowercase());
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projects() -> Vec<Project> {
+        vec![
+            Project {
+                project_references: vec![
+                    PathBuf::from("/repo/Billing/Billing.csproj"),
+                    PathBuf::from("/repo/Missing/Missing.csproj"),
+                ],
+                ..Project::new("/repo/Web/Web.csproj")
+            },
+            Project::new("/repo/Billing/Billing.csproj"),
+        ]
+    }
+
+    fn press(browser: &mut Browser, code: KeyCode) -> bool {
+        browser.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn expand_shows_references_and_dependents_and_collapse_hides_them() {
+        let projects = projects();
+        let mut browser = Browser::new(&projects);
+        browser.findings = Some(Vec::new());
+        let nodes = |browser: &Browser| {
+            browser
+                .rows
+                .iter()
+                .map(|row| (row.depth, row.node.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            nodes(&browser),
+            vec![(0, Node::Project(1)), (0, Node::Project(0))]
+        );
+
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Right);
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Enter);
+        assert_eq!(
+            nodes(&browser),
+            vec![
+                (0, Node::Project(1)),
+                (0, Node::Project(0)),
+                (1, Node::Reference(1)),
+                (2, Node::Dependent(0)),
+                (
+                    1,
+                    Node::Missing(PathBuf::from("/repo/Missing/Missing.csproj"))
+                ),
+            ]
+        );
+
+        // Left on a collapsed row selects its parent, the next left collapses it.
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Left);
+        assert_eq!(browser.selected, 2);
+        press(&mut browser, KeyCode::Up);
+        press(&mut browser, KeyCode::Left);
+        assert_eq!(
+            nodes(&browser),
+            vec![(0, Node::Project(1)), (0, Node::Project(0))]
+        );
+    }
+
+    #[test]
+    fn typed_keys_go_to_the_search_until_enter() {
+        let projects = projects();
+        let mut browser = Browser::new(&projects);
+
+        press(&mut browser, KeyCode::Char('/'));
+        assert!(press(&mut browser, KeyCode::Char('W')));
+        assert!(press(&mut browser, KeyCode::Char('q')));
+        press(&mut browser, KeyCode::Backspace);
+        press(&mut browser, KeyCode::Enter);
+        assert_eq!(browser.search, "W");
+        assert_eq!(browser.rows.len(), 1);
+
+        press(&mut browser, KeyCode::Esc);
+        assert_eq!(browser.rows.len(), 2);
+        assert!(!press(&mut browser, KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn search_ignores_case_and_sorts_by_name() {
+        let names = ["Acme.Web", "acme.billing", "Tools", "Acme.Api"];
+        assert_eq!(search(&names, "ACME"), vec![3, 1, 0]);
+        assert!(search(&names, "missing").is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+
+use crate::{
+    cli,
+    csproj::Project,
+    encoding, list, output,
+    path_extensions::{from_msbuild_path, relative_path, PathExt},
+    plan::Effects,
+    sln,
+    traversal::traversal_project,
+};
+
+const ARG_ENTRY: &'static str = "entry";
+const ARG_WRITE_TRAVERSAL: &'static str = "write-traversal";
+const CMD_UNREACHABLE: &'static str = "unreachable";
+
+#[derive(Debug)]
+pub struct UnreachableCommand {
+    search_path: PathBuf,
+    entries: Vec<PathBuf>,
+    traversal_path: Option<PathBuf>,
+    plan_json: Option<PathBuf>,
+}
+
+impl UnreachableCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_UNREACHABLE)
+            .about("List projects that are not built by any of the entry points")
+            .arg(cli::arg_search())
+            .arg(
+                Arg::with_name(ARG_ENTRY)
+                    .long("entry")
+                    .short("e")
+                    .value_name("PATH")
+                    .help("A solution, traversal project or project that the build starts from, can be passed multiple times")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name(ARG_WRITE_TRAVERSAL)
+                    .long("write-traversal")
+                    .value_name("PROJ_PATH")
+                    .help("Writes a traversal project like dirs.proj that references every project")
+                    .takes_value(true),
+            )
+            .arg(cli::arg_plan_json())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_UNREACHABLE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            entries: matches
+                .values_of_os(ARG_ENTRY)
+                .unwrap()
+                .map(PathBuf::from)
+                .collect(),
+            traversal_path: matches.value_of_os(ARG_WRITE_TRAVERSAL).map(PathBuf::from),
+            plan_json: cli::get_plan_json(matches),
+        }
+    }
+
+    pub fn execute(&self) {
+        let projects = list::list(list::Options {
+            search_path: &self.search_path,
+            follow_incoming_project_references: false,
+            follow_outgoing_project_references: false,
+        });
+
+        let path_to_project = projects
+            .iter()
+            .map(|project| (project.path.as_path(), project))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut todo = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry_projects(&entry.simplified_absolute().unwrap(), &projects))
+            .collect::<Vec<_>>();
+
+        let mut reachable = BTreeSet::new();
+        while let Some(path) = todo.pop() {
+            let path = std::fs::canonicalize(&path).unwrap_or(path);
+            if !reachable.insert(path.clone()) {
+                continue;
+            }
+            if let Some(project) = path_to_project.get(path.as_path()) {
+                todo.extend(project.project_references.iter().cloned());
+            }
+        }
+
+        let current_dir = std::env::current_dir().unwrap();
+        let unreachable = projects
+            .iter()
+            .filter(|project| !reachable.contains(&project.path))
+            .collect::<Vec<_>>();
+
+        let paths = unreachable
+            .iter()
+            .map(|project| relative_path(&current_dir, &project.path))
+            .collect::<Vec<_>>();
+        if output::is_json() {
+            output::print_json(&paths);
+        } else {
+            for path in paths.iter() {
+                println!("{}", path.display());
+            }
+        }
+
+        if let Some(traversal_path) = &self.traversal_path {
+            let traversal_path = traversal_path.simplified_absolute().unwrap();
+            let mut effects = Effects::new(CMD_UNREACHABLE, self.plan_json.as_deref());
+            effects
+                .write_xml_file(
+                    &traversal_path,
+                    &traversal_project(traversal_path.parent().unwrap(), &projects),
+                )
+                .unwrap();
+            effects.finish();
+        }
+
+        if !unreachable.is_empty() {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+    }
+}
+
+/// Returns the projects an entry point builds directly, project references are followed by the
+/// caller.
+fn entry_projects(entry: &Path, projects: &[Project]) -> Vec<PathBuf> {
+    let extension = entry
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase);
+
+    match extension.as_deref() {
+        Some("sln") => sln::read_project_paths(entry)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", entry.display(), e)),
+        Some("proj") => traversal_projects(entry, projects, &mut HashSet::new()),
+        _ => vec![entry.to_owned()],
+    }
+}
+
+/// Reads the ProjectReference and ProjectFile items of a traversal project, which may use
+/// wildcards like `src\**\*.csproj`. Traversal projects that were visited before are skipped so
+/// that cycles between them end.
+fn traversal_projects(
+    proj_path: &Path,
+    projects: &[Project],
+    visited: &mut HashSet<PathBuf>,
+) -> Vec<PathBuf> {
+    if !visited.insert(proj_path.to_owned()) {
+        debug!("Skipping {} which was visited before", proj_path.display());
+        return Vec::new();
+    }
+
+    let proj_dir = proj_path.parent().unwrap();
+    let contents = encoding::read_to_string(proj_path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", proj_path.display(), e));
+    let document = roxmltree::Document::parse(&contents)
+        .unwrap_or_else(|e| panic!("Failed to parse {}: {}", proj_path.display(), e));
+
+    let mut paths = Vec::new();
+    for include in document
+        .descendants()
+        .filter(|node| matches!(node.tag_name().name(), "ProjectReference" | "ProjectFile"))
+        .filter_map(|node| node.attribute("Include"))
+        .flat_map(|include| include.split(';'))
+        .map(str::trim)
+        .filter(|include| !include.is_empty())
+    {
+        if include.contains("$(") {
+            warn!(
+                "Ignoring {} in {} which uses properties",
+                include,
+                proj_path.display()
+            );
+        } else if include.contains('*') {
+            let matcher = globset::Glob::new(&include.replace('\\', "/"))
+                .unwrap()
+                .compile_matcher();
+            paths.extend(
+                projects
+                    .iter()
+                    .filter(|project| {
+                        let rel_path = relative_path(proj_dir, &project.path);
+                        matcher.is_match(rel_path.to_string_lossy().replace('\\', "/"))
+                    })
+                    .map(|project| project.path.clone()),
+            );
+        } else {
+            let path = proj_dir.join(from_msbuild_path(include)).simplify();
+            // Traversal projects may reference other traversal projects.
+            if path.extension() == Some(OsStr::new("proj")) {
+                paths.extend(traversal_projects(&path, projects, visited));
+            } else {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
@@ -17,10 +17,18 @@ fn entry_is_git(entry: &std::fs::DirEntry) -> bool {
     entry.file_type().unwrap().is_dir() && entry.file_name() == ".git"
 }
 
-pub fn find_dir_csproj(dir: &Path) -> impl Iterator<Item = PathBuf> {
+/// The extensions of C#, F# and Visual Basic projects, which are all handled the same way, and of
+/// shared projects.
+pub const PROJECT_EXTENSIONS: [&'static str; 4] = ["csproj", "fsproj", "vbproj", "shproj"];
+
+/// Shared projects consist of the items in a .projitems file that projects import.
+pub const SHARED_PROJECT_EXTENSION: &'static str = "shproj";
+pub const SHARED_ITEMS_EXTENSION: &'static str = "projitems";
+
+pub fn find_dir_projects(dir: &Path) -> impl Iterator<Item = PathBuf> {
     std::fs::read_dir(dir).unwrap().filter_map(|entry| {
         let entry = entry.unwrap();
-        if std_entry_is_csproj(&entry) {
+        if std_entry_is_project(&entry) {
             Some(entry.path())
         } else {
             None
@@ -28,14 +36,52 @@ pub fn find_dir_csproj(dir: &Path) -> impl Iterator<Item = PathBuf> {
     })
 }
 
-pub fn path_extension_is_csproj(path: &Path) -> bool {
-    path.extension() == Some(OsStr::new("csproj"))
+pub fn path_extension_is_project(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| {
+        PROJECT_EXTENSIONS
+            .iter()
+            .any(|&project_extension| extension == OsStr::new(project_extension))
+    })
+}
+
+fn std_entry_is_project(entry: &std::fs::DirEntry) -> bool {
+    entry.file_type().unwrap().is_file() && path_extension_is_project(entry.file_name().as_ref())
+}
+
+pub fn entry_is_project(entry: &ignore::DirEntry) -> bool {
+    entry.file_type().unwrap().is_file() && path_extension_is_project(entry.file_name().as_ref())
+}
+
+/// Finds the solution files below the directory, skipping files like `walk::builder`.
+pub fn find_sln_files(dir: &Path) -> Vec<PathBuf> {
+    find_files_with_extensions(dir, &["sln"])
+}
+
+/// Finds the solution and solution filter files below the directory, skipping files like
+/// `walk::builder`.
+pub fn find_sln_and_slnf_files(dir: &Path) -> Vec<PathBuf> {
+    find_files_with_extensions(dir, &["sln", "slnf"])
 }
 
-fn std_entry_is_csproj(entry: &std::fs::DirEntry) -> bool {
-    entry.file_type().unwrap().is_file() && path_extension_is_csproj(entry.file_name().as_ref())
+/// Finds the package manifests below the directory, skipping files like `walk::builder`.
+pub fn find_nuspec_files(dir: &Path) -> Vec<PathBuf> {
+    find_files_with_extensions(dir, &["nuspec"])
 }
 
-pub fn entry_is_csproj(entry: &ignore::DirEntry) -> bool {
-    entry.file_type().unwrap().is_file() && path_extension_is_csproj(entry.file_name().as_ref())
+fn find_files_with_extensions(dir: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let mut paths = crate::walk::builder(dir)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().map_or(false, |t| t.is_file())
+                && entry.path().extension().map_or(false, |extension| {
+                    extensions
+                        .iter()
+                        .any(|&wanted| extension == OsStr::new(wanted))
+                })
+        })
+        .map(|entry| entry.into_path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
 }
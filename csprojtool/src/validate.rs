@@ -0,0 +1,950 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    cli,
+    csproj::Project,
+    encoding, list,
+    output::{self, status},
+    output_format::{print_issues, OutputFormat},
+    path_extensions::{from_msbuild_path, relative_path, PathExt},
+    plan::Effects,
+    sarif,
+    sln::{self, Solution, SolutionProject},
+    utils::{find_git_root, find_sln_files},
+    xml_extensions::process_tree,
+};
+
+const ARG_FIX: &'static str = "fix";
+const CMD_VALIDATE: &'static str = "validate";
+
+#[derive(Debug)]
+pub struct ValidateCommand {
+    search_paths: Vec<PathBuf>,
+    fix: bool,
+    plan_json: Option<PathBuf>,
+    format: OutputFormat,
+}
+
+/// A solution or project and the paths in it that point at missing files.
+#[derive(Debug)]
+struct Row {
+    path: PathBuf,
+    missing: Vec<String>,
+}
+
+/// The problems as printed with `--json`, paths are relative to the current directory.
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    solutions_with_missing_projects: Vec<JsonRow<'a>>,
+    projects_with_missing_references: Vec<JsonRow<'a>>,
+    solutions_without_referenced_projects: Vec<JsonRow<'a>>,
+    solutions_with_mismatched_guids: Vec<JsonRow<'a>>,
+    projects_with_miscased_references: Vec<JsonRow<'a>>,
+    projects_with_missing_hint_paths: Vec<JsonRow<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRow<'a> {
+    path: PathBuf,
+    missing: &'a [String],
+}
+
+#[derive(Debug)]
+struct SolutionCheck {
+    path: PathBuf,
+    /// The projects in the solution that do not exist.
+    missing: Vec<String>,
+    /// The referenced projects that are not in the solution, with the project that references
+    /// them.
+    unreferenced: BTreeMap<PathBuf, PathBuf>,
+    mismatches: Vec<Mismatch>,
+}
+
+/// A ProjectReference whose path differs in case from the files on disk. It works on Windows and
+/// macOS but not on file systems that are case sensitive, like those of Linux build agents.
+#[derive(Debug)]
+struct Miscased {
+    written: String,
+    actual: String,
+    line: u32,
+}
+
+/// A HintPath of a Reference that points at a file that does not exist.
+#[derive(Debug)]
+struct MissingHintPath {
+    written: String,
+    line: u32,
+}
+
+/// A project whose GUID in the solution differs from the ProjectGuid in the project file.
+#[derive(Debug, PartialEq)]
+struct Mismatch {
+    rel_path: String,
+    sln_guid: Uuid,
+    project_guid: Uuid,
+}
+
+impl ValidateCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(CMD_VALIDATE)
+            .about("Check solutions and project references for missing projects")
+            .arg(cli::arg_search())
+            .arg(cli::arg_search_paths())
+            .arg(
+                clap::Arg::with_name(ARG_FIX)
+                    .long("fix")
+                    .takes_value(false)
+                    .help("Add missing referenced projects to the solutions, update mismatched project GUIDs and correct the case of project references"),
+            )
+            .arg(cli::arg_plan_json())
+            .arg(cli::arg_issues_format())
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_VALIDATE)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_paths: cli::get_search_paths(matches),
+            fix: matches.is_present(ARG_FIX),
+            plan_json: cli::get_plan_json(matches),
+            format: cli::get_format(matches),
+        }
+    }
+
+    /// Validates without writing a plan, for callers that validate repeatedly.
+    pub fn new(search_path: PathBuf, fix: bool) -> Self {
+        Self {
+            search_paths: vec![search_path],
+            fix,
+            plan_json: None,
+            format: OutputFormat::Text,
+        }
+    }
+
+    pub fn execute(&self) {
+        if !self.validate() {
+            std::process::exit(crate::error::EXIT_VALIDATION_FAILED);
+        }
+    }
+
+    /// Prints the problems and fixes them if requested, returns false if problems remain.
+    pub fn validate(&self) -> bool {
+        if self.format == OutputFormat::Sarif {
+            // The log is json as well, messages about fixes have to go to stderr.
+            output::init(true);
+        }
+
+        let search_paths = self
+            .search_paths
+            .iter()
+            .map(|search_path| search_path.simplified_absolute().unwrap())
+            .collect::<Vec<_>>();
+        let search_dirs = search_paths
+            .iter()
+            .map(|search_path| {
+                if search_path.is_file() {
+                    search_path.parent().unwrap().to_owned()
+                } else {
+                    search_path.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Referenced projects are followed so that the solutions can be checked for the projects
+        // they need outside of the search paths.
+        let projects = list::list_each(&search_paths, false, true);
+        let by_path = projects
+            .iter()
+            .map(|project| (&project.path, project))
+            .collect::<HashMap<_, _>>();
+        let references = projects
+            .iter()
+            .map(|project| (project.path.clone(), project.project_references.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let checks = search_dirs
+            .iter()
+            .flat_map(|search_dir| find_sln_files(search_dir))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|sln_path| {
+                let solution = Solution::read(&sln_path)
+                    .unwrap_or_else(|e| panic!("Failed to read {}: {}", sln_path.display(), e));
+                let projects = solution
+                    .projects
+                    .iter()
+                    .filter(|project| is_project_file(project))
+                    .collect::<Vec<_>>();
+                let included = projects
+                    .iter()
+                    .map(|project| canonical_path(&project.path))
+                    .collect::<HashSet<_>>();
+                SolutionCheck {
+                    missing: missing_projects(&projects, |path| path.is_file()),
+                    unreferenced: missing_references(&included, &references),
+                    mismatches: guid_mismatches(&projects, |path| {
+                        by_path.get(&canonical_path(path))?.project_guid
+                    }),
+                    path: sln_path,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let current_dir = std::env::current_dir().unwrap();
+        let mut incomplete = checks
+            .iter()
+            .filter(|check| !check.unreferenced.is_empty())
+            .map(|check| Row {
+                path: check.path.clone(),
+                missing: check
+                    .unreferenced
+                    .iter()
+                    .map(|(reference, from)| {
+                        format!(
+                            "{} (referenced by {})",
+                            relative_path(&current_dir, reference).display(),
+                            relative_path(&current_dir, from).display()
+                        )
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        let mut mismatched = checks
+            .iter()
+            .filter(|check| !check.mismatches.is_empty())
+            .map(|check| Row {
+                path: check.path.clone(),
+                missing: check
+                    .mismatches
+                    .iter()
+                    .map(|mismatch| {
+                        format!(
+                            "{} is {{{:X}}} in the solution and {{{:X}}} in the project",
+                            mismatch.rel_path, mismatch.sln_guid, mismatch.project_guid
+                        )
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        let miscased = projects
+            .iter()
+            .map(|project| (&project.path, miscased_references(&project.path)))
+            .filter(|(_, miscased)| !miscased.is_empty())
+            .collect::<Vec<_>>();
+        let mut miscased_rows = miscased
+            .iter()
+            .map(|(path, miscased)| Row {
+                path: (*path).clone(),
+                missing: miscased
+                    .iter()
+                    .map(|miscased| format!("{} is {} on disk", miscased.written, miscased.actual))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        let missing_hint_paths = projects
+            .iter()
+            .map(|project| (&project.path, missing_hint_paths(&project.path)))
+            .filter(|(_, missing)| !missing.is_empty())
+            .collect::<Vec<_>>();
+        let hint_path_rows = missing_hint_paths
+            .iter()
+            .map(|(path, missing)| Row {
+                path: (*path).clone(),
+                missing: missing
+                    .iter()
+                    .map(|missing| missing.written.clone())
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+
+        if self.fix && (!incomplete.is_empty() || !mismatched.is_empty() || !miscased.is_empty()) {
+            let mut effects = Effects::new(CMD_VALIDATE, self.plan_json.as_deref());
+            for check in checks.iter() {
+                if !check.mismatches.is_empty() {
+                    let replacements = check
+                        .mismatches
+                        .iter()
+                        .map(|mismatch| (mismatch.sln_guid, mismatch.project_guid))
+                        .collect();
+                    sln::replace_project_guids(&mut effects, &check.path, &replacements)
+                        .unwrap_or_else(|e| e.exit());
+                    for mismatch in check.mismatches.iter() {
+                        status!(
+                            "Changed the GUID of {} to {{{:X}}} in {}",
+                            mismatch.rel_path,
+                            mismatch.project_guid,
+                            relative_path(&current_dir, &check.path).display()
+                        );
+                    }
+                }
+                if !check.unreferenced.is_empty() {
+                    let added = check
+                        .unreferenced
+                        .keys()
+                        .map(|path| *by_path.get(path).unwrap())
+                        .collect::<Vec<&Project>>();
+                    sln::add_to_solution(&mut effects, &check.path, &added)
+                        .unwrap_or_else(|e| e.exit());
+                }
+            }
+            for (path, miscased) in miscased.iter() {
+                effects
+                    .transform_xml_file(path, |mut root| {
+                        let mut corrected = false;
+                        process_tree(&mut root, |element| {
+                            if element.name != "ProjectReference" {
+                                return;
+                            }
+                            if let Some(include) = element.attributes.get_mut("Include") {
+                                if let Some(miscased) = miscased
+                                    .iter()
+                                    .find(|miscased| &miscased.written == include)
+                                {
+                                    *include = miscased.actual.clone();
+                                    corrected = true;
+                                }
+                            }
+                        });
+                        if corrected {
+                            Some(root)
+                        } else {
+                            None
+                        }
+                    })
+                    .unwrap();
+                status!(
+                    "Corrected the case of the project references in {}",
+                    relative_path(&current_dir, path).display()
+                );
+            }
+            effects.finish();
+            incomplete.clear();
+            mismatched.clear();
+            miscased_rows.clear();
+        }
+
+        let issues = if self.format.reports_issues() {
+            issues(&checks, &projects, &miscased, &missing_hint_paths, self.fix)
+        } else {
+            Vec::new()
+        };
+
+        let solutions = checks
+            .iter()
+            .map(|check| Row {
+                path: check.path.clone(),
+                missing: check.missing.clone(),
+            })
+            .collect::<Vec<_>>();
+        let projects = projects
+            .iter()
+            .map(|project| {
+                let project_dir = project.path.parent().unwrap();
+                Row {
+                    missing: project
+                        .project_references
+                        .iter()
+                        .filter(|reference| !reference.is_file())
+                        .map(|reference| {
+                            relative_path(project_dir, reference).display().to_string()
+                        })
+                        .collect(),
+                    path: project.path.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let broken_solutions = solutions
+            .iter()
+            .filter(|row| !row.missing.is_empty())
+            .collect::<Vec<_>>();
+        let broken_projects = projects
+            .iter()
+            .filter(|row| !row.missing.is_empty())
+            .collect::<Vec<_>>();
+        let incomplete = incomplete.iter().collect::<Vec<_>>();
+        let mismatched = mismatched.iter().collect::<Vec<_>>();
+        let miscased_rows = miscased_rows.iter().collect::<Vec<_>>();
+        let hint_path_rows = hint_path_rows.iter().collect::<Vec<_>>();
+
+        if self.format.reports_issues() {
+            print_issues(
+                self.format,
+                &issues,
+                find_git_root(&search_dirs[0]).unwrap_or(&current_dir),
+            );
+        } else if output::is_json() {
+            output::print_json(&JsonReport {
+                solutions_with_missing_projects: json_rows(&current_dir, &broken_solutions),
+                projects_with_missing_references: json_rows(&current_dir, &broken_projects),
+                solutions_without_referenced_projects: json_rows(&current_dir, &incomplete),
+                solutions_with_mismatched_guids: json_rows(&current_dir, &mismatched),
+                projects_with_miscased_references: json_rows(&current_dir, &miscased_rows),
+                projects_with_missing_hint_paths: json_rows(&current_dir, &hint_path_rows),
+            });
+        } else {
+            for (title, rows) in [
+                ("Solutions with missing projects:", &broken_solutions),
+                (
+                    "Projects with missing project references:",
+                    &broken_projects,
+                ),
+                (
+                    "Solutions without projects that their projects reference, pass --fix to add them:",
+                    &incomplete,
+                ),
+                (
+                    "Solutions with project GUIDs that differ from the ProjectGuid, pass --fix to update them:",
+                    &mismatched,
+                ),
+                (
+                    "Projects with project references in a different case than on disk, pass --fix to correct them:",
+                    &miscased_rows,
+                ),
+                (
+                    "Projects with references whose HintPath does not exist:",
+                    &hint_path_rows,
+                ),
+            ] {
+                if rows.is_empty() {
+                    continue;
+                }
+                println!("{}", title);
+                for row in rows.iter() {
+                    println!("  {}", relative_path(&current_dir, &row.path).display());
+                    for missing in row.missing.iter() {
+                        println!("    {}", missing);
+                    }
+                }
+                println!();
+            }
+
+            print_summary(&[("Solutions", &solutions), ("Projects", &projects)]);
+        }
+
+        broken_solutions.is_empty()
+            && broken_projects.is_empty()
+            && incomplete.is_empty()
+            && mismatched.is_empty()
+            && miscased_rows.is_empty()
+            && hint_path_rows.is_empty()
+    }
+}
+
+fn json_rows<'a>(current_dir: &Path, rows: &[&'a Row]) -> Vec<JsonRow<'a>> {
+    rows.iter()
+        .map(|row| JsonRow {
+            path: relative_path(current_dir, &row.path),
+            missing: &row.missing,
+        })
+        .collect()
+}
+
+/// Returns the problems as issues, located at the lines that name the missing projects where
+/// possible. The problems that `--fix` fixes are left out when it was passed.
+fn issues(
+    checks: &[SolutionCheck],
+    projects: &[Project],
+    miscased: &[(&PathBuf, Vec<Miscased>)],
+    missing_hint_paths: &[(&PathBuf, Vec<MissingHintPath>)],
+    fixed: bool,
+) -> Vec<sarif::Issue> {
+    let mut issues = Vec::new();
+    for check in checks.iter() {
+        let sln_dir = check.path.parent().unwrap();
+        let contents = std::fs::read_to_string(&check.path).unwrap_or_default();
+        let at = |line: Option<u32>| sarif::Location {
+            path: check.path.clone(),
+            line,
+        };
+
+        for rel_path in check.missing.iter() {
+            issues.push(sarif::Issue {
+                rule: "missing-projects",
+                level: sarif::Level::Error,
+                message: format!("{} does not exist", rel_path),
+                locations: vec![at(sarif::line_containing(
+                    &contents,
+                    &format!("\"{}\"", rel_path),
+                ))],
+            });
+        }
+        if fixed {
+            continue;
+        }
+        for (reference, from) in check.unreferenced.iter() {
+            issues.push(sarif::Issue {
+                rule: "unreferenced-projects",
+                level: sarif::Level::Error,
+                message: format!(
+                    "{} is referenced by {} but is not in the solution",
+                    relative_path(sln_dir, reference).display(),
+                    relative_path(sln_dir, from).display()
+                ),
+                locations: vec![at(None)],
+            });
+        }
+        for mismatch in check.mismatches.iter() {
+            issues.push(sarif::Issue {
+                rule: "mismatched-guids",
+                level: sarif::Level::Error,
+                message: format!(
+                    "{} is {{{:X}}} in the solution and {{{:X}}} in the project",
+                    mismatch.rel_path, mismatch.sln_guid, mismatch.project_guid
+                ),
+                locations: vec![at(sarif::line_containing(
+                    &contents,
+                    &format!("\"{}\"", mismatch.rel_path),
+                ))],
+            });
+        }
+    }
+
+    for project in projects.iter() {
+        let missing = project
+            .project_references
+            .iter()
+            .filter(|reference| !reference.is_file())
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            continue;
+        }
+        let project_dir = project.path.parent().unwrap();
+        let lines = project_reference_lines(&project.path);
+        for reference in missing {
+            issues.push(sarif::Issue {
+                rule: "missing-project-references",
+                level: sarif::Level::Error,
+                message: format!(
+                    "{} does not exist",
+                    relative_path(project_dir, reference).display()
+                ),
+                locations: vec![sarif::Location {
+                    path: project.path.clone(),
+                    line: lines.get(reference).copied(),
+                }],
+            });
+        }
+    }
+
+    for (path, missing) in missing_hint_paths.iter() {
+        for missing in missing.iter() {
+            issues.push(sarif::Issue {
+                rule: "missing-hint-paths",
+                level: sarif::Level::Error,
+                message: format!("{} does not exist", missing.written),
+                locations: vec![sarif::Location {
+                    path: (*path).clone(),
+                    line: Some(missing.line),
+                }],
+            });
+        }
+    }
+
+    if fixed {
+        return issues;
+    }
+    for (path, miscased) in miscased.iter() {
+        for miscased in miscased.iter() {
+            issues.push(sarif::Issue {
+                rule: "miscased-project-references",
+                level: sarif::Level::Error,
+                message: format!("{} is {} on disk", miscased.written, miscased.actual),
+                locations: vec![sarif::Location {
+                    path: (*path).clone(),
+                    line: Some(miscased.line),
+                }],
+            });
+        }
+    }
+    issues
+}
+
+/// Returns the lines of the ProjectReference elements of a project by the path they resolve to,
+/// which is how the project references of missing projects are stored.
+fn project_reference_lines(project_path: &Path) -> HashMap<PathBuf, u32> {
+    let contents = encoding::read_to_string(project_path).unwrap_or_default();
+    let document = match roxmltree::Document::parse(&contents) {
+        Ok(document) => document,
+        Err(_) => return HashMap::new(),
+    };
+    let project_dir = project_path.parent().unwrap();
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("ProjectReference"))
+        .filter_map(|node| {
+            let path = project_dir
+                .join(from_msbuild_path(node.attribute("Include")?))
+                .simplify();
+            Some((path, document.text_pos_at(node.range().start).row))
+        })
+        .collect()
+}
+
+/// Returns the ProjectReferences of a project whose paths differ in case from the files on disk.
+fn miscased_references(project_path: &Path) -> Vec<Miscased> {
+    let contents = encoding::read_to_string(project_path).unwrap_or_default();
+    let document = match roxmltree::Document::parse(&contents) {
+        Ok(document) => document,
+        Err(_) => return Vec::new(),
+    };
+    let project_dir = project_path.parent().unwrap();
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("ProjectReference"))
+        .filter_map(|node| {
+            let written = node.attribute("Include")?;
+            Some(Miscased {
+                written: written.to_owned(),
+                actual: actual_case(project_dir, written, dir_entries)?,
+                line: document.text_pos_at(node.range().start).row,
+            })
+        })
+        .collect()
+}
+
+/// Returns the HintPaths of the References of a project that point at files that do not exist.
+/// HintPaths with properties are skipped, they cannot be resolved without evaluating the project.
+fn missing_hint_paths(project_path: &Path) -> Vec<MissingHintPath> {
+    let contents = encoding::read_to_string(project_path).unwrap_or_default();
+    let document = match roxmltree::Document::parse(&contents) {
+        Ok(document) => document,
+        Err(_) => return Vec::new(),
+    };
+    let project_dir = project_path.parent().unwrap();
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("HintPath"))
+        .filter(|node| {
+            node.parent()
+                .map_or(false, |parent| parent.has_tag_name("Reference"))
+        })
+        .filter_map(|node| {
+            let written = node.text()?.trim();
+            if written.is_empty()
+                || written.contains("$(")
+                || project_dir.join(from_msbuild_path(written)).is_file()
+            {
+                return None;
+            }
+            Some(MissingHintPath {
+                written: written.to_owned(),
+                line: document.text_pos_at(node.range().start).row,
+            })
+        })
+        .collect()
+}
+
+fn dir_entries(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect()
+}
+
+/// Compares the relative path, as written in a project, component by component with the names of
+/// the entries in the directories and returns it in the case of the entries if that differs. Paths
+/// with properties and paths that do not exist are skipped, the separators are kept as written.
+fn actual_case<F>(dir: &Path, written: &str, entries: F) -> Option<String>
+where
+    F: Fn(&Path) -> Vec<String>,
+{
+    if written.contains("$(") || from_msbuild_path(written).is_absolute() {
+        return None;
+    }
+
+    let mut dir = dir.to_owned();
+    let mut actual = String::with_capacity(written.len());
+    let mut differs = false;
+    let mut rest = written;
+    loop {
+        let (component, separator) = match rest.find(|c| c == '/' || c == '\\') {
+            Some(index) => (&rest[..index], Some(&rest[index..index + 1])),
+            None => (rest, None),
+        };
+        match component {
+            "" | "." => actual.push_str(component),
+            ".." => {
+                dir = dir.join(component).simplify();
+                actual.push_str(component);
+            }
+            _ => {
+                let names = entries(&dir);
+                let name = match names.iter().find(|name| name.as_str() == component) {
+                    Some(name) => name,
+                    None => {
+                        let lowercase = component.to_lowercase();
+                        differs = true;
+                        names.iter().find(|name| name.to_lowercase() == lowercase)?
+                    }
+                };
+                dir.push(name);
+                actual.push_str(name);
+            }
+        }
+        match separator {
+            Some(separator) => {
+                actual.push_str(separator);
+                rest = &rest[component.len() + 1..];
+            }
+            None => break,
+        }
+    }
+
+    if differs {
+        Some(actual)
+    } else {
+        None
+    }
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Solutions can contain web sites and other entries that are not project files.
+fn is_project_file(project: &SolutionProject) -> bool {
+    project
+        .path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            extension.to_lowercase().ends_with("proj")
+        })
+}
+
+/// Returns the paths, as written in the solution, of the projects that do not exist.
+fn missing_projects<F>(projects: &[&SolutionProject], exists: F) -> Vec<String>
+where
+    F: Fn(&Path) -> bool,
+{
+    projects
+        .iter()
+        .filter(|project| !exists(&project.path))
+        .map(|project| project.rel_path.clone())
+        .collect()
+}
+
+/// Returns the projects that the included projects reference, directly or through other
+/// projects, but that are not included themselves, with the project that references them.
+/// References to projects that do not exist are reported separately and skipped.
+fn missing_references(
+    included: &HashSet<PathBuf>,
+    references: &HashMap<PathBuf, Vec<PathBuf>>,
+) -> BTreeMap<PathBuf, PathBuf> {
+    let mut missing = BTreeMap::new();
+    let mut stack = included.iter().collect::<Vec<_>>();
+    while let Some(path) = stack.pop() {
+        for reference in references.get(path).into_iter().flatten() {
+            if included.contains(reference)
+                || missing.contains_key(reference)
+                || !references.contains_key(reference)
+            {
+                continue;
+            }
+            missing.insert(reference.clone(), path.clone());
+            stack.push(reference);
+        }
+    }
+    missing
+}
+
+/// Returns the projects whose GUID in the solution differs from the ProjectGuid of the project.
+/// Projects without a ProjectGuid, which SDK-style projects usually do not have, are skipped.
+fn guid_mismatches<F>(projects: &[&SolutionProject], project_guid: F) -> Vec<Mismatch>
+where
+    F: Fn(&Path) -> Option<Uuid>,
+{
+    projects
+        .iter()
+        .filter_map(|project| {
+            let guid = project_guid(&project.path)?;
+            if guid == project.guid {
+                None
+            } else {
+                Some(Mismatch {
+                    rel_path: project.rel_path.clone(),
+                    sln_guid: project.guid,
+                    project_guid: guid,
+                })
+            }
+        })
+        .collect()
+}
+
+fn print_summary(groups: &[(&str, &[Row])]) {
+    let headers = ["Checked", "Files", "Broken", "Missing"];
+    let cells = groups
+        .iter()
+        .map(|(name, rows)| {
+            [
+                name.to_string(),
+                rows.len().to_string(),
+                rows.iter()
+                    .filter(|row| !row.missing.is_empty())
+                    .count()
+                    .to_string(),
+                rows.iter()
+                    .map(|row| row.missing.len())
+                    .sum::<usize>()
+                    .to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = [0; 4];
+    for (width, header) in widths.iter_mut().zip(headers.iter()) {
+        *width = header.len();
+    }
+    for row in cells.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    println!(
+        "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+        headers[0],
+        headers[1],
+        headers[2],
+        headers[3],
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+        w3 = widths[3],
+    );
+    for row in cells.iter() {
+        println!(
+            "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_references_follows_references_of_missing_projects() {
+        let references = [
+            ("/A.csproj", vec!["/B.csproj"]),
+            ("/B.csproj", vec!["/C.csproj", "/Gone.csproj"]),
+            ("/C.csproj", vec![]),
+        ]
+        .iter()
+        .map(|(path, references)| {
+            (
+                PathBuf::from(path),
+                references.iter().map(PathBuf::from).collect(),
+            )
+        })
+        .collect::<HashMap<_, Vec<_>>>();
+        let included = std::iter::once(PathBuf::from("/A.csproj")).collect();
+
+        assert_eq!(
+            missing_references(&included, &references)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![
+                (PathBuf::from("/B.csproj"), PathBuf::from("/A.csproj")),
+                (PathBuf::from("/C.csproj"), PathBuf::from("/B.csproj")),
+            ]
+        );
+    }
+
+    #[test]
+    fn guid_mismatches_skips_projects_without_project_guid() {
+        let solution = Solution::parse(
+            Path::new("/repo/All.sln"),
+            "\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"A\\A.csproj\", \"{00000000-0000-0000-0000-00000000000A}\"\n\
+EndProject\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"B\", \"B\\B.csproj\", \"{00000000-0000-0000-0000-00000000000B}\"\n\
+EndProject\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"C\", \"C\\C.csproj\", \"{00000000-0000-0000-0000-00000000000C}\"\n\
+EndProject\n",
+        );
+        let projects = solution.projects.iter().collect::<Vec<_>>();
+        let guid = |value: u128| Uuid::from_u128(value);
+
+        assert_eq!(
+            guid_mismatches(&projects, |path| match path.to_str().unwrap() {
+                "/repo/A/A.csproj" => Some(guid(0xA)),
+                "/repo/B/B.csproj" => Some(guid(0xF)),
+                _ => None,
+            }),
+            vec![Mismatch {
+                rel_path: String::from("B\\B.csproj"),
+                sln_guid: guid(0xB),
+                project_guid: guid(0xF),
+            }]
+        );
+    }
+
+    #[test]
+    fn actual_case_compares_each_component_with_the_entries() {
+        let entries = |dir: &Path| -> Vec<String> {
+            let names: &[&str] = match dir.to_str().unwrap() {
+                "/repo" => &["src", "Tests"],
+                "/repo/src" => &["Core", "Web"],
+                "/repo/src/Core" => &["Core.csproj"],
+                _ => &[],
+            };
+            names.iter().map(|name| name.to_string()).collect()
+        };
+        let dir = Path::new("/repo/Tests");
+
+        assert_eq!(
+            actual_case(dir, r"..\SRC\core/core.csproj", entries),
+            Some(String::from(r"..\src\Core/Core.csproj"))
+        );
+        assert_eq!(actual_case(dir, r"..\src\Core\Core.csproj", entries), None);
+        assert_eq!(actual_case(dir, r"..\src\Gone\Gone.csproj", entries), None);
+        assert_eq!(
+            actual_case(dir, r"$(Root)\src\core\core.csproj", entries),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_projects_skips_entries_that_are_not_project_files() {
+        let solution = Solution::parse(
+            Path::new("/repo/All.sln"),
+            "\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"A\", \"src\\A\\A.csproj\", \"{00000000-0000-0000-0000-00000000000A}\"\n\
+EndProject\n\
+Project(\"{FAE04EC0-301F-11D3-BF4B-00C04F79EFBC}\") = \"B\", \"src\\B\\B.csproj\", \"{00000000-0000-0000-0000-00000000000B}\"\n\
+EndProject\n\
+Project(\"{E24C65DC-7377-472B-9ABA-BC803B73C61A}\") = \"Web\", \"http://localhost/Web\", \"{00000000-0000-0000-0000-00000000000C}\"\n\
+EndProject\n",
+        );
+        let projects = solution
+            .projects
+            .iter()
+            .filter(|project| is_project_file(project))
+            .collect::<Vec<_>>();
+
+        assert_eq!(projects.len(), 2);
+        assert_eq!(
+            missing_projects(&projects, |path| path == Path::new("/repo/src/A/A.csproj")),
+            vec![String::from("src\\B\\B.csproj")]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use log::warn;
+
+/// Ignore files with this name are respected like .gitignore files, also with `--no-ignore`.
+/// They keep projects out of every command without affecting git.
+pub const IGNORE_FILE_NAME: &'static str = ".csprojtoolignore";
+
+/// Which files the walker that finds projects and solutions skips.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Respect .gitignore, .ignore, .git/info/exclude and the global git excludes.
+    pub ignore: bool,
+    /// Walk hidden directories and files, those whose name starts with a dot.
+    pub hidden: bool,
+    /// Ignore files in the format of .gitignore that apply to every walk.
+    pub ignore_files: Vec<PathBuf>,
+    /// Walk into symbolically linked directories. Projects are identified by their canonical
+    /// path, so a project that can be reached through several links is found once.
+    pub follow_links: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            ignore: true,
+            hidden: false,
+            ignore_files: Vec::new(),
+            follow_links: false,
+        }
+    }
+}
+
+lazy_static! {
+    static ref OPTIONS: RwLock<Options> = RwLock::new(Options::default());
+}
+
+/// Applies the options given on the command line, must be called before anything is walked.
+pub fn init(options: Options) {
+    *OPTIONS.write().unwrap() = options;
+}
+
+/// Returns a walker for the directory that skips files the way the options say.
+pub fn builder(path: &Path) -> ignore::WalkBuilder {
+    let options = OPTIONS.read().unwrap();
+    let mut builder = ignore::WalkBuilder::new(path);
+    builder
+        .hidden(!options.hidden)
+        .follow_links(options.follow_links)
+        .ignore(options.ignore)
+        .git_ignore(options.ignore)
+        .git_exclude(options.ignore)
+        .git_global(options.ignore)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME);
+    for ignore_file in options.ignore_files.iter() {
+        if let Some(e) = builder.add_ignore(ignore_file) {
+            warn!("Failed to read {}: {}", ignore_file.display(), e);
+        }
+    }
+    builder
+}
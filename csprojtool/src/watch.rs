@@ -0,0 +1,237 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use log::warn;
+use notify::event::{ModifyKind, RemoveKind};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    cli,
+    path_extensions::{relative_path, PathExt},
+    serve::{invalidated, Snapshot},
+    utils::path_extension_is_project,
+    validate::ValidateCommand,
+};
+
+const ARG_FIX: &'static str = "fix";
+const ARG_INTERVAL: &'static str = "interval";
+const ARG_POLL: &'static str = "poll";
+const CMD_WATCH: &'static str = "watch";
+/// Directories whose contents never change the projects or solutions, builds and git write to
+/// them all the time.
+const IGNORED_DIRS: [&'static str; 3] = [".git", "bin", "obj"];
+
+/// How long to wait for more events after a change, saving a file or checking out a branch
+/// causes a burst of them.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub struct WatchCommand {
+    search_path: PathBuf,
+    fix: bool,
+    poll: bool,
+    interval: Duration,
+}
+
+impl WatchCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_WATCH)
+            .about("Validate the solutions and project references whenever a project or solution is added, removed or changed")
+            .arg(cli::arg_search())
+            .arg(
+                Arg::with_name(ARG_FIX)
+                    .long("fix")
+                    .takes_value(false)
+                    .help("Fix what validate --fix can fix after every change"),
+            )
+            .arg(
+                Arg::with_name(ARG_POLL)
+                    .long("poll")
+                    .takes_value(false)
+                    .help("Look for changes every interval instead of waiting for file system notifications, which network drives often do not send"),
+            )
+            .arg(
+                Arg::with_name(ARG_INTERVAL)
+                    .long("interval")
+                    .value_name("SECONDS")
+                    .help("How often to look for changes with --poll or when the directory can not be watched")
+                    .takes_value(true)
+                    .default_value("2")
+                    .validator(|value| match value.parse::<u64>() {
+                        Ok(0) => Err(String::from("expected at least 1 second")),
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(e.to_string()),
+                    }),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_WATCH)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            search_path: cli::get_search_path(matches),
+            fix: matches.is_present(ARG_FIX),
+            poll: matches.is_present(ARG_POLL),
+            interval: Duration::from_secs(matches.value_of(ARG_INTERVAL).unwrap().parse().unwrap()),
+        }
+    }
+
+    /// Waits for file system notifications and compares the modification times of the projects
+    /// and solutions after each, or polls them with `--poll`. Runs until killed.
+    pub fn execute(&self) {
+        let search_path = self.search_path.simplified_absolute().unwrap();
+        let search_dir = if search_path.is_file() {
+            search_path.parent().unwrap().to_owned()
+        } else {
+            search_path
+        };
+        let validate = ValidateCommand::new(self.search_path.clone(), self.fix);
+        let current_dir = std::env::current_dir().unwrap();
+
+        validate.validate();
+        // Taken after validating so that the fixes do not count as changes.
+        let mut snapshot = scan(&search_dir);
+
+        // The watcher stops sending events when it is dropped.
+        let (sender, receiver) = channel();
+        let mut watcher = if self.poll {
+            None
+        } else {
+            match watch(&search_dir, sender) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    warn!(
+                        "Failed to watch {}, looking for changes every {} seconds instead: {}",
+                        search_dir.display(),
+                        self.interval.as_secs(),
+                        e
+                    );
+                    None
+                }
+            }
+        };
+        println!("Watching {} for changes", search_dir.display());
+
+        loop {
+            if watcher.is_none() {
+                std::thread::sleep(self.interval);
+            } else if !wait_for_change(&receiver, &search_dir) {
+                warn!(
+                    "The file system watcher stopped, looking for changes every {} seconds instead",
+                    self.interval.as_secs()
+                );
+                watcher = None;
+            }
+
+            let current = scan(&search_dir);
+            let changed = invalidated(&snapshot, &current);
+            if changed.is_empty() {
+                continue;
+            }
+
+            println!();
+            for path in changed.iter() {
+                let state = if current.contains_key(*path) {
+                    if snapshot.contains_key(*path) {
+                        "Changed"
+                    } else {
+                        "Added"
+                    }
+                } else {
+                    "Removed"
+                };
+                println!("{} {}", state, relative_path(&current_dir, path).display());
+            }
+            println!();
+
+            if validate.validate() {
+                println!("No problems found");
+            }
+            snapshot = scan(&search_dir);
+        }
+    }
+}
+
+fn watch(
+    dir: &Path,
+    sender: std::sync::mpsc::Sender<notify::Result<notify::Event>>,
+) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(dir, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// Blocks until an event may have changed a project or solution and the events that follow it
+/// have settled. Returns false when the watcher stopped sending events.
+fn wait_for_change(receiver: &Receiver<notify::Result<notify::Event>>, dir: &Path) -> bool {
+    loop {
+        match receiver.recv() {
+            Ok(Ok(event)) if is_relevant(&event, dir) => break,
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!("Failed to watch for changes: {}", e),
+            Err(_) => return false,
+        }
+    }
+
+    loop {
+        match receiver.recv_timeout(DEBOUNCE) {
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+    }
+}
+
+/// Whether the event may have changed a project or solution below the directory. Removed and
+/// renamed directories may contain projects, events for other files are ignored.
+fn is_relevant(event: &notify::Event, dir: &Path) -> bool {
+    let moves_directories = matches!(
+        event.kind,
+        EventKind::Remove(RemoveKind::Folder | RemoveKind::Any)
+            | EventKind::Modify(ModifyKind::Name(_))
+    );
+    event
+        .paths
+        .iter()
+        .filter(|path| {
+            !path
+                .strip_prefix(dir)
+                .unwrap_or(path)
+                .components()
+                .any(|component| {
+                    IGNORED_DIRS
+                        .iter()
+                        .any(|ignored| component.as_os_str() == *ignored)
+                })
+        })
+        .any(|path| {
+            path_extension_is_project(path)
+                || path.extension() == Some(OsStr::new("sln"))
+                || (moves_directories && !path.is_file())
+        })
+}
+
+/// Returns the modification times of the projects and solutions below the directory.
+fn scan(dir: &Path) -> Snapshot {
+    crate::walk::builder(dir)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().map_or(false, |t| t.is_file())
+                && (path_extension_is_project(entry.path())
+                    || entry.path().extension() == Some(OsStr::new("sln")))
+        })
+        .map(|entry| {
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            (entry.into_path(), modified)
+        })
+        .collect()
+}
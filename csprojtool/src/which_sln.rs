@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    cli, output,
+    path_extensions::{relative_path, PathExt},
+    sln, slnf,
+    utils::find_sln_and_slnf_files,
+};
+
+const ARG_FORMAT: &'static str = "format";
+const ARG_PROJECT: &'static str = "project";
+const CMD_WHICH_SLN: &'static str = "which-sln";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct WhichSlnCommand {
+    project_path: PathBuf,
+    search_path: PathBuf,
+    format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Inclusion {
+    path: PathBuf,
+    /// The solution that the filter filters, absent for solutions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filters: Option<PathBuf>,
+}
+
+impl WhichSlnCommand {
+    pub fn subcommand() -> clap::App<'static, 'static> {
+        use clap::Arg;
+
+        clap::SubCommand::with_name(CMD_WHICH_SLN)
+            .about("List the solutions and solution filters that include a project")
+            .arg(
+                Arg::with_name(ARG_PROJECT)
+                    .value_name("PROJECT_PATH")
+                    .help("The project to look for")
+                    .required(true)
+                    .takes_value(true),
+            )
+            .arg(cli::arg_search())
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Sets the output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+    }
+
+    pub fn try_from_matches(matches: &clap::ArgMatches) -> Option<Self> {
+        matches
+            .subcommand_matches(CMD_WHICH_SLN)
+            .map(Self::from_matches)
+    }
+
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            project_path: PathBuf::from(matches.value_of_os(ARG_PROJECT).unwrap()),
+            search_path: cli::get_search_path(matches),
+            format: match matches.value_of(ARG_FORMAT).unwrap() {
+                "json" => Format::Json,
+                _ => Format::Text,
+            },
+        }
+    }
+
+    pub fn execute(&self) {
+        let project_path = std::fs::canonicalize(&self.project_path)
+            .unwrap_or_else(|e| panic!("Failed to find {}: {}", self.project_path.display(), e));
+        let search_path = self.search_path.simplified_absolute().unwrap();
+
+        let inclusions = find_sln_and_slnf_files(&search_path)
+            .into_iter()
+            .filter_map(|path| match inclusion(&path, &project_path) {
+                Ok(inclusion) => inclusion,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let current_dir = std::env::current_dir().unwrap();
+        let format = if output::is_json() {
+            Format::Json
+        } else {
+            self.format
+        };
+        match format {
+            Format::Text => {
+                for inclusion in inclusions.iter() {
+                    match &inclusion.filters {
+                        Some(sln_path) => println!(
+                            "{} (filters {})",
+                            relative_path(&current_dir, &inclusion.path).display(),
+                            relative_path(&current_dir, sln_path).display()
+                        ),
+                        None => {
+                            println!("{}", relative_path(&current_dir, &inclusion.path).display())
+                        }
+                    }
+                }
+            }
+            Format::Json => {
+                let inclusions = inclusions
+                    .into_iter()
+                    .map(|inclusion| Inclusion {
+                        path: relative_path(&current_dir, &inclusion.path),
+                        filters: inclusion
+                            .filters
+                            .map(|sln_path| relative_path(&current_dir, &sln_path)),
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::to_writer_pretty(std::io::stdout(), &inclusions).unwrap();
+                println!();
+            }
+        }
+    }
+}
+
+/// Reads the solution or solution filter, None when it does not include the project.
+fn inclusion(path: &Path, project_path: &Path) -> std::io::Result<Option<Inclusion>> {
+    let (filters, project_paths) = if path.extension().map_or(false, |e| e == "slnf") {
+        let (sln_path, project_paths) = slnf::read_filter(path)?;
+        (Some(canonical(&sln_path)), project_paths)
+    } else {
+        (None, sln::read_project_paths(path)?)
+    };
+    Ok(project_paths
+        .iter()
+        .any(|path| canonical(path) == project_path)
+        .then(|| Inclusion {
+            path: path.to_owned(),
+            filters,
+        }))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use log::debug;
+
+use crate::path_extensions::relative_path;
+use crate::tools::{self, Tool};
+
+/// How many more times to check a locked file before giving up.
+static RETRIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether read-only files may be made writable, for checkouts that mark unopened files
+/// read-only like Perforce.
+static FORCE_WRITABLE: AtomicBool = AtomicBool::new(false);
+
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Applies the options given on the command line, must be called before any files are written.
+pub fn init(retries: Option<usize>, force_writable: bool) {
+    RETRIES.store(retries.unwrap_or(0), Ordering::Relaxed);
+    FORCE_WRITABLE.store(force_writable, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Writable,
+    ReadOnly,
+    /// Opened without sharing by another process, which Visual Studio does on Windows.
+    Locked,
+}
+
+/// Checks that the existing files can be changed before anything is written so that a command
+/// does not fail halfway. Exits with a report of the files that can not be written.
+pub fn ensure_writable(paths: &[PathBuf]) {
+    let mut read_only = Vec::new();
+    let mut locked = Vec::new();
+    for path in paths.iter() {
+        match state(path) {
+            State::Writable => {}
+            State::ReadOnly => read_only.push(path),
+            State::Locked => locked.push(path),
+        }
+    }
+
+    if FORCE_WRITABLE.load(Ordering::Relaxed) {
+        for path in read_only.drain(..) {
+            debug!("making {} writable", path.display());
+            make_writable(path)
+                .unwrap_or_else(|e| panic!("Failed to make {} writable: {}", path.display(), e));
+        }
+    }
+
+    for attempt in 1..=RETRIES.load(Ordering::Relaxed) {
+        if locked.is_empty() {
+            break;
+        }
+        debug!(
+            "{} files are locked, retrying in {:?} ({}/{})",
+            locked.len(),
+            RETRY_DELAY,
+            attempt,
+            RETRIES.load(Ordering::Relaxed)
+        );
+        std::thread::sleep(RETRY_DELAY);
+        locked.retain(|path| state(path) == State::Locked);
+    }
+
+    if read_only.is_empty() && locked.is_empty() {
+        return;
+    }
+
+    let current_dir = std::env::current_dir().unwrap();
+    if !read_only.is_empty() {
+        eprintln!("Read-only files, check them out or pass --force-writable:");
+        for path in read_only {
+            eprintln!("  {}", relative_path(&current_dir, path).display());
+        }
+    }
+    if !locked.is_empty() {
+        eprintln!("Files in use by another process, close it or pass --retry:");
+        for path in locked {
+            match lock_holder(path) {
+                Some(holder) => eprintln!(
+                    "  {} (held by {})",
+                    relative_path(&current_dir, path).display(),
+                    holder
+                ),
+                None => eprintln!("  {}", relative_path(&current_dir, path).display()),
+            }
+        }
+    }
+    eprintln!("Nothing has been changed.");
+    std::process::exit(crate::error::EXIT_USER_ERROR);
+}
+
+fn state(path: &Path) -> State {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => return State::ReadOnly,
+        Ok(_) => {}
+        // Files that do not exist yet are created next to them.
+        Err(_) => return State::Writable,
+    }
+
+    match open_exclusive(path) {
+        Ok(_) => State::Writable,
+        Err(e) if is_sharing_violation(&e) => State::Locked,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => State::ReadOnly,
+        Err(_) => State::Writable,
+    }
+}
+
+#[cfg(windows)]
+fn make_writable(path: &Path) -> std::io::Result<()> {
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(path, permissions)
+}
+
+/// Only gives the owner write access, unlike `set_readonly(false)` which makes the file world
+/// writable.
+#[cfg(unix)]
+fn make_writable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o200);
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(windows)]
+fn open_exclusive(path: &Path) -> std::io::Result<std::fs::File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .share_mode(0)
+        .open(path)
+}
+
+#[cfg(not(windows))]
+fn open_exclusive(path: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new().write(true).open(path)
+}
+
+fn is_sharing_violation(error: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION, other platforms do not lock files.
+    cfg!(windows) && matches!(error.raw_os_error(), Some(32) | Some(33))
+}
+
+/// Asks Sysinternals handle, when it is on the path, which process has the file open.
+fn lock_holder(path: &Path) -> Option<String> {
+    let mut handle = tools::command(Tool::Handle);
+    handle.args(["-nobanner", "-accepteula"]).arg(path);
+    debug!("{:?}", &handle);
+    let output = handle.output().ok()?;
+    parse_handle_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Reads lines like `devenv.exe         pid: 1234   type: File   1F4: C:\src\A\A.csproj`.
+fn parse_handle_output(output: &str) -> Option<String> {
+    let mut holders = output
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(" pid: ")?;
+            let pid = rest.split_whitespace().next()?;
+            Some(format!("{} ({})", name.trim(), pid))
+        })
+        .collect::<Vec<_>>();
+    holders.dedup();
+    if holders.is_empty() {
+        None
+    } else {
+        Some(holders.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_handle_output_lists_processes() {
+        let output = "\
+devenv.exe         pid: 1234   type: File           1F4: C:\\src\\A\\A.csproj\r\n\
+devenv.exe         pid: 1234   type: File           2A0: C:\\src\\A\\A.csproj\r\n";
+        assert_eq!(
+            parse_handle_output(output),
+            Some(String::from("devenv.exe (1234)"))
+        );
+        assert_eq!(parse_handle_output("No matching handles found.\r\n"), None);
+    }
+}
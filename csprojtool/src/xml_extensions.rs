@@ -3,6 +3,7 @@ use std::path::Path;
 use xmltree::{Element, XMLNode};
 
 use crate::csproj::Error;
+use crate::path_extensions::to_extended_length;
 
 pub fn process_tree<F>(element: &mut Element, process_element: F)
 where
@@ -53,56 +54,61 @@ pub fn all_children_whitespace(element: &Element) -> bool {
     })
 }
 
-fn strip_bom<R: std::io::BufRead>(reader: &mut R) {
-    // Get rid of UTF-8 BOM if present.
-    let bytes = std::io::BufRead::fill_buf(reader).unwrap();
-
-    let mut consume_count = 0;
-    if &bytes[0..2] == "\u{FEFF}".as_bytes() {
-        consume_count = 2;
-    };
+pub fn parse_xml_str(contents: &str) -> Result<Element, Error> {
+    let contents = contents.trim_start_matches('\u{FEFF}');
+    Ok(Element::parse(contents.as_bytes())?)
+}
 
-    // What the hell http://www.herongyang.com/Unicode/Notepad-Byte-Order-Mark-BOM-FEFF-EFBBBF.html
-    if &bytes[0..3] == [0xEF, 0xBB, 0xBF] {
-        consume_count = 3;
-    };
+/// Writes the contents to a temporary file next to the target and then replaces the target so
+/// that an interrupted write does not leave a truncated file behind. Missing directories are
+/// created.
+pub fn write_file_atomic(file_path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let file_path = &to_extended_length(file_path);
+    let dir_path = file_path.parent().unwrap();
+    std::fs::create_dir_all(dir_path)?;
 
-    std::io::BufRead::consume(reader, consume_count);
-}
+    let mut writer = std::io::BufWriter::new(tempfile::NamedTempFile::new_in(dir_path)?);
+    std::io::Write::write_all(&mut writer, contents)?;
+    writer.into_inner().unwrap().persist(file_path)?;
 
-pub fn read_xml_file<P: AsRef<Path>>(path: P) -> Result<Element, Error> {
-    let mut reader = std::io::BufReader::new(std::fs::File::open(path.as_ref())?);
-    strip_bom(&mut reader);
-    Ok(Element::parse(&mut reader)?)
+    Ok(())
 }
 
-pub fn transform_xml_file<F>(file_path: &Path, transform: F) -> Result<(), Error>
-where
-    F: FnOnce(Element) -> Option<Element>,
-{
-    let dir_path = file_path.parent().unwrap();
+pub fn xml_to_string(root: &Element) -> String {
+    let write_document_declaration = root.attributes.get("Sdk").is_none();
 
-    if let Some(root) = transform(read_xml_file(file_path)?) {
-        let mut writer = std::io::BufWriter::new(tempfile::NamedTempFile::new_in(dir_path)?);
+    let mut buffer = Vec::new();
+    root.write_with_config(
+        &mut buffer,
+        xmltree::EmitterConfig {
+            perform_escaping: true,
+            perform_indent: true,
+            write_document_declaration,
+            line_separator: "\r\n".into(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
 
-        let write_document_declaration = root.attributes.get("Sdk").is_none();
+    String::from_utf8(buffer).unwrap()
+}
 
-        root.write_with_config(
-            &mut writer,
+/// Formats a single element on one line, used to describe inserted and removed elements.
+pub fn element_to_string(element: &Element) -> String {
+    let mut buffer = Vec::new();
+    element
+        .write_with_config(
+            &mut buffer,
             xmltree::EmitterConfig {
                 perform_escaping: true,
-                perform_indent: true,
-                write_document_declaration,
-                line_separator: "\r\n".into(),
+                perform_indent: false,
+                write_document_declaration: false,
                 ..Default::default()
             },
         )
         .unwrap();
 
-        writer.into_inner().unwrap().persist(&file_path)?;
-    }
-
-    Ok(())
+    String::from_utf8(buffer).unwrap()
 }
 
 fn node_as_element(node: &XMLNode) -> Option<&Element> {
@@ -126,3 +132,361 @@ fn node_as_element_mut(node: &mut XMLNode) -> Option<&mut Element> {
 pub fn child_elements_mut(element: &mut Element) -> impl Iterator<Item = &mut Element> {
     element.children.iter_mut().filter_map(node_as_element_mut)
 }
+
+/// Sets the property in the last unconditional definition or adds it to the first unconditional
+/// PropertyGroup, creating the group if needed. Returns true if the element was modified.
+pub fn set_property(project: &mut Element, name: &str, value: &str) -> bool {
+    let existing = child_elements_mut(project)
+        .filter(|group| {
+            group.name == "PropertyGroup" && !group.attributes.contains_key("Condition")
+        })
+        .flat_map(child_elements_mut)
+        .filter(|property| property.name == name && !property.attributes.contains_key("Condition"))
+        .last();
+
+    if let Some(property) = existing {
+        if property.get_text().as_deref() == Some(value) {
+            return false;
+        }
+        property.children = vec![XMLNode::Text(value.to_owned())];
+        return true;
+    }
+
+    let mut property = Element::new(name);
+    property.children.push(XMLNode::Text(value.to_owned()));
+
+    let group = child_elements_mut(project)
+        .find(|group| group.name == "PropertyGroup" && !group.attributes.contains_key("Condition"));
+    match group {
+        Some(group) => group.children.push(XMLNode::Element(property)),
+        None => {
+            let mut group = Element::new("PropertyGroup");
+            group.children.push(XMLNode::Element(property));
+            project.children.insert(0, XMLNode::Element(group));
+        }
+    }
+
+    true
+}
+
+/// Removes every definition of the property, compared case insensitively, from all property
+/// groups. If a value is given only definitions with that value are removed. Property groups
+/// that become empty are removed as well. Returns true if the element was modified.
+pub fn remove_property(project: &mut Element, name: &str, value: Option<&str>) -> bool {
+    let mut removed = false;
+
+    let children = std::mem::take(&mut project.children);
+    for mut node in children {
+        if let XMLNode::Element(group) = &mut node {
+            if group.name == "PropertyGroup" {
+                let count = group.children.len();
+                group.children.retain(|node| match node {
+                    XMLNode::Element(property) => {
+                        !property.name.eq_ignore_ascii_case(name)
+                            || value.map_or(false, |value| {
+                                property.get_text().as_deref().unwrap_or_default().trim() != value
+                            })
+                    }
+                    _ => true,
+                });
+
+                if group.children.len() != count {
+                    removed = true;
+                    if all_children_whitespace(group) {
+                        continue;
+                    }
+                }
+            }
+        }
+        project.children.push(node);
+    }
+
+    removed
+}
+
+/// Returns the target frameworks of the last unconditional TargetFramework or TargetFrameworks
+/// property, in the order in which they are written.
+pub fn get_target_frameworks(project: &Element) -> Option<Vec<String>> {
+    let property = child_elements(project)
+        .filter(|group| {
+            group.name == "PropertyGroup" && !group.attributes.contains_key("Condition")
+        })
+        .flat_map(child_elements)
+        .filter(|property| {
+            (property.name == "TargetFramework" || property.name == "TargetFrameworks")
+                && !property.attributes.contains_key("Condition")
+        })
+        .last()?;
+    Some(
+        property
+            .get_text()
+            .unwrap_or_default()
+            .split(';')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+/// Sets the target frameworks, using TargetFramework for a single framework and
+/// TargetFrameworks otherwise. An existing definition is renamed in place to keep its position.
+/// Returns true if the element was modified.
+pub fn set_target_frameworks(project: &mut Element, target_frameworks: &[String]) -> bool {
+    let name = if target_frameworks.len() == 1 {
+        "TargetFramework"
+    } else {
+        "TargetFrameworks"
+    };
+    let value = target_frameworks.join(";");
+
+    let existing = child_elements_mut(project)
+        .filter(|group| {
+            group.name == "PropertyGroup" && !group.attributes.contains_key("Condition")
+        })
+        .flat_map(child_elements_mut)
+        .filter(|property| {
+            (property.name == "TargetFramework" || property.name == "TargetFrameworks")
+                && !property.attributes.contains_key("Condition")
+        })
+        .last();
+    match existing {
+        Some(property) => {
+            if property.name == name && property.get_text().as_deref() == Some(value.as_str()) {
+                return false;
+            }
+            property.name = name.to_owned();
+            property.children = vec![XMLNode::Text(value)];
+            true
+        }
+        None => set_property(project, name, &value),
+    }
+}
+
+/// Adds the item to the first unconditional ItemGroup that holds items of the same kind, keeping
+/// its items sorted by Include if they are, or to a new ItemGroup after the last one.
+pub fn add_item(project: &mut Element, item: Element) {
+    let include_of = |element: &Element| {
+        element
+            .attributes
+            .get("Include")
+            .map(|include| include.to_lowercase())
+            .unwrap_or_default()
+    };
+
+    let group = child_elements_mut(project).find(|group| {
+        group.name == "ItemGroup"
+            && !group.attributes.contains_key("Condition")
+            && child_elements(group).any(|child| child.name == item.name)
+    });
+
+    if let Some(group) = group {
+        let siblings = child_elements(group)
+            .filter(|child| child.name == item.name)
+            .map(include_of)
+            .collect::<Vec<_>>();
+        let sorted = siblings.windows(2).all(|pair| pair[0] <= pair[1]);
+
+        let include = include_of(&item);
+        let index = if sorted {
+            group.children.iter().position(|node| {
+                matches!(node, XMLNode::Element(child) if child.name == item.name && include_of(child) > include)
+            })
+        } else {
+            None
+        };
+
+        let index = index.unwrap_or_else(|| {
+            group
+                .children
+                .iter()
+                .rposition(|node| matches!(node, XMLNode::Element(_)))
+                .map_or(group.children.len(), |index| index + 1)
+        });
+        group.children.insert(index, XMLNode::Element(item));
+        return;
+    }
+
+    let mut group = Element::new("ItemGroup");
+    group.children.push(XMLNode::Element(item));
+
+    let index = project
+        .children
+        .iter()
+        .rposition(|node| matches!(node, XMLNode::Element(e) if e.name == "ItemGroup"))
+        .map_or(project.children.len(), |index| index + 1);
+    project.children.insert(index, XMLNode::Element(group));
+}
+
+/// Removes the items with the given name and Include, which is compared case insensitively, from
+/// all item groups. Item groups that become empty are removed as well. Returns true if the
+/// element was modified.
+pub fn remove_items(project: &mut Element, name: &str, include: &str) -> bool {
+    let mut removed = false;
+
+    let children = std::mem::take(&mut project.children);
+    for mut node in children {
+        if let XMLNode::Element(group) = &mut node {
+            if group.name == "ItemGroup" {
+                let count = group.children.len();
+                group.children.retain(|node| match node {
+                    XMLNode::Element(child) => {
+                        child.name != name
+                            || !child
+                                .attributes
+                                .get("Include")
+                                .map_or(false, |value| value.eq_ignore_ascii_case(include))
+                    }
+                    _ => true,
+                });
+
+                if group.children.len() != count {
+                    removed = true;
+                    if all_children_whitespace(group) {
+                        continue;
+                    }
+                }
+            }
+        }
+        project.children.push(node);
+    }
+
+    removed
+}
+
+/// Builds an element node with the attributes in the given order, for tests.
+#[cfg(test)]
+pub fn element(name: &str, attributes: &[(&str, &str)], children: Vec<XMLNode>) -> XMLNode {
+    let mut element = Element::new(name);
+    for (key, value) in attributes {
+        element
+            .attributes
+            .insert(String::from(*key), String::from(*value));
+    }
+    element.children = children;
+    XMLNode::Element(element)
+}
+
+/// Builds an SDK style project root, for tests.
+#[cfg(test)]
+pub fn project(children: Vec<XMLNode>) -> Element {
+    match element("Project", &[("Sdk", "Microsoft.NET.Sdk")], children) {
+        XMLNode::Element(project) => project,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(include: &str) -> Element {
+        let mut element = Element::new("PackageReference");
+        element
+            .attributes
+            .insert("Include".to_owned(), include.to_owned());
+        element
+    }
+
+    fn group(items: Vec<Element>) -> XMLNode {
+        let mut group = Element::new("ItemGroup");
+        group
+            .children
+            .extend(items.into_iter().map(XMLNode::Element));
+        XMLNode::Element(group)
+    }
+
+    fn includes(project: &Element) -> Vec<Vec<String>> {
+        child_elements(project)
+            .map(|group| {
+                child_elements(group)
+                    .map(|item| item.attributes.get("Include").unwrap().clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn set_target_frameworks_converts_between_singular_and_plural() {
+        let mut project = Element::new("Project");
+        set_property(&mut project, "TargetFramework", "net48");
+
+        let mut target_frameworks = get_target_frameworks(&project).unwrap();
+        target_frameworks.push(String::from("net8.0"));
+        assert!(set_target_frameworks(&mut project, &target_frameworks));
+
+        let group = child_elements(&project).next().unwrap();
+        let property = child_elements(group).next().unwrap();
+        assert_eq!(property.name, "TargetFrameworks");
+        assert_eq!(property.get_text().as_deref(), Some("net48;net8.0"));
+
+        assert!(set_target_frameworks(
+            &mut project,
+            &[String::from("net8.0")]
+        ));
+        let group = child_elements(&project).next().unwrap();
+        assert_eq!(child_elements(group).count(), 1);
+        assert_eq!(
+            child_elements(group).next().unwrap().name,
+            "TargetFramework"
+        );
+    }
+
+    #[test]
+    fn add_item_keeps_sorted_groups_sorted() {
+        let mut project = Element::new("Project");
+        project
+            .children
+            .push(group(vec![reference("A"), reference("C")]));
+
+        add_item(&mut project, reference("b"));
+
+        assert_eq!(includes(&project), vec![vec!["A", "b", "C"]]);
+    }
+
+    #[test]
+    fn remove_property_only_removes_matching_values() {
+        let mut project = Element::new("Project");
+        set_property(&mut project, "Nullable", "enable");
+        set_property(&mut project, "LangVersion", "latest");
+        let mut conditional = Element::new("PropertyGroup");
+        conditional.attributes.insert(
+            "Condition".to_owned(),
+            "'$(Configuration)' == 'Debug'".to_owned(),
+        );
+        let mut nullable = Element::new("nullable");
+        nullable.children.push(XMLNode::Text("disable".to_owned()));
+        conditional.children.push(XMLNode::Element(nullable));
+        project.children.push(XMLNode::Element(conditional));
+
+        assert!(!remove_property(
+            &mut project,
+            "Nullable",
+            Some("annotations")
+        ));
+        assert!(remove_property(&mut project, "Nullable", Some("disable")));
+        assert_eq!(child_elements(&project).count(), 1);
+
+        assert!(remove_property(&mut project, "Nullable", None));
+        let group = child_elements(&project).next().unwrap();
+        assert_eq!(
+            child_elements(group)
+                .map(|property| property.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["LangVersion"]
+        );
+    }
+
+    #[test]
+    fn remove_items_drops_emptied_groups() {
+        let mut project = Element::new("Project");
+        project.children.push(group(vec![]));
+        project.children.push(group(vec![reference("A")]));
+        project
+            .children
+            .push(group(vec![reference("a"), reference("B")]));
+
+        assert!(remove_items(&mut project, "PackageReference", "A"));
+        assert_eq!(includes(&project), vec![vec![], vec!["B"]]);
+    }
+}